@@ -36,6 +36,18 @@ gen_rktk_keys! {
     RgbBrightnessDown = 8,
     RgbPatternRainbow = 9,
     MagCal = 10,
+    ProfileNext = 11,
+    Profile0 = 12,
+    Profile1 = 13,
+    Profile2 = 14,
+    Profile3 = 15,
+    BleProfileNext = 16,
+    BleProfilePrev = 17,
+    BleProfile0 = 18,
+    BleProfile1 = 19,
+    BleProfile2 = 20,
+    BleProfile3 = 21,
+    BleProfileClear = 22,
 }
 
 use core::fmt::{self, Display, Formatter};
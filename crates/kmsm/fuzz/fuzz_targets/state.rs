@@ -0,0 +1,12 @@
+#![no_main]
+
+use kmsm::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((first, events)) = data.split_first() else {
+        return;
+    };
+    let result = fuzz::run(fuzz::config(first & 1 == 0), fuzz::decode(events));
+    assert_eq!(result, Ok(()));
+});
@@ -0,0 +1,306 @@
+//! Invariant checks of the state for fuzzing.
+//!
+//! [`run`] feeds input events into [`HidReportState`] with [`KEYMAP`], which uses all kinds of key
+//! actions. Then it releases all keys, waits until all timeouts are elapsed and checks that the
+//! keyboard went back to the idle state:
+//!
+//! - The last sent reports have no keys, modifiers, mouse buttons and media keys.
+//! - No keycode is pressed anymore.
+//! - Only layers which can be toggled are active.
+//!
+//! Panics in the state are not caught, so that fuzzer reports them as crashes.
+//!
+//! This is used by proptest tests of this crate and cargo-fuzz target in `fuzz` directory.
+
+use usbd_hid::descriptor::{KeyboardReport, MediaKeyboardReport, MouseReport};
+
+use crate::{
+    interface::state::{
+        config::{
+            ComboConfig, KeyResolverConfig, MouseConfig, PointerMode, StateConfig, TapDanceConfig,
+            TapHoldConfig,
+        },
+        input_event::{EncoderDirection, InputEvent, KeyChangeEvent},
+        output_event::{EventType, OutputEvent},
+    },
+    keycode::{KeyAction, KeyCode, layer::LayerOp, prelude::*},
+    keymap::{ComboDefinition, Keymap, Layer, TapDanceDefinition},
+    state::hid_report::HidReportState,
+};
+
+pub const LAYER: usize = 4;
+pub const ROWS: usize = 4;
+pub const COLS: usize = 4;
+pub const ENCODERS: usize = 1;
+
+pub type FuzzKeymap = Keymap<LAYER, ROWS, COLS, ENCODERS, 2, 4, 2, 3>;
+pub type FuzzState = HidReportState<LAYER, ROWS, COLS, ENCODERS, 8, 4, 2, 4, 2, 3>;
+
+/// Layer which is activated by auto mouse layer.
+const AUTO_MOUSE_LAYER: u8 = 3;
+
+/// Time to wait after all keys are released. This is longer than any timeout of [`config`].
+const IDLE_MS: u32 = 1000;
+
+const fn th(tap: KeyCode, hold: KeyCode) -> KeyAction {
+    KeyAction::TapHold(tap, hold)
+}
+
+const fn os(kc: KeyCode) -> KeyAction {
+    KeyAction::OneShot(kc)
+}
+
+const G_KEY: KeyCode = KeyCode::Key(Key::G);
+const H_KEY: KeyCode = KeyCode::Key(Key::H);
+const SHIFT: KeyCode = KeyCode::Modifier(Modifier::LShft);
+const CTRL: KeyCode = KeyCode::Modifier(Modifier::LCtrl);
+
+/// Keymap to fuzz. Layer 2 is arrow mouse layer and layer 3 is auto mouse layer.
+#[rustfmt::skip]
+pub const KEYMAP: FuzzKeymap = Keymap {
+    layers: [
+        Layer {
+            keymap: [
+                [ A     , th(KeyCode::Key(Key::B), SHIFT), th(KeyCode::Key(Key::C), KeyCode::Layer(LayerOp::Momentary(1))), os(CTRL) ],
+                [ G     , H     , MO(1) , TG(2) ],
+                [ TD(0) , os(KeyCode::Layer(LayerOp::Momentary(1))), SF(D), MO(2) ],
+                [ M_LEFT, VOLUP , MO_SCRL, TD(1) ],
+            ],
+            encoder_keys: [(Some(KeyCode::Media(Media::VolumeDecrement)), Some(KeyCode::Media(Media::VolumeIncrement)))],
+            arrow_mouse: false,
+        },
+        Layer {
+            keymap: [
+                [ F     , _____ , L_ALT , th(G_KEY, SHIFT) ],
+                [ th(KeyCode::Key(Key::J), KeyCode::Modifier(Modifier::RShft)), G, _____, TG(1) ],
+                [ _____ , K     , XXXXX , _____ ],
+                [ M_RIGHT, _____, LOCK_TG, TD(0) ],
+            ],
+            encoder_keys: [(Some(KeyCode::Layer(LayerOp::Toggle(2))), None)],
+            arrow_mouse: false,
+        },
+        Layer {
+            keymap: [
+                [ L     , MO(1) , _____ , _____ ],
+                [ H     , TD(1) , _____ , _____ ],
+                [ _____ , _____ , _____ , _____ ],
+                [ _____ , AML_RESET, _____, os(SHIFT) ],
+            ],
+            encoder_keys: [(None, Some(KeyCode::Mouse(Mouse::MMiddle)))],
+            arrow_mouse: true,
+        },
+        Layer {
+            keymap: [
+                [ M_LEFT, M_RIGHT, _____ , _____ ],
+                [ _____ , _____ , MO_SCRL, _____ ],
+                [ _____ , _____ , _____ , _____ ],
+                [ _____ , _____ , _____ , _____ ],
+            ],
+            encoder_keys: [(None, None)],
+            arrow_mouse: false,
+        },
+    ],
+    tap_dance: [
+        Some(TapDanceDefinition {
+            tap: [Some(KeyCode::Key(Key::N)), Some(KeyCode::Layer(LayerOp::Toggle(1))), None, None],
+            hold: [Some(CTRL), Some(KeyCode::Layer(LayerOp::Momentary(2))), None, None],
+        }),
+        Some(TapDanceDefinition {
+            tap: [Some(G_KEY), Some(KeyCode::Media(Media::MMute)), Some(KeyCode::Key(Key::O)), None],
+            hold: [Some(KeyCode::Mouse(Mouse::MBack)), Some(SHIFT), None, Some(H_KEY)],
+        }),
+    ],
+    combo: [
+        Some(ComboDefinition { src: [Some(G_KEY), Some(H_KEY), None], dst: KeyCode::Key(Key::I) }),
+        Some(ComboDefinition {
+            src: [Some(KeyCode::Key(Key::A)), Some(SHIFT), Some(KeyCode::Key(Key::F))],
+            dst: KeyCode::Layer(LayerOp::Momentary(1)),
+        }),
+    ],
+};
+
+/// Config to fuzz. Timeouts are shorter than [`IDLE_MS`].
+pub fn config(hold_on_other_key: bool) -> StateConfig {
+    StateConfig {
+        mouse: MouseConfig {
+            auto_mouse_layer: AUTO_MOUSE_LAYER,
+            auto_mouse_duration: 500,
+            auto_mouse_threshold: 5,
+            scroll_divider_x: 20,
+            scroll_divider_y: -12,
+            pointer_modes: [PointerMode::Move, PointerMode::Scroll],
+        },
+        key_resolver: KeyResolverConfig {
+            tap_hold: TapHoldConfig { threshold: 200, hold_on_other_key },
+            tap_dance: TapDanceConfig { threshold: 100 },
+            combo: ComboConfig { threshold: 20 },
+        },
+    }
+}
+
+/// Returns layers which can be activated by [`LayerOp::Toggle`] in [`KEYMAP`].
+fn toggled_layers() -> [bool; LAYER] {
+    let mut toggled = [false; LAYER];
+    let mut mark = |kc: &KeyCode| {
+        if let KeyCode::Layer(LayerOp::Toggle(l)) = kc {
+            toggled[*l as usize] = true;
+        }
+    };
+    for layer in &KEYMAP.layers {
+        for action in layer.keymap.iter().flatten() {
+            match action {
+                KeyAction::Normal(kc) | KeyAction::OneShot(kc) => mark(kc),
+                KeyAction::Normal2(k1, k2) | KeyAction::TapHold(k1, k2) => {
+                    mark(k1);
+                    mark(k2);
+                }
+                KeyAction::Inherit | KeyAction::TapDance(_) => {}
+            }
+        }
+        layer.encoder_keys.iter().flat_map(|(ccw, cw)| [ccw, cw]).flatten().for_each(&mut mark);
+    }
+    for def in KEYMAP.tap_dance.iter().flatten() {
+        def.tap.iter().chain(def.hold.iter()).flatten().for_each(&mut mark);
+    }
+    for def in KEYMAP.combo.iter().flatten() {
+        mark(&def.dst);
+    }
+    toggled
+}
+
+/// Decodes fuzzer input into input events and time in milliseconds since the previous event.
+///
+/// Each event is made of three bytes. The first byte selects the kind of the event, the second
+/// byte is the time and the third byte is mouse movement.
+pub fn decode(data: &[u8]) -> impl Iterator<Item = (InputEvent, u32)> + '_ {
+    data.chunks_exact(3).map(|b| {
+        let event = match (b[0] >> 5, b[0] & 0b1_0000 != 0) {
+            (0..=5, pressed) => InputEvent::Key(KeyChangeEvent {
+                row: (b[0] >> 2 & 0b11) % ROWS as u8,
+                col: (b[0] & 0b11) % COLS as u8,
+                pressed,
+            }),
+            (6, false) => InputEvent::Mouse((b[2] as i8, b[2].rotate_left(4) as i8)),
+            (7, false) => InputEvent::Pointer((1, (b[2] as i8, b[2].rotate_left(4) as i8))),
+            (6, true) => InputEvent::Encoder((
+                0,
+                if b[2] & 1 == 0 {
+                    EncoderDirection::Clockwise
+                } else {
+                    EncoderDirection::CounterClockwise
+                },
+            )),
+            _ => InputEvent::None,
+        };
+        (event, b[1] as u32)
+    })
+}
+
+/// Invariant violated after all keys are released and all timeouts are elapsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// Last sent keyboard report has keys or modifiers.
+    Keyboard(KeyboardReport),
+    /// Last sent mouse report has buttons.
+    Mouse(MouseReport),
+    /// Last sent media keyboard report has a key.
+    Media(MediaKeyboardReport),
+    /// Keycode is still pressed.
+    Keycode(KeyCode),
+    /// Layer which can't be toggled is active.
+    Layer(u8),
+    /// Highest layer in the report differs from the active layers.
+    HighestLayer { reported: u8, actual: u8 },
+}
+
+/// Runs `inputs` and checks invariants. See [module level documentation](self) for details.
+///
+/// Key events which don't change the state of the key (e.g. press of pressed key) are skipped as
+/// they never come from keyscan drivers.
+pub fn run(
+    config: StateConfig,
+    inputs: impl IntoIterator<Item = (InputEvent, u32)>,
+) -> Result<(), Violation> {
+    let mut state = FuzzState::new(KEYMAP, config);
+    let mut host = Host::default();
+    let mut pressed = [[false; COLS]; ROWS];
+
+    for (event, ms) in inputs {
+        if let InputEvent::Key(ev) = &event
+            && let Some(key) =
+                pressed.get_mut(ev.row as usize).and_then(|r| r.get_mut(ev.col as usize))
+        {
+            if *key == ev.pressed {
+                continue;
+            }
+            *key = ev.pressed;
+        }
+        host.update(&mut state, event, ms, |_| {});
+    }
+
+    for (row, cols) in pressed.iter().enumerate() {
+        for (col, _) in cols.iter().enumerate().filter(|(_, p)| **p) {
+            let event = KeyChangeEvent { row: row as u8, col: col as u8, pressed: false };
+            host.update(&mut state, InputEvent::Key(event), 1, |_| {});
+        }
+    }
+    for _ in 0..IDLE_MS / 10 {
+        host.update(&mut state, InputEvent::None, 10, |_| {});
+    }
+
+    let mut stuck = None;
+    let highest_layer = host.update(&mut state, InputEvent::None, 10, |ev| {
+        if let OutputEvent::KeyCode((kc, EventType::Pressed | EventType::Pressing)) = ev {
+            stuck.get_or_insert(kc);
+        }
+    });
+
+    if let Some(kc) = stuck {
+        return Err(Violation::Keycode(kc));
+    }
+    if let Some(r) = host.keyboard.filter(|r| r.modifier != 0 || r.keycodes != [0; 6]) {
+        return Err(Violation::Keyboard(r));
+    }
+    if let Some(r) = host.mouse.filter(|r| r.buttons != 0) {
+        return Err(Violation::Mouse(r));
+    }
+    if let Some(r) = host.media.filter(|r| r.usage_id != 0) {
+        return Err(Violation::Media(r));
+    }
+    let active = state.inner().get_layer_active();
+    if let Some(l) = (0..LAYER).find(|l| active[*l] && !toggled_layers()[*l]) {
+        return Err(Violation::Layer(l as u8));
+    }
+    let actual = active.iter().rposition(|a| *a).unwrap_or(0) as u8;
+    if highest_layer != actual {
+        return Err(Violation::HighestLayer { reported: highest_layer, actual });
+    }
+
+    Ok(())
+}
+
+/// Reports which the host received last.
+#[derive(Default)]
+struct Host {
+    keyboard: Option<KeyboardReport>,
+    mouse: Option<MouseReport>,
+    media: Option<MediaKeyboardReport>,
+}
+
+impl Host {
+    /// Updates the state and returns the highest layer.
+    fn update(
+        &mut self,
+        state: &mut FuzzState,
+        event: InputEvent,
+        ms: u32,
+        cb: impl FnMut(OutputEvent),
+    ) -> u8 {
+        let since_last_update = core::time::Duration::from_millis(ms as u64);
+        let report = state.update_with_cb(event, since_last_update, cb);
+        self.keyboard = report.keyboard_report.or(self.keyboard.take());
+        self.mouse = report.mouse_report.or(self.mouse.take());
+        self.media = report.media_keyboard_report.or(self.media.take());
+        report.highest_layer
+    }
+}
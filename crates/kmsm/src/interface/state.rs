@@ -28,6 +28,23 @@ pub mod config {
         pub auto_mouse_threshold: u8,
         pub scroll_divider_x: i8,
         pub scroll_divider_y: i8,
+        /// Mode of each pointing device. Index is the id of [`super::input_event::InputEvent::Pointer`].
+        pub pointer_modes: [PointerMode; MAX_POINTERS],
+    }
+
+    /// Max number of pointing devices which can be distinguished.
+    pub const MAX_POINTERS: usize = 2;
+
+    /// How movement of a pointing device is handled.
+    ///
+    /// Pointers whose id is out of [`MAX_POINTERS`] are treated as [`PointerMode::Move`].
+    #[derive(Copy)]
+    #[apply(common_derive)]
+    pub enum PointerMode {
+        /// Moves the cursor. Scrolls while `MoScrl` is pressed.
+        Move,
+        /// Always scrolls.
+        Scroll,
     }
 
     #[apply(common_derive)]
@@ -80,7 +97,10 @@ pub mod input_event {
     #[apply(common_derive)]
     pub enum InputEvent {
         Key(KeyChangeEvent),
+        /// Movement of the pointing device 0. Same as `Pointer((0, movement))`.
         Mouse((i8, i8)),
+        /// Movement of the pointing device with id.
+        Pointer((u8, (i8, i8))),
         Encoder((u8, EncoderDirection)),
         None,
     }
@@ -4,6 +4,8 @@
 //! - [`keycode`]: Keycode definitions
 //! - [`keymap`]: Keymap definition
 //! - [`state`]: State management
+//! - [`scenario`]: Scenario test harness for keymaps
+//! - [`fuzz`]: Invariant checks of the state for fuzzing
 //!
 //! To know how to define keymap, see `keycode` and `keymap` modules.
 //!
@@ -19,5 +21,9 @@ pub mod keymap;
 mod macros;
 mod time;
 
+#[cfg(any(test, feature = "fuzz"))]
+pub mod fuzz;
+#[cfg(any(test, feature = "scenario"))]
+pub mod scenario;
 #[cfg(any(test, feature = "state"))]
 pub mod state;
@@ -0,0 +1,392 @@
+//! Scenario test harness.
+//!
+//! A scenario is a list of inputs and expected reports with the time they happen. It is run
+//! against [`HidReportState`] with virtual time, so the result doesn't depend on the speed of the
+//! machine, and mismatches are shown as a diff of the report transcript.
+//!
+//! ```
+//! # use kmsm::{keycode::prelude::*, keymap::Keymap, scenario::Scenario, state::hid_report::HidReportState};
+//! # use kmsm::interface::state::config::*;
+//! let mut keymap = Keymap::<1, 1, 2, 0, 0, 0, 0, 0>::const_default();
+//! keymap.layers[0].keymap[0] = [A, KeyAction::TapHold(KeyCode::Key(Key::B), KeyCode::Modifier(Modifier::LShft))];
+//! # let config = StateConfig {
+//! #     mouse: MouseConfig { auto_mouse_layer: 0, auto_mouse_duration: 0, auto_mouse_threshold: 0, scroll_divider_x: 1, scroll_divider_y: 1, pointer_modes: [PointerMode::Move; 2] },
+//! #     key_resolver: KeyResolverConfig {
+//! #         tap_hold: TapHoldConfig { threshold: 200, hold_on_other_key: false },
+//! #         tap_dance: TapDanceConfig { threshold: 100 },
+//! #         combo: ComboConfig { threshold: 20 },
+//! #     },
+//! # };
+//! let mut state = HidReportState::<1, 1, 2, 0, 8, 4, 0, 0, 0, 0>::new(keymap, config);
+//!
+//! Scenario::parse(
+//!     "
+//!     ## Hold the tap-hold key to use it as shift
+//!     0ms   press 0 1
+//!     201ms => keyboard LShft
+//!     250ms press 0 0
+//!     250ms => keyboard LShft A
+//!     300ms release 0 0
+//!     300ms => keyboard LShft
+//!     ",
+//! )
+//! .unwrap()
+//! .assert(&mut state);
+//! ```
+//!
+//! ## Format
+//!
+//! Each line starts with time in milliseconds (`ms` suffix is optional) and is followed by one of
+//! the commands. Empty lines and lines starting with `#` are ignored.
+//!
+//! | Command                | Description                                  |
+//! | ---------------------- | -------------------------------------------- |
+//! | `press <row> <col>`    | Presses the key.                             |
+//! | `release <row> <col>`  | Releases the key.                            |
+//! | `mouse <x> <y>`        | Moves the mouse.                             |
+//! | `pointer <id> <x> <y>` | Moves the pointing device `id`.              |
+//! | `encoder <id> cw\|ccw` | Rotates the encoder.                         |
+//! | `=> <report>`          | Expects the report to be sent at this time.  |
+//!
+//! Reports are written in the same way as they are shown in the diff:
+//!
+//! - `keyboard <modifiers and keys>`: e.g. `keyboard LShft A`. Modifiers come first, then keys in
+//!   the order of the report. `keyboard` alone means all keys are released.
+//! - `mouse <buttons> x=<x> y=<y> wheel=<wheel> pan=<pan>`: Zero values are omitted.
+//! - `media <key>`: e.g. `media VolumeIncrement`. `media` alone means the key is released.
+//! - `layer <n>`: Highest active layer changed.
+//!
+//! ## Time
+//!
+//! The state is updated every millisecond until the time of the last line, so reports caused by
+//! timeouts (e.g. tap-hold) are sent at the exact time. Reports after the last line are not
+//! checked. Inputs at the same time are applied in the order of lines.
+
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString as _},
+    vec::Vec,
+};
+use core::{fmt, time::Duration};
+
+use strum::IntoEnumIterator as _;
+
+use crate::{
+    interface::state::input_event::{EncoderDirection, InputEvent, KeyChangeEvent},
+    keycode::{key::Key, media::Media, modifier::Modifier, mouse::Mouse},
+    state::hid_report::{HidReportState, Report},
+};
+
+/// State which a scenario can run against.
+///
+/// Implemented for [`HidReportState`] of any size.
+pub trait ScenarioState {
+    fn update(&mut self, event: InputEvent, since_last_update: Duration) -> Report;
+}
+
+impl<
+    const LAYER: usize,
+    const ROW: usize,
+    const COL: usize,
+    const ENCODER_COUNT: usize,
+    const NORMAL_MAX_PRESSED_KEYS: usize,
+    const ONESHOT_STATE_SIZE: usize,
+    const TAP_DANCE_MAX_DEFINITIONS: usize,
+    const TAP_DANCE_MAX_REPEATS: usize,
+    const COMBO_KEY_MAX_DEFINITIONS: usize,
+    const COMBO_KEY_MAX_SOURCES: usize,
+> ScenarioState
+    for HidReportState<
+        LAYER,
+        ROW,
+        COL,
+        ENCODER_COUNT,
+        NORMAL_MAX_PRESSED_KEYS,
+        ONESHOT_STATE_SIZE,
+        TAP_DANCE_MAX_DEFINITIONS,
+        TAP_DANCE_MAX_REPEATS,
+        COMBO_KEY_MAX_DEFINITIONS,
+        COMBO_KEY_MAX_SOURCES,
+    >
+{
+    fn update(&mut self, event: InputEvent, since_last_update: Duration) -> Report {
+        HidReportState::update(self, event, since_last_update)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Input(InputEvent),
+    Expect(String),
+}
+
+/// Inputs and expected reports. See [module level documentation](self) for details.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scenario {
+    /// Steps with the time in milliseconds.
+    steps: Vec<(u32, Step)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line number starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses scenario written in the format described in [module level documentation](self).
+    pub fn parse(src: &str) -> Result<Self, ParseError> {
+        let mut scenario = Self::new();
+        for (i, line) in src.lines().enumerate() {
+            let step = parse_line(line).map_err(|message| ParseError { line: i + 1, message })?;
+            scenario.steps.extend(step);
+        }
+        Ok(scenario)
+    }
+
+    pub fn input(mut self, at_ms: u32, event: InputEvent) -> Self {
+        self.steps.push((at_ms, Step::Input(event)));
+        self
+    }
+
+    pub fn press(self, at_ms: u32, row: u8, col: u8) -> Self {
+        self.input(at_ms, InputEvent::Key(KeyChangeEvent { row, col, pressed: true }))
+    }
+
+    pub fn release(self, at_ms: u32, row: u8, col: u8) -> Self {
+        self.input(at_ms, InputEvent::Key(KeyChangeEvent { row, col, pressed: false }))
+    }
+
+    /// Expects the report to be sent at `at_ms`. `report` is written like `keyboard LShft A`.
+    pub fn expect(mut self, at_ms: u32, report: &str) -> Self {
+        self.steps.push((at_ms, Step::Expect(normalize(report))));
+        self
+    }
+
+    /// Runs the scenario and returns transcript of the reports.
+    ///
+    /// Each line of the transcript is formatted as `<time>ms <report>`.
+    pub fn transcript(&self, state: &mut impl ScenarioState) -> Vec<String> {
+        let mut inputs: Vec<_> = self
+            .steps
+            .iter()
+            .filter_map(|(at, step)| match step {
+                Step::Input(event) => Some((*at, event.clone())),
+                Step::Expect(_) => None,
+            })
+            .collect();
+        // Stable sort keeps the order of lines at the same time.
+        inputs.sort_by_key(|(at, _)| *at);
+        let end = self.steps.iter().map(|(at, _)| *at).max().unwrap_or(0);
+
+        let mut transcript = Vec::new();
+        let mut layer = state.update(InputEvent::None, Duration::ZERO).highest_layer;
+        let mut last_update = 0;
+        let mut inputs = inputs.into_iter().peekable();
+        for now in 0..=end {
+            let mut events = Vec::new();
+            while let Some((_, event)) = inputs.next_if(|(at, _)| *at == now) {
+                events.push(event);
+            }
+            if events.is_empty() {
+                events.push(InputEvent::None);
+            }
+            for event in events {
+                let since_last_update = Duration::from_millis((now - last_update) as u64);
+                last_update = now;
+                let report = state.update(event, since_last_update);
+                for line in format_report(&report, &mut layer) {
+                    transcript.push(format!("{now}ms {line}"));
+                }
+            }
+        }
+        transcript
+    }
+
+    /// Runs the scenario and compares reports with the expected ones.
+    pub fn run(&self, state: &mut impl ScenarioState) -> Result<(), Mismatch> {
+        let mut expected: Vec<_> = self
+            .steps
+            .iter()
+            .filter_map(|(at, step)| match step {
+                Step::Expect(report) => Some((*at, format!("{at}ms {report}"))),
+                Step::Input(_) => None,
+            })
+            .collect();
+        expected.sort_by_key(|(at, _)| *at);
+        let expected: Vec<_> = expected.into_iter().map(|(_, line)| line).collect();
+
+        let actual = self.transcript(state);
+        if expected == actual { Ok(()) } else { Err(Mismatch { expected, actual }) }
+    }
+
+    /// Same as [`Scenario::run`], but panics with the diff on mismatch.
+    #[track_caller]
+    pub fn assert(&self, state: &mut impl ScenarioState) {
+        if let Err(e) = self.run(state) {
+            panic!("{e}");
+        }
+    }
+}
+
+/// Reports differ from the expected ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub expected: Vec<String>,
+    pub actual: Vec<String>,
+}
+
+impl fmt::Display for Mismatch {
+    /// Shows diff of transcripts. Lines only in expected are marked with `-`, and lines only in
+    /// actual are marked with `+`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Reports differ from the scenario (-expected +actual):")?;
+        for (mark, line) in diff(&self.expected, &self.actual) {
+            writeln!(f, "{mark} {line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for Mismatch {}
+
+/// Line based diff using longest common subsequence.
+fn diff<'a>(a: &'a [String], b: &'a [String]) -> Vec<(char, &'a str)> {
+    // lcs[i][j] is the length of LCS of a[i..] and b[j..].
+    let mut lcs = alloc::vec![alloc::vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] =
+                if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push((' ', a[i].as_str()));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', a[i].as_str()));
+            i += 1;
+        } else {
+            lines.push(('+', b[j].as_str()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+fn normalize(report: &str) -> String {
+    report.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn parse_line(line: &str) -> Result<Option<(u32, Step)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (time, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let at = number(time.strip_suffix("ms").unwrap_or(time))?;
+    if let Some(report) = rest.trim_start().strip_prefix("=>") {
+        return Ok(Some((at, Step::Expect(normalize(report)))));
+    }
+
+    let mut args = rest.split_whitespace();
+    let command = args.next().ok_or("missing command")?;
+    let mut arg = |name: &str| args.next().ok_or_else(|| format!("missing argument: {name}"));
+    let event = match command {
+        "press" | "release" => InputEvent::Key(KeyChangeEvent {
+            row: number(arg("row")?)?,
+            col: number(arg("col")?)?,
+            pressed: command == "press",
+        }),
+        "mouse" => InputEvent::Mouse((number(arg("x")?)?, number(arg("y")?)?)),
+        "pointer" => {
+            InputEvent::Pointer((number(arg("id")?)?, (number(arg("x")?)?, number(arg("y")?)?)))
+        }
+        "encoder" => {
+            let id = number(arg("id")?)?;
+            let direction = match arg("direction")? {
+                "cw" => EncoderDirection::Clockwise,
+                "ccw" => EncoderDirection::CounterClockwise,
+                d => return Err(format!("invalid direction: {d}")),
+            };
+            InputEvent::Encoder((id, direction))
+        }
+        _ => return Err(format!("unknown command: {command}")),
+    };
+    if args.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+    Ok(Some((at, Step::Input(event))))
+}
+
+fn number<T: core::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid number: {s}"))
+}
+
+/// Formats reports which are sent, and layer if it is changed from `layer`.
+fn format_report(report: &Report, layer: &mut u8) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if let Some(r) = &report.keyboard_report {
+        let mut line = String::from("keyboard");
+        for m in Modifier::iter().filter(|m| r.modifier & *m as u8 != 0) {
+            line += &format!(" {m}");
+        }
+        for code in r.keycodes.iter().filter(|c| **c != 0) {
+            match Key::iter().find(|k| *k as u8 == *code) {
+                Some(key) => line += &format!(" {key}"),
+                None => line += &format!(" 0x{code:02x}"),
+            }
+        }
+        lines.push(line);
+    }
+
+    if let Some(r) = &report.mouse_report {
+        let mut line = String::from("mouse");
+        for b in Mouse::iter().filter(|b| r.buttons & *b as u8 != 0) {
+            line += &format!(" {b}");
+        }
+        for (name, value) in [("x", r.x), ("y", r.y), ("wheel", r.wheel), ("pan", r.pan)] {
+            if value != 0 {
+                line += &format!(" {name}={value}");
+            }
+        }
+        lines.push(line);
+    }
+
+    if let Some(r) = &report.media_keyboard_report {
+        match Media::iter().find(|m| *m as u16 == r.usage_id) {
+            Some(Media::Zero) => lines.push("media".to_string()),
+            Some(m) => lines.push(format!("media {m}")),
+            None => lines.push(format!("media 0x{:04x}", r.usage_id)),
+        }
+    }
+
+    if report.highest_layer != *layer {
+        *layer = report.highest_layer;
+        lines.push(format!("layer {layer}"));
+    }
+
+    lines
+}
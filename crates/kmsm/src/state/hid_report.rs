@@ -43,6 +43,7 @@ pub struct HidReportState<
         COMBO_KEY_MAX_SOURCES,
     >,
     next_send_keyboard_report: bool,
+    next_send_mouse_report: bool,
     next_send_mkb_report: bool,
 }
 
@@ -87,6 +88,7 @@ impl<
         Self {
             state: super::State::new(keymap, config),
             next_send_keyboard_report: false,
+            next_send_mouse_report: false,
             next_send_mkb_report: false,
         }
     }
@@ -106,12 +108,14 @@ impl<
         let mut keys: Vec<u8, 6> = Vec::new();
         let mut modifier = 0u8;
 
-        let mut mouse_change = false;
+        let mut mouse_change = self.next_send_mouse_report;
+        self.next_send_mouse_report = false;
         let mut movement = (0, 0);
         let mut scroll = (0, 0);
         let mut mouse_buttons = 0u8;
 
         let mut media_keyboard_change = self.next_send_mkb_report;
+        self.next_send_mkb_report = false;
         let mut media_keys = 0u16;
 
         self.state.update(event, since_last_update, |ev| {
@@ -149,6 +153,9 @@ impl<
                             if ev != EventType::Released {
                                 modifier |= m as u8;
                             }
+                            if ev == EventType::Released && modifier & m as u8 != 0 {
+                                self.next_send_keyboard_report = true;
+                            }
                         }
                         KeyCode::Mouse(m) => {
                             if ev != EventType::Pressing {
@@ -157,6 +164,9 @@ impl<
                             if ev != EventType::Released {
                                 mouse_buttons |= m as u8;
                             }
+                            if ev == EventType::Released && mouse_buttons & m as u8 != 0 {
+                                self.next_send_mouse_report = true;
+                            }
                         }
                         // Other types of key codes will not appear as the HID report.
                         _ => {}
@@ -52,7 +52,16 @@ impl<const MAX_DEFINITIONS: usize, const MAX_SOURCES: usize>
         }
     }
 
-    pub fn process_keycode(&mut self, event_type: &EventType, keycode: &mut KeyCode, now: Instant) {
+    /// Converts keycode of the event for combo.
+    ///
+    /// `cb` is called for additional events which should be sent before the event.
+    pub fn process_keycode(
+        &mut self,
+        event_type: &EventType,
+        keycode: &mut KeyCode,
+        now: Instant,
+        mut cb: impl FnMut(EventType, KeyCode),
+    ) {
         for unit in self.state.iter_mut() {
             if let Some(def) = &mut unit.def {
                 for (i, key) in def.src.iter().enumerate() {
@@ -88,6 +97,18 @@ impl<const MAX_DEFINITIONS: usize, const MAX_SOURCES: usize>
                             ) => {
                                 *keycode = def.dst;
                             }
+                            (EventType::Released, &ComboUnitState::Pending((_, state))) => {
+                                // Source key is released before the combo is completed. Press
+                                // source keys which are swallowed so far, so that they are sent
+                                // as normal keys.
+                                for (j, pressed) in state.iter().enumerate() {
+                                    if *pressed && j != i {
+                                        cb(EventType::Pressed, def.src[j].unwrap());
+                                    }
+                                }
+                                cb(EventType::Pressed, *keycode);
+                                unit.state = ComboUnitState::None;
+                            }
                             (EventType::Released, &ComboUnitState::Pressing(mut state)) => {
                                 state[i] = false;
                                 if state.iter().all(|&b| !b) {
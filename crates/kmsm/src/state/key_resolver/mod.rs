@@ -5,6 +5,7 @@ use crate::{
     },
     keycode::{KeyAction, KeyCode},
     keymap::{ComboDefinitions, TapDanceDefinitions},
+    time::Instant,
 };
 
 mod combo;
@@ -97,7 +98,9 @@ impl<
         macro_rules! with_layer {
             ($cb:expr) => {
                 |event_type, mut key_code| {
-                    self.combo.process_keycode(&event_type, &mut key_code, now);
+                    self.combo.process_keycode(&event_type, &mut key_code, now, |et, kc| {
+                        $cb(shared_state, et, kc)
+                    });
                     $cb(shared_state, event_type, key_code);
                 }
             };
@@ -114,61 +117,112 @@ impl<
             });
         }
 
+        if let Some(event) = event {
+            if event.pressed {
+                self.resolve_press(shared_state, event, now, &mut cb);
+            } else {
+                // Keys are released by the position, because the layer may have been changed
+                // since the key was pressed.
+                let mut cb_with_layer = with_layer!(cb);
+                self.normal_state.process_release(event, &mut cb_with_layer);
+                self.tap_hold.process_release(event, &mut cb_with_layer);
+                self.tap_dance.process_release(event, now, &mut cb_with_layer);
+            }
+        }
+
+        let mut cb_with_layer = with_layer!(cb);
+        self.tap_dance.post_resolve(now, &mut cb_with_layer);
+        self.normal_state.post_resolve(&mut cb_with_layer);
+    }
+
+    fn resolve_press<
+        const LAYER: usize,
+        const ROW: usize,
+        const COL: usize,
+        const ENCODER_COUNT: usize,
+    >(
+        &mut self,
+        shared_state: &mut SharedState<
+            LAYER,
+            ROW,
+            COL,
+            ENCODER_COUNT,
+            TAP_DANCE_MAX_DEFINITIONS,
+            TAP_DANCE_MAX_REPEATS,
+            COMBO_KEY_MAX_DEFINITIONS,
+            COMBO_KEY_MAX_SOURCES,
+        >,
+        event: &KeyChangeEvent,
+        now: Instant,
+        mut cb: impl FnMut(
+            &mut SharedState<
+                LAYER,
+                ROW,
+                COL,
+                ENCODER_COUNT,
+                TAP_DANCE_MAX_DEFINITIONS,
+                TAP_DANCE_MAX_REPEATS,
+                COMBO_KEY_MAX_DEFINITIONS,
+                COMBO_KEY_MAX_SOURCES,
+            >,
+            EventType,
+            KeyCode,
+        ),
+    ) {
         let highest_layer = shared_state.highest_layer();
 
-        if let Some(event) = event {
-            let Some(mut key_action) = shared_state
-                .keymap
-                .get_keyaction(highest_layer, event.row as usize, event.col as usize)
-                .copied()
-            else {
-                return;
-            };
+        let Some(mut key_action) = shared_state
+            .keymap
+            .get_keyaction(highest_layer, event.row as usize, event.col as usize)
+            .copied()
+        else {
+            return;
+        };
 
-            if key_action == KeyAction::Inherit {
-                for layer in 0..highest_layer {
-                    let Some(action) = shared_state.keymap.get_keyaction(
-                        layer,
-                        event.row as usize,
-                        event.col as usize,
-                    ) else {
-                        return;
-                    };
-                    if *action != KeyAction::Inherit {
-                        key_action = *action;
-                        break;
-                    }
+        if key_action == KeyAction::Inherit {
+            for layer in 0..highest_layer {
+                let Some(action) = shared_state.keymap.get_keyaction(
+                    layer,
+                    event.row as usize,
+                    event.col as usize,
+                ) else {
+                    return;
+                };
+                if *action != KeyAction::Inherit {
+                    key_action = *action;
+                    break;
                 }
             }
+        }
 
-            let mut cb_with_layer = with_layer!(cb);
+        let mut cb_with_layer = |event_type, mut key_code| {
+            self.combo.process_keycode(&event_type, &mut key_code, now, |et, kc| {
+                cb(shared_state, et, kc)
+            });
+            cb(shared_state, event_type, key_code);
+        };
 
-            match key_action {
-                KeyAction::Inherit => {}
-                KeyAction::Normal(key_code) => {
-                    self.normal_state.process_event(event, (key_code, None), &mut cb_with_layer);
-                }
-                KeyAction::Normal2(key_code, key_code1) => {
-                    self.normal_state.process_event(
-                        event,
-                        (key_code, Some(key_code1)),
-                        &mut cb_with_layer,
-                    );
-                }
-                KeyAction::TapHold(tkc, hkc) => {
-                    self.tap_hold.process_event(now, event, (tkc, hkc), &mut cb_with_layer);
-                }
-                KeyAction::OneShot(key_code) => {
-                    self.oneshot.process_keycode(&key_code, event.pressed);
-                }
-                KeyAction::TapDance(id) => {
-                    self.tap_dance.process_event(id, now, event.pressed, &mut cb_with_layer);
-                }
+        match key_action {
+            KeyAction::Inherit => {}
+            KeyAction::Normal(key_code) => {
+                self.normal_state.process_press(event, (key_code, None), &mut cb_with_layer);
+            }
+            KeyAction::Normal2(key_code, key_code1) => {
+                self.normal_state.process_press(
+                    event,
+                    (key_code, Some(key_code1)),
+                    &mut cb_with_layer,
+                );
+            }
+            KeyAction::TapHold(tkc, hkc) => {
+                self.tap_hold.process_press(now, event, (tkc, hkc));
+            }
+            KeyAction::OneShot(key_code) => {
+                self.oneshot.process_keycode(&key_code);
+            }
+            KeyAction::TapDance(id) => {
+                self.tap_dance.process_press(id, now, event);
             }
         }
-
-        let mut cb_with_layer = with_layer!(cb);
-        self.tap_dance.post_resolve(now, &mut cb_with_layer);
-        self.normal_state.post_resolve(&mut cb_with_layer);
     }
 }
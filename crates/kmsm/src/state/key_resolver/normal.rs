@@ -20,7 +20,7 @@ impl<const MAX_PRESSED_KEYS: usize> NormalState<MAX_PRESSED_KEYS> {
         Self { pressed: heapless::Vec::new() }
     }
 
-    pub fn process_event(
+    pub fn process_press(
         &mut self,
         event: &KeyChangeEvent,
         kc: (KeyCode, Option<KeyCode>),
@@ -28,30 +28,35 @@ impl<const MAX_PRESSED_KEYS: usize> NormalState<MAX_PRESSED_KEYS> {
     ) {
         let new_key_state = NormalKeyState { col: event.col, row: event.row, k1: kc.0, k2: kc.1 };
 
-        if event.pressed && !self.pressed.contains(&new_key_state) {
-            self.pressed.push(new_key_state).ok();
-
+        // If too many keys are pressed, the key is ignored as it can't be released later.
+        if !self.pressed.contains(&new_key_state) && self.pressed.push(new_key_state).is_ok() {
             cb(EventType::Pressed, kc.0);
             if let Some(kc) = kc.1 {
                 cb(EventType::Pressed, kc);
             }
-        } else if !event.pressed {
-            // release all keys that are pressed even if in other layers
-            self.pressed.retain(|k| {
-                if event.col == k.col && event.row == k.row {
-                    cb(EventType::Released, k.k1);
-                    if let Some(kc) = k.k2 {
-                        cb(EventType::Released, kc);
-                    }
-
-                    false
-                } else {
-                    true
-                }
-            });
         }
     }
 
+    /// Releases keys pressed at the position of the event even if they are in other layers.
+    pub fn process_release(
+        &mut self,
+        event: &KeyChangeEvent,
+        mut cb: impl FnMut(EventType, KeyCode),
+    ) {
+        self.pressed.retain(|k| {
+            if event.col == k.col && event.row == k.row {
+                cb(EventType::Released, k.k1);
+                if let Some(kc) = k.k2 {
+                    cb(EventType::Released, kc);
+                }
+
+                false
+            } else {
+                true
+            }
+        });
+    }
+
     pub fn post_resolve(&mut self, mut cb: impl FnMut(EventType, KeyCode)) {
         for s in self.pressed.iter() {
             cb(EventType::Pressing, s.k1);
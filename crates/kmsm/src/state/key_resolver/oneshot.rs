@@ -46,9 +46,7 @@ impl<const SIZE: usize> OneshotState<SIZE> {
         });
     }
 
-    pub fn process_keycode(&mut self, kc: &KeyCode, pressed: bool) {
-        if pressed {
-            let _ = self.oneshot.push(OneshotKeyState { key: *kc, active: None });
-        }
+    pub fn process_keycode(&mut self, kc: &KeyCode) {
+        let _ = self.oneshot.push(OneshotKeyState { key: *kc, active: None });
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    interface::state::config::TapDanceConfig,
+    interface::state::{config::TapDanceConfig, input_event::KeyChangeEvent},
     keycode::KeyCode,
     keymap::{TapDanceDefinition, TapDanceDefinitions},
     time::{Duration, Instant},
@@ -10,9 +10,20 @@ use super::EventType;
 #[derive(Debug)]
 enum TapDanceKeyState {
     None,
-    PressedPending { tap_count: u8, hold_start: Instant },
-    ReleasedPending { tap_count: u8, last_release: Instant },
-    Holding { tap_count: u8 },
+    /// `key` is the position of the pressed key.
+    PressedPending {
+        tap_count: u8,
+        hold_start: Instant,
+        key: (u8, u8),
+    },
+    ReleasedPending {
+        tap_count: u8,
+        last_release: Instant,
+    },
+    Holding {
+        tap_count: u8,
+        key: (u8, u8),
+    },
 }
 
 struct TapDanceUnit<const MAX_REPEATS: usize> {
@@ -52,13 +63,13 @@ impl<const MAX_DEFINITIONS: usize, const MAX_REPEATS: usize>
         for td in &mut self.state {
             match td.state {
                 TapDanceKeyState::None => {}
-                TapDanceKeyState::PressedPending { tap_count, hold_start } => {
+                TapDanceKeyState::PressedPending { tap_count, hold_start, key } => {
                     // If the Pending state continues for a while (if it remains pressed), it will become Hold.
                     if now - hold_start > self.threshold {
                         if let Some(hkc) = td.get_hold_key(tap_count) {
                             cb(EventType::Pressed, hkc);
                         }
-                        td.state = TapDanceKeyState::Holding { tap_count };
+                        td.state = TapDanceKeyState::Holding { tap_count, key };
                     }
                 }
                 TapDanceKeyState::ReleasedPending { tap_count, last_release } => {
@@ -71,8 +82,8 @@ impl<const MAX_DEFINITIONS: usize, const MAX_REPEATS: usize>
                         td.state = TapDanceKeyState::None;
                     }
                 }
-                TapDanceKeyState::Holding { tap_count } => {
-                    if let Some(hkc) = td.get_tap_key(tap_count) {
+                TapDanceKeyState::Holding { tap_count, .. } => {
+                    if let Some(hkc) = td.get_hold_key(tap_count) {
                         cb(EventType::Pressing, hkc);
                     }
                 }
@@ -80,31 +91,43 @@ impl<const MAX_DEFINITIONS: usize, const MAX_REPEATS: usize>
         }
     }
 
-    pub fn process_event(
-        &mut self,
-        id: u8,
-        now: Instant,
-        pressed: bool,
-        mut cb: impl FnMut(EventType, KeyCode),
-    ) {
+    pub fn process_press(&mut self, id: u8, now: Instant, event: &KeyChangeEvent) {
+        let key = (event.row, event.col);
         if let Some(td) = self.state.get_mut(id as usize) {
-            match (pressed, &td.state) {
-                (true, TapDanceKeyState::None) => {
-                    td.state = TapDanceKeyState::PressedPending { tap_count: 0, hold_start: now };
+            match &td.state {
+                TapDanceKeyState::None => {
+                    td.state =
+                        TapDanceKeyState::PressedPending { tap_count: 0, hold_start: now, key };
                 }
-                (true, TapDanceKeyState::ReleasedPending { tap_count, .. }) => {
+                TapDanceKeyState::ReleasedPending { tap_count, .. } => {
                     td.state = TapDanceKeyState::PressedPending {
                         tap_count: tap_count + 1,
                         hold_start: now,
+                        key,
                     };
                 }
-                (false, TapDanceKeyState::Holding { tap_count }) => {
+                _ => {}
+            }
+        }
+    }
+
+    /// Releases tap dance pressed at the position of the event even if it is in other layers.
+    pub fn process_release(
+        &mut self,
+        event: &KeyChangeEvent,
+        now: Instant,
+        mut cb: impl FnMut(EventType, KeyCode),
+    ) {
+        let released = (event.row, event.col);
+        for td in &mut self.state {
+            match &td.state {
+                TapDanceKeyState::Holding { tap_count, key } if *key == released => {
                     if let Some(hkc) = td.get_hold_key(*tap_count) {
                         cb(EventType::Released, hkc);
                     }
                     td.state = TapDanceKeyState::None;
                 }
-                (false, TapDanceKeyState::PressedPending { tap_count, .. }) => {
+                TapDanceKeyState::PressedPending { tap_count, key, .. } if *key == released => {
                     td.state = TapDanceKeyState::ReleasedPending {
                         tap_count: *tap_count,
                         last_release: now,
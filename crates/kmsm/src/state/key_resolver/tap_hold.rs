@@ -64,18 +64,23 @@ impl TapHoldState {
         }
     }
 
-    pub fn process_event(
+    pub fn process_press(
         &mut self,
         now: Instant,
         event: &KeyChangeEvent,
         (tkc, hkc): (KeyCode, KeyCode),
+    ) {
+        let _ = self
+            .pressed
+            .insert((event.row, event.col), TapHoldKeyState { tkc, hkc, pending: Some(now) });
+    }
+
+    pub fn process_release(
+        &mut self,
+        event: &KeyChangeEvent,
         mut cb: impl FnMut(EventType, KeyCode),
     ) {
-        if event.pressed {
-            let _ = self
-                .pressed
-                .insert((event.row, event.col), TapHoldKeyState { tkc, hkc, pending: Some(now) });
-        } else if let Some(state) = self.pressed.remove(&(event.row, event.col)) {
+        if let Some(state) = self.pressed.remove(&(event.row, event.col)) {
             if state.pending.is_some() {
                 // released in tapping term. it's a tap.
                 cb(EventType::Pressed, state.tkc);
@@ -154,7 +154,11 @@ impl<
         let key_change = match event {
             InputEvent::Key(key_change) => Some(key_change),
             InputEvent::Mouse(movement) => {
-                updater.update_by_mouse_move(movement, &mut cb);
+                updater.update_by_mouse_move(0, movement, &mut cb);
+                None
+            }
+            InputEvent::Pointer((id, movement)) => {
+                updater.update_by_mouse_move(id, movement, &mut cb);
                 None
             }
             InputEvent::Encoder((id, dir)) => {
@@ -117,3 +117,21 @@ fn tap_dance_tap3() {
     let report = state.update(InputEvent::None, time(700));
     assert_eq!(report.highest_layer, 2, "tap3");
 }
+
+#[test]
+fn tap_dance_hold_then_other_key() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = KeyAction::TapDance(0);
+    keymap.layers[0].keymap[0][1] = KeyAction::Normal(KeyCode::Key(Key::B));
+
+    let mut state = new_state(keymap);
+    let _ = update!(state, time(0));
+
+    let _ = update!(state, time(0), (0, 0, true));
+    let _ = state.update(InputEvent::None, time(600));
+
+    let report = update!(state, time(10), (0, 1, true));
+    let mut expected = report_with_keycodes([0x05, 0, 0, 0, 0, 0]);
+    expected.keyboard_report.as_mut().unwrap().modifier = 0x01;
+    assert_eq!(report, expected, "hold key is kept while holding. tap key is not sent");
+}
@@ -0,0 +1,43 @@
+use proptest::prelude::*;
+
+use crate::{
+    fuzz::{self, COLS, ENCODERS, ROWS},
+    interface::state::input_event::{EncoderDirection, InputEvent, KeyChangeEvent},
+};
+
+fn input_event() -> impl Strategy<Value = InputEvent> {
+    prop_oneof![
+        8 => (0..ROWS as u8, 0..COLS as u8, any::<bool>())
+            .prop_map(|(row, col, pressed)| InputEvent::Key(KeyChangeEvent { row, col, pressed })),
+        1 => any::<(i8, i8)>().prop_map(InputEvent::Mouse),
+        1 => (0..2u8, any::<(i8, i8)>()).prop_map(InputEvent::Pointer),
+        1 => (
+            0..ENCODERS as u8,
+            prop_oneof![Just(EncoderDirection::Clockwise), Just(EncoderDirection::CounterClockwise)],
+        )
+            .prop_map(InputEvent::Encoder),
+        1 => Just(InputEvent::None),
+    ]
+}
+
+/// Time since the previous event. Short intervals are preferred to hit thresholds of the config.
+fn interval() -> impl Strategy<Value = u32> {
+    prop_oneof![3 => 0..30u32, 1 => 0..400u32]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn fuzz_idle_after_release(
+        hold_on_other_key in any::<bool>(),
+        inputs in prop::collection::vec((input_event(), interval()), 0..64),
+    ) {
+        prop_assert_eq!(fuzz::run(fuzz::config(hold_on_other_key), inputs), Ok(()));
+    }
+
+    #[test]
+    fn fuzz_decode(data in prop::collection::vec(any::<u8>(), 0..256)) {
+        prop_assert_eq!(fuzz::run(fuzz::config(data.first() == Some(&0)), fuzz::decode(&data)), Ok(()));
+    }
+}
@@ -34,3 +34,29 @@ fn layer_change_during_press() {
         "Aml deactivated"
     );
 }
+
+#[test]
+fn release_after_layer_change() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = MO(1);
+    keymap.layers[0].keymap[0][1] =
+        KeyAction::TapHold(KeyCode::Key(Key::B), KeyCode::Modifier(Modifier::LShft));
+    keymap.layers[1].keymap[0][1] = KeyAction::Normal(KeyCode::Key(Key::A));
+
+    let mut state = new_state(keymap);
+    let _ = update!(state, time(0));
+
+    let _ = update!(state, time(0), (0, 0, true));
+    let report = update!(state, time(10), (0, 1, true));
+    assert_eq!(report.keyboard_report.unwrap().keycodes[0], 0x04, "Key 'A' at layer 1 is pressed");
+
+    let report = update!(state, time(10), (0, 0, false));
+    assert_eq!(report.highest_layer, 0, "Layer 1 is deactivated");
+
+    let report = update!(state, time(10), (0, 1, false));
+    assert_eq!(
+        report.keyboard_report.unwrap().keycodes,
+        [0, 0, 0, 0, 0, 0],
+        "Key 'A' is released even though the key is tap-hold in layer 0"
+    );
+}
@@ -2,10 +2,12 @@ mod action;
 mod basic;
 mod combo;
 mod encoder;
+mod fuzz;
 mod keycode;
 mod keymap;
 mod layer;
 mod mouse;
+mod scenario;
 mod special;
 
 #[allow(unused_imports)]
@@ -14,7 +16,8 @@ mod prelude {
 
     pub(super) use super::keymap::EMPTY_KEYMAP;
     use crate::interface::state::config::{
-        ComboConfig, KeyResolverConfig, MouseConfig, StateConfig, TapDanceConfig, TapHoldConfig,
+        ComboConfig, KeyResolverConfig, MouseConfig, PointerMode, StateConfig, TapDanceConfig,
+        TapHoldConfig,
     };
     pub(super) use crate::{
         interface::state::input_event::InputEvent,
@@ -96,6 +99,7 @@ mod prelude {
                     auto_mouse_threshold: 5,
                     scroll_divider_x: 20,
                     scroll_divider_y: -12,
+                    pointer_modes: [PointerMode::Move, PointerMode::Scroll],
                 },
                 key_resolver: KeyResolverConfig {
                     tap_hold: TapHoldConfig { threshold: 300, hold_on_other_key: true },
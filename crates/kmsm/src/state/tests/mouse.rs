@@ -61,3 +61,44 @@ pub fn scroll_remained() {
     expected.mouse_report.as_mut().unwrap().wheel = -1;
     assert_eq!(report, expected, "Consume remaining pan");
 }
+
+#[test]
+pub fn pointer_modes() {
+    let mut state = new_state(EMPTY_KEYMAP);
+
+    let report = state.update(InputEvent::Pointer((0, (3, 4))), time(0));
+    let mut expected = MOUSE_ONLY_REPORT;
+    expected.mouse_report.as_mut().unwrap().x = 3;
+    expected.mouse_report.as_mut().unwrap().y = 4;
+    assert_eq!(report.mouse_report, expected.mouse_report, "Pointer 0 moves the cursor");
+
+    let report = state.update(InputEvent::Pointer((1, (20, -12))), time(10));
+    let mut expected = MOUSE_ONLY_REPORT;
+    expected.mouse_report.as_mut().unwrap().pan = 1;
+    expected.mouse_report.as_mut().unwrap().wheel = 1;
+    assert_eq!(report.mouse_report, expected.mouse_report, "Pointer 1 scrolls");
+
+    let report = state.update(InputEvent::Pointer((5, (1, 2))), time(20));
+    let mut expected = MOUSE_ONLY_REPORT;
+    expected.mouse_report.as_mut().unwrap().x = 1;
+    expected.mouse_report.as_mut().unwrap().y = 2;
+    assert_eq!(report.mouse_report, expected.mouse_report, "Unknown pointer moves the cursor");
+}
+
+#[test]
+pub fn pointer_scroll_with_moscrl() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = KeyAction::Normal(KeyCode::Special(Special::MoScrl));
+
+    let mut state = new_state(keymap);
+
+    let _ = update!(state, time(0), (0, 0, true));
+    let _ = state.update(InputEvent::Pointer((1, (10, 0))), time(10));
+    let report = state.update(InputEvent::Pointer((0, (10, 0))), time(20));
+    let mut expected = MOUSE_ONLY_REPORT;
+    expected.mouse_report.as_mut().unwrap().pan = 1;
+    assert_eq!(
+        report.mouse_report, expected.mouse_report,
+        "Both pointers scroll while MoScrl is pressed and share the remainder"
+    );
+}
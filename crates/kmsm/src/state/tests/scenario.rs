@@ -0,0 +1,83 @@
+use super::prelude::*;
+use crate::scenario::{Mismatch, ParseError, Scenario};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn scenario_combo() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = KeyAction::Normal(KeyCode::Key(Key::G));
+    keymap.layers[0].keymap[0][1] = KeyAction::Normal(KeyCode::Key(Key::H));
+
+    Scenario::parse(
+        "
+        # 'G' and 'H' within combo threshold is 'I'
+        0ms   press 0 0
+        10ms  press 0 1
+        10ms  => keyboard I
+        150ms release 0 0
+        150ms => keyboard I
+        200ms release 0 1
+        200ms => keyboard
+        ",
+    )
+    .unwrap()
+    .assert(&mut new_state(keymap));
+}
+
+#[test]
+fn scenario_builder() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = KeyAction::Normal(KeyCode::Key(Key::A));
+    keymap.layers[0].keymap[0][1] = KeyAction::Normal(KeyCode::Modifier(Modifier::LShft));
+
+    Scenario::new()
+        .press(0, 0, 1)
+        .expect(0, "keyboard LShft")
+        .press(30, 0, 0)
+        .expect(30, "keyboard  LShft   A")
+        .release(250, 0, 0)
+        .expect(250, "keyboard LShft")
+        .assert(&mut new_state(keymap));
+}
+
+#[test]
+fn scenario_mismatch() {
+    let mut keymap = EMPTY_KEYMAP;
+    keymap.layers[0].keymap[0][0] = KeyAction::Normal(KeyCode::Key(Key::G));
+
+    let result = Scenario::new()
+        .press(0, 0, 0)
+        .expect(0, "keyboard G")
+        .release(100, 0, 0)
+        .expect(100, "keyboard")
+        .run(&mut new_state(keymap));
+
+    let mismatch = Mismatch {
+        expected: vec!["0ms keyboard G".to_string(), "100ms keyboard".to_string()],
+        actual: vec!["21ms keyboard G".to_string(), "100ms keyboard".to_string()],
+    };
+    assert_eq!(result, Err(mismatch.clone()));
+    assert_eq!(
+        mismatch.to_string(),
+        "Reports differ from the scenario (-expected +actual):\n\
+         - 0ms keyboard G\n\
+         + 21ms keyboard G\n\
+         \x20 100ms keyboard\n"
+    );
+}
+
+#[test]
+fn scenario_parse_error() {
+    assert_eq!(
+        Scenario::parse("0ms press 0 0\n10ms hold 0 0"),
+        Err(ParseError { line: 2, message: "unknown command: hold".to_string() })
+    );
+    assert_eq!(
+        Scenario::parse("0ms press 0"),
+        Err(ParseError { line: 1, message: "missing argument: col".to_string() })
+    );
+    assert_eq!(
+        Scenario::parse("now press 0 0"),
+        Err(ParseError { line: 1, message: "invalid number: now".to_string() })
+    );
+}
@@ -69,8 +69,8 @@ impl Updater<'_> {
         cb(output_event);
     }
 
-    pub fn update_by_mouse_move(&mut self, mv: (i8, i8), _cb: impl FnMut(OutputEvent)) {
-        self.mouse.update_by_mouse_move(mv);
+    pub fn update_by_mouse_move(&mut self, id: u8, mv: (i8, i8), _cb: impl FnMut(OutputEvent)) {
+        self.mouse.update_by_mouse_move(id, mv);
     }
 
     pub fn end<
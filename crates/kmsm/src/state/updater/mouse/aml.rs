@@ -44,7 +44,9 @@ impl Aml {
                 }
             }
             AmlState::Inactive(movement) => {
-                *movement += mouse_event.0.unsigned_abs() + mouse_event.1.unsigned_abs();
+                *movement = movement
+                    .saturating_add(mouse_event.0.unsigned_abs())
+                    .saturating_add(mouse_event.1.unsigned_abs());
                 if *movement > self.auto_mouse_threshold {
                     changed = true;
                     self.state = AmlState::Active(now);
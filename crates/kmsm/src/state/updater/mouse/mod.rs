@@ -1,6 +1,6 @@
 use crate::{
     interface::state::{
-        config::MouseConfig,
+        config::{MAX_POINTERS, MouseConfig, PointerMode},
         output_event::{EventType, OutputEvent},
     },
     keycode::{KeyCode, key::Key, special::Special},
@@ -17,6 +17,7 @@ pub struct MouseState {
     scroll_remained: (i8, i8),
     scroll_divider_x: i8,
     scroll_divider_y: i8,
+    pointer_modes: [PointerMode; MAX_POINTERS],
 
     aml: Aml,
     arrow_mouse_move: (i8, i8),
@@ -30,6 +31,7 @@ impl MouseState {
             scroll_remained: (0, 0),
             scroll_divider_x: config.scroll_divider_x,
             scroll_divider_y: config.scroll_divider_y,
+            pointer_modes: config.pointer_modes,
 
             aml: Aml::new(
                 Duration::from_millis(config.auto_mouse_duration),
@@ -41,7 +43,13 @@ impl MouseState {
     }
 
     pub fn start_update<'a>(&'a mut self) -> MouseUpdater<'a> {
-        MouseUpdater { state: self, mouse_move: (0, 0), disable_aml: false, extend_aml: false }
+        MouseUpdater {
+            state: self,
+            mouse_move: (0, 0),
+            scroll_move: (0, 0),
+            disable_aml: false,
+            extend_aml: false,
+        }
     }
 }
 
@@ -49,6 +57,8 @@ impl MouseState {
 pub struct MouseUpdater<'a> {
     state: &'a mut MouseState,
     mouse_move: (i8, i8),
+    /// Movement of pointers in [`PointerMode::Scroll`]
+    scroll_move: (i8, i8),
     disable_aml: bool,
     extend_aml: bool,
 }
@@ -83,9 +93,13 @@ impl<'a> MouseUpdater<'a> {
         }
     }
 
-    pub fn update_by_mouse_move(&mut self, (x, y): (i8, i8)) {
-        self.mouse_move.0 += x;
-        self.mouse_move.1 += y;
+    pub fn update_by_mouse_move(&mut self, id: u8, (x, y): (i8, i8)) {
+        let mv = match self.state.pointer_modes.get(id as usize) {
+            Some(PointerMode::Scroll) => &mut self.scroll_move,
+            _ => &mut self.mouse_move,
+        };
+        mv.0 = mv.0.saturating_add(x);
+        mv.1 = mv.1.saturating_add(y);
     }
 
     pub fn end<
@@ -113,8 +127,10 @@ impl<'a> MouseUpdater<'a> {
         mut cb: impl FnMut(OutputEvent),
     ) {
         if shared_state.keymap.layers[highest_layer].arrow_mouse {
-            self.state.arrow_mouse_move.0 += self.mouse_move.0;
-            self.state.arrow_mouse_move.1 += self.mouse_move.1;
+            self.state.arrow_mouse_move.0 =
+                self.state.arrow_mouse_move.0.saturating_add(self.mouse_move.0);
+            self.state.arrow_mouse_move.1 =
+                self.state.arrow_mouse_move.1.saturating_add(self.mouse_move.1);
 
             let key = if self.state.arrow_mouse_move.1 > 50 {
                 Some(Key::Right)
@@ -129,7 +145,10 @@ impl<'a> MouseUpdater<'a> {
             };
 
             if let Some(key) = key {
+                // Arrow key is tapped. Both events are sent in the same update and the key is
+                // released in the next report.
                 cb(OutputEvent::KeyCode((KeyCode::Key(key), EventType::Pressed)));
+                cb(OutputEvent::KeyCode((KeyCode::Key(key), EventType::Released)));
                 self.state.arrow_mouse_move = (0, 0);
             }
 
@@ -138,8 +157,11 @@ impl<'a> MouseUpdater<'a> {
             self.state.arrow_mouse_move = (0, 0);
             let (enabled, changed) = self.state.aml.enabled_changed(
                 shared_state.now,
-                self.mouse_move,
-                self.extend_aml || self.state.scroll_mode,
+                (
+                    self.mouse_move.0.saturating_add(self.scroll_move.0),
+                    self.mouse_move.1.saturating_add(self.scroll_move.1),
+                ),
+                self.extend_aml || self.state.scroll_mode || self.scroll_move != (0, 0),
                 self.disable_aml,
             );
             if changed {
@@ -147,20 +169,24 @@ impl<'a> MouseUpdater<'a> {
             }
         }
 
-        if self.mouse_move != (0, 0) {
-            if self.state.scroll_mode {
-                let pan_raw = self.mouse_move.0 + self.state.scroll_remained.0;
-                let pan = pan_raw / self.state.scroll_divider_x;
-                self.state.scroll_remained.0 = pan_raw % self.state.scroll_divider_x;
+        let mut scroll_move = self.scroll_move;
+        if self.state.scroll_mode {
+            scroll_move.0 = scroll_move.0.saturating_add(self.mouse_move.0);
+            scroll_move.1 = scroll_move.1.saturating_add(self.mouse_move.1);
+        } else if self.mouse_move != (0, 0) {
+            cb(OutputEvent::MouseMove(self.mouse_move));
+        }
 
-                let wheel_raw = self.mouse_move.1 + self.state.scroll_remained.1;
-                let wheel = wheel_raw / self.state.scroll_divider_y;
-                self.state.scroll_remained.1 = wheel_raw % self.state.scroll_divider_y;
+        if scroll_move != (0, 0) {
+            let pan_raw = scroll_move.0.saturating_add(self.state.scroll_remained.0);
+            let pan = pan_raw / self.state.scroll_divider_x;
+            self.state.scroll_remained.0 = pan_raw % self.state.scroll_divider_x;
 
-                cb(OutputEvent::MouseScroll((pan, wheel)));
-            } else {
-                cb(OutputEvent::MouseMove(self.mouse_move));
-            }
+            let wheel_raw = scroll_move.1.saturating_add(self.state.scroll_remained.1);
+            let wheel = wheel_raw / self.state.scroll_divider_y;
+            self.state.scroll_remained.1 = wheel_raw % self.state.scroll_divider_y;
+
+            cb(OutputEvent::MouseScroll((pan, wheel)));
         }
     }
 }
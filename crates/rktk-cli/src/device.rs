@@ -0,0 +1,200 @@
+//! Commands over rrp, independent of the transport.
+
+use std::fmt::Debug;
+
+use futures::TryStreamExt as _;
+use kmsm::{interface::state::config::StateConfig, keycode::KeyAction};
+use rktk_rrp::{
+    client::Client,
+    endpoints::{
+        KeyActionLoc,
+        copy_profile::CopyProfile,
+        get_backup::BackupItem,
+        get_calibration::CalibrationChunk,
+        get_keyboard_info::KeyboardInfo,
+        get_log::{LogChunk, LogLevel},
+        get_profiles::ProfileInfo,
+        get_split_stats::SplitStats,
+        get_storage_stats::StorageStats,
+        reset_key::KeyPos,
+    },
+    transport::{ReadTransport, Target, WriteTransport},
+};
+
+#[derive(Debug, PartialEq)]
+pub struct LogRecord {
+    pub time: u64,
+    pub level: LogLevel,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+pub struct Device<RT: ReadTransport + Unpin, WT: WriteTransport + Unpin> {
+    client: Client<RT, WT>,
+}
+
+impl<RT, WT> Device<RT, WT>
+where
+    RT: ReadTransport + Unpin,
+    WT: WriteTransport + Unpin,
+    RT::Error: Debug + Send + Sync + 'static,
+    WT::Error: Debug + Send + Sync + 'static,
+{
+    pub fn new(client: Client<RT, WT>) -> Self {
+        Self { client }
+    }
+
+    pub fn set_target(&mut self, target: Target) {
+        self.client.set_target(target);
+    }
+
+    pub async fn info(&mut self) -> anyhow::Result<KeyboardInfo> {
+        Ok(self.client.get_keyboard_info(()).await?)
+    }
+
+    pub async fn keymap_dump(&mut self) -> anyhow::Result<Vec<KeyActionLoc>> {
+        Ok(self.client.get_keymaps(()).await?.try_collect().await?)
+    }
+
+    /// Writes keys after checking that all of them are in range of the keyboard.
+    pub async fn keymap_load(&mut self, keys: Vec<KeyActionLoc>) -> anyhow::Result<()> {
+        let info = self.info().await?;
+        if let Some(key) = keys.iter().find(|k| {
+            k.layer >= info.keymap.layer_count || k.row >= info.rows || k.col >= info.cols
+        }) {
+            anyhow::bail!(
+                "Key out of range: layer={}, row={}, col={} (keyboard has {} layers, {} rows, {} cols)",
+                key.layer,
+                key.row,
+                key.col,
+                info.keymap.layer_count,
+                info.rows,
+                info.cols
+            );
+        }
+
+        self.client.set_keymaps(futures::stream::iter(keys)).await?;
+        Ok(())
+    }
+
+    /// Resets the key to the default keymap of the firmware and returns the new key action.
+    pub async fn keymap_reset(&mut self, layer: u8, row: u8, col: u8) -> anyhow::Result<KeyAction> {
+        Ok(self.client.reset_key(KeyPos { layer, row, col }).await?)
+    }
+
+    pub async fn config_get(&mut self) -> anyhow::Result<StateConfig> {
+        Ok(self.client.get_keymap_config(()).await?)
+    }
+
+    pub async fn config_set(&mut self, config: StateConfig) -> anyhow::Result<()> {
+        self.client.set_keymap_config(config).await?;
+        Ok(())
+    }
+
+    /// Fetches logs buffered in the keyboard.
+    pub async fn log(&mut self) -> anyhow::Result<Vec<LogRecord>> {
+        let chunks: Vec<LogChunk> = self.client.get_log(()).await?.try_collect().await?;
+
+        let mut records = Vec::new();
+        let mut current = None;
+        for chunk in chunks {
+            match chunk {
+                LogChunk::Start { time, level, line } => {
+                    if let Some(record) = current.take() {
+                        records.push(record);
+                    }
+                    current = Some(LogRecord { time, level, line, message: String::new() });
+                }
+                LogChunk::Bytes { bytes, len } => {
+                    if let Some(record) = &mut current {
+                        record.message.push_str(&String::from_utf8_lossy(&bytes[..len as usize]));
+                    }
+                }
+                LogChunk::End => {
+                    if let Some(record) = current.take() {
+                        records.push(record);
+                    }
+                }
+            }
+        }
+        records.extend(current);
+
+        Ok(records)
+    }
+
+    pub async fn calibrate(&mut self, enabled: bool) -> anyhow::Result<()> {
+        self.client.set_calibration_mode(enabled).await?;
+        Ok(())
+    }
+
+    /// Reads calibration data of magnetic switches. Empty if the keyboard doesn't support
+    /// calibration.
+    pub async fn calibration(&mut self) -> anyhow::Result<Vec<u8>> {
+        let chunks: Vec<CalibrationChunk> =
+            self.client.get_calibration(()).await?.try_collect().await?;
+        let mut data = Vec::new();
+        for chunk in chunks {
+            if chunk.offset as usize != data.len() {
+                anyhow::bail!("Unexpected calibration chunk at offset {}", chunk.offset);
+            }
+            data.extend_from_slice(&chunk.data);
+        }
+        Ok(data)
+    }
+
+    pub async fn profiles(&mut self) -> anyhow::Result<ProfileInfo> {
+        Ok(self.client.get_profiles(()).await?)
+    }
+
+    pub async fn profile_select(&mut self, profile: u8) -> anyhow::Result<()> {
+        self.client
+            .set_active_profile(profile)
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to select profile: {e:?}"))
+    }
+
+    pub async fn profile_copy(&mut self, from: u8, to: u8) -> anyhow::Result<()> {
+        self.client
+            .copy_profile(CopyProfile { from, to })
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to copy profile: {e:?}"))
+    }
+
+    pub async fn storage_stats(&mut self) -> anyhow::Result<StorageStats> {
+        self.client.get_storage_stats(()).await?.ok_or_else(|| anyhow::anyhow!("No storage"))
+    }
+
+    pub async fn split_stats(&mut self) -> anyhow::Result<SplitStats> {
+        self.client
+            .get_split_stats(())
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Not a split keyboard"))
+    }
+
+    pub async fn storage_save(&mut self) -> anyhow::Result<()> {
+        self.client
+            .commit_storage(())
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to save changes: {e:?}"))
+    }
+
+    pub async fn backup(&mut self) -> anyhow::Result<Vec<BackupItem>> {
+        Ok(self.client.get_backup(()).await?.try_collect().await?)
+    }
+
+    /// Restores backup after checking that it is compatible with the keyboard.
+    pub async fn restore(&mut self, items: Vec<BackupItem>) -> anyhow::Result<()> {
+        let Some(BackupItem::Header(header)) = items.first() else {
+            anyhow::bail!("Backup has no header");
+        };
+        let info = self.info().await?;
+        header
+            .check_compatible(&info)
+            .map_err(|e| anyhow::anyhow!("Backup is not compatible with this keyboard: {e:?}"))?;
+
+        self.client
+            .set_backup(futures::stream::iter(items))
+            .await?
+            .map_err(|e| anyhow::anyhow!("Failed to restore backup: {e:?}"))
+    }
+}
@@ -0,0 +1,73 @@
+//! rrp-hid transport using `async-hid`.
+//!
+//! Same as the native backend of rktk-client.
+
+use async_hid::{AsyncHidRead as _, AsyncHidWrite as _, DeviceReader, DeviceWriter, HidBackend};
+use futures::StreamExt as _;
+use rktk_rrp::transport::{ReadTransport, WriteTransport};
+
+const RRP_USAGE_PAGE: u16 = 0xFF70;
+const RRP_USAGE: u16 = 0x71;
+
+/// Opens the first keyboard which has rrp interface.
+pub async fn open() -> anyhow::Result<(HidReader, HidWriter)> {
+    let backend = HidBackend::default();
+    let mut devices = backend.enumerate().await?;
+    while let Some(info) = devices.next().await {
+        if info.usage_page == RRP_USAGE_PAGE && info.usage_id == RRP_USAGE {
+            let (reader, writer) = info.open().await?;
+            return Ok((
+                HidReader { device: reader, remained: Vec::new() },
+                HidWriter { device: writer },
+            ));
+        }
+    }
+
+    anyhow::bail!("Device not found")
+}
+
+pub struct HidReader {
+    device: DeviceReader,
+    remained: Vec<u8>,
+}
+
+impl ReadTransport for HidReader {
+    type Error = anyhow::Error;
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        while self.remained.len() < buf.len() {
+            // One hid report is consist of
+            // data length: 1byte
+            // data:        31byte
+            let mut tmp_buf = [0; 32];
+            let _ = self.device.read_input_report(&mut tmp_buf).await?;
+            let size = tmp_buf[0] as usize;
+            self.remained.extend_from_slice(&tmp_buf[1..=size]);
+        }
+
+        buf.copy_from_slice(&self.remained[..buf.len()]);
+        self.remained.drain(..buf.len());
+
+        Ok(buf.len())
+    }
+}
+
+pub struct HidWriter {
+    device: DeviceWriter,
+}
+
+impl WriteTransport for HidWriter {
+    type Error = anyhow::Error;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for chunk in buf.chunks(31) {
+            // When sending, first byte is report id.
+            let mut data = vec![0, chunk.len() as u8];
+            data.extend_from_slice(chunk);
+            data.resize(33, 0);
+            self.device.write_output_report(&data).await?;
+        }
+
+        Ok(buf.len())
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use clap::{Parser, Subcommand};
+use rktk_rrp::{
+    client::Client,
+    transport::{ReadTransport, Target, WriteTransport},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use device::Device;
+
+mod device;
+mod hid;
+mod socket;
+#[cfg(test)]
+mod tests;
+
+#[derive(Parser)]
+#[command(version, about = "Command line rrp client for rktk keyboards")]
+struct Cli {
+    /// Send requests to the slave side of split keyboard.
+    #[arg(long, global = true)]
+    slave: bool,
+    /// Connect to the simulator (`rktk-sim`) listening on the address instead of hid device.
+    #[arg(long, global = true, value_name = "ADDR")]
+    socket: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show keyboard information
+    Info,
+    /// Dump or load keymap as JSON
+    Keymap {
+        #[command(subcommand)]
+        command: KeymapCommand,
+    },
+    /// Get or set keymap config as JSON
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show logs of the keyboard
+    Log {
+        #[command(subcommand)]
+        command: LogCommand,
+    },
+    /// Start or stop calibration of magnetic switches
+    Calibrate {
+        #[command(subcommand)]
+        command: CalibrateCommand,
+    },
+    /// List, select or copy keymap profiles
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Show storage usage or write pending changes
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommand,
+    },
+    /// Show health of the split link
+    Split {
+        #[command(subcommand)]
+        command: SplitCommand,
+    },
+    /// Save keymap, config and calibration to a file
+    Backup { file: PathBuf },
+    /// Restore keymap, config and calibration from a file
+    Restore { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum KeymapCommand {
+    /// Print keymap as JSON
+    Dump,
+    /// Write keymap from JSON file
+    Load { file: PathBuf },
+    /// Reset a key to the default keymap of the firmware
+    Reset { layer: u8, row: u8, col: u8 },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print config as JSON
+    Get,
+    /// Write config from JSON file
+    Set { file: PathBuf },
+}
+
+#[derive(Subcommand)]
+enum LogCommand {
+    /// Print buffered logs
+    Tail {
+        /// Keep polling logs
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// Show number of profiles and the active one
+    List,
+    /// Make the profile active
+    Select { profile: u8 },
+    /// Copy keymap and config of a profile to another
+    Copy { from: u8, to: u8 },
+}
+
+#[derive(Subcommand)]
+enum StorageCommand {
+    /// Show storage usage
+    Stats,
+    /// Write pending changes to the storage immediately
+    Save,
+}
+
+#[derive(Subcommand)]
+enum SplitCommand {
+    /// Show split link statistics
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum CalibrateCommand {
+    Start,
+    Stop,
+    /// Print calibration data as hex
+    Get,
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    smol::block_on(run(cli))
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    match &cli.socket {
+        Some(addr) => {
+            let (reader, writer) = socket::open(addr).await?;
+            run_command(cli, Device::new(Client::new(reader, writer))).await
+        }
+        None => {
+            let (reader, writer) = hid::open().await?;
+            run_command(cli, Device::new(Client::new(reader, writer))).await
+        }
+    }
+}
+
+async fn run_command<RT, WT>(cli: Cli, mut device: Device<RT, WT>) -> anyhow::Result<()>
+where
+    RT: ReadTransport + Unpin,
+    WT: WriteTransport + Unpin,
+    RT::Error: Debug + Send + Sync + 'static,
+    WT::Error: Debug + Send + Sync + 'static,
+{
+    if cli.slave {
+        device.set_target(Target::Slave);
+    }
+
+    match cli.command {
+        Command::Info => {
+            let info = device.info().await?;
+            println!("Name:   {}", info.name);
+            println!("Rows:   {}", info.rows);
+            println!("Cols:   {}", info.cols);
+            println!("Layers: {}", info.keymap.layer_count);
+        }
+        Command::Keymap { command: KeymapCommand::Dump } => {
+            print_json(&device.keymap_dump().await?)?
+        }
+        Command::Keymap { command: KeymapCommand::Load { file } } => {
+            device.keymap_load(read_json(&file)?).await?;
+        }
+        Command::Keymap { command: KeymapCommand::Reset { layer, row, col } } => {
+            let key = device.keymap_reset(layer, row, col).await?;
+            print_json(&key)?
+        }
+        Command::Config { command: ConfigCommand::Get } => print_json(&device.config_get().await?)?,
+        Command::Config { command: ConfigCommand::Set { file } } => {
+            device.config_set(read_json(&file)?).await?;
+        }
+        Command::Log { command: LogCommand::Tail { follow } } => loop {
+            for log in device.log().await? {
+                println!(
+                    "{:>10} {:<5} {:>4} {}",
+                    log.time,
+                    format!("{:?}", log.level),
+                    log.line.map(|l| l.to_string()).unwrap_or_default(),
+                    log.message
+                );
+            }
+            if !follow {
+                break;
+            }
+            smol::Timer::after(Duration::from_millis(500)).await;
+        },
+        Command::Calibrate { command: CalibrateCommand::Get } => {
+            let data = device.calibration().await?;
+            if data.is_empty() {
+                println!("Calibration is not supported");
+            }
+            for line in data.chunks(16) {
+                let hex: Vec<_> = line.iter().map(|b| format!("{b:02x}")).collect();
+                println!("{}", hex.join(" "));
+            }
+        }
+        Command::Calibrate { command } => {
+            device.calibrate(matches!(command, CalibrateCommand::Start)).await?;
+        }
+        Command::Profile { command: ProfileCommand::List } => {
+            let profiles = device.profiles().await?;
+            for i in 0..profiles.count {
+                let mark = if i == profiles.active { "*" } else { " " };
+                println!("{mark} {i}");
+            }
+        }
+        Command::Profile { command: ProfileCommand::Select { profile } } => {
+            device.profile_select(profile).await?;
+        }
+        Command::Profile { command: ProfileCommand::Copy { from, to } } => {
+            device.profile_copy(from, to).await?;
+        }
+        Command::Storage { command: StorageCommand::Stats } => {
+            let stats = device.storage_stats().await?;
+            if let Some(usage) = stats.usage {
+                println!("Free:    {} / {} bytes", usage.free_bytes, usage.capacity);
+                println!("Erases:  {} (since boot)", usage.erase_count);
+            }
+            println!("Pending: {}", stats.pending);
+        }
+        Command::Storage { command: StorageCommand::Save } => device.storage_save().await?,
+        Command::Split { command: SplitCommand::Stats } => {
+            let stats = device.split_stats().await?;
+            println!("Alive:       {}", stats.alive);
+            match stats.latency_ms {
+                Some(latency) => println!("Latency:     {latency} ms"),
+                None => println!("Latency:     -"),
+            }
+            println!("Sent:        {}", stats.frames_sent);
+            println!("Received:    {}", stats.frames_received);
+            println!("Retransmits: {}", stats.retransmits);
+            println!("Errors:      {}", stats.errors);
+            println!("Lost:        {}", stats.lost);
+            println!("Reinits:     {}", stats.reinits);
+        }
+        Command::Backup { file } => {
+            let items = device.backup().await?;
+            std::fs::write(&file, serde_json::to_vec_pretty(&items)?)
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+        }
+        Command::Restore { file } => device.restore(read_json(&file)?).await?,
+    }
+
+    Ok(())
+}
+
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn read_json<T: DeserializeOwned>(file: &Path) -> anyhow::Result<T> {
+    let data = std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    serde_json::from_slice(&data).with_context(|| format!("Invalid JSON: {}", file.display()))
+}
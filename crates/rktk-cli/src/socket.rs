@@ -0,0 +1,37 @@
+//! rrp transport over TCP, used to connect to `rktk-sim`.
+//!
+//! Unlike hid, rrp data is sent as is because the stream has no packet boundary.
+
+use futures::{AsyncReadExt as _, AsyncWriteExt as _};
+use rktk_rrp::transport::{ReadTransport, WriteTransport};
+use smol::net::TcpStream;
+
+pub async fn open(addr: &str) -> anyhow::Result<(SocketReader, SocketWriter)> {
+    let stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    Ok((SocketReader(stream.clone()), SocketWriter(stream)))
+}
+
+pub struct SocketReader(TcpStream);
+
+impl ReadTransport for SocketReader {
+    type Error = anyhow::Error;
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.0.read(buf).await? {
+            0 => anyhow::bail!("Connection closed"),
+            n => Ok(n),
+        }
+    }
+}
+
+pub struct SocketWriter(TcpStream);
+
+impl WriteTransport for SocketWriter {
+    type Error = anyhow::Error;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write_all(buf).await?;
+        Ok(buf.len())
+    }
+}
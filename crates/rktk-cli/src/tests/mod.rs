@@ -0,0 +1,574 @@
+//! Tests against a simulated keyboard which speaks rrp over an in-process transport.
+
+use std::{cell::RefCell, fmt::Display, future::Future, pin::pin, rc::Rc};
+
+use futures::{
+    Stream, StreamExt as _,
+    channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded},
+    future::{Either, select},
+    stream,
+};
+use kmsm::{
+    interface::state::{
+        KeymapInfo,
+        config::{
+            ComboConfig, KeyResolverConfig, MouseConfig, PointerMode, StateConfig, TapDanceConfig,
+            TapHoldConfig,
+        },
+    },
+    keycode::{KeyAction, prelude::*},
+};
+use rktk_rrp::{
+    client::Client,
+    endpoints::{
+        KeyActionLoc,
+        commit_storage::CommitError,
+        copy_profile::CopyProfile,
+        get_backup::{BACKUP_VERSION, BackupHeader, BackupItem},
+        get_calibration::{CHUNK_SIZE, CalibrationChunk},
+        get_keyboard_info::KeyboardInfo,
+        get_log::{LogChunk, LogLevel},
+        get_profiles::ProfileInfo,
+        get_split_stats::SplitStats,
+        get_storage_stats::{StorageStats, StorageUsage},
+        reset_key::KeyPos,
+        set_active_profile::ProfileError,
+        set_backup::RestoreError,
+    },
+    server::{Server, ServerHandlers},
+    transport::{ReadTransport, WriteTransport, error::ReceiveError},
+};
+
+use crate::device::{Device, LogRecord};
+
+const ROWS: u8 = 2;
+const COLS: u8 = 3;
+const LAYERS: u8 = 2;
+const PROFILES: u8 = 2;
+
+struct ChannelReader {
+    rx: UnboundedReceiver<Vec<u8>>,
+    remained: Vec<u8>,
+}
+
+impl ReadTransport for ChannelReader {
+    type Error = &'static str;
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        while self.remained.len() < buf.len() {
+            let data = self.rx.next().await.ok_or("closed")?;
+            self.remained.extend_from_slice(&data);
+        }
+        buf.copy_from_slice(&self.remained[..buf.len()]);
+        self.remained.drain(..buf.len());
+        Ok(buf.len())
+    }
+}
+
+struct ChannelWriter(UnboundedSender<Vec<u8>>);
+
+impl WriteTransport for ChannelWriter {
+    type Error = &'static str;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.unbounded_send(buf.to_vec()).map_err(|_| "closed")?;
+        Ok(buf.len())
+    }
+}
+
+fn channel() -> (ChannelWriter, ChannelReader) {
+    let (tx, rx) = unbounded();
+    (ChannelWriter(tx), ChannelReader { rx, remained: Vec::new() })
+}
+
+#[derive(Clone)]
+struct SimState {
+    keymap: Vec<KeyActionLoc>,
+    config: StateConfig,
+    calibrating: bool,
+    calibration: Vec<u8>,
+    logs: Vec<LogChunk>,
+    /// Keymap and config of each profile. Values of the active profile are in `keymap` and
+    /// `config`.
+    profiles: Vec<(Vec<KeyActionLoc>, StateConfig)>,
+    active_profile: u8,
+    /// True if keymap or config is changed after the last commit.
+    pending: bool,
+    commit_fails: bool,
+    /// `None` if the keyboard is not split.
+    split: Option<SplitStats>,
+}
+
+impl SimState {
+    fn new() -> Self {
+        let mut keymap = Vec::new();
+        for layer in 0..LAYERS {
+            for row in 0..ROWS {
+                for col in 0..COLS {
+                    keymap.push(KeyActionLoc { layer, row, col, key: KeyAction::Inherit });
+                }
+            }
+        }
+        let config = StateConfig {
+            mouse: MouseConfig {
+                auto_mouse_layer: 1,
+                auto_mouse_duration: 500,
+                auto_mouse_threshold: 1,
+                scroll_divider_x: 20,
+                scroll_divider_y: -12,
+                pointer_modes: [PointerMode::Move, PointerMode::Scroll],
+            },
+            key_resolver: KeyResolverConfig {
+                tap_hold: TapHoldConfig { threshold: 200, hold_on_other_key: true },
+                tap_dance: TapDanceConfig { threshold: 200 },
+                combo: ComboConfig { threshold: 20 },
+            },
+        };
+        Self {
+            profiles: vec![(keymap.clone(), config.clone()); PROFILES as usize],
+            keymap,
+            config,
+            calibrating: false,
+            calibration: Vec::new(),
+            logs: Vec::new(),
+            active_profile: 0,
+            pending: false,
+            commit_fails: false,
+            split: None,
+        }
+    }
+
+    fn save_profile(&mut self) {
+        self.profiles[self.active_profile as usize] = (self.keymap.clone(), self.config.clone());
+    }
+
+    fn select_profile(&mut self, profile: u8) {
+        self.save_profile();
+        (self.keymap, self.config) = self.profiles[profile as usize].clone();
+        self.active_profile = profile;
+    }
+
+    fn set_key(&mut self, key: KeyActionLoc) {
+        if let Some(k) = self
+            .keymap
+            .iter_mut()
+            .find(|k| k.layer == key.layer && k.row == key.row && k.col == key.col)
+        {
+            *k = key;
+        }
+    }
+}
+
+struct SimHandlers(Rc<RefCell<SimState>>);
+
+impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for SimHandlers {
+    type Error = &'static str;
+
+    async fn get_keyboard_info(&mut self, _req: ()) -> Result<KeyboardInfo, Self::Error> {
+        Ok(KeyboardInfo {
+            name: "sim".to_string(),
+            rows: ROWS,
+            cols: COLS,
+            keymap: KeymapInfo {
+                layer_count: LAYERS,
+                max_tap_dance_key_count: 0,
+                max_tap_dance_repeat_count: 0,
+                oneshot_state_size: 0,
+            },
+        })
+    }
+
+    async fn get_keymaps(
+        &mut self,
+        _req: (),
+    ) -> Result<impl Stream<Item = KeyActionLoc>, Self::Error> {
+        Ok(stream::iter(self.0.borrow().keymap.clone()))
+    }
+
+    async fn set_keymaps(
+        &mut self,
+        req: impl Stream<Item = Result<KeyActionLoc, ReceiveError<RE>>>,
+    ) -> Result<(), Self::Error> {
+        let mut req = pin!(req);
+        while let Some(Ok(key)) = req.next().await {
+            self.0.borrow_mut().set_key(key);
+        }
+        self.0.borrow_mut().pending = true;
+        Ok(())
+    }
+
+    async fn get_keymap_config(&mut self, _req: ()) -> Result<StateConfig, Self::Error> {
+        Ok(self.0.borrow().config.clone())
+    }
+
+    async fn set_keymap_config(&mut self, req: StateConfig) -> Result<(), Self::Error> {
+        self.0.borrow_mut().config = req;
+        Ok(())
+    }
+
+    async fn get_log(&mut self, _req: ()) -> Result<impl Stream<Item = LogChunk>, Self::Error> {
+        Ok(stream::iter(std::mem::take(&mut self.0.borrow_mut().logs)))
+    }
+
+    async fn set_calibration_mode(&mut self, req: bool) -> Result<(), Self::Error> {
+        self.0.borrow_mut().calibrating = req;
+        Ok(())
+    }
+
+    async fn get_calibration(
+        &mut self,
+        _req: (),
+    ) -> Result<impl Stream<Item = CalibrationChunk>, Self::Error> {
+        let chunks: Vec<_> = self
+            .0
+            .borrow()
+            .calibration
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(i, data)| CalibrationChunk {
+                offset: (i * CHUNK_SIZE) as u16,
+                data: data.to_vec(),
+            })
+            .collect();
+        Ok(stream::iter(chunks))
+    }
+
+    async fn get_backup(
+        &mut self,
+        _req: (),
+    ) -> Result<impl Stream<Item = BackupItem>, Self::Error> {
+        let state = self.0.borrow();
+        let header = BackupItem::Header(BackupHeader {
+            version: BACKUP_VERSION,
+            storage_version: 1,
+            rows: ROWS,
+            cols: COLS,
+            layers: LAYERS,
+            profiles: 1,
+        });
+        let profile = BackupItem::Profile(state.active_profile);
+        let items = [header, profile, BackupItem::StateConfig(state.config.clone())]
+            .into_iter()
+            .chain(state.keymap.iter().cloned().map(BackupItem::Key))
+            .collect::<Vec<_>>();
+        Ok(stream::iter(items))
+    }
+
+    async fn set_backup(
+        &mut self,
+        req: impl Stream<Item = Result<BackupItem, ReceiveError<RE>>>,
+    ) -> Result<Result<(), RestoreError>, Self::Error> {
+        let items: Vec<_> = req.filter_map(|x| async { x.ok() }).collect().await;
+        let mut state = self.0.borrow_mut();
+        for item in items {
+            match item {
+                BackupItem::Header(h) if h.rows != ROWS || h.cols != COLS => {
+                    return Ok(Err(RestoreError::KeyboardMismatch));
+                }
+                BackupItem::StateConfig(config) => state.config = config,
+                BackupItem::Key(key) => state.set_key(key),
+                _ => {}
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    async fn get_profiles(&mut self, _req: ()) -> Result<ProfileInfo, Self::Error> {
+        Ok(ProfileInfo { count: PROFILES, active: self.0.borrow().active_profile })
+    }
+
+    async fn set_active_profile(
+        &mut self,
+        req: u8,
+    ) -> Result<Result<(), ProfileError>, Self::Error> {
+        if req >= PROFILES {
+            return Ok(Err(ProfileError::OutOfRange));
+        }
+        self.0.borrow_mut().select_profile(req);
+        Ok(Ok(()))
+    }
+
+    async fn copy_profile(
+        &mut self,
+        req: CopyProfile,
+    ) -> Result<Result<(), ProfileError>, Self::Error> {
+        if req.from >= PROFILES || req.to >= PROFILES {
+            return Ok(Err(ProfileError::OutOfRange));
+        }
+        let state = &mut *self.0.borrow_mut();
+        state.save_profile();
+        state.profiles[req.to as usize] = state.profiles[req.from as usize].clone();
+        // Reload the active profile in case it is overwritten.
+        (state.keymap, state.config) = state.profiles[state.active_profile as usize].clone();
+        Ok(Ok(()))
+    }
+
+    async fn reset_key(&mut self, req: KeyPos) -> Result<KeyAction, Self::Error> {
+        if req.layer >= LAYERS || req.row >= ROWS || req.col >= COLS {
+            return Err("Key out of range");
+        }
+        let mut state = self.0.borrow_mut();
+        state.set_key(KeyActionLoc {
+            layer: req.layer,
+            row: req.row,
+            col: req.col,
+            key: KeyAction::Inherit,
+        });
+        state.pending = true;
+        Ok(KeyAction::Inherit)
+    }
+
+    async fn get_storage_stats(&mut self, _req: ()) -> Result<Option<StorageStats>, Self::Error> {
+        Ok(Some(StorageStats {
+            usage: Some(StorageUsage { erase_count: 3, free_bytes: 1000, capacity: 4096 }),
+            pending: self.0.borrow().pending,
+        }))
+    }
+
+    async fn commit_storage(&mut self, _req: ()) -> Result<Result<(), CommitError>, Self::Error> {
+        let mut state = self.0.borrow_mut();
+        if state.commit_fails {
+            return Ok(Err(CommitError::Storage));
+        }
+        state.pending = false;
+        Ok(Ok(()))
+    }
+
+    async fn get_split_stats(&mut self, _req: ()) -> Result<Option<SplitStats>, Self::Error> {
+        Ok(self.0.borrow().split.clone())
+    }
+}
+
+/// Runs `test` with a client connected to the simulated keyboard which has `state`.
+fn run_with_device<F: Future<Output = ()>>(
+    state: Rc<RefCell<SimState>>,
+    test: impl FnOnce(Device<ChannelReader, ChannelWriter>) -> F,
+) {
+    let (c2s_tx, c2s_rx) = channel();
+    let (s2c_tx, s2c_rx) = channel();
+
+    smol::block_on(async {
+        let server = pin!(async {
+            Server::<_, _, _>::new(c2s_rx, s2c_tx, SimHandlers(state)).start::<1024>().await;
+        });
+        let client = pin!(test(Device::new(Client::new(s2c_rx, c2s_tx))));
+        if let Either::Left(_) = select(server, client).await {
+            panic!("server stopped");
+        }
+    });
+}
+
+#[test]
+fn test_info() {
+    run_with_device(Rc::new(RefCell::new(SimState::new())), |mut device| async move {
+        let info = device.info().await.unwrap();
+        assert_eq!(info.name, "sim");
+        assert_eq!((info.rows, info.cols, info.keymap.layer_count), (ROWS, COLS, LAYERS));
+    });
+}
+
+#[test]
+fn test_keymap_dump_load() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        let mut keymap = device.keymap_dump().await.unwrap();
+        assert_eq!(keymap.len(), (ROWS * COLS * LAYERS) as usize);
+
+        keymap[4].key = A;
+        let json = serde_json::to_string(&keymap).unwrap();
+        device.keymap_load(serde_json::from_str(&json).unwrap()).await.unwrap();
+
+        assert_eq!(device.keymap_dump().await.unwrap(), keymap);
+    });
+    assert_eq!(state.borrow().keymap[4].key, A);
+}
+
+#[test]
+fn test_keymap_load_out_of_range() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        let keys = vec![KeyActionLoc { layer: 0, row: ROWS, col: 0, key: B }];
+        assert!(device.keymap_load(keys).await.is_err());
+    });
+    assert!(state.borrow().keymap.iter().all(|k| k.key == KeyAction::Inherit));
+}
+
+#[test]
+fn test_config_get_set() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        let mut config = device.config_get().await.unwrap();
+        config.mouse.auto_mouse_layer = 3;
+        device.config_set(config.clone()).await.unwrap();
+        assert_eq!(device.config_get().await.unwrap(), config);
+    });
+    assert_eq!(state.borrow().config.mouse.auto_mouse_layer, 3);
+}
+
+#[test]
+fn test_log() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    let mut bytes = [0; 32];
+    bytes[..5].copy_from_slice(b"hello");
+    state.borrow_mut().logs = vec![
+        LogChunk::Start { time: 10, level: LogLevel::Warn, line: Some(42) },
+        LogChunk::Bytes { bytes, len: 5 },
+        LogChunk::End,
+        LogChunk::Start { time: 20, level: LogLevel::Info, line: None },
+        LogChunk::End,
+    ];
+    run_with_device(state, |mut device| async move {
+        let logs = device.log().await.unwrap();
+        assert_eq!(
+            logs,
+            vec![
+                LogRecord {
+                    time: 10,
+                    level: LogLevel::Warn,
+                    line: Some(42),
+                    message: "hello".to_string()
+                },
+                LogRecord { time: 20, level: LogLevel::Info, line: None, message: String::new() },
+            ]
+        );
+        assert!(device.log().await.unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_calibrate() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        device.calibrate(true).await.unwrap();
+    });
+    assert!(state.borrow().calibrating);
+}
+
+#[test]
+fn test_calibration() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        assert!(device.calibration().await.unwrap().is_empty());
+    });
+
+    let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+    state.borrow_mut().calibration = data.clone();
+    run_with_device(state, |mut device| async move {
+        assert_eq!(device.calibration().await.unwrap(), data);
+    });
+}
+
+#[test]
+fn test_backup_restore() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    state.borrow_mut().keymap[0].key = A;
+    let original = state.borrow().clone();
+
+    run_with_device(state.clone(), |mut device| async move {
+        let backup = device.backup().await.unwrap();
+        let json = serde_json::to_vec(&backup).unwrap();
+
+        let mut keymap = device.keymap_dump().await.unwrap();
+        keymap[0].key = B;
+        device.keymap_load(keymap).await.unwrap();
+
+        device.restore(serde_json::from_slice(&json).unwrap()).await.unwrap();
+    });
+
+    assert_eq!(state.borrow().keymap, original.keymap);
+    assert_eq!(state.borrow().config, original.config);
+}
+
+#[test]
+fn test_restore_incompatible() {
+    run_with_device(Rc::new(RefCell::new(SimState::new())), |mut device| async move {
+        let backup = vec![BackupItem::Header(BackupHeader {
+            version: BACKUP_VERSION,
+            storage_version: 1,
+            rows: ROWS + 1,
+            cols: COLS,
+            layers: LAYERS,
+            profiles: 1,
+        })];
+        assert!(device.restore(backup).await.is_err());
+        assert!(device.restore(vec![]).await.is_err());
+    });
+}
+
+#[test]
+fn test_profiles() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        assert_eq!(device.profiles().await.unwrap(), ProfileInfo { count: PROFILES, active: 0 });
+
+        let mut keymap = device.keymap_dump().await.unwrap();
+        keymap[0].key = A;
+        device.keymap_load(keymap).await.unwrap();
+
+        device.profile_select(1).await.unwrap();
+        assert_eq!(device.profiles().await.unwrap().active, 1);
+        assert_eq!(device.keymap_dump().await.unwrap()[0].key, KeyAction::Inherit);
+
+        device.profile_copy(0, 1).await.unwrap();
+        assert_eq!(device.keymap_dump().await.unwrap()[0].key, A);
+
+        assert!(device.profile_select(PROFILES).await.is_err());
+        assert!(device.profile_copy(0, PROFILES).await.is_err());
+    });
+    assert_eq!(state.borrow().active_profile, 1);
+}
+
+#[test]
+fn test_storage() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        let keymap = device.keymap_dump().await.unwrap();
+        device.keymap_load(keymap).await.unwrap();
+
+        let stats = device.storage_stats().await.unwrap();
+        assert!(stats.pending);
+        assert_eq!(stats.usage.unwrap().free_bytes, 1000);
+
+        device.storage_save().await.unwrap();
+        assert!(!device.storage_stats().await.unwrap().pending);
+    });
+
+    state.borrow_mut().commit_fails = true;
+    run_with_device(state, |mut device| async move {
+        assert!(device.storage_save().await.is_err());
+    });
+}
+
+#[test]
+fn test_keymap_reset() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    state.borrow_mut().keymap[1].key = A;
+    run_with_device(state.clone(), |mut device| async move {
+        assert_eq!(device.keymap_reset(0, 0, 1).await.unwrap(), KeyAction::Inherit);
+        assert!(device.keymap_reset(0, ROWS, 0).await.is_err());
+    });
+    assert_eq!(state.borrow().keymap[1].key, KeyAction::Inherit);
+}
+
+#[test]
+fn test_split_stats() {
+    let state = Rc::new(RefCell::new(SimState::new()));
+    run_with_device(state.clone(), |mut device| async move {
+        assert!(device.split_stats().await.is_err());
+    });
+
+    let stats = SplitStats {
+        alive: true,
+        frames_sent: 100,
+        frames_received: 98,
+        retransmits: 3,
+        errors: 1,
+        lost: 0,
+        reinits: 0,
+        latency_ms: Some(4),
+    };
+    state.borrow_mut().split = Some(stats.clone());
+    run_with_device(state, |mut device| async move {
+        assert_eq!(device.split_stats().await.unwrap(), stats);
+    });
+}
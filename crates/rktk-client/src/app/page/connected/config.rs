@@ -1,5 +1,5 @@
 use dioxus::prelude::*;
-use kmsm::interface::state::config::StateConfig;
+use kmsm::interface::state::config::{PointerMode, StateConfig};
 
 use crate::app::{
     cache::{invalidate_cache, use_cache, with_cache},
@@ -44,6 +44,7 @@ pub fn Config() -> Element {
 
 #[component]
 pub fn ConfigInner(initial_config: StateConfig, refetch: Callback<()>) -> Element {
+    let cache = use_cache();
     let mut config = use_signal(|| initial_config.clone());
     let mut calibrating = use_signal(|| false);
 
@@ -86,8 +87,30 @@ pub fn ConfigInner(initial_config: StateConfig, refetch: Callback<()>) -> Elemen
         }};
     }
 
+    macro_rules! pointer_scroll_form {
+        ($name:literal, $index:literal) => {{
+            let value = config.read().mouse.pointer_modes[$index] == PointerMode::Scroll;
+            rsx! {
+                p { class: "col-span-2", $name}
+                input {
+                    class: "col-span-3 checkbox checkbox-sm ml-auto mr-auto",
+                    r#type: "checkbox",
+                    checked: value,
+                    onchange: move |evt| {
+                        config.write().mouse.pointer_modes[$index] = if evt.checked() {
+                            PointerMode::Scroll
+                        } else {
+                            PointerMode::Move
+                        };
+                    }
+                }
+            }
+        }};
+    }
+
     rsx! {
         div { class: "flex flex-col max-w-lg items-center w-full px-4",
+            Profiles { refetch }
             div { class: "grid grid-cols-5 items-center gap-2 w-full",
                 h2 { class: "col-span-5 text-lg font-bold", "Mouse" }
                 {number_form!("Auto mouse layer", mouse.auto_mouse_layer)}
@@ -95,6 +118,8 @@ pub fn ConfigInner(initial_config: StateConfig, refetch: Callback<()>) -> Elemen
                 {number_form!("Auto mouse threshold", mouse.auto_mouse_threshold)}
                 {number_form!("Scroll divider x", mouse.scroll_divider_x)}
                 {number_form!("Scroll divider y", mouse.scroll_divider_y)}
+                {pointer_scroll_form!("Left pointer scrolls", 0)}
+                {pointer_scroll_form!("Right pointer scrolls", 1)}
                 h2 { class: "col-span-5 text-lg mt-5 font-bold", "Key Resolver" }
                 {number_form!("Tap hold threshold", key_resolver.tap_hold.threshold)}
                 {bool_form!("Hold on other key", key_resolver.tap_hold.hold_on_other_key)}
@@ -168,6 +193,205 @@ pub fn ConfigInner(initial_config: StateConfig, refetch: Callback<()>) -> Elemen
                 },
                 if *calibrating.read() { "Stop Calibration" } else { "Start Calibration" }
             }
+
+            div { class: "divider my-6 w-full" }
+
+            Storage {}
+
+            div { class: "divider my-6 w-full" }
+
+            h2 { class: "text-lg font-bold mb-2 text-center", "Backup" }
+            p { class: "text-sm text-gray-500 mb-4 text-center",
+                "Save keymap, config and calibration to a file, or restore them from a file."
+            }
+            button {
+                class: "btn btn-neutral w-full",
+                onclick: move |_| {
+                    spawn(async move {
+                        let result = fetcher::save_backup().await;
+                        match result {
+                            Ok(name) => push_notification(Notification {
+                                message: format!("Backup saved: {name}"),
+                                level: NotificationLevel::Info,
+                                ..Default::default()
+                            }),
+                            Err(e) => push_notification(Notification {
+                                message: format!("Could not save backup: {e:?}"),
+                                level: NotificationLevel::Error,
+                                ..Default::default()
+                            }),
+                        }
+                    });
+                },
+                "Save Backup"
+            }
+            p { class: "text-sm mt-4 mb-1 w-full", "Restore from file" }
+            input {
+                class: "file-input file-input-bordered w-full",
+                r#type: "file",
+                accept: ".json",
+                onchange: move |evt| {
+                    let cache = cache.clone();
+                    async move {
+                        let Some(file) = evt.files().into_iter().next() else {
+                            return;
+                        };
+                        let result = match file.read_bytes().await {
+                            Ok(data) => fetcher::restore_backup(&data).await,
+                            Err(e) => Err(anyhow::anyhow!("{e:?}")),
+                        };
+                        if let Err(e) = result {
+                            push_notification(Notification {
+                                message: format!("Could not restore backup: {e:?}"),
+                                level: NotificationLevel::Error,
+                                ..Default::default()
+                            });
+                        } else {
+                            push_notification(Notification {
+                                message: "Backup restored".to_string(),
+                                level: NotificationLevel::Info,
+                                ..Default::default()
+                            });
+                            invalidate_cache(cache, "get_keymap");
+                            refetch(());
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Shows the active profile and lets the user switch or copy profiles.
+///
+/// Hidden if the keyboard has only one profile.
+#[component]
+fn Profiles(refetch: Callback<()>) -> Element {
+    let cache = use_cache();
+    let mut profiles_res = use_resource(fetcher::get_profiles);
+    let mut copy_to = use_signal(|| 0u8);
+
+    let (count, active) = match &*profiles_res.value().read() {
+        Some(Ok(profiles)) if profiles.count > 1 => (profiles.count, profiles.active),
+        _ => return rsx! {},
+    };
+
+    let on_changed = Callback::new(move |message: String| {
+        push_notification(Notification {
+            message,
+            level: NotificationLevel::Info,
+            ..Default::default()
+        });
+        invalidate_cache(cache.clone(), "get_keymap");
+        profiles_res.restart();
+        refetch(());
+    });
+
+    rsx! {
+        div { class: "grid grid-cols-5 items-center gap-2 w-full mb-5",
+            h2 { class: "col-span-5 text-lg font-bold", "Profile" }
+            p { class: "col-span-2", "Active profile" }
+            select {
+                class: "col-span-3 select select-bordered select-sm",
+                onchange: move |evt| {
+                    let Ok(profile) = evt.value().parse::<u8>() else {
+                        return;
+                    };
+                    spawn(async move {
+                        match fetcher::set_active_profile(profile).await {
+                            Ok(()) => on_changed(format!("Switched to profile {profile}")),
+                            Err(e) => push_notification(Notification {
+                                message: format!("Could not switch profile: {e:?}"),
+                                level: NotificationLevel::Error,
+                                ..Default::default()
+                            }),
+                        }
+                    });
+                },
+                for i in 0..count {
+                    option { value: "{i}", selected: i == active, "Profile {i}" }
+                }
+            }
+            p { class: "col-span-2", "Copy active profile to" }
+            select {
+                class: "col-span-2 select select-bordered select-sm",
+                onchange: move |evt| {
+                    if let Ok(to) = evt.value().parse() {
+                        copy_to.set(to);
+                    }
+                },
+                for i in 0..count {
+                    option { value: "{i}", selected: i == *copy_to.read(), "Profile {i}" }
+                }
+            }
+            button {
+                class: "col-span-1 btn btn-sm btn-neutral",
+                disabled: *copy_to.read() == active,
+                onclick: move |_| {
+                    let (from, to) = (active, *copy_to.read());
+                    spawn(async move {
+                        match fetcher::copy_profile(from, to).await {
+                            Ok(()) => on_changed(format!("Copied profile {from} to {to}")),
+                            Err(e) => push_notification(Notification {
+                                message: format!("Could not copy profile: {e:?}"),
+                                level: NotificationLevel::Error,
+                                ..Default::default()
+                            }),
+                        }
+                    });
+                },
+                "Copy"
+            }
+        }
+    }
+}
+
+/// Shows storage usage and lets the user write pending changes immediately.
+#[component]
+fn Storage() -> Element {
+    let mut stats_res = use_resource(fetcher::get_storage_stats);
+
+    let stats = match &*stats_res.value().read() {
+        Some(Ok(Some(stats))) => stats.clone(),
+        _ => return rsx! {},
+    };
+
+    rsx! {
+        h2 { class: "text-lg font-bold mb-2 text-center", "Storage" }
+        div { class: "grid grid-cols-5 items-center gap-2 w-full",
+            if let Some(usage) = &stats.usage {
+                p { class: "col-span-2", "Free space" }
+                p { class: "col-span-3", "{usage.free_bytes} / {usage.capacity} bytes" }
+                p { class: "col-span-2", "Erases since boot" }
+                p { class: "col-span-3", "{usage.erase_count}" }
+            }
+            p { class: "col-span-2", "Unsaved changes" }
+            p { class: "col-span-3", if stats.pending { "Yes" } else { "No" } }
+        }
+        div { class: "flex gap-2 mt-2 w-full",
+            button {
+                class: "btn btn-neutral grow",
+                disabled: !stats.pending,
+                onclick: move |_| {
+                    spawn(async move {
+                        match fetcher::commit_storage().await {
+                            Ok(()) => push_notification(Notification {
+                                message: "Changes saved to storage".to_string(),
+                                level: NotificationLevel::Info,
+                                ..Default::default()
+                            }),
+                            Err(e) => push_notification(Notification {
+                                message: format!("Could not save changes: {e:?}"),
+                                level: NotificationLevel::Error,
+                                ..Default::default()
+                            }),
+                        }
+                        stats_res.restart();
+                    });
+                },
+                "Save now"
+            }
+            button { class: "btn grow", onclick: move |_| stats_res.restart(), "Refresh" }
         }
     }
 }
@@ -175,7 +399,12 @@ pub fn ConfigInner(initial_config: StateConfig, refetch: Callback<()>) -> Elemen
 mod fetcher {
     use anyhow::Context as _;
     use dioxus::signals::ReadableExt as _;
+    use futures::StreamExt as _;
     use kmsm::interface::state::config::StateConfig;
+    use rktk_rrp::endpoints::{
+        copy_profile::CopyProfile, get_backup::BackupItem, get_profiles::ProfileInfo,
+        get_storage_stats::StorageStats,
+    };
 
     use crate::{app::state::CONN, backend::RrpHidDevice as _};
 
@@ -195,6 +424,64 @@ mod fetcher {
         Ok(())
     }
 
+    pub async fn get_profiles() -> anyhow::Result<ProfileInfo> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        let profiles = conn.device.lock().await.get_client().get_profiles(()).await?;
+
+        Ok(profiles)
+    }
+
+    pub async fn set_active_profile(profile: u8) -> anyhow::Result<()> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        conn.device
+            .lock()
+            .await
+            .get_client()
+            .set_active_profile(profile)
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(())
+    }
+
+    pub async fn copy_profile(from: u8, to: u8) -> anyhow::Result<()> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        conn.device
+            .lock()
+            .await
+            .get_client()
+            .copy_profile(CopyProfile { from, to })
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(())
+    }
+
+    pub async fn get_storage_stats() -> anyhow::Result<Option<StorageStats>> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        let stats = conn.device.lock().await.get_client().get_storage_stats(()).await?;
+
+        Ok(stats)
+    }
+
+    pub async fn commit_storage() -> anyhow::Result<()> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        conn.device
+            .lock()
+            .await
+            .get_client()
+            .commit_storage(())
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(())
+    }
+
     pub async fn set_calibration_mode(enabled: bool) -> anyhow::Result<()> {
         let conn = &*CONN.read();
         let conn = conn.as_ref().context("Not connected")?;
@@ -202,4 +489,48 @@ mod fetcher {
 
         Ok(())
     }
+
+    /// Saves backup as JSON file and returns its name.
+    pub async fn save_backup() -> anyhow::Result<String> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+        let items: Vec<BackupItem> = {
+            let mut device = conn.device.lock().await;
+            let stream = device.get_client().get_backup(()).await?;
+            stream.collect::<Vec<_>>().await.into_iter().collect::<Result<_, _>>()?
+        };
+
+        let name = format!(
+            "{}-backup-{}.json",
+            conn.keyboard.name,
+            jiff::Zoned::now().strftime("%Y%m%d-%H%M%S")
+        );
+        crate::utils::save_file(&name, &serde_json::to_vec_pretty(&items)?)?;
+
+        Ok(name)
+    }
+
+    /// Validates backup against connected keyboard and restores it.
+    pub async fn restore_backup(data: &[u8]) -> anyhow::Result<()> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+
+        let items: Vec<BackupItem> = serde_json::from_slice(data).context("Invalid backup file")?;
+        let Some(BackupItem::Header(header)) = items.first() else {
+            anyhow::bail!("Backup has no header");
+        };
+        header
+            .check_compatible(&conn.keyboard)
+            .map_err(|e| anyhow::anyhow!("Backup is not compatible with this keyboard: {e:?}"))?;
+
+        conn.device
+            .lock()
+            .await
+            .get_client()
+            .set_backup(futures::stream::iter(items))
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,162 @@
+use dioxus::prelude::*;
+
+use crate::app::components::notification::{Notification, NotificationLevel, push_notification};
+
+#[component]
+pub fn Firmware() -> Element {
+    let mut image = use_signal(|| Option::<(String, Vec<u8>)>::None);
+    let mut upload_progress = use_signal(|| Option::<(usize, usize)>::None);
+
+    rsx! {
+        div { class: "w-full flex justify-center",
+            div { class: "flex flex-col max-w-lg items-center w-full px-4 gap-2",
+                h2 { class: "text-lg font-bold text-center", "Firmware Update" }
+                p { class: "text-sm text-gray-500 text-center",
+                    "Select firmware (.bin or .uf2). The keyboard reboots into the bootloader after the firmware is written and verified."
+                }
+                input {
+                    class: "file-input file-input-bordered w-full",
+                    r#type: "file",
+                    accept: ".bin,.uf2",
+                    disabled: upload_progress.read().is_some(),
+                    onchange: move |evt| async move {
+                        let Some(file) = evt.files().into_iter().next() else {
+                            return;
+                        };
+                        let name = file.name();
+                        let res = match file.read_bytes().await {
+                            Ok(data) if name.ends_with(".uf2") => uf2::to_bin(&data),
+                            Ok(data) => Ok(data.to_vec()),
+                            Err(e) => Err(anyhow::anyhow!("{e:?}")),
+                        };
+                        match res {
+                            Ok(data) => image.set(Some((name, data))),
+                            Err(e) => {
+                                image.set(None);
+                                push_notification(Notification {
+                                    message: format!("Could not read firmware: {e:?}"),
+                                    level: NotificationLevel::Error,
+                                    ..Default::default()
+                                });
+                            }
+                        }
+                    },
+                }
+                if let Some((name, data)) = &*image.read() {
+                    p { class: "text-sm", "{name} ({data.len()} bytes)" }
+                }
+                if let Some((sent, total)) = *upload_progress.read() {
+                    progress { class: "progress w-full", value: sent, max: total }
+                }
+                button {
+                    class: "btn btn-primary w-full",
+                    disabled: image.read().is_none() || upload_progress.read().is_some(),
+                    onclick: move |_| {
+                        let Some((_, data)) = image.read().clone() else {
+                            return;
+                        };
+                        spawn(async move {
+                            let result = fetcher::update_firmware(&data, upload_progress).await;
+                            upload_progress.set(None);
+                            match result {
+                                Ok(()) => push_notification(Notification {
+                                    message: "Firmware updated. Keyboard is rebooting.".to_string(),
+                                    level: NotificationLevel::Info,
+                                    ..Default::default()
+                                }),
+                                Err(e) => push_notification(Notification {
+                                    message: format!("Firmware update failed: {e:?}"),
+                                    level: NotificationLevel::Error,
+                                    ..Default::default()
+                                }),
+                            }
+                        });
+                    },
+                    "Update"
+                }
+            }
+        }
+    }
+}
+
+mod uf2 {
+    use anyhow::{Context as _, bail};
+
+    const BLOCK_SIZE: usize = 512;
+    const MAGIC_START0: u32 = 0x0A32_4655;
+    const MAGIC_START1: u32 = 0x9E5D_5157;
+    const MAGIC_END: u32 = 0x0AB1_6F30;
+    const FLAG_NOT_MAIN_FLASH: u32 = 0x0000_0001;
+
+    /// Converts UF2 to the raw binary which starts at the lowest address in the file.
+    pub fn to_bin(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if data.len() % BLOCK_SIZE != 0 {
+            bail!("Invalid UF2 file size");
+        }
+
+        let mut blocks = Vec::new();
+        for block in data.chunks(BLOCK_SIZE) {
+            let word = |i: usize| u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
+            if word(0) != MAGIC_START0
+                || word(1) != MAGIC_START1
+                || word(BLOCK_SIZE / 4 - 1) != MAGIC_END
+            {
+                bail!("Invalid UF2 block");
+            }
+            if word(2) & FLAG_NOT_MAIN_FLASH != 0 {
+                continue;
+            }
+            let (addr, size) = (word(3), word(4) as usize);
+            let payload = block.get(32..32 + size).context("Invalid UF2 payload size")?;
+            blocks.push((addr, payload));
+        }
+
+        let base = blocks.iter().map(|(addr, _)| *addr).min().context("Empty UF2 file")?;
+        let mut bin = Vec::new();
+        for (addr, payload) in blocks {
+            let offset = (addr - base) as usize;
+            if bin.len() < offset + payload.len() {
+                bin.resize(offset + payload.len(), 0xFF);
+            }
+            bin[offset..offset + payload.len()].copy_from_slice(payload);
+        }
+
+        Ok(bin)
+    }
+}
+
+mod fetcher {
+    use anyhow::Context as _;
+    use dioxus::{prelude::*, signals::ReadableExt as _};
+    use futures::StreamExt as _;
+    use rktk_rrp::firmware::firmware_update_requests;
+
+    use crate::{app::state::CONN, backend::RrpHidDevice as _};
+
+    pub async fn update_firmware(
+        image: &[u8],
+        mut progress: Signal<Option<(usize, usize)>>,
+    ) -> anyhow::Result<()> {
+        let conn = &*CONN.read();
+        let conn = conn.as_ref().context("Not connected")?;
+
+        let requests = firmware_update_requests(image);
+        let total = requests.len();
+        progress.set(Some((0, total)));
+
+        let stream =
+            futures::stream::iter(requests.into_iter().enumerate()).map(move |(i, req)| {
+                progress.set(Some((i + 1, total)));
+                req
+            });
+        conn.device
+            .lock()
+            .await
+            .get_client()
+            .update_firmware(stream)
+            .await?
+            .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        Ok(())
+    }
+}
@@ -2,14 +2,18 @@ use std::time::Duration;
 
 use dioxus::prelude::*;
 use jiff::Zoned;
+use rktk_rrp::transport::Target;
 
 use crate::utils::sleep;
 
 #[component]
 pub fn Log() -> Element {
+    let mut target = use_signal(|| Target::Master);
+
     let base_time = use_resource(move || async move {
+        let target = *target.read();
         Result::<_, anyhow::Error>::Ok(
-            (&Zoned::now()) - Duration::from_millis(fetcher::get_device_time().await?),
+            (&Zoned::now()) - Duration::from_millis(fetcher::get_device_time(target).await?),
         )
     });
 
@@ -22,7 +26,8 @@ pub fn Log() -> Element {
             loop {
                 sleep(Duration::from_millis(1000)).await;
                 if *streaming.read() {
-                    let Ok(new_logs) = fetcher::get_log().await else {
+                    let target = *target.peek();
+                    let Ok(new_logs) = fetcher::get_log(target).await else {
                         continue;
                     };
                     logs.write().extend(new_logs);
@@ -34,6 +39,28 @@ pub fn Log() -> Element {
     match &*base_time.value().read() {
         Some(Ok(base_time)) => rsx! {
             div { class: "p-2",
+                select {
+                    class: "select select-sm mr-2",
+                    onchange: move |evt| {
+                        let new_target = match evt.data().value().as_str() {
+                            "master" => Target::Master,
+                            "slave" => Target::Slave,
+                            _ => return,
+                        };
+                        logs.write().clear();
+                        target.set(new_target);
+                    },
+                    option {
+                        value: "master",
+                        selected: *target.read() == Target::Master,
+                        "Master"
+                    }
+                    option {
+                        value: "slave",
+                        selected: *target.read() == Target::Slave,
+                        "Slave"
+                    }
+                }
                 button {
                     class: "btn btn-sm",
                     class: if *streaming.read() { "btn-primary" } else { "btn-secondary" },
@@ -90,16 +117,23 @@ mod fetcher {
     use anyhow::Context as _;
     use dioxus::signals::ReadableExt as _;
     use futures::TryStreamExt;
-    use rktk_rrp::endpoints::get_log::{LogChunk, LogLevel};
+    use rktk_rrp::{
+        endpoints::get_log::{LogChunk, LogLevel},
+        transport::Target,
+    };
 
     use crate::{app::state::CONN, backend::RrpHidDevice as _};
 
-    pub async fn get_device_time() -> anyhow::Result<u64> {
+    pub async fn get_device_time(target: Target) -> anyhow::Result<u64> {
         let conn = &*CONN.read();
         let conn = conn.as_ref().context("Not connected")?;
-        let now = conn.device.lock().await.get_client().get_now(()).await?;
+        let mut device = conn.device.lock().await;
+        let client = device.get_client();
+        client.set_target(target);
+        let now = client.get_now(()).await;
+        client.set_target(Target::Master);
 
-        Ok(now)
+        Ok(now?)
     }
 
     pub struct LogRecord {
@@ -109,18 +143,18 @@ mod fetcher {
         pub message: String,
     }
 
-    pub async fn get_log() -> Result<Vec<LogRecord>, anyhow::Error> {
+    pub async fn get_log(target: Target) -> Result<Vec<LogRecord>, anyhow::Error> {
         let conn = &*CONN.read();
         let conn = conn.as_ref().context("Not connected")?;
-        let log = conn
-            .device
-            .lock()
-            .await
-            .get_client()
-            .get_log(())
-            .await?
-            .try_collect::<Vec<LogChunk>>()
-            .await?;
+        let mut device = conn.device.lock().await;
+        let client = device.get_client();
+        client.set_target(target);
+        let log = match client.get_log(()).await {
+            Ok(stream) => stream.try_collect::<Vec<LogChunk>>().await.map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        client.set_target(Target::Master);
+        let log = log?;
 
         let mut records = Vec::new();
         let mut current_record =
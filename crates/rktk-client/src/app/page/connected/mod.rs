@@ -3,6 +3,7 @@ use dioxus::prelude::*;
 use crate::app::cache::use_cache_context_provider;
 
 mod config;
+mod firmware;
 mod log;
 mod remap;
 
@@ -11,6 +12,7 @@ enum Tabs {
     Remap,
     Config,
     Log,
+    Firmware,
 }
 
 #[component]
@@ -45,6 +47,13 @@ pub fn Connected() -> Element {
                     onclick: move |_| tab.set(Tabs::Log),
                     "Log"
                 }
+                a {
+                    role: "tab",
+                    class: "tab",
+                    class: if *tab.read() == Tabs::Firmware { "tab-active" },
+                    onclick: move |_| tab.set(Tabs::Firmware),
+                    "Firmware"
+                }
             }
             div { class: "grow",
                 match *tab.read() {
@@ -57,6 +66,9 @@ pub fn Connected() -> Element {
                     Tabs::Log => rsx! {
                         log::Log {}
                     },
+                    Tabs::Firmware => rsx! {
+                        firmware::Firmware {}
+                    },
                 }
             }
         }
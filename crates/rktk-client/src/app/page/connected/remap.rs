@@ -132,8 +132,34 @@ pub fn RemapInner(keyboard: KeyboardInfo, keymap: KeymapData, refetch: Callback<
                         let orig_key = keymap[*layer.read()][row][col].clone();
                         rsx! {
                             if let Some(key_action) = modified_keymap.read()[*layer.read()][row][col].action {
-                                div {
+                                div { class: "flex items-center gap-2",
                                     code { {format!("Layer: {}, Row: {}, Col: {}", *layer.read(), row, col)} }
+                                    button {
+                                        class: "btn btn-xs btn-outline",
+                                        onclick: move |_| {
+                                            let layer = *layer.read() as u8;
+                                            spawn(async move {
+                                                match fetcher::reset_key(layer, row as u8, col as u8).await {
+                                                    Ok(_) => {
+                                                        push_notification(Notification {
+                                                            message: "Key reset to default".to_string(),
+                                                            level: NotificationLevel::Info,
+                                                            ..Default::default()
+                                                        });
+                                                        refetch(())
+                                                    }
+                                                    Err(e) => {
+                                                        push_notification(Notification {
+                                                            message: format!("Cannot reset key: {e:?}"),
+                                                            level: NotificationLevel::Error,
+                                                            ..Default::default()
+                                                        });
+                                                    }
+                                                }
+                                            });
+                                        },
+                                        "Reset to default"
+                                    }
                                 }
                                 KeyActionSelector {
                                     key_action,
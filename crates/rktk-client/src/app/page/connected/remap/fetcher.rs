@@ -5,7 +5,7 @@ use dioxus::signals::ReadableExt as _;
 use futures::TryStreamExt as _;
 use kle_serial::Keyboard;
 use kmsm::keycode::KeyAction;
-use rktk_rrp::endpoints::{KeyActionLoc, get_keyboard_info::KeyboardInfo};
+use rktk_rrp::endpoints::{KeyActionLoc, get_keyboard_info::KeyboardInfo, reset_key::KeyPos};
 
 use crate::{app::state::CONN, backend::RrpHidDevice as _};
 
@@ -102,3 +102,12 @@ pub async fn set_keymap(changes: &HashMap<(u8, u8, u8), KeyAction>) -> anyhow::R
 
     Ok(())
 }
+
+/// Resets the key to the default keymap of the firmware and returns the new key action.
+pub async fn reset_key(layer: u8, row: u8, col: u8) -> anyhow::Result<KeyAction> {
+    let conn = &*CONN.read();
+    let conn = conn.as_ref().context("Not connected")?;
+    let key = conn.device.lock().await.get_client().reset_key(KeyPos { layer, row, col }).await?;
+
+    Ok(key)
+}
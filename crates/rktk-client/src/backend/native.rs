@@ -1,10 +1,18 @@
 use async_hid::{AsyncHidRead as _, AsyncHidWrite as _, DeviceReader, DeviceWriter, HidBackend};
 use futures::stream::StreamExt;
 use rktk_rrp::transport::{ReadTransport, WriteTransport};
-use smol::Task;
+use smol::{
+    Task,
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpStream,
+};
 
 use super::{RrpHidBackend, RrpHidDevice};
 
+/// If this environment variable is set, the backend connects to the simulator (`rktk-sim`)
+/// listening on the address instead of hid device.
+const SIM_ADDR_ENV: &str = "RKTK_SIM_ADDR";
+
 pub struct NativeBackend {
     backend: HidBackend,
     _watch_task: Task<()>,
@@ -20,6 +28,17 @@ impl RrpHidBackend for NativeBackend {
         usage_page: u16,
         usage: u16,
     ) -> Result<Self::HidDevice, Self::Error> {
+        if let Ok(addr) = std::env::var(SIM_ADDR_ENV) {
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            return Ok(NativeHidDevice {
+                client: rktk_rrp::client::Client::new(
+                    NativeReader::Socket(stream.clone()),
+                    NativeWriter::Socket(stream),
+                ),
+            });
+        }
+
         let mut device = None;
         let mut devices = self.backend.enumerate().await?;
         while let Some(info) = devices.next().await {
@@ -36,8 +55,8 @@ impl RrpHidBackend for NativeBackend {
 
         Ok(NativeHidDevice {
             client: rktk_rrp::client::Client::new(
-                HidReader { device: reader, remained: Vec::new() },
-                HidWriter { device: writer },
+                NativeReader::Hid(HidReader { device: reader, remained: Vec::new() }),
+                NativeWriter::Hid(HidWriter { device: writer }),
             ),
         })
     }
@@ -59,14 +78,14 @@ impl RrpHidBackend for NativeBackend {
 }
 
 pub struct NativeHidDevice {
-    client: rktk_rrp::client::Client<HidReader, HidWriter>,
+    client: rktk_rrp::client::Client<NativeReader, NativeWriter>,
 }
 impl RrpHidDevice for NativeHidDevice {
     type Error = anyhow::Error;
 
-    type ReadTransport = HidReader;
+    type ReadTransport = NativeReader;
 
-    type WriteTransport = HidWriter;
+    type WriteTransport = NativeWriter;
 
     async fn close(&mut self) -> Result<(), Self::Error> {
         Ok(())
@@ -79,6 +98,45 @@ impl RrpHidDevice for NativeHidDevice {
     }
 }
 
+pub enum NativeReader {
+    Hid(HidReader),
+    /// rrp data is sent as is over TCP, as the stream has no packet boundary.
+    Socket(TcpStream),
+}
+
+impl ReadTransport for NativeReader {
+    type Error = anyhow::Error;
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self {
+            Self::Hid(reader) => reader.read(buf).await,
+            Self::Socket(stream) => match stream.read(buf).await? {
+                0 => Err(anyhow::anyhow!("Connection closed")),
+                n => Ok(n),
+            },
+        }
+    }
+}
+
+pub enum NativeWriter {
+    Hid(HidWriter),
+    Socket(TcpStream),
+}
+
+impl WriteTransport for NativeWriter {
+    type Error = anyhow::Error;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        match self {
+            Self::Hid(writer) => writer.write(buf).await,
+            Self::Socket(stream) => {
+                stream.write_all(buf).await?;
+                Ok(buf.len())
+            }
+        }
+    }
+}
+
 pub struct HidReader {
     device: DeviceReader,
     remained: Vec<u8>,
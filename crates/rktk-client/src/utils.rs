@@ -37,4 +37,10 @@ mod native {
     pub async fn sleep(delay: Duration) {
         smol::Timer::after(delay).await;
     }
+
+    /// Saves data as a file in the current directory.
+    pub fn save_file(name: &str, data: &[u8]) -> anyhow::Result<()> {
+        std::fs::write(name, data)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,142 @@
+//! DFU driver compatible with [embassy-boot](https://crates.io/crates/embassy-boot).
+//!
+//! Firmware is written to the DFU partition and the swap magic is written to the state partition
+//! in the same format as `embassy_boot::FirmwareUpdater`. The bootloader swaps the firmware on
+//! next boot.
+
+use core::fmt::Debug;
+
+use embassy_embedded_hal::flash::partition::Partition;
+use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex};
+use embedded_storage_async::nor_flash::NorFlash;
+use rktk::drivers::interface::{Error, dfu::DfuDriver};
+
+const SWAP_MAGIC: u8 = 0xF0;
+const STATE_ERASE_VALUE: u8 = 0xFF;
+/// Max `WRITE_SIZE` of flash supported by this driver.
+const MAX_WRITE_SIZE: usize = 32;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EmbassyBootDfuError<E: Debug> {
+    Flash(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] E),
+    /// `WRITE_SIZE` of the flash is larger than [`MAX_WRITE_SIZE`].
+    UnsupportedWriteSize,
+}
+impl<E: Debug> Error for EmbassyBootDfuError<E> {}
+
+impl<E: Debug> From<E> for EmbassyBootDfuError<E> {
+    fn from(e: E) -> Self {
+        Self::Flash(e)
+    }
+}
+
+/// DFU driver for embassy-boot.
+///
+/// `dfu` and `state` should be the partitions passed to embassy-boot's bootloader (ex: created
+/// with [`Partition`] or [`EmbassyBootDfu::from_linkerfile`]).
+pub struct EmbassyBootDfu<DFU: NorFlash, STATE: NorFlash> {
+    dfu: DFU,
+    state: STATE,
+}
+
+impl<DFU: NorFlash, STATE: NorFlash> EmbassyBootDfu<DFU, STATE> {
+    pub fn new(dfu: DFU, state: STATE) -> Self {
+        Self { dfu, state }
+    }
+}
+
+impl<'a, M: RawMutex, F: NorFlash> EmbassyBootDfu<Partition<'a, M, F>, Partition<'a, M, F>> {
+    /// Creates partitions of `flash` from the symbols defined in the linker script, same as
+    /// `embassy_boot::FirmwareUpdaterConfig::from_linkerfile`.
+    ///
+    /// `memory.x` must define `__bootloader_state_start`, `__bootloader_state_end`,
+    /// `__bootloader_dfu_start` and `__bootloader_dfu_end` as offsets from the start of `flash`.
+    pub fn from_linkerfile(flash: &'a Mutex<M, F>) -> Self {
+        unsafe extern "C" {
+            static __bootloader_state_start: u32;
+            static __bootloader_state_end: u32;
+            static __bootloader_dfu_start: u32;
+            static __bootloader_dfu_end: u32;
+        }
+
+        // Only addresses of the symbols are meaningful.
+        let state_start = &raw const __bootloader_state_start as u32;
+        let state_end = &raw const __bootloader_state_end as u32;
+        let dfu_start = &raw const __bootloader_dfu_start as u32;
+        let dfu_end = &raw const __bootloader_dfu_end as u32;
+
+        Self::new(
+            Partition::new(flash, dfu_start, dfu_end - dfu_start),
+            Partition::new(flash, state_start, state_end - state_start),
+        )
+    }
+}
+
+impl<DFU: NorFlash, STATE: NorFlash<Error = DFU::Error>> DfuDriver for EmbassyBootDfu<DFU, STATE> {
+    type Error = EmbassyBootDfuError<DFU::Error>;
+
+    fn capacity(&self) -> u32 {
+        self.dfu.capacity() as u32
+    }
+
+    async fn erase(&mut self, size: u32) -> Result<(), Self::Error> {
+        let erase_size = DFU::ERASE_SIZE as u32;
+        self.dfu.erase(0, size.div_ceil(erase_size) * erase_size).await?;
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+        if DFU::WRITE_SIZE > MAX_WRITE_SIZE {
+            return Err(EmbassyBootDfuError::UnsupportedWriteSize);
+        }
+
+        let aligned_len = data.len() / DFU::WRITE_SIZE * DFU::WRITE_SIZE;
+        if aligned_len > 0 {
+            self.dfu.write(offset, &data[..aligned_len]).await?;
+        }
+
+        // Pad the last unaligned part with erased value.
+        let rest = &data[aligned_len..];
+        if !rest.is_empty() {
+            let mut buf = [0xFF; MAX_WRITE_SIZE];
+            buf[..rest.len()].copy_from_slice(rest);
+            self.dfu.write(offset + aligned_len as u32, &buf[..DFU::WRITE_SIZE]).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.dfu.read(offset, buf).await?;
+        Ok(())
+    }
+
+    async fn mark_updated(&mut self) -> Result<(), Self::Error> {
+        if STATE::WRITE_SIZE > MAX_WRITE_SIZE {
+            return Err(EmbassyBootDfuError::UnsupportedWriteSize);
+        }
+
+        let mut buf = [0; MAX_WRITE_SIZE];
+        let aligned = &mut buf[..STATE::WRITE_SIZE];
+
+        self.state.read(0, aligned).await?;
+        if aligned.iter().all(|&b| b == SWAP_MAGIC) {
+            return Ok(());
+        }
+
+        // Invalidate the swap progress before erasing, same as embassy-boot does.
+        self.state.read(STATE::WRITE_SIZE as u32, aligned).await?;
+        if aligned.iter().all(|&b| b == STATE_ERASE_VALUE) {
+            aligned.fill(!STATE_ERASE_VALUE);
+            self.state.write(STATE::WRITE_SIZE as u32, aligned).await?;
+        }
+
+        self.state.erase(0, self.state.capacity() as u32).await?;
+
+        aligned.fill(SWAP_MAGIC);
+        self.state.write(0, aligned).await?;
+
+        Ok(())
+    }
+}
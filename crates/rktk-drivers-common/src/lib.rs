@@ -5,6 +5,7 @@
 #![cfg_attr(doc, feature(doc_cfg))]
 
 pub mod debounce;
+pub mod dfu;
 pub mod display;
 pub mod encoder;
 pub mod keyscan;
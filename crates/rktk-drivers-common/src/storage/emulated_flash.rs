@@ -0,0 +1,339 @@
+//! Nor flash emulated on a memory or file image.
+//!
+//! Combined with [`FlashSequentialMapStorage`](super::flash_sequential_map::FlashSequentialMapStorage),
+//! this runs the same storage code as the firmware on the host. Power loss can be injected to test
+//! that stored data survives interrupted writes.
+
+use core::{convert::Infallible, fmt::Debug, ops::Range};
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+#[cfg(feature = "std")]
+pub use file::{FileImage, FileStorage};
+
+/// Memory which holds the content of [`EmulatedNorFlash`].
+pub trait FlashImage {
+    type Error: Debug;
+
+    fn bytes(&self) -> &[u8];
+    fn bytes_mut(&mut self) -> &mut [u8];
+
+    /// Called after `range` of the image is modified.
+    fn flush(&mut self, _range: Range<usize>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl FlashImage for &mut [u8] {
+    type Error = Infallible;
+
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl<const N: usize> FlashImage for [u8; N] {
+    type Error = Infallible;
+
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EmulatedFlashError<E: Debug> {
+    NotAligned,
+    OutOfBounds,
+    /// Power was lost by [`EmulatedNorFlash::with_power_loss_after`].
+    PowerLoss,
+    Image(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] E),
+}
+
+impl<E: Debug> NorFlashError for EmulatedFlashError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            EmulatedFlashError::NotAligned => NorFlashErrorKind::NotAligned,
+            EmulatedFlashError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+/// Nor flash with pages of `PAGE_SIZE` bytes.
+///
+/// Like real nor flash, erasing sets bytes to `0xFF` and writing can only clear bits.
+pub struct EmulatedNorFlash<I, const PAGE_SIZE: usize> {
+    image: I,
+    /// Number of writes and erases which complete before power is lost.
+    power_loss_after: Option<usize>,
+    powered: bool,
+}
+
+impl<I: FlashImage, const PAGE_SIZE: usize> EmulatedNorFlash<I, PAGE_SIZE> {
+    /// Size of the image must be a multiple of `PAGE_SIZE`.
+    pub fn new(image: I) -> Self {
+        assert!(PAGE_SIZE % <Self as NorFlash>::WRITE_SIZE == 0);
+        assert!(image.bytes().len() % PAGE_SIZE == 0, "Image size must be a multiple of page size");
+        Self { image, power_loss_after: None, powered: true }
+    }
+
+    /// Loses power in the middle of the write or erase after `n` ones complete.
+    ///
+    /// Only the first half of the interrupted operation is applied. The operation and all following
+    /// ones, including reads, fail with [`EmulatedFlashError::PowerLoss`].
+    pub fn with_power_loss_after(mut self, n: usize) -> Self {
+        self.power_loss_after = Some(n);
+        self
+    }
+
+    /// Returns false if power was lost.
+    pub fn is_powered(&self) -> bool {
+        self.powered
+    }
+
+    pub fn into_image(self) -> I {
+        self.image
+    }
+
+    fn range(
+        &self,
+        offset: u32,
+        len: usize,
+        align: usize,
+    ) -> Result<Range<usize>, EmulatedFlashError<I::Error>> {
+        let start = offset as usize;
+        if start % align != 0 || len % align != 0 {
+            return Err(EmulatedFlashError::NotAligned);
+        }
+        if start + len > self.image.bytes().len() {
+            return Err(EmulatedFlashError::OutOfBounds);
+        }
+        Ok(start..start + len)
+    }
+
+    /// Counts a write or erase of `range`, and returns the range which is actually applied.
+    fn begin_op(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<Range<usize>, EmulatedFlashError<I::Error>> {
+        if !self.powered {
+            return Err(EmulatedFlashError::PowerLoss);
+        }
+        match &mut self.power_loss_after {
+            Some(0) => {
+                self.powered = false;
+                let half = range.len() / 2 / Self::WRITE_SIZE * Self::WRITE_SIZE;
+                Ok(range.start..range.start + half)
+            }
+            Some(n) => {
+                *n -= 1;
+                Ok(range)
+            }
+            None => Ok(range),
+        }
+    }
+
+    fn end_op(&mut self, applied: Range<usize>) -> Result<(), EmulatedFlashError<I::Error>> {
+        self.image.flush(applied).map_err(EmulatedFlashError::Image)?;
+        if self.powered { Ok(()) } else { Err(EmulatedFlashError::PowerLoss) }
+    }
+}
+
+impl<I: FlashImage, const PAGE_SIZE: usize> ErrorType for EmulatedNorFlash<I, PAGE_SIZE> {
+    type Error = EmulatedFlashError<I::Error>;
+}
+
+impl<I: FlashImage, const PAGE_SIZE: usize> ReadNorFlash for EmulatedNorFlash<I, PAGE_SIZE> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        if !self.powered {
+            return Err(EmulatedFlashError::PowerLoss);
+        }
+        let range = self.range(offset, bytes.len(), Self::READ_SIZE)?;
+        bytes.copy_from_slice(&self.image.bytes()[range]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.image.bytes().len()
+    }
+}
+
+impl<I: FlashImage, const PAGE_SIZE: usize> NorFlash for EmulatedNorFlash<I, PAGE_SIZE> {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let len = to.checked_sub(from).ok_or(EmulatedFlashError::OutOfBounds)? as usize;
+        let range = self.range(from, len, Self::ERASE_SIZE)?;
+        let applied = self.begin_op(range)?;
+        self.image.bytes_mut()[applied.clone()].fill(0xFF);
+        self.end_op(applied)
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len(), Self::WRITE_SIZE)?;
+        let applied = self.begin_op(range)?;
+        for (dst, src) in self.image.bytes_mut()[applied.clone()].iter_mut().zip(bytes) {
+            *dst &= *src;
+        }
+        self.end_op(applied)
+    }
+}
+
+impl<I: FlashImage, const PAGE_SIZE: usize> MultiwriteNorFlash for EmulatedNorFlash<I, PAGE_SIZE> {}
+
+#[cfg(feature = "std")]
+mod file {
+    extern crate std;
+
+    use core::ops::Range;
+    use std::{
+        fs::{File, OpenOptions},
+        io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+        path::Path,
+        vec,
+        vec::Vec,
+    };
+
+    use super::{EmulatedNorFlash, FlashImage};
+    use crate::storage::flash_sequential_map::FlashSequentialMapStorage;
+
+    /// Storage driver which stores values in a flash image file.
+    pub type FileStorage<const PAGE_SIZE: usize> =
+        FlashSequentialMapStorage<EmulatedNorFlash<FileImage, PAGE_SIZE>>;
+
+    /// Flash image stored in a file. Every modification is written to the file immediately.
+    pub struct FileImage {
+        file: File,
+        data: Vec<u8>,
+    }
+
+    impl FileImage {
+        /// Opens the image at `path`, or creates an erased image of `size` bytes if the file is
+        /// empty or doesn't exist.
+        pub fn open(path: impl AsRef<Path>, size: usize) -> io::Result<Self> {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)?;
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            if data.is_empty() {
+                data = vec![0xFF; size];
+                file.write_all(&data)?;
+            } else if data.len() != size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Flash image has different size",
+                ));
+            }
+            Ok(Self { file, data })
+        }
+    }
+
+    impl FlashImage for FileImage {
+        type Error = io::Error;
+
+        fn bytes(&self) -> &[u8] {
+            &self.data
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.data
+        }
+
+        fn flush(&mut self, range: Range<usize>) -> Result<(), Self::Error> {
+            self.file.seek(SeekFrom::Start(range.start as u64))?;
+            self.file.write_all(&self.data[range])
+        }
+    }
+
+    impl<const PAGE_SIZE: usize> FileStorage<PAGE_SIZE> {
+        /// Opens storage of `size` bytes stored in a flash image file at `path`.
+        pub fn open(path: impl AsRef<Path>, size: u32) -> io::Result<Self> {
+            let image = FileImage::open(path, size as usize)?;
+            Ok(Self::new(EmulatedNorFlash::new(image), 0, size))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::{NorFlash as _, ReadNorFlash as _};
+
+    use super::{EmulatedFlashError, EmulatedNorFlash};
+
+    #[test]
+    fn write_only_clears_bits() {
+        let mut flash = EmulatedNorFlash::<_, 16>::new([0xFF; 32]);
+        block_on(async {
+            flash.write(0, &[0xF0, 0x0F, 0xFF, 0x00]).await.unwrap();
+            flash.write(0, &[0x3C, 0x3C, 0x3C, 0x3C]).await.unwrap();
+            let mut buf = [0; 4];
+            flash.read(0, &mut buf).await.unwrap();
+            assert_eq!(buf, [0x30, 0x0C, 0x3C, 0x00]);
+
+            flash.erase(0, 16).await.unwrap();
+            flash.read(0, &mut buf).await.unwrap();
+            assert_eq!(buf, [0xFF; 4]);
+
+            assert!(matches!(flash.write(2, &[0; 4]).await, Err(EmulatedFlashError::NotAligned)));
+            assert!(matches!(flash.erase(0, 8).await, Err(EmulatedFlashError::NotAligned)));
+            assert!(matches!(flash.write(32, &[0; 4]).await, Err(EmulatedFlashError::OutOfBounds)));
+        });
+    }
+
+    #[test]
+    fn power_loss_applies_half_of_operation() {
+        let mut flash = EmulatedNorFlash::<_, 16>::new([0xFF; 32]).with_power_loss_after(1);
+        block_on(async {
+            flash.write(0, &[0; 4]).await.unwrap();
+            assert!(matches!(flash.write(16, &[0; 8]).await, Err(EmulatedFlashError::PowerLoss)));
+            assert!(!flash.is_powered());
+            assert!(matches!(flash.read(0, &mut [0; 4]).await, Err(EmulatedFlashError::PowerLoss)));
+        });
+
+        let image = flash.into_image();
+        assert_eq!(image[..4], [0; 4]);
+        assert_eq!(image[16..24], [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn file_storage_persists_values() {
+        use rktk::drivers::interface::storage::StorageDriver as _;
+
+        use super::FileStorage;
+
+        let path = std::env::temp_dir().join(std::format!("rktk-flash-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        block_on(async {
+            let storage = FileStorage::<1024>::open(&path, 4096).unwrap();
+            storage.write(1, &[1, 2, 3]).await.unwrap();
+        });
+        block_on(async {
+            let storage = FileStorage::<1024>::open(&path, 4096).unwrap();
+            let mut buf = [0; 8];
+            assert_eq!(storage.read(1, &mut buf).await.unwrap(), Some(3));
+            assert_eq!(buf[..3], [1, 2, 3]);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
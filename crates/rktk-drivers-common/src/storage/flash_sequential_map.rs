@@ -1,9 +1,21 @@
 //! Storage driver using [`sequential_storage`] and nor flash.
+//!
+//! [`StorageDriver::commit`] is made atomic with a journal. Operations are written to journal items
+//! first, then a commit marker which has the number of operations is written, and the operations
+//! are applied. If power is lost before the marker is written, the journal is discarded. If it is
+//! lost after that, the journal is replayed when the storage is accessed next time.
+//!
+//! Only the marker is removed after the commit. Journal items without the marker are ignored and
+//! overwritten by the next journal, so they take the space of the largest commit at most. A commit
+//! of a single operation doesn't need the journal because writing an item is atomic.
 
-use core::fmt::Debug;
-use embedded_storage_async::nor_flash::{MultiwriteNorFlash, NorFlash, ReadNorFlash};
+use core::{fmt::Debug, ops::Range};
+use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash, ReadNorFlash};
 use rktk::{
-    drivers::interface::{Error, storage::StorageDriver},
+    drivers::interface::{
+        Error,
+        storage::{StorageDriver, StorageOp, StorageStats, key_has_prefix},
+    },
     utils::Mutex,
 };
 pub use sequential_storage;
@@ -14,13 +26,36 @@ use sequential_storage::{
 
 type NoCache = Cache<Uncached, Uncached, Uncached, u64>;
 
+const DATA_BUFFER_SIZE: usize = 1024;
+/// Most significant byte of keys used by this driver. See [`StorageDriver`].
+const RESERVED_KEY_MARK: u8 = 0xFF;
+/// Key of the commit marker.
+const COMMIT_KEY: u64 = u64::MAX;
+/// Size of the op kind and the key in a journal item.
+const JOURNAL_HEADER_SIZE: usize = 9;
+
+fn journal_key(index: u16) -> u64 {
+    let [lo, hi] = index.to_le_bytes();
+    u64::from_le_bytes([lo, hi, 0, 0, 0, 0, 0, RESERVED_KEY_MARK])
+}
+
+fn is_reserved(key: u64) -> bool {
+    key.to_le_bytes()[7] == RESERVED_KEY_MARK
+}
+
 // error type
 
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum FlashSequentialMapStorageError<E: Debug> {
     Storage(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] sequential_storage::Error<E>),
-    NotFound,
+    Flash(#[cfg_attr(feature = "defmt", defmt(Debug2Format))] E),
+    /// Buffer passed to read is smaller than the value.
+    BufferTooSmall,
+    /// Value or batch is too large to be written.
+    TooLarge,
+    /// Journal of the interrupted commit is broken.
+    InvalidJournal,
 }
 impl<E: Debug> Error for FlashSequentialMapStorageError<E> {}
 
@@ -30,20 +65,201 @@ impl<E: Debug> From<sequential_storage::Error<E>> for FlashSequentialMapStorageE
     }
 }
 
+// flash wrapper
+
+/// Nor flash which counts erased pages.
+struct StatsFlash<F> {
+    flash: F,
+    erase_count: u32,
+}
+
+impl<F: ErrorType> ErrorType for StatsFlash<F> {
+    type Error = F::Error;
+}
+
+impl<F: ReadNorFlash> ReadNorFlash for StatsFlash<F> {
+    const READ_SIZE: usize = F::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.flash.read(offset, bytes).await
+    }
+
+    fn capacity(&self) -> usize {
+        self.flash.capacity()
+    }
+}
+
+impl<F: NorFlash> NorFlash for StatsFlash<F> {
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+    const ERASE_SIZE: usize = F::ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.flash.erase(from, to).await?;
+        self.erase_count += (to - from) / F::ERASE_SIZE as u32;
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.flash.write(offset, bytes).await
+    }
+}
+
+impl<F: MultiwriteNorFlash> MultiwriteNorFlash for StatsFlash<F> {}
+
+// journal
+
+/// Encodes `op` as a journal item into `buf` and returns the length.
+fn encode_op(op: &StorageOp<'_>, buf: &mut [u8]) -> Option<usize> {
+    let (kind, key, value) = match op {
+        StorageOp::Write { key, value } => (0, key, *value),
+        StorageOp::Remove { key } => (1, key, &[][..]),
+    };
+    let len = JOURNAL_HEADER_SIZE + value.len();
+    let buf = buf.get_mut(..len)?;
+    buf[0] = kind;
+    buf[1..JOURNAL_HEADER_SIZE].copy_from_slice(&key.to_le_bytes());
+    buf[JOURNAL_HEADER_SIZE..].copy_from_slice(value);
+    Some(len)
+}
+
+fn decode_op(item: &[u8]) -> Option<StorageOp<'_>> {
+    let key = u64::from_le_bytes(item.get(1..JOURNAL_HEADER_SIZE)?.try_into().ok()?);
+    match item[0] {
+        0 => Some(StorageOp::Write { key, value: &item[JOURNAL_HEADER_SIZE..] }),
+        1 => Some(StorageOp::Remove { key }),
+        _ => None,
+    }
+}
+
 // storage driver
 
+struct State<F> {
+    flash: StatsFlash<F>,
+    /// False if a commit marker may be left in the flash.
+    journal_clean: bool,
+}
+
 pub struct FlashSequentialMapStorage<F: NorFlash + ReadNorFlash + MultiwriteNorFlash> {
-    storage: Mutex<MapStorage<u64, F, NoCache>>,
+    state: Mutex<State<F>>,
+    range: Range<u32>,
 }
 
 impl<F: NorFlash + ReadNorFlash + MultiwriteNorFlash> FlashSequentialMapStorage<F> {
     pub fn new(flash: F, start_address: u32, storage_size: u32) -> Self {
-        let storage = MapStorage::new(
-            flash,
-            MapConfig::new(start_address..start_address + storage_size),
-            Cache::new_uncached(),
-        );
-        Self { storage: Mutex::new(storage) }
+        Self {
+            state: Mutex::new(State {
+                flash: StatsFlash { flash, erase_count: 0 },
+                journal_clean: false,
+            }),
+            range: start_address..start_address + storage_size,
+        }
+    }
+
+    // As the cache is not used, creating map for each operation costs nothing.
+    fn map<'a>(
+        &self,
+        flash: &'a mut StatsFlash<F>,
+    ) -> MapStorage<u64, &'a mut StatsFlash<F>, NoCache> {
+        MapStorage::new(flash, MapConfig::new(self.range.clone()), Cache::new_uncached())
+    }
+
+    /// Counts erased bytes at the end of each page.
+    ///
+    /// Space used by removed or overwritten items is not included, as it can be reused only after
+    /// the page is erased.
+    async fn free_bytes(&self, flash: &mut StatsFlash<F>) -> Result<u32, F::Error> {
+        let mut free = 0;
+        let mut buf = [0; 32];
+        for page in self.range.clone().step_by(F::ERASE_SIZE) {
+            let mut end = page + F::ERASE_SIZE as u32;
+            'page: while end > page {
+                let start = end - buf.len() as u32;
+                flash.read(start, &mut buf).await?;
+                for b in buf.iter().rev() {
+                    if *b != 0xFF {
+                        break 'page;
+                    }
+                    free += 1;
+                }
+                end = start;
+            }
+        }
+        Ok(free)
+    }
+
+    /// Calls `f` with every stored key. Old items of overwritten keys are also visited, so the same
+    /// key may be passed more than once.
+    async fn for_each_key(
+        &self,
+        flash: &mut StatsFlash<F>,
+        mut f: impl FnMut(u64),
+    ) -> Result<(), FlashSequentialMapStorageError<F::Error>> {
+        let mut map = self.map(flash);
+        let mut buf = [0; DATA_BUFFER_SIZE];
+        let mut iter = map.fetch_all_items(&mut buf).await?;
+        while let Some((key, _)) = iter.next::<&[u8]>(&mut buf).await? {
+            f(key);
+        }
+        Ok(())
+    }
+
+    async fn apply(
+        &self,
+        flash: &mut StatsFlash<F>,
+        op: &StorageOp<'_>,
+    ) -> Result<(), FlashSequentialMapStorageError<F::Error>> {
+        let mut map = self.map(flash);
+        let mut buf = [0; DATA_BUFFER_SIZE];
+        match op {
+            StorageOp::Write { key, value } => map.store_item(&mut buf, key, value).await?,
+            StorageOp::Remove { key } => map.remove_item(&mut buf, key).await?,
+        }
+        Ok(())
+    }
+
+    /// Replays the journal if its commit marker exists, then discards the journal.
+    async fn recover(
+        &self,
+        state: &mut State<F>,
+    ) -> Result<(), FlashSequentialMapStorageError<F::Error>> {
+        if state.journal_clean {
+            return Ok(());
+        }
+
+        let mut buf = [0; DATA_BUFFER_SIZE];
+        let marker =
+            self.map(&mut state.flash).fetch_item::<[u8; 2]>(&mut buf, &COMMIT_KEY).await?;
+        if let Some(count) = marker.map(u16::from_le_bytes) {
+            rktk_log::info!("Replaying interrupted storage commit of {} operations", count);
+            for i in 0..count {
+                let mut item = [0; DATA_BUFFER_SIZE];
+                let len = match self
+                    .map(&mut state.flash)
+                    .fetch_item::<&[u8]>(&mut buf, &journal_key(i))
+                    .await?
+                {
+                    Some(data) => {
+                        item[..data.len()].copy_from_slice(data);
+                        data.len()
+                    }
+                    None => return Err(FlashSequentialMapStorageError::InvalidJournal),
+                };
+                let op = decode_op(&item[..len])
+                    .ok_or(FlashSequentialMapStorageError::InvalidJournal)?;
+                self.apply(&mut state.flash, &op).await?;
+            }
+        }
+        self.clear_journal(state).await
+    }
+
+    /// Removes the commit marker, which discards the journal.
+    async fn clear_journal(
+        &self,
+        state: &mut State<F>,
+    ) -> Result<(), FlashSequentialMapStorageError<F::Error>> {
+        self.apply(&mut state.flash, &StorageOp::Remove { key: COMMIT_KEY }).await?;
+        state.journal_clean = true;
+        Ok(())
     }
 }
 
@@ -53,24 +269,188 @@ impl<F: NorFlash + ReadNorFlash + MultiwriteNorFlash> StorageDriver
     type Error = FlashSequentialMapStorageError<F::Error>;
 
     async fn format(&self) -> Result<(), Self::Error> {
-        let mut storage = self.storage.lock().await;
-        storage.remove_all_items(&mut [0; 1024]).await?;
+        let mut state = self.state.lock().await;
+        self.map(&mut state.flash).remove_all_items(&mut [0; DATA_BUFFER_SIZE]).await?;
+        state.journal_clean = true;
         Ok(())
     }
 
-    async fn read<const N: usize>(&self, key: u64, buf: &mut [u8]) -> Result<(), Self::Error> {
-        let mut storage = self.storage.lock().await;
-        let val: [u8; N] = storage
-            .fetch_item(&mut [0; 1024], &key)
-            .await?
-            .ok_or(FlashSequentialMapStorageError::NotFound)?;
-        buf.copy_from_slice(&val);
-        Ok(())
+    async fn read(&self, key: u64, buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.recover(&mut state).await?;
+        let mut data = [0; DATA_BUFFER_SIZE];
+        let Some(val) = self.map(&mut state.flash).fetch_item::<&[u8]>(&mut data, &key).await?
+        else {
+            return Ok(None);
+        };
+        buf.get_mut(..val.len())
+            .ok_or(FlashSequentialMapStorageError::BufferTooSmall)?
+            .copy_from_slice(val);
+        Ok(Some(val.len()))
     }
 
-    async fn write<const N: usize>(&self, key: u64, item_buf: &[u8]) -> Result<(), Self::Error> {
-        let mut storage = self.storage.lock().await;
-        storage.store_item(&mut [0; 1024], &key, &item_buf).await?;
-        Ok(())
+    async fn write(&self, key: u64, value: &[u8]) -> Result<(), Self::Error> {
+        let mut state = self.state.lock().await;
+        self.recover(&mut state).await?;
+        self.apply(&mut state.flash, &StorageOp::Write { key, value }).await
+    }
+
+    async fn remove(&self, key: u64) -> Result<(), Self::Error> {
+        let mut state = self.state.lock().await;
+        self.recover(&mut state).await?;
+        self.apply(&mut state.flash, &StorageOp::Remove { key }).await
+    }
+
+    async fn keys(&self, prefix: &[u8], keys: &mut [u64]) -> Result<usize, Self::Error> {
+        let mut state = self.state.lock().await;
+        self.recover(&mut state).await?;
+        let mut count = 0;
+        self.for_each_key(&mut state.flash, |key| {
+            if count < keys.len()
+                && !is_reserved(key)
+                && key_has_prefix(key, prefix)
+                && !keys[..count].contains(&key)
+            {
+                keys[count] = key;
+                count += 1;
+            }
+        })
+        .await?;
+        Ok(count)
+    }
+
+    async fn commit(&self, ops: &[StorageOp<'_>]) -> Result<(), Self::Error> {
+        if ops.len() > u16::MAX as usize {
+            return Err(FlashSequentialMapStorageError::TooLarge);
+        }
+
+        let mut state = self.state.lock().await;
+        self.recover(&mut state).await?;
+
+        match ops {
+            [] => return Ok(()),
+            [op] => return self.apply(&mut state.flash, op).await,
+            _ => {}
+        }
+
+        state.journal_clean = false;
+        let mut item = [0; DATA_BUFFER_SIZE];
+        for (i, op) in ops.iter().enumerate() {
+            let len = encode_op(op, &mut item).ok_or(FlashSequentialMapStorageError::TooLarge)?;
+            let journal = StorageOp::Write { key: journal_key(i as u16), value: &item[..len] };
+            self.apply(&mut state.flash, &journal).await?;
+        }
+        let count = (ops.len() as u16).to_le_bytes();
+        self.apply(&mut state.flash, &StorageOp::Write { key: COMMIT_KEY, value: &count }).await?;
+
+        for op in ops {
+            self.apply(&mut state.flash, op).await?;
+        }
+        self.clear_journal(&mut state).await
+    }
+
+    async fn stats(&self) -> Result<Option<StorageStats>, Self::Error> {
+        let mut state = self.state.lock().await;
+        let free_bytes = self
+            .free_bytes(&mut state.flash)
+            .await
+            .map_err(FlashSequentialMapStorageError::Flash)?;
+        Ok(Some(StorageStats {
+            erase_count: state.flash.erase_count,
+            free_bytes,
+            capacity: self.range.end - self.range.start,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use embassy_futures::block_on;
+    use rktk::drivers::interface::storage::{StorageDriver as _, StorageOp};
+    use std::{vec, vec::Vec};
+
+    use super::{DATA_BUFFER_SIZE, FlashSequentialMapStorage, journal_key};
+    use crate::storage::emulated_flash::EmulatedNorFlash;
+
+    const PAGE_SIZE: usize = 1024;
+    const SIZE: usize = PAGE_SIZE * 4;
+
+    type Storage<'a> = FlashSequentialMapStorage<EmulatedNorFlash<&'a mut [u8], PAGE_SIZE>>;
+
+    fn storage(image: &mut [u8], power_loss_after: Option<usize>) -> Storage<'_> {
+        let mut flash = EmulatedNorFlash::new(image);
+        if let Some(n) = power_loss_after {
+            flash = flash.with_power_loss_after(n);
+        }
+        FlashSequentialMapStorage::new(flash, 0, SIZE as u32)
+    }
+
+    fn values(storage: &Storage<'_>) -> [Option<Vec<u8>>; 3] {
+        [1, 2, 3].map(|key| {
+            let mut buf = [0; 16];
+            let len = block_on(storage.read(key, &mut buf)).unwrap()?;
+            Some(buf[..len].to_vec())
+        })
+    }
+
+    #[test]
+    fn read_write_remove() {
+        let mut image = vec![0xFF; SIZE];
+        let s = storage(&mut image, None);
+        block_on(async {
+            s.write(1, &[1, 2, 3]).await.unwrap();
+            s.write(2, &[4]).await.unwrap();
+            s.remove(2).await.unwrap();
+
+            let mut keys = [0; 4];
+            assert_eq!(s.keys(&[], &mut keys).await.unwrap(), 1);
+            assert_eq!(keys[0], 1);
+        });
+        assert_eq!(values(&s), [Some(vec![1, 2, 3]), None, None]);
+    }
+
+    #[test]
+    fn single_op_commit_skips_journal() {
+        let mut image = vec![0xFF; SIZE];
+        let s = storage(&mut image, None);
+        block_on(async {
+            s.commit(&[StorageOp::Write { key: 1, value: &[1, 2, 3] }]).await.unwrap();
+
+            let mut state = s.state.lock().await;
+            let mut buf = [0; DATA_BUFFER_SIZE];
+            let journal = s.map(&mut state.flash).fetch_item::<&[u8]>(&mut buf, &journal_key(0));
+            assert_eq!(journal.await.unwrap(), None);
+        });
+        assert_eq!(values(&s), [Some(vec![1, 2, 3]), None, None]);
+    }
+
+    #[test]
+    fn interrupted_commit_is_all_or_nothing() {
+        let old = [Some(vec![1; 8]), Some(vec![1; 8]), None];
+        let new = [Some(vec![2; 8]), None, Some(vec![2; 8])];
+        let ops = [
+            StorageOp::Write { key: 1, value: &[2; 8] },
+            StorageOp::Remove { key: 2 },
+            StorageOp::Write { key: 3, value: &[2; 8] },
+        ];
+
+        // Loses power at every flash operation of the commit, until the commit completes.
+        for n in 0.. {
+            let mut image = vec![0xFF; SIZE];
+            block_on(storage(&mut image, None).write(1, &[1; 8])).unwrap();
+            block_on(storage(&mut image, None).write(2, &[1; 8])).unwrap();
+
+            let res = block_on(storage(&mut image, Some(n)).commit(&ops));
+
+            // Reboot
+            let values = values(&storage(&mut image, None));
+            if res.is_ok() {
+                assert_eq!(values, new);
+                break;
+            }
+            assert!(values == old || values == new, "Broken after {n} operations: {values:?}");
+        }
     }
 }
@@ -1,3 +1,5 @@
 //! Storage driver implementations.
 
+pub mod emulated_flash;
 pub mod flash_sequential_map;
+pub mod ram;
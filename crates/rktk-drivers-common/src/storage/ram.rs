@@ -0,0 +1,164 @@
+//! Storage driver which keeps values in RAM.
+//!
+//! Values are lost on reset, so this is mainly useful for tests on the host.
+
+use heapless::{LinearMap, Vec};
+use rktk::{
+    drivers::interface::{
+        Error,
+        storage::{StorageDriver, StorageOp, key_has_prefix},
+    },
+    utils::Mutex,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RamStorageError {
+    /// Number of keys exceeds the capacity.
+    Full,
+    /// Value is larger than the max value size.
+    TooLarge,
+    /// Buffer passed to read is smaller than the value.
+    BufferTooSmall,
+}
+
+impl Error for RamStorageError {}
+
+/// Storage of up to `N` keys, each of which has a value of up to `V` bytes.
+pub struct RamStorage<const N: usize, const V: usize> {
+    items: Mutex<LinearMap<u64, Vec<u8, V>, N>>,
+}
+
+impl<const N: usize, const V: usize> RamStorage<N, V> {
+    pub const fn new() -> Self {
+        Self { items: Mutex::new(LinearMap::new()) }
+    }
+}
+
+impl<const N: usize, const V: usize> Default for RamStorage<N, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn apply<const N: usize, const V: usize>(
+    items: &mut LinearMap<u64, Vec<u8, V>, N>,
+    op: &StorageOp<'_>,
+) -> Result<(), RamStorageError> {
+    match op {
+        StorageOp::Write { key, value } => {
+            let value = Vec::from_slice(value).map_err(|_| RamStorageError::TooLarge)?;
+            items.insert(*key, value).map_err(|_| RamStorageError::Full)?;
+        }
+        StorageOp::Remove { key } => {
+            items.remove(key);
+        }
+    }
+    Ok(())
+}
+
+impl<const N: usize, const V: usize> StorageDriver for RamStorage<N, V> {
+    type Error = RamStorageError;
+
+    async fn format(&self) -> Result<(), Self::Error> {
+        self.items.lock().await.clear();
+        Ok(())
+    }
+
+    async fn read(&self, key: u64, buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        let items = self.items.lock().await;
+        let Some(value) = items.get(&key) else {
+            return Ok(None);
+        };
+        buf.get_mut(..value.len()).ok_or(RamStorageError::BufferTooSmall)?.copy_from_slice(value);
+        Ok(Some(value.len()))
+    }
+
+    async fn write(&self, key: u64, value: &[u8]) -> Result<(), Self::Error> {
+        apply(&mut *self.items.lock().await, &StorageOp::Write { key, value })
+    }
+
+    async fn remove(&self, key: u64) -> Result<(), Self::Error> {
+        apply(&mut *self.items.lock().await, &StorageOp::Remove { key })
+    }
+
+    async fn keys(&self, prefix: &[u8], keys: &mut [u64]) -> Result<usize, Self::Error> {
+        let items = self.items.lock().await;
+        let found = items.keys().filter(|k| key_has_prefix(**k, prefix));
+        Ok(keys.iter_mut().zip(found).map(|(dst, k)| *dst = *k).count())
+    }
+
+    async fn commit(&self, ops: &[StorageOp<'_>]) -> Result<(), Self::Error> {
+        let mut items = self.items.lock().await;
+        // Operations are applied to a copy, so that a failed commit leaves nothing applied.
+        let mut new = items.clone();
+        for op in ops {
+            apply(&mut new, op)?;
+        }
+        *items = new;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use rktk::drivers::interface::storage::{StorageDriver as _, StorageOp};
+
+    use super::{RamStorage, RamStorageError};
+
+    #[test]
+    fn read_returns_length() {
+        let s = RamStorage::<4, 8>::new();
+        block_on(async {
+            s.write(1, &[1, 2, 3]).await.unwrap();
+
+            let mut buf = [0; 8];
+            assert_eq!(s.read(1, &mut buf).await, Ok(Some(3)));
+            assert_eq!(&buf[..3], &[1, 2, 3]);
+            assert_eq!(s.read(2, &mut buf).await, Ok(None));
+            assert_eq!(s.read(1, &mut [0; 2]).await, Err(RamStorageError::BufferTooSmall));
+            assert_eq!(s.write(2, &[0; 9]).await, Err(RamStorageError::TooLarge));
+        });
+    }
+
+    #[test]
+    fn remove_and_keys() {
+        let s = RamStorage::<4, 8>::new();
+        block_on(async {
+            for key in [0x0101, 0x0201, 0x0102] {
+                s.write(key, &[0]).await.unwrap();
+            }
+            s.remove(0x0201).await.unwrap();
+            s.remove(0x0301).await.unwrap();
+
+            let mut keys = [0; 4];
+            let count = s.keys(&[0x01], &mut keys).await.unwrap();
+            assert_eq!(&keys[..count], &[0x0101]);
+            assert_eq!(s.keys(&[], &mut keys).await, Ok(2));
+            assert_eq!(s.keys(&[], &mut keys[..1]).await, Ok(1));
+        });
+    }
+
+    #[test]
+    fn failed_commit_applies_nothing() {
+        let s = RamStorage::<2, 8>::new();
+        block_on(async {
+            s.write(1, &[1]).await.unwrap();
+
+            let ops = [
+                StorageOp::Remove { key: 1 },
+                StorageOp::Write { key: 2, value: &[2] },
+                StorageOp::Write { key: 3, value: &[3] },
+                StorageOp::Write { key: 4, value: &[4] },
+            ];
+            assert_eq!(s.commit(&ops).await, Err(RamStorageError::Full));
+            assert_eq!(s.keys(&[], &mut [0; 4]).await, Ok(1));
+
+            s.commit(&ops[..3]).await.unwrap();
+            let mut buf = [0; 8];
+            assert_eq!(s.read(1, &mut buf).await, Ok(None));
+            assert_eq!(s.read(3, &mut buf).await, Ok(Some(1)));
+        });
+    }
+}
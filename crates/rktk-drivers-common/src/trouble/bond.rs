@@ -0,0 +1,156 @@
+//! Persistence of bonds made by trouble drivers.
+//!
+//! Bonds are stored in a [`StorageDriver`] passed to the builder of each driver. It should be a
+//! storage dedicated to bonds (e.g. separate flash partition), not the one passed to rktk.
+
+use core::convert::Infallible;
+
+use rktk::drivers::interface::storage::{StorageDriver, StorageOp};
+use rktk_log::warn;
+use trouble_host::{
+    BdAddr, BondInformation, Identity, IdentityResolvingKey, LongTermKey, prelude::SecurityLevel,
+};
+
+/// Storage used when bonds are not persisted. Bonds are kept only in memory and lost at reboot.
+pub enum NoBondStorage {}
+
+impl StorageDriver for NoBondStorage {
+    type Error = Infallible;
+
+    async fn format(&self) -> Result<(), Self::Error> {
+        match *self {}
+    }
+    async fn read(&self, _key: u64, _buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        match *self {}
+    }
+    async fn write(&self, _key: u64, _value: &[u8]) -> Result<(), Self::Error> {
+        match *self {}
+    }
+    async fn remove(&self, _key: u64) -> Result<(), Self::Error> {
+        match *self {}
+    }
+    async fn keys(&self, _prefix: &[u8], _keys: &mut [u64]) -> Result<usize, Self::Error> {
+        match *self {}
+    }
+    async fn commit(&self, _ops: &[StorageOp<'_>]) -> Result<(), Self::Error> {
+        match *self {}
+    }
+}
+
+/// Key of the bond of host profile `profile` of the reporter.
+#[cfg(feature = "reporter-trouble")]
+pub(crate) fn profile_bond_key(profile: u8) -> u64 {
+    u64::from_le_bytes([b'b', b'p', profile, 0, 0, 0, 0, 0])
+}
+
+/// Key of the bond of the other half of split.
+#[cfg(feature = "split-trouble")]
+pub(crate) const SPLIT_BOND_KEY: u64 = u64::from_le_bytes([b'b', b's', 0, 0, 0, 0, 0, 0]);
+
+/// Address (6), IRK flag (1), IRK (16), LTK (16) and security level (1).
+const BOND_SIZE: usize = 40;
+
+fn encode(bond: &BondInformation) -> [u8; BOND_SIZE] {
+    let mut buf = [0u8; BOND_SIZE];
+    buf[0..6].copy_from_slice(bond.identity.bd_addr.raw());
+    if let Some(irk) = &bond.identity.irk {
+        buf[6] = 1;
+        buf[7..23].copy_from_slice(&irk.0.to_le_bytes());
+    }
+    buf[23..39].copy_from_slice(&bond.ltk.0.to_le_bytes());
+    buf[39] = match bond.security_level {
+        SecurityLevel::NoEncryption => 0,
+        SecurityLevel::Encrypted => 1,
+        SecurityLevel::EncryptedAuthenticated => 2,
+    };
+    buf
+}
+
+fn decode(buf: &[u8]) -> Option<BondInformation> {
+    let buf: &[u8; BOND_SIZE] = buf.try_into().ok()?;
+    let mut addr = [0u8; 6];
+    addr.copy_from_slice(&buf[0..6]);
+    let irk = (buf[6] == 1)
+        .then(|| IdentityResolvingKey(u128::from_le_bytes(buf[7..23].try_into().unwrap())));
+    let ltk = LongTermKey(u128::from_le_bytes(buf[23..39].try_into().unwrap()));
+    let security_level = match buf[39] {
+        0 => SecurityLevel::NoEncryption,
+        1 => SecurityLevel::Encrypted,
+        2 => SecurityLevel::EncryptedAuthenticated,
+        _ => return None,
+    };
+    Some(BondInformation::new(
+        Identity { bd_addr: BdAddr::new(addr), irk },
+        ltk,
+        security_level,
+        true,
+    ))
+}
+
+/// Reads the bond of `key`. Returns `None` if it is not stored or can't be read.
+pub(crate) async fn load<S: StorageDriver>(
+    storage: &Option<S>,
+    key: u64,
+) -> Option<BondInformation> {
+    let storage = storage.as_ref()?;
+    let mut buf = [0u8; BOND_SIZE];
+    match storage.read(key, &mut buf).await {
+        Ok(Some(len)) => {
+            let bond = decode(&buf[..len]);
+            if bond.is_none() {
+                warn!("[bond] invalid bond is stored");
+            }
+            bond
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("[bond] failed to read bond: {:?}", e);
+            None
+        }
+    }
+}
+
+pub(crate) async fn save<S: StorageDriver>(storage: &Option<S>, key: u64, bond: &BondInformation) {
+    if let Some(storage) = storage
+        && let Err(e) = storage.write(key, &encode(bond)).await
+    {
+        warn!("[bond] failed to store bond: {:?}", e);
+    }
+}
+
+pub(crate) async fn remove<S: StorageDriver>(storage: &Option<S>, key: u64) {
+    if let Some(storage) = storage
+        && let Err(e) = storage.remove(key).await
+    {
+        warn!("[bond] failed to remove bond: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        for irk in [None, Some(IdentityResolvingKey(0x0123_4567_89ab_cdef_0011_2233_4455_6677))] {
+            let bond = BondInformation::new(
+                Identity { bd_addr: BdAddr::new([1, 2, 3, 4, 5, 0xc6]), irk },
+                LongTermKey(0xfedc_ba98_7654_3210_8899_aabb_ccdd_eeff),
+                SecurityLevel::Encrypted,
+                true,
+            );
+            let decoded = decode(&encode(&bond)).unwrap();
+            assert_eq!(decoded.identity, bond.identity);
+            assert_eq!(decoded.ltk, bond.ltk);
+            assert_eq!(decoded.security_level, bond.security_level);
+        }
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert!(decode(&[0; BOND_SIZE - 1]).is_none());
+        let mut buf = [0; BOND_SIZE];
+        buf[39] = 3;
+        assert!(decode(&buf).is_none());
+    }
+}
@@ -1,2 +1,5 @@
+pub mod bond;
 #[cfg(feature = "reporter-trouble")]
 pub mod reporter;
+#[cfg(feature = "split-trouble")]
+pub mod split;
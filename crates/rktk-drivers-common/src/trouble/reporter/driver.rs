@@ -5,7 +5,10 @@ use rktk::{
     utils::Sender,
 };
 
-use super::Report;
+use super::{
+    BATTERY_LEVEL_SIGNAL, PROFILE_COMMAND_CHANNEL, PROFILE_COUNT, ProfileCommand, RRP_RECV_PIPE,
+    RRP_SEND_PIPE, Report,
+};
 
 pub struct TroubleReporter {
     pub(super) output_tx: Sender<'static, Report, 4>,
@@ -38,10 +41,15 @@ impl ReporterDriver for TroubleReporter {
         Ok(())
     }
 
-    async fn send_rrp_data(&self, _data: &[u8]) -> Result<(), Self::Error> {
+    async fn send_rrp_data(&self, data: &[u8]) -> Result<(), Self::Error> {
+        RRP_SEND_PIPE.write_all(data).await;
         Ok(())
     }
 
+    async fn recv_rrp_data(&self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(RRP_RECV_PIPE.read(buf).await)
+    }
+
     fn wakeup(&self) -> Result<bool, Self::Error> {
         Ok(false)
     }
@@ -50,6 +58,32 @@ impl WirelessReporterDriver for TroubleReporter {
     type Error = Infallible;
 
     async fn clear_bond_data(&self) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        PROFILE_COMMAND_CHANNEL.send(ProfileCommand::ClearAllBonds).await;
+        Ok(())
+    }
+
+    fn profile_count(&self) -> u8 {
+        PROFILE_COUNT
+    }
+
+    async fn select_profile(
+        &self,
+        profile: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        PROFILE_COMMAND_CHANNEL.send(ProfileCommand::Select(profile)).await;
+        Ok(())
+    }
+
+    async fn clear_profile_bond_data(&self) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        PROFILE_COMMAND_CHANNEL.send(ProfileCommand::ClearBond).await;
+        Ok(())
+    }
+
+    async fn set_battery_level(
+        &self,
+        level: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        BATTERY_LEVEL_SIGNAL.signal(level);
         Ok(())
     }
 }
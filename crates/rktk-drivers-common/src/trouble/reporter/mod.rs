@@ -1,7 +1,13 @@
 use driver::TroubleReporter;
-use rktk::{drivers::interface::wireless::WirelessReporterDriverBuilder, utils::Channel};
+use embassy_sync::pipe::Pipe;
+use rktk::{
+    drivers::interface::{storage::StorageDriver, wireless::WirelessReporterDriverBuilder},
+    utils::{Channel, RawMutex, Signal},
+};
 use trouble_host::{Controller, gap::PeripheralConfig};
 
+use super::bond::NoBondStorage;
+
 mod driver;
 mod server;
 mod task;
@@ -12,7 +18,20 @@ enum Report {
     Mouse(usbd_hid::descriptor::MouseReport),
 }
 
+/// Number of host profiles. Each profile is bonded to at most one host.
+const PROFILE_COUNT: u8 = 4;
+
+enum ProfileCommand {
+    Select(u8),
+    ClearBond,
+    ClearAllBonds,
+}
+
 static OUTPUT_CHANNEL: Channel<Report, 4> = Channel::new();
+static RRP_SEND_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+static RRP_RECV_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+static PROFILE_COMMAND_CHANNEL: Channel<ProfileCommand, 4> = Channel::new();
+static BATTERY_LEVEL_SIGNAL: Signal<u8> = Signal::new();
 
 pub struct TroubleReporterConfig {
     pub advertise_name: &'static str,
@@ -24,9 +43,13 @@ pub struct TroubleReporterBuilder<
     const CONNECTIONS_MAX: usize,
     const L2CAP_CHANNELS_MAX: usize,
     const L2CAP_MTU: usize,
+    S: StorageDriver + 'static = NoBondStorage,
 > {
     controller: C,
     config: TroubleReporterConfig,
+    bond_storage: Option<S>,
+    #[cfg(feature = "split-trouble")]
+    split: Option<crate::trouble::split::TroubleSplitConfig>,
 }
 
 impl<
@@ -37,7 +60,54 @@ impl<
 > TroubleReporterBuilder<C, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU>
 {
     pub fn new(controller: C, config: TroubleReporterConfig) -> Self {
-        Self { controller, config }
+        Self {
+            controller,
+            config,
+            bond_storage: None,
+            #[cfg(feature = "split-trouble")]
+            split: None,
+        }
+    }
+}
+
+impl<
+    C: Controller + 'static,
+    const CONNECTIONS_MAX: usize,
+    const L2CAP_CHANNELS_MAX: usize,
+    const L2CAP_MTU: usize,
+    S: StorageDriver + 'static,
+> TroubleReporterBuilder<C, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU, S>
+{
+    /// Persists bonds of each profile to `storage`, so hosts don't have to pair again after
+    /// reboot. Without this, bonds are lost at reboot.
+    ///
+    /// See [`crate::trouble::bond`] for the requirement of the storage.
+    pub fn with_bond_storage<S2: StorageDriver + 'static>(
+        self,
+        storage: S2,
+    ) -> TroubleReporterBuilder<C, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU, S2> {
+        TroubleReporterBuilder {
+            controller: self.controller,
+            config: self.config,
+            bond_storage: Some(storage),
+            #[cfg(feature = "split-trouble")]
+            split: self.split,
+        }
+    }
+
+    /// Runs BLE split as central on the same BLE stack, and returns the split driver for the
+    /// master side.
+    ///
+    /// `split.address` is used as the address of this keyboard for all profiles, because the slave
+    /// connects to it. `CONNECTIONS_MAX` must be 2 or more because the host and the slave are
+    /// connected at the same time.
+    #[cfg(feature = "split-trouble")]
+    pub fn with_split(
+        mut self,
+        split: crate::trouble::split::TroubleSplitConfig,
+    ) -> (Self, crate::trouble::split::TroubleSplitDriver) {
+        self.split = Some(split);
+        (self, crate::trouble::split::TroubleSplitDriver::new())
     }
 }
 
@@ -46,8 +116,9 @@ impl<
     const CONNECTIONS_MAX: usize,
     const L2CAP_CHANNELS_MAX: usize,
     const L2CAP_MTU: usize,
+    S: StorageDriver + 'static,
 > WirelessReporterDriverBuilder
-    for TroubleReporterBuilder<C, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU>
+    for TroubleReporterBuilder<C, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU, S>
 {
     type Output = TroubleReporter;
 
@@ -58,10 +129,13 @@ impl<
     ) -> Result<(Self::Output, impl Future<Output = ()> + 'static), Self::Error> {
         Ok((
             TroubleReporter { output_tx: OUTPUT_CHANNEL.sender() },
-            task::run::<_, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU>(
+            task::run::<_, _, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX, L2CAP_MTU>(
                 self.controller,
                 OUTPUT_CHANNEL.receiver(),
                 self.config,
+                self.bond_storage,
+                #[cfg(feature = "split-trouble")]
+                self.split,
             ),
         ))
     }
@@ -1,3 +1,4 @@
+use rktk::drivers::interface::wireless::RRP_BLE_BUFFER_SIZE;
 use trouble_host::prelude::*;
 
 mod hid;
@@ -5,9 +6,10 @@ mod hid;
 // GATT Server definition
 #[gatt_server]
 pub(super) struct Server {
-    // pub battery_service: BatteryService,
+    pub battery_service: BatteryService,
     // pub dis: DeviceInformationService,
     pub hid_service: HidService,
+    pub rrp_service: RrpService,
 }
 
 /// Battery service
@@ -16,7 +18,7 @@ pub(super) struct BatteryService {
     /// Battery Level
     #[descriptor(uuid = descriptors::VALID_RANGE, read, value = [0, 100])]
     #[descriptor(uuid = descriptors::MEASUREMENT_DESCRIPTION, read, value = "Battery Level")]
-    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify, value = 10)]
+    #[characteristic(uuid = characteristic::BATTERY_LEVEL, read, notify, value = 100)]
     pub level: u8,
     // #[characteristic(uuid = "408813df-5dd4-1f87-ec11-cdb001100000", write, read, notify)]
     // pub status: bool,
@@ -63,3 +65,14 @@ pub(super) struct HidService {
     #[descriptor(uuid = hid_uuid::HID_REPORT_REF, read, value = [hid::BleCompositeReportType::Mouse as u8, hid::HidReportType::Input as u8])]
     pub input_mouse: [u8; 5],
 }
+
+/// rrp (rktk remote protocol) service
+#[gatt_service(uuid = "8c5a0001-5e2f-4a8b-9d1e-72726b746b00")]
+pub(super) struct RrpService {
+    /// Request data from the client (client to keyboard)
+    #[characteristic(uuid = "8c5a0002-5e2f-4a8b-9d1e-72726b746b00", write, write_without_response)]
+    pub request: [u8; RRP_BLE_BUFFER_SIZE],
+    /// Response data to the client (keyboard to client)
+    #[characteristic(uuid = "8c5a0003-5e2f-4a8b-9d1e-72726b746b00", read, notify)]
+    pub response: [u8; RRP_BLE_BUFFER_SIZE],
+}
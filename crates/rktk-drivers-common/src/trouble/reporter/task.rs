@@ -1,17 +1,55 @@
-use embassy_futures::{join::join, select::select};
-use rktk::utils::Receiver;
+use core::cell::RefCell;
+
+use embassy_futures::{
+    join::join,
+    select::{Either, select, select4},
+};
+use rktk::{
+    drivers::interface::{storage::StorageDriver, wireless::RRP_BLE_BUFFER_SIZE},
+    utils::Receiver,
+};
 use rktk_log::{info, warn};
 use trouble_host::{
-    Address, Controller, Error, HostResources,
+    Address, BdAddr, BondInformation, Controller, Error, HostResources, Identity,
+    bt_hci::cmd::le::LeSetRandomAddr,
     gap::{GapConfig, PeripheralConfig},
     prelude::*,
 };
 use usbd_hid::descriptor::AsInputReport;
 
-use super::{Report, TroubleReporterConfig, server::Server};
+use super::{
+    BATTERY_LEVEL_SIGNAL, PROFILE_COMMAND_CHANNEL, PROFILE_COUNT, ProfileCommand, RRP_RECV_PIPE,
+    RRP_SEND_PIPE, Report, TroubleReporterConfig, server::Server,
+};
+use crate::trouble::bond::{self, profile_bond_key};
+
+/// Address of the keyboard. Address of each profile is derived from this.
+const BASE_ADDRESS: [u8; 6] = [0xff, 0x8f, 0x1a, 0x05, 0xe4, 0xff];
+
+/// Bonds of each profile.
+///
+/// Each profile has its own address, so hosts see each profile as a different device. When split
+/// runs on the same stack, all profiles share the split address because the slave connects to
+/// it. In that case, a host bonded to other profile may connect, and such connections are
+/// rejected.
+struct Profiles {
+    active: u8,
+    bonds: [Option<BondInformation>; PROFILE_COUNT as usize],
+}
+
+impl Profiles {
+    /// Returns true if `peer` may connect to the active profile.
+    fn accepts(&self, peer: &Identity) -> bool {
+        match &self.bonds[self.active as usize] {
+            Some(bond) => bond.identity == *peer,
+            None => !self.bonds.iter().flatten().any(|bond| bond.identity == *peer),
+        }
+    }
+}
 
 pub async fn run<
     C: Controller + 'static,
+    S: StorageDriver,
     const CONNECTIONS_MAX: usize,
     const L2CAP_CHANNELS_MAX: usize,
     const L2CAP_MTU: usize,
@@ -19,9 +57,19 @@ pub async fn run<
     controller: C,
     output_rx: Receiver<'static, Report, 4>,
     config: TroubleReporterConfig,
+    bond_storage: Option<S>,
+    #[cfg(feature = "split-trouble")] split: Option<crate::trouble::split::TroubleSplitConfig>,
 ) {
     info!("Trouble BLE starting");
-    let address: Address = Address::random([0xff, 0x8f, 0x1a, 0x05, 0xe4, 0xff]);
+    #[allow(unused_mut)]
+    let mut address: Address = Address::random(BASE_ADDRESS);
+    #[allow(unused_mut)]
+    let mut per_profile_address = true;
+    #[cfg(feature = "split-trouble")]
+    if let Some(split) = &split {
+        address = Address::random(split.address);
+        per_profile_address = false;
+    }
     info!("Our address = {:?}", address);
 
     let mut resources: HostResources<
@@ -42,27 +90,93 @@ pub async fn run<
     ));
     match server {
         Ok(server) => {
-            let _ = join(ble_task(stack.runner()), async {
+            let mut bonds = [const { None }; PROFILE_COUNT as usize];
+            for (profile, slot) in bonds.iter_mut().enumerate() {
+                let Some(bond) = bond::load(&bond_storage, profile_bond_key(profile as u8)).await
+                else {
+                    continue;
+                };
+                match stack.add_bond_information(bond.clone()) {
+                    Ok(()) => *slot = Some(bond),
+                    Err(e) => warn!("[profile] failed to restore bond of {}: {:?}", profile, e),
+                }
+            }
+            let profiles = RefCell::new(Profiles { active: 0, bonds });
+            let reporter_task = async {
+                // Profile whose address is set to the controller. Address of profile 0 is set
+                // when the stack is built.
+                let mut address_profile = 0;
                 loop {
-                    match advertise(config.advertise_name, &mut stack.peripheral()).await {
-                        Ok(conn) => {
-                            let gatt_conn = conn.with_attribute_server(&server).unwrap();
-                            select(
-                                gatt_events_task(&gatt_conn),
-                                hid_task(&server, &gatt_conn, &stack, &output_rx),
-                            )
-                            .await;
-                        }
-                        Err(e) => {
+                    let active = profiles.borrow().active;
+                    if per_profile_address && active != address_profile {
+                        set_profile_address(&stack, active).await;
+                        address_profile = active;
+                    }
+
+                    let conn = match select(
+                        advertise(config.advertise_name, &mut stack.peripheral()),
+                        PROFILE_COMMAND_CHANNEL.receive(),
+                    )
+                    .await
+                    {
+                        Either::First(Ok(conn)) => conn,
+                        Either::First(Err(e)) => {
                             #[cfg(feature = "defmt")]
                             let e = defmt::Debug2Format(&e);
                             rktk_log::error!("[adv] error: {:?}", e);
                             return;
                         }
+                        Either::Second(cmd) => {
+                            handle_profile_command(&stack, &profiles, &bond_storage, cmd).await;
+                            continue;
+                        }
+                    };
+
+                    if !profiles.borrow().accepts(&conn.peer_identity()) {
+                        info!("[profile] host of other profile connected. disconnecting.");
+                        conn.disconnect();
+                        continue;
+                    }
+                    if let Err(e) = conn.set_bondable(true) {
+                        warn!("[gatt] failed to set bondable: {:?}", e);
                     }
+                    let gatt_conn = conn.with_attribute_server(&server).unwrap();
+                    select4(
+                        gatt_events_task(&server, &gatt_conn, &profiles, &bond_storage),
+                        select(
+                            hid_task(&server, &gatt_conn, &stack, &output_rx),
+                            battery_task(&server, &gatt_conn),
+                        ),
+                        rrp_task(&server, &gatt_conn),
+                        async {
+                            loop {
+                                let cmd = PROFILE_COMMAND_CHANNEL.receive().await;
+                                if handle_profile_command(&stack, &profiles, &bond_storage, cmd)
+                                    .await
+                                {
+                                    break;
+                                }
+                            }
+                            gatt_conn.raw().disconnect();
+                        },
+                    )
+                    .await;
+
+                    // Partial rrp data of the previous connection would corrupt the stream of the
+                    // next one.
+                    RRP_SEND_PIPE.clear();
+                    RRP_RECV_PIPE.clear();
+                }
+            };
+
+            #[cfg(feature = "split-trouble")]
+            let reporter_task = join(reporter_task, async {
+                if let Some(split) = &split {
+                    crate::trouble::split::central::run(&stack, split, &bond_storage).await;
                 }
-            })
-            .await;
+            });
+
+            let _ = join(ble_task(stack.runner()), reporter_task).await;
         }
         Err(e) => {
             rktk_log::error!("[gatt] error: {:?}", e);
@@ -81,7 +195,64 @@ async fn ble_task<C: Controller, P: PacketPool>(mut runner: Runner<'_, C, P>) {
     }
 }
 
-async fn gatt_events_task<P: PacketPool>(conn: &GattConnection<'_, '_, P>) -> Result<(), Error> {
+/// Sets the static random address of `profile`, which is derived from [`BASE_ADDRESS`] like
+/// the softdevice reporter does.
+async fn set_profile_address<C: Controller, P: PacketPool>(stack: &Stack<'_, C, P>, profile: u8) {
+    let mut bytes = BASE_ADDRESS;
+    bytes[0] = bytes[0].wrapping_add(profile);
+    if let Err(e) = stack.command(LeSetRandomAddr::new(BdAddr::new(bytes))).await {
+        #[cfg(feature = "defmt")]
+        let e = defmt::Debug2Format(&e);
+        warn!("[profile] failed to set address of {}: {:?}", profile, e);
+    }
+}
+
+/// Applies `cmd`, and returns true if the current host should be disconnected.
+async fn handle_profile_command<C: Controller, P: PacketPool, S: StorageDriver>(
+    stack: &Stack<'_, C, P>,
+    profiles: &RefCell<Profiles>,
+    bond_storage: &Option<S>,
+    cmd: ProfileCommand,
+) -> bool {
+    let cleared = match cmd {
+        ProfileCommand::Select(profile) => {
+            let mut profiles = profiles.borrow_mut();
+            if profile == profiles.active || profile >= PROFILE_COUNT {
+                return false;
+            }
+            profiles.active = profile;
+            info!("[profile] switched to {}", profile);
+            0..0
+        }
+        ProfileCommand::ClearBond => {
+            let active = profiles.borrow().active;
+            info!("[profile] clearing bond of {}", active);
+            active..active + 1
+        }
+        ProfileCommand::ClearAllBonds => {
+            info!("[profile] clearing all bonds");
+            0..PROFILE_COUNT
+        }
+    };
+
+    for profile in cleared {
+        let bond = profiles.borrow_mut().bonds[profile as usize].take();
+        if let Some(bond) = bond
+            && let Err(e) = stack.remove_bond_information(bond.identity)
+        {
+            warn!("[profile] failed to remove bond: {:?}", e);
+        }
+        bond::remove(bond_storage, profile_bond_key(profile)).await;
+    }
+    true
+}
+
+async fn gatt_events_task<P: PacketPool, S: StorageDriver>(
+    server: &Server<'_>,
+    conn: &GattConnection<'_, '_, P>,
+    profiles: &RefCell<Profiles>,
+    bond_storage: &Option<S>,
+) -> Result<(), Error> {
     loop {
         match conn.next().await {
             GattConnectionEvent::Disconnected { reason } => {
@@ -97,9 +268,13 @@ async fn gatt_events_task<P: PacketPool>(conn: &GattConnection<'_, '_, P>) -> Re
                             Some(AttErrorCode::INSUFFICIENT_ENCRYPTION)
                         }
                     }
-                    GattEvent::Write(_event) => {
+                    GattEvent::Write(event) => {
                         if conn.raw().security_level().map(|l| l.encrypted()) == Ok(true) {
-                            None
+                            if event.handle() == server.rrp_service.request.handle {
+                                on_rrp_packet(event.data())
+                            } else {
+                                None
+                            }
                         } else {
                             Some(AttErrorCode::INSUFFICIENT_ENCRYPTION)
                         }
@@ -118,6 +293,14 @@ async fn gatt_events_task<P: PacketPool>(conn: &GattConnection<'_, '_, P>) -> Re
                     }
                 }
             }
+            GattConnectionEvent::PairingComplete { security_level, bond } => {
+                info!("[gatt] pairing complete: {:?}", security_level);
+                if let Some(bond) = bond {
+                    let active = profiles.borrow().active;
+                    bond::save(bond_storage, profile_bond_key(active), &bond).await;
+                    profiles.borrow_mut().bonds[active as usize] = Some(bond);
+                }
+            }
             _ => {}
         }
     }
@@ -125,6 +308,30 @@ async fn gatt_events_task<P: PacketPool>(conn: &GattConnection<'_, '_, P>) -> Re
     Ok(())
 }
 
+/// Writes rrp payload of the packet written to the request characteristic, and returns the error
+/// to reject the write with.
+///
+/// This is called from the GATT event loop, so it must not wait. If the pipe doesn't have room for
+/// the whole payload, the write is rejected and none of it is written.
+fn on_rrp_packet(data: &[u8]) -> Option<AttErrorCode> {
+    let Some(&len) = data.first() else {
+        warn!("[rrp] invalid packet");
+        return None;
+    };
+    let len = len as usize;
+    if len == 0 || len >= RRP_BLE_BUFFER_SIZE || len >= data.len() {
+        warn!("[rrp] invalid packet");
+        return None;
+    }
+    if RRP_RECV_PIPE.free_capacity() < len
+        || !matches!(RRP_RECV_PIPE.try_write(&data[1..=len]), Ok(n) if n == len)
+    {
+        warn!("[rrp] recv pipe full. packet rejected.");
+        return Some(AttErrorCode::INSUFFICIENT_RESOURCES);
+    }
+    None
+}
+
 /// Create an advertiser to use to connect to a BLE Central, and wait for it to connect.
 async fn advertise<'a, C: Controller, P: PacketPool>(
     name: &'a str,
@@ -205,3 +412,23 @@ async fn hid_task<C: Controller, P: PacketPool>(
         rktk_log::debug!("Successfully sent report");
     }
 }
+
+async fn battery_task<P: PacketPool>(server: &Server<'_>, conn: &GattConnection<'_, '_, P>) {
+    loop {
+        let level = BATTERY_LEVEL_SIGNAL.wait().await;
+        if let Err(e) = server.battery_service.level.notify(conn, &level, true).await {
+            rktk_log::error!("failed to send battery level: {:?}", e);
+        }
+    }
+}
+
+async fn rrp_task<P: PacketPool>(server: &Server<'_>, conn: &GattConnection<'_, '_, P>) {
+    loop {
+        let mut data = [0u8; RRP_BLE_BUFFER_SIZE];
+        let to_send_bytes = RRP_SEND_PIPE.read(&mut data[1..]).await;
+        data[0] = to_send_bytes as u8;
+        if let Err(e) = server.rrp_service.response.notify(conn, &data, true).await {
+            rktk_log::error!("failed to send rrp data: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use embassy_futures::select::{select, select3};
+use rktk::drivers::interface::storage::StorageDriver;
+use rktk_log::{info, warn};
+use trouble_host::{Address, Controller, Identity, prelude::*};
+
+use super::{
+    TroubleSplitConfig, accepts, next_packet, on_packet_received, on_pairing_complete,
+    restore_bond,
+    server::{M2S_UUID, S2M_UUID, SPLIT_BLE_BUFFER_SIZE, SPLIT_SERVICE_UUID},
+    set_connected,
+};
+
+pub(crate) async fn run<C: Controller, P: PacketPool, S: StorageDriver>(
+    stack: &Stack<'_, C, P>,
+    config: &TroubleSplitConfig,
+    bond_storage: &Option<S>,
+) {
+    let mut bonded = restore_bond(stack, bond_storage).await;
+    let peer = Address::random(config.peer_address);
+    let mut central = stack.central();
+    let connect_config = ConnectConfig {
+        connect_params: Default::default(),
+        scan_config: ScanConfig {
+            filter_accept_list: &[(peer.kind, &peer.addr)],
+            ..Default::default()
+        },
+    };
+
+    loop {
+        info!("[split] connecting to slave");
+        let conn = match central.connect(&connect_config).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                rktk_log::error!("[split] connect error: {:?}", e);
+                embassy_time::Timer::after_secs(1).await;
+                continue;
+            }
+        };
+
+        if !accepts(&bonded, &conn.peer_identity()) {
+            warn!("[split] slave is not the bonded one. disconnecting.");
+            conn.disconnect();
+            embassy_time::Timer::after_secs(1).await;
+            continue;
+        }
+        if let Err(e) = conn.set_bondable(true) {
+            warn!("[split] failed to set bondable: {:?}", e);
+        }
+        if let Err(e) = conn.request_security() {
+            warn!("[split] failed to request security: {:?}", e);
+        }
+
+        match GattClient::<C, P, 4>::new(stack, &conn).await {
+            Ok(client) => {
+                select(client.task(), async {
+                    let Some((m2s, s2m)) = discover(&client).await else {
+                        return;
+                    };
+                    let mut listener = match client.subscribe(&s2m, false).await {
+                        Ok(listener) => listener,
+                        Err(e) => {
+                            #[cfg(feature = "defmt")]
+                            let e = defmt::Debug2Format(&e);
+                            warn!("[split] failed to subscribe: {:?}", e);
+                            return;
+                        }
+                    };
+
+                    set_connected(true);
+                    select3(
+                        async {
+                            loop {
+                                let data = listener.next().await;
+                                on_packet_received(data.as_ref());
+                            }
+                        },
+                        async {
+                            loop {
+                                let data = next_packet().await;
+                                if let Err(e) = client.write_characteristic(&m2s, &data).await {
+                                    #[cfg(feature = "defmt")]
+                                    let e = defmt::Debug2Format(&e);
+                                    warn!("[split] failed to send data: {:?}", e);
+                                }
+                            }
+                        },
+                        connection_events(&conn, &mut bonded, bond_storage),
+                    )
+                    .await;
+                })
+                .await;
+            }
+            Err(e) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                warn!("[split] failed to create gatt client: {:?}", e);
+            }
+        }
+        set_connected(false);
+
+        embassy_time::Timer::after_millis(500).await;
+    }
+}
+
+type SplitCharacteristic = Characteristic<[u8; SPLIT_BLE_BUFFER_SIZE]>;
+
+async fn discover<C: Controller, P: PacketPool>(
+    client: &GattClient<'_, C, P, 4>,
+) -> Option<(SplitCharacteristic, SplitCharacteristic)> {
+    let services =
+        client.services_by_uuid(&Uuid::new_long(SPLIT_SERVICE_UUID.to_le_bytes())).await.ok()?;
+    let Some(service) = services.first() else {
+        warn!("[split] split service not found");
+        return None;
+    };
+    let m2s = client
+        .characteristic_by_uuid(service, &Uuid::new_long(M2S_UUID.to_le_bytes()))
+        .await
+        .ok()?;
+    let s2m = client
+        .characteristic_by_uuid(service, &Uuid::new_long(S2M_UUID.to_le_bytes()))
+        .await
+        .ok()?;
+    Some((m2s, s2m))
+}
+
+/// Handles events of `conn` until it is disconnected.
+async fn connection_events<P: PacketPool, S: StorageDriver>(
+    conn: &Connection<'_, P>,
+    bonded: &mut Option<Identity>,
+    bond_storage: &Option<S>,
+) {
+    loop {
+        match conn.next().await {
+            ConnectionEvent::Disconnected { reason } => {
+                info!("[split] disconnected: {:?}", reason);
+                return;
+            }
+            ConnectionEvent::PairingComplete { bond, .. } => {
+                info!("[split] pairing complete");
+                on_pairing_complete(bonded, bond_storage, bond).await;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! BLE split driver using trouble.
+//!
+//! Master is the BLE central and slave is the peripheral. Slave hosts the split GATT service and
+//! master writes data to `m2s` characteristic and receives data from `s2m` characteristic by
+//! notification.
+//!
+//! Halves only connect to each other's static address in [`TroubleSplitConfig`], and the link is
+//! encrypted by pairing. If the link is lost, master reconnects and slave advertises again.
+//!
+//! If a storage is passed with [`TroubleSplitBuilder::with_bond_storage`] (or
+//! [`super::reporter::TroubleReporterBuilder::with_bond_storage`] on master sharing the stack),
+//! the bond is persisted and restored at boot. Once bonded, only the bonded half is accepted.
+//!
+//! - On slave, use [`TroubleSplitBuilder::build_peripheral`].
+//! - On master which reports to the host with trouble, use
+//!   [`super::reporter::TroubleReporterBuilder::with_split`] so that both share one BLE stack.
+//!   Otherwise, use [`TroubleSplitBuilder::build_central`].
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_sync::pipe::Pipe;
+use rktk::{
+    drivers::interface::{split::SplitDriver, storage::StorageDriver},
+    utils::RawMutex,
+};
+use rktk_log::{info, warn};
+use trouble_host::{Address, BondInformation, Controller, HostResources, Identity, prelude::*};
+
+use super::bond::{self, NoBondStorage, SPLIT_BOND_KEY};
+
+pub(crate) mod central;
+mod peripheral;
+mod server;
+
+static TX_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+static RX_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+/// True while the other half is connected and the split service is ready.
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+pub struct TroubleSplitConfig {
+    /// Static random address of this half.
+    pub address: [u8; 6],
+    /// Static random address of the other half. Other devices are not connected.
+    pub peer_address: [u8; 6],
+}
+
+/// Split driver which sends and receives data through the link managed by the BLE task.
+pub struct TroubleSplitDriver {
+    _private: (),
+}
+
+impl TroubleSplitDriver {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl SplitDriver for TroubleSplitDriver {
+    type Error = core::convert::Infallible;
+
+    async fn recv(&mut self, buf: &mut [u8], _is_master: bool) -> Result<usize, Self::Error> {
+        Ok(RX_PIPE.read(buf).await)
+    }
+
+    /// Data is dropped while the other half is not connected. Split handler retransmits it.
+    async fn send_all(&mut self, buf: &[u8], _is_master: bool) -> Result<(), Self::Error> {
+        if CONNECTED.load(Ordering::Acquire) {
+            TX_PIPE.write_all(buf).await;
+        }
+        Ok(())
+    }
+}
+
+fn set_connected(connected: bool) {
+    CONNECTED.store(connected, Ordering::Release);
+    if !connected {
+        // Discard data which couldn't be sent to the previous connection.
+        let mut buf = [0u8; 32];
+        while TX_PIPE.try_read(&mut buf).is_ok() {}
+        info!("[split] disconnected");
+    } else {
+        info!("[split] connected");
+    }
+}
+
+/// Builder of split driver which runs its own BLE stack.
+pub struct TroubleSplitBuilder<
+    C: Controller + 'static,
+    const L2CAP_CHANNELS_MAX: usize,
+    const L2CAP_MTU: usize,
+    S: StorageDriver + 'static = NoBondStorage,
+> {
+    controller: C,
+    config: TroubleSplitConfig,
+    bond_storage: Option<S>,
+}
+
+impl<C: Controller + 'static, const L2CAP_CHANNELS_MAX: usize, const L2CAP_MTU: usize>
+    TroubleSplitBuilder<C, L2CAP_CHANNELS_MAX, L2CAP_MTU>
+{
+    pub fn new(controller: C, config: TroubleSplitConfig) -> Self {
+        Self { controller, config, bond_storage: None }
+    }
+}
+
+impl<
+    C: Controller + 'static,
+    const L2CAP_CHANNELS_MAX: usize,
+    const L2CAP_MTU: usize,
+    S: StorageDriver + 'static,
+> TroubleSplitBuilder<C, L2CAP_CHANNELS_MAX, L2CAP_MTU, S>
+{
+    /// Persists the bond with the other half to `storage`, so halves don't have to pair again
+    /// after reboot. Without this, the bond is lost at reboot.
+    ///
+    /// See [`crate::trouble::bond`] for the requirement of the storage.
+    pub fn with_bond_storage<S2: StorageDriver + 'static>(
+        self,
+        storage: S2,
+    ) -> TroubleSplitBuilder<C, L2CAP_CHANNELS_MAX, L2CAP_MTU, S2> {
+        TroubleSplitBuilder {
+            controller: self.controller,
+            config: self.config,
+            bond_storage: Some(storage),
+        }
+    }
+
+    /// Builds split driver for master side.
+    ///
+    /// Returned future runs the BLE stack and must be run along with rktk (e.g. join it with
+    /// [`rktk::task::start`]).
+    pub fn build_central(self) -> (TroubleSplitDriver, impl Future<Output = ()> + 'static) {
+        (TroubleSplitDriver::new(), async move {
+            let mut resources: HostResources<
+                _,
+                DefaultPacketPool,
+                1,
+                L2CAP_CHANNELS_MAX,
+                L2CAP_MTU,
+            > = HostResources::new();
+            let stack = trouble_host::new(self.controller, &mut resources)
+                .set_random_address(Address::random(self.config.address))
+                .build();
+            embassy_futures::join::join(
+                run_stack(stack.runner()),
+                central::run(&stack, &self.config, &self.bond_storage),
+            )
+            .await;
+        })
+    }
+
+    /// Builds split driver for slave side.
+    ///
+    /// Returned future runs the BLE stack and must be run along with rktk (e.g. join it with
+    /// [`rktk::task::start`]).
+    pub fn build_peripheral(self) -> (TroubleSplitDriver, impl Future<Output = ()> + 'static) {
+        (TroubleSplitDriver::new(), async move {
+            let mut resources: HostResources<
+                _,
+                DefaultPacketPool,
+                1,
+                L2CAP_CHANNELS_MAX,
+                L2CAP_MTU,
+            > = HostResources::new();
+            let stack = trouble_host::new(self.controller, &mut resources)
+                .set_random_address(Address::random(self.config.address))
+                .build();
+            embassy_futures::join::join(
+                run_stack(stack.runner()),
+                peripheral::run(&stack, &self.config, &self.bond_storage),
+            )
+            .await;
+        })
+    }
+}
+
+async fn run_stack<C: Controller, P: PacketPool>(mut runner: Runner<'_, C, P>) {
+    loop {
+        if let Err(e) = runner.run().await {
+            #[cfg(feature = "defmt")]
+            let e = defmt::Debug2Format(&e);
+            rktk_log::error!("[split] {:?}", e);
+            return;
+        }
+    }
+}
+
+/// Restores the bond with the other half, and returns its identity.
+async fn restore_bond<C: Controller, P: PacketPool, S: StorageDriver>(
+    stack: &Stack<'_, C, P>,
+    bond_storage: &Option<S>,
+) -> Option<Identity> {
+    let bond = bond::load(bond_storage, SPLIT_BOND_KEY).await?;
+    match stack.add_bond_information(bond.clone()) {
+        Ok(()) => {
+            info!("[split] bond restored");
+            Some(bond.identity)
+        }
+        Err(e) => {
+            warn!("[split] failed to restore bond: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Returns true if `peer` may connect. Once bonded, only the bonded half is accepted.
+fn accepts(bonded: &Option<Identity>, peer: &Identity) -> bool {
+    bonded.as_ref().is_none_or(|bonded| bonded == peer)
+}
+
+async fn on_pairing_complete<S: StorageDriver>(
+    bonded: &mut Option<Identity>,
+    bond_storage: &Option<S>,
+    bond: Option<BondInformation>,
+) {
+    if let Some(bond) = bond {
+        bond::save(bond_storage, SPLIT_BOND_KEY, &bond).await;
+        *bonded = Some(bond.identity);
+    }
+}
+
+/// Writes a packet received from the other half. The first byte of each packet is the length of
+/// the payload.
+///
+/// This is called from the BLE event loop, so it must not wait. If the pipe doesn't have room
+/// for the whole payload, the packet is dropped and retransmitted by the split handler.
+fn on_packet_received(data: &[u8]) {
+    let Some(&len) = data.first() else {
+        return;
+    };
+    let len = len as usize;
+    if len == 0 || len >= data.len() {
+        rktk_log::warn!("[split] invalid packet");
+        return;
+    }
+    if RX_PIPE.free_capacity() < len
+        || !matches!(RX_PIPE.try_write(&data[1..=len]), Ok(n) if n == len)
+    {
+        warn!("[split] rx pipe full. packet dropped.");
+    }
+}
+
+/// Waits data to send and returns it as a packet.
+async fn next_packet() -> [u8; server::SPLIT_BLE_BUFFER_SIZE] {
+    let mut data = [0u8; server::SPLIT_BLE_BUFFER_SIZE];
+    let len = TX_PIPE.read(&mut data[1..]).await;
+    data[0] = len as u8;
+    data
+}
@@ -0,0 +1,165 @@
+use embassy_futures::select::select;
+use rktk::drivers::interface::storage::StorageDriver;
+use rktk_log::{info, warn};
+use trouble_host::{
+    Controller, Identity,
+    gap::{GapConfig, PeripheralConfig},
+    prelude::*,
+};
+
+use super::{
+    TroubleSplitConfig, accepts, next_packet, on_packet_received, on_pairing_complete,
+    restore_bond,
+    server::{SPLIT_BLE_BUFFER_SIZE, SplitServer},
+    set_connected,
+};
+
+pub async fn run<C: Controller, P: PacketPool, S: StorageDriver>(
+    stack: &Stack<'_, C, P>,
+    config: &TroubleSplitConfig,
+    bond_storage: &Option<S>,
+) {
+    let server = match SplitServer::new_with_config(GapConfig::Peripheral(PeripheralConfig {
+        name: "rktk split",
+        appearance: &appearance::human_interface_device::KEYBOARD,
+    })) {
+        Ok(server) => server,
+        Err(e) => {
+            rktk_log::error!("[split] failed to create gatt server: {:?}", e);
+            return;
+        }
+    };
+    let mut bonded = restore_bond(stack, bond_storage).await;
+    let peer = BdAddr::new(config.peer_address);
+    let mut peripheral = stack.peripheral();
+
+    loop {
+        let conn = match advertise(&mut peripheral).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                #[cfg(feature = "defmt")]
+                let e = defmt::Debug2Format(&e);
+                rktk_log::error!("[split] advertise error: {:?}", e);
+                embassy_time::Timer::after_secs(1).await;
+                continue;
+            }
+        };
+        if conn.peer_address() != peer || !accepts(&bonded, &conn.peer_identity()) {
+            warn!("[split] unknown device connected. disconnecting.");
+            conn.disconnect();
+            continue;
+        }
+        if let Err(e) = conn.set_bondable(true) {
+            warn!("[split] failed to set bondable: {:?}", e);
+        }
+        let gatt_conn = match conn.with_attribute_server(&server) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("[split] failed to attach gatt server: {:?}", e);
+                continue;
+            }
+        };
+
+        set_connected(true);
+        select(
+            gatt_events_task(&server, &gatt_conn, &mut bonded, bond_storage),
+            send_task(&server, &gatt_conn),
+        )
+        .await;
+        set_connected(false);
+    }
+}
+
+/// Advertises only for the other half, and waits for it to connect.
+async fn advertise<'a, C: Controller, P: PacketPool>(
+    peripheral: &mut Peripheral<'a, C, P>,
+) -> Result<Connection<'a, P>, BleHostError<C::Error>> {
+    let mut advertiser_data = [0; 31];
+    AdStructure::encode_slice(
+        &[AdStructure::Flags(LE_GENERAL_DISCOVERABLE | BR_EDR_NOT_SUPPORTED)],
+        &mut advertiser_data[..],
+    )?;
+    let advertiser = peripheral
+        .advertise(
+            &Default::default(),
+            Advertisement::ConnectableScannableUndirected {
+                adv_data: &advertiser_data[..],
+                scan_data: &[],
+            },
+        )
+        .await?;
+    info!("[split] advertising");
+    advertiser.accept().await
+}
+
+async fn gatt_events_task<P: PacketPool, S: StorageDriver>(
+    server: &SplitServer<'_>,
+    conn: &GattConnection<'_, '_, P>,
+    bonded: &mut Option<Identity>,
+    bond_storage: &Option<S>,
+) {
+    loop {
+        match conn.next().await {
+            GattConnectionEvent::Disconnected { reason } => {
+                info!("[split] disconnected: {:?}", reason);
+                break;
+            }
+            GattConnectionEvent::Gatt { event } => {
+                let mut packet = None;
+                let result = match &event {
+                    GattEvent::Write(event)
+                        if event.handle() == server.split_service.m2s.handle =>
+                    {
+                        if is_encrypted(conn) {
+                            let mut buf = [0u8; SPLIT_BLE_BUFFER_SIZE];
+                            let len = event.data().len().min(SPLIT_BLE_BUFFER_SIZE);
+                            buf[..len].copy_from_slice(&event.data()[..len]);
+                            packet = Some((buf, len));
+                            None
+                        } else {
+                            Some(AttErrorCode::INSUFFICIENT_ENCRYPTION)
+                        }
+                    }
+                    _ => None,
+                };
+
+                let result =
+                    if let Some(code) = result { event.reject(code) } else { event.accept() };
+                match result {
+                    Ok(reply) => {
+                        reply.send().await;
+                    }
+                    Err(e) => {
+                        warn!("[split] error sending response: {:?}", e);
+                    }
+                }
+
+                if let Some((buf, len)) = packet {
+                    on_packet_received(&buf[..len]);
+                }
+            }
+            GattConnectionEvent::PairingComplete { bond, .. } => {
+                info!("[split] pairing complete");
+                on_pairing_complete(bonded, bond_storage, bond).await;
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn send_task<P: PacketPool>(server: &SplitServer<'_>, conn: &GattConnection<'_, '_, P>) {
+    loop {
+        let data = next_packet().await;
+        if !is_encrypted(conn) {
+            // Data sent before pairing is dropped. It is retransmitted by the split handler.
+            continue;
+        }
+        if let Err(e) = server.split_service.s2m.notify(conn, &data, true).await {
+            rktk_log::error!("[split] failed to send data: {:?}", e);
+        }
+    }
+}
+
+fn is_encrypted<P: PacketPool>(conn: &GattConnection<'_, '_, P>) -> bool {
+    conn.raw().security_level().map(|l| l.encrypted()) == Ok(true)
+}
@@ -0,0 +1,27 @@
+use trouble_host::prelude::*;
+
+/// Size of one split packet over GATT.
+///
+/// Like rrp over BLE, the first byte of each packet is the length of the payload.
+pub(super) const SPLIT_BLE_BUFFER_SIZE: usize = 32;
+
+pub(super) const SPLIT_SERVICE_UUID: u128 = 0x8c5a1001_5e2f_4a8b_9d1e_72726b746b00;
+pub(super) const M2S_UUID: u128 = 0x8c5a1002_5e2f_4a8b_9d1e_72726b746b00;
+pub(super) const S2M_UUID: u128 = 0x8c5a1003_5e2f_4a8b_9d1e_72726b746b00;
+
+// GATT Server of the slave side
+#[gatt_server]
+pub(super) struct SplitServer {
+    pub split_service: SplitService,
+}
+
+/// Split service
+#[gatt_service(uuid = "8c5a1001-5e2f-4a8b-9d1e-72726b746b00")]
+pub(super) struct SplitService {
+    /// Data from master to slave
+    #[characteristic(uuid = "8c5a1002-5e2f-4a8b-9d1e-72726b746b00", write, write_without_response)]
+    pub m2s: [u8; SPLIT_BLE_BUFFER_SIZE],
+    /// Data from slave to master
+    #[characteristic(uuid = "8c5a1003-5e2f-4a8b-9d1e-72726b746b00", read, notify)]
+    pub s2m: [u8; SPLIT_BLE_BUFFER_SIZE],
+}
@@ -117,4 +117,12 @@ impl UsbReporterDriver for CommonUsbDriver {
     async fn vbus_detect(&self) {
         super::POWERED_SIGNAL.wait().await;
     }
+
+    fn is_connected(&self) -> bool {
+        super::CONFIGURED.load(core::sync::atomic::Ordering::Acquire)
+    }
+
+    async fn wait_connection_change(&self) {
+        super::CONNECTION_SIGNAL.wait().await;
+    }
 }
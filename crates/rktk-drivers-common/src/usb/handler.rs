@@ -1,7 +1,7 @@
 use core::sync::atomic::Ordering;
 use embassy_usb::Handler;
 
-use super::SUSPENDED;
+use super::{CONFIGURED, CONNECTION_SIGNAL, SUSPENDED};
 
 pub struct UsbDeviceHandler {}
 
@@ -13,9 +13,20 @@ impl UsbDeviceHandler {
 
 // Ref: https://www.itf.co.jp/tech/road-to-usb-master/usb-status
 impl Handler for UsbDeviceHandler {
-    fn enabled(&mut self, _enabled: bool) {
+    fn enabled(&mut self, enabled: bool) {
         super::POWERED_SIGNAL.signal(());
         SUSPENDED.store(false, Ordering::Release);
+        if !enabled {
+            set_configured(false);
+        }
+    }
+
+    fn reset(&mut self) {
+        set_configured(false);
+    }
+
+    fn configured(&mut self, configured: bool) {
+        set_configured(configured);
     }
 
     fn suspended(&mut self, suspended: bool) {
@@ -26,3 +37,9 @@ impl Handler for UsbDeviceHandler {
         }
     }
 }
+
+fn set_configured(configured: bool) {
+    if CONFIGURED.swap(configured, Ordering::AcqRel) != configured {
+        CONNECTION_SIGNAL.signal(());
+    }
+}
@@ -19,6 +19,10 @@ static POWERED_SIGNAL: rktk::utils::Signal<()> = rktk::utils::Signal::new();
 
 static SUSPENDED: AtomicBool = AtomicBool::new(false);
 
+/// True while the device is configured by the host.
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+static CONNECTION_SIGNAL: rktk::utils::Signal<()> = rktk::utils::Signal::new();
+
 pub use builder::CommonUsbReporterBuilder;
 /// Re-export of underlying embassy-usb driver's config type
 pub use embassy_usb::Config as UsbDriverConfig;
@@ -0,0 +1,74 @@
+//! Battery driver using SAADC.
+
+use core::convert::Infallible;
+
+use embassy_nrf::{
+    gpio::{Input, Level},
+    saadc::Saadc,
+};
+use rktk::drivers::interface::battery::{BatteryDriver, DischargeCurve};
+
+/// Voltage (mV) at the input pin per LSB with the default config of [`Saadc`] (12bit resolution,
+/// 1/6 gain and 0.6V internal reference).
+const MV_PER_LSB: f32 = 3600.0 / 4096.0;
+
+/// Battery driver which measures the battery voltage with SAADC.
+///
+/// `saadc` must have one channel connected to the battery (usually through a voltage divider) and
+/// use the default config.
+pub struct SaadcBattery<'d> {
+    saadc: Saadc<'d, 1>,
+    divider_ratio: f32,
+    curve: DischargeCurve,
+    charger: Option<(Input<'d>, Level)>,
+    calibrated: bool,
+}
+
+impl<'d> SaadcBattery<'d> {
+    /// Creates the driver.
+    ///
+    /// `divider_ratio` is the ratio of the battery voltage to the voltage at the input pin. For
+    /// example, this is `2.0` if the battery is connected through a divider of two equal resistors,
+    /// and `5.0` if the battery is connected to `VDDH` and measured with `VddhDiv5Input`.
+    pub fn new(saadc: Saadc<'d, 1>, divider_ratio: f32) -> Self {
+        Self { saadc, divider_ratio, curve: DischargeCurve::LIPO, charger: None, calibrated: false }
+    }
+
+    /// Sets the discharge curve. [`DischargeCurve::LIPO`] is used by default.
+    pub fn with_discharge_curve(mut self, curve: DischargeCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Sets the status pin of the charger, which is `charging_level` while charging.
+    ///
+    /// For example, `CHRG` pin of TP4056 is low while charging.
+    pub fn with_charger_pin(mut self, pin: Input<'d>, charging_level: Level) -> Self {
+        self.charger = Some((pin, charging_level));
+        self
+    }
+}
+
+impl BatteryDriver for SaadcBattery<'_> {
+    type Error = Infallible;
+
+    async fn read_voltage(&mut self) -> Result<u16, Self::Error> {
+        if !self.calibrated {
+            self.saadc.calibrate().await;
+            self.calibrated = true;
+        }
+
+        let mut buf = [0i16; 1];
+        self.saadc.sample(&mut buf).await;
+        let voltage = buf[0].max(0) as f32 * MV_PER_LSB * self.divider_ratio;
+        Ok(voltage as u16)
+    }
+
+    fn discharge_curve(&self) -> DischargeCurve {
+        self.curve
+    }
+
+    fn is_charging(&mut self) -> Option<bool> {
+        self.charger.as_ref().map(|(pin, level)| pin.get_level() == *level)
+    }
+}
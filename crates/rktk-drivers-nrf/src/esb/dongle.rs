@@ -1,90 +1,55 @@
-use core::{marker::PhantomData, sync::atomic::AtomicBool};
-
-use embassy_nrf::{
-    interrupt::{self, typelevel::Binding},
-    pac::Interrupt,
-    radio::{self},
-    timer,
-};
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use esb_ng::{
-    EsbApp, EsbBuffer, EsbIrq, IrqTimer, irq::StatePRX, payload::BBQueueType,
-    peripherals::PtrTimer as _,
-};
+use embassy_nrf::{interrupt::typelevel::Binding, timer};
+use esb_ng::{EsbApp, EsbHeader};
+use postcard::experimental::max_size::MaxSize as _;
 use rktk::drivers::interface::dongle::{DongleData, DongleDriver, DongleDriverBuilder};
 use rktk_log::helper::Debug2Format;
 
-use super::Config;
-
-macro_rules! use_peripheral {
-    ($radio:ident, $timer:ident, $esb_timer:ident) => {
-        type DongleRadio = embassy_nrf::peripherals::$radio;
-        const DONGLE_RADIO_PAC: embassy_nrf::pac::radio::Radio = embassy_nrf::pac::$radio;
-        type DongleTimer = embassy_nrf::peripherals::$timer;
-        type DongleTimerEsb = esb_ng::peripherals::$esb_timer;
-    };
-}
-
-use_peripheral!(RADIO, TIMER0, Timer0);
-
-static IRQ_TIMER: Mutex<CriticalSectionRawMutex, Option<IrqTimer<DongleTimerEsb>>> =
-    Mutex::new(None);
-
-pub struct TimerInterruptHandler {
-    _phantom: PhantomData<()>,
-}
-
-impl interrupt::typelevel::Handler<<DongleTimer as timer::Instance>::Interrupt>
-    for TimerInterruptHandler
-{
-    unsafe fn on_interrupt() {
-        if let Ok(mut irq_timer) = IRQ_TIMER.try_lock()
-            && let Some(irq_timer) = &mut *irq_timer
-        {
-            irq_timer.timer_interrupt();
-        }
-    }
-}
-
-static ESB_IRQ: Mutex<
-    CriticalSectionRawMutex,
-    Option<EsbIrq<1024, 1024, DongleTimerEsb, StatePRX>>,
-> = Mutex::new(None);
-
-pub struct EsbInterruptHandler {
-    _phantom: PhantomData<()>,
-}
-
-impl interrupt::typelevel::Handler<<DongleRadio as radio::Instance>::Interrupt>
-    for EsbInterruptHandler
-{
-    unsafe fn on_interrupt() {
-        if let Ok(mut esb_irq) = ESB_IRQ.try_lock()
-            && let Some(esb_irq) = &mut *esb_irq
-            && let Err(e) = esb_irq.radio_interrupt()
-        {
-            rktk_log::warn!("Irq error: {:?}", Debug2Format(&e));
-        }
-    }
-}
+pub use super::prx::{EsbInterruptHandler, TimerInterruptHandler};
+use super::{
+    Config, EsbRadio, EsbTimer, MAX_PAYLOAD_SIZE,
+    packet::EsbPacket,
+    prx,
+    split::{self, EsbSplitDriver},
+};
 
 // ---- Builder -------
 
 pub struct EsbDongleDriverBuilder {
-    _timer: DongleTimer,
-    _radio: DongleRadio,
+    _timer: EsbTimer,
+    _radio: EsbRadio,
     config: Config,
 }
 
 impl EsbDongleDriverBuilder {
     pub fn new(
-        timer: DongleTimer,
-        radio: DongleRadio,
-        _irqs: impl Binding<<DongleTimer as timer::Instance>::Interrupt, TimerInterruptHandler>,
+        timer: EsbTimer,
+        radio: EsbRadio,
+        _irqs: impl Binding<<EsbTimer as timer::Instance>::Interrupt, TimerInterruptHandler>,
         config: Config,
     ) -> Self {
         Self { _timer: timer, _radio: radio, config }
     }
+
+    /// Builds split drivers of the left and right halves, for the dongle which processes keys (see
+    /// [`rktk::dongle_task::start_central_dongle`]).
+    ///
+    /// Left half must use pipe 0 and right half must use pipe 1. On halves, use
+    /// [`super::split::EsbSplitDriverBuilder::build_slave`].
+    ///
+    /// Returned future runs the radio and must be run along with rktk.
+    pub async fn build_central(
+        self,
+    ) -> Result<(EsbSplitDriver, EsbSplitDriver, impl Future<Output = ()> + 'static), &'static str>
+    {
+        let esb_app = prx::init(self.config).await?;
+        let [left, right] = &split::CENTRAL_LINKS;
+
+        Ok((
+            EsbSplitDriver::new(left),
+            EsbSplitDriver::new(right),
+            split::prx_task(esb_app, [(0, left), (1, right)]),
+        ))
+    }
 }
 
 impl DongleDriverBuilder for EsbDongleDriverBuilder {
@@ -93,32 +58,9 @@ impl DongleDriverBuilder for EsbDongleDriverBuilder {
     type Error = &'static str;
 
     async fn build(self) -> Result<(Self::Output, impl Future<Output = ()>), Self::Error> {
-        static BUFFER: EsbBuffer<1024, 1024> = EsbBuffer {
-            app_to_radio_buf: BBQueueType::new(),
-            radio_to_app_buf: BBQueueType::new(),
-            timer_flag: AtomicBool::new(false),
-        };
-        let config =
-            self.config.config.max_payload_size(192).check().map_err(|_| "Invalid config")?;
-        let (esb_app, esb_irq, esb_timer) = BUFFER
-            .try_split(
-                unsafe { DongleTimerEsb::take() },
-                DONGLE_RADIO_PAC,
-                self.config.addresses,
-                config,
-            )
-            .map_err(|_| "Failed to initialize")?;
-        let mut esb_irq = esb_irq.into_prx();
-        esb_irq.start_receiving().map_err(|_| "Failed to start receiving")?;
-        ESB_IRQ.lock().await.replace(esb_irq);
-        IRQ_TIMER.lock().await.replace(esb_timer);
-
-        unsafe {
-            cortex_m::peripheral::NVIC::unmask(Interrupt::RADIO);
-            cortex_m::peripheral::NVIC::unmask(Interrupt::TIMER0);
-        }
+        let esb_app = prx::init(self.config).await?;
 
-        Ok((EsbDongleDriver { esb: esb_app, cnt: 0 }, async {}))
+        Ok((EsbDongleDriver { esb: esb_app, cnt: 0, pid: 0 }, async {}))
     }
 }
 
@@ -127,6 +69,7 @@ impl DongleDriverBuilder for EsbDongleDriverBuilder {
 pub struct EsbDongleDriver {
     esb: EsbApp<1024, 1024>,
     cnt: u8,
+    pid: u8,
 }
 
 #[derive(Debug)]
@@ -142,23 +85,60 @@ impl DongleDriver for EsbDongleDriver {
     type Error = EsbDongleError;
 
     async fn recv(&mut self) -> Result<DongleData, Self::Error> {
-        let payload = self.esb.wait_read_packet().await;
-        let (cnt, data) = match postcard::from_bytes::<'_, (u8, DongleData)>(&payload) {
-            Ok(d) => d,
-            Err(e) => {
-                payload.release();
-                return Err(EsbDongleError::Deserialization(e));
+        loop {
+            let payload = self.esb.wait_read_packet().await;
+            let pipe = payload.pipe();
+            let packet = postcard::from_bytes::<EsbPacket>(&payload);
+            payload.release();
+
+            match packet.map_err(EsbDongleError::Deserialization)? {
+                EsbPacket::Report(cnt, data) => {
+                    rktk::print!("recv:{:?}", cnt);
+
+                    if cnt.wrapping_sub(self.cnt) > 1 {
+                        rktk_log::warn!("Packet dropped: {} -> {}", self.cnt, cnt);
+                    }
+                    self.cnt = cnt;
+
+                    return Ok(data);
+                }
+                EsbPacket::Split(chunk) => {
+                    self.relay_split(pipe, EsbPacket::Split(chunk));
+                }
+                EsbPacket::Poll => {}
             }
-        };
-        payload.release();
-
-        rktk::print!("recv:{:?}", cnt);
-
-        if cnt.wrapping_sub(self.cnt) > 1 {
-            rktk_log::warn!("Packet dropped: {} -> {}", self.cnt, cnt);
         }
-        self.cnt = cnt;
+    }
+}
 
-        Ok(data)
+impl EsbDongleDriver {
+    /// Relays split data to the other half. Data from pipe 0 is sent to pipe 1 and vice versa, as
+    /// an ACK payload of the next packet from it.
+    fn relay_split(&mut self, from_pipe: u8, packet: EsbPacket) {
+        let mut buf = [0; EsbPacket::POSTCARD_MAX_SIZE];
+        let Some(slice) = packet.encode(&mut buf) else {
+            return;
+        };
+        let Ok(esb_header) = EsbHeader::build()
+            .max_payload(MAX_PAYLOAD_SIZE)
+            .pid(self.pid)
+            .pipe(if from_pipe == 0 { 1 } else { 0 })
+            .no_ack(false)
+            .check()
+        else {
+            return;
+        };
+        // If the other half is not polling, the queue is full and data is dropped. Split handler
+        // retransmits it.
+        match self.esb.grant_packet(esb_header) {
+            Ok(mut p) => {
+                p[..slice.len()].copy_from_slice(slice);
+                p.commit(slice.len());
+                self.pid = (self.pid + 1) % 4;
+            }
+            Err(e) => {
+                rktk_log::warn!("Failed to relay split data: {:?}", Debug2Format(&e));
+            }
+        }
     }
 }
@@ -1,12 +1,34 @@
 pub use esb_ng::{Addresses, ConfigBuilder, Error};
 
 pub mod dongle;
+mod packet;
+pub mod prx;
+pub mod ptx;
 pub mod reporter;
+pub mod split;
+
+macro_rules! use_peripheral {
+    ($radio:ident, $timer:ident, $esb_timer:ident) => {
+        type EsbRadio = embassy_nrf::peripherals::$radio;
+        const ESB_RADIO_PAC: embassy_nrf::pac::radio::Radio = embassy_nrf::pac::$radio;
+        type EsbTimer = embassy_nrf::peripherals::$timer;
+        type EsbTimerEsb = esb_ng::peripherals::$esb_timer;
+    };
+}
+
+use_peripheral!(RADIO, TIMER0, Timer0);
+
+const MAX_PAYLOAD_SIZE: u8 = 192;
 
 #[derive(Default)]
 pub struct Config {
     pub addresses: Addresses,
     pub config: ConfigBuilder,
+    /// Pipe used to send data on PTX side (keyboard and slave of split).
+    ///
+    /// In dongle mode of split, halves must use different pipes (e.g. 0 for master and 1 for
+    /// slave).
+    pub pipe: u8,
 }
 
 pub fn create_address(channel: u8) -> Result<Addresses, Error> {
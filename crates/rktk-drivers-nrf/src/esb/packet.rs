@@ -0,0 +1,43 @@
+use postcard::experimental::max_size::MaxSize;
+use rktk::drivers::interface::dongle::DongleData;
+use serde::{Deserialize, Serialize};
+
+/// Max size of split data in one [`SplitChunk`].
+pub(super) const SPLIT_CHUNK_SIZE: usize = 32;
+
+/// Chunk of split data.
+#[derive(Debug, Serialize, Deserialize, MaxSize)]
+pub(super) struct SplitChunk {
+    pub len: u8,
+    pub data: [u8; SPLIT_CHUNK_SIZE],
+}
+
+impl SplitChunk {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..(self.len as usize).min(SPLIT_CHUNK_SIZE)]
+    }
+}
+
+/// Payload of each ESB packet.
+#[derive(Debug, Serialize, Deserialize, MaxSize)]
+pub(super) enum EsbPacket {
+    /// HID report with the counter to detect dropped packets.
+    Report(u8, DongleData),
+    /// Data of split.
+    Split(SplitChunk),
+    /// Sent periodically by PTX side, so that PRX side can send split data as an ACK payload.
+    Poll,
+}
+
+impl EsbPacket {
+    /// Serializes the packet. `buf` should be at least [`MaxSize::POSTCARD_MAX_SIZE`] bytes.
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> Option<&'a [u8]> {
+        match postcard::to_slice(self, buf) {
+            Ok(slice) => Some(&*slice),
+            Err(_) => {
+                rktk_log::warn!("Postcard error");
+                None
+            }
+        }
+    }
+}
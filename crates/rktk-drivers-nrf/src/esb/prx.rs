@@ -0,0 +1,81 @@
+//! ESB in PRX (primary receiver) mode, used on the dongle side and the master side of split.
+
+use core::{marker::PhantomData, sync::atomic::AtomicBool};
+
+use embassy_nrf::{interrupt, pac::Interrupt, radio, timer};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use esb_ng::{
+    EsbApp, EsbBuffer, EsbIrq, IrqTimer, irq::StatePRX, payload::BBQueueType,
+    peripherals::PtrTimer as _,
+};
+use rktk_log::helper::Debug2Format;
+
+use super::{ESB_RADIO_PAC, EsbRadio, EsbTimer, EsbTimerEsb};
+
+static IRQ_TIMER: Mutex<CriticalSectionRawMutex, Option<IrqTimer<EsbTimerEsb>>> = Mutex::new(None);
+
+pub struct TimerInterruptHandler {
+    _phantom: PhantomData<()>,
+}
+
+impl interrupt::typelevel::Handler<<EsbTimer as timer::Instance>::Interrupt>
+    for TimerInterruptHandler
+{
+    unsafe fn on_interrupt() {
+        if let Ok(mut irq_timer) = IRQ_TIMER.try_lock()
+            && let Some(irq_timer) = &mut *irq_timer
+        {
+            irq_timer.timer_interrupt();
+        }
+    }
+}
+
+static ESB_IRQ: Mutex<CriticalSectionRawMutex, Option<EsbIrq<1024, 1024, EsbTimerEsb, StatePRX>>> =
+    Mutex::new(None);
+
+pub struct EsbInterruptHandler {
+    _phantom: PhantomData<()>,
+}
+
+impl interrupt::typelevel::Handler<<EsbRadio as radio::Instance>::Interrupt>
+    for EsbInterruptHandler
+{
+    unsafe fn on_interrupt() {
+        if let Ok(mut esb_irq) = ESB_IRQ.try_lock()
+            && let Some(esb_irq) = &mut *esb_irq
+            && let Err(e) = esb_irq.radio_interrupt()
+        {
+            rktk_log::warn!("Irq error: {:?}", Debug2Format(&e));
+        }
+    }
+}
+
+/// Initializes ESB as PRX and starts receiving.
+///
+/// This function must be called only once.
+pub(super) async fn init(config: super::Config) -> Result<EsbApp<1024, 1024>, &'static str> {
+    static BUFFER: EsbBuffer<1024, 1024> = EsbBuffer {
+        app_to_radio_buf: BBQueueType::new(),
+        radio_to_app_buf: BBQueueType::new(),
+        timer_flag: AtomicBool::new(false),
+    };
+    let esb_config = config
+        .config
+        .max_payload_size(super::MAX_PAYLOAD_SIZE)
+        .check()
+        .map_err(|_| "Config error")?;
+
+    let (esb_app, esb_irq, esb_timer) = BUFFER
+        .try_split(unsafe { EsbTimerEsb::take() }, ESB_RADIO_PAC, config.addresses, esb_config)
+        .map_err(|_| "Failed to initialize")?;
+    let mut esb_irq = esb_irq.into_prx();
+    esb_irq.start_receiving().map_err(|_| "Failed to start receiving")?;
+    ESB_IRQ.lock().await.replace(esb_irq);
+    IRQ_TIMER.lock().await.replace(esb_timer);
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(Interrupt::RADIO);
+        cortex_m::peripheral::NVIC::unmask(Interrupt::TIMER0);
+    }
+
+    Ok(esb_app)
+}
@@ -0,0 +1,175 @@
+//! ESB in PTX (primary transmitter) mode, used on the keyboard side.
+
+use core::{future::pending, marker::PhantomData, sync::atomic::AtomicBool};
+
+use embassy_futures::{
+    join::join,
+    select::{Either, select},
+};
+use embassy_nrf::{interrupt, pac::Interrupt, radio, timer};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::{Duration, with_timeout};
+use esb_ng::{
+    EsbApp, EsbBuffer, EsbHeader, EsbIrq, IrqTimer, irq::StatePTX, payload::BBQueueType,
+    peripherals::PtrTimer as _,
+};
+use postcard::experimental::max_size::MaxSize as _;
+use rktk::{drivers::interface::dongle::DongleData, utils::Receiver};
+use rktk_log::{debug, helper::Debug2Format, warn};
+
+use super::{ESB_RADIO_PAC, EsbRadio, EsbTimer, EsbTimerEsb, packet::EsbPacket, split};
+
+static IRQ_TIMER: Mutex<CriticalSectionRawMutex, Option<IrqTimer<EsbTimerEsb>>> = Mutex::new(None);
+
+pub struct TimerInterruptHandler {
+    _phantom: PhantomData<()>,
+}
+
+impl interrupt::typelevel::Handler<<EsbTimer as timer::Instance>::Interrupt>
+    for TimerInterruptHandler
+{
+    unsafe fn on_interrupt() {
+        if let Ok(mut irq_timer) = IRQ_TIMER.try_lock()
+            && let Some(irq_timer) = &mut *irq_timer
+        {
+            irq_timer.timer_interrupt();
+        }
+    }
+}
+
+static ESB_IRQ: Mutex<CriticalSectionRawMutex, Option<EsbIrq<1024, 1024, EsbTimerEsb, StatePTX>>> =
+    Mutex::new(None);
+
+pub struct EsbInterruptHandler {
+    _phantom: PhantomData<()>,
+}
+
+impl interrupt::typelevel::Handler<<EsbRadio as radio::Instance>::Interrupt>
+    for EsbInterruptHandler
+{
+    unsafe fn on_interrupt() {
+        if let Ok(mut esb_irq) = ESB_IRQ.try_lock()
+            && let Some(esb_irq) = &mut *esb_irq
+            && let Err(e) = esb_irq.radio_interrupt()
+        {
+            rktk_log::warn!("Irq error: {:?}", Debug2Format(&e));
+        }
+    }
+}
+
+/// Initializes ESB as PTX.
+///
+/// This function must be called only once.
+pub(super) async fn init(config: super::Config) -> Result<EsbApp<1024, 1024>, &'static str> {
+    static BUFFER: EsbBuffer<1024, 1024> = EsbBuffer {
+        app_to_radio_buf: BBQueueType::new(),
+        radio_to_app_buf: BBQueueType::new(),
+        timer_flag: AtomicBool::new(false),
+    };
+    let esb_config = config
+        .config
+        .max_payload_size(super::MAX_PAYLOAD_SIZE)
+        .check()
+        .map_err(|_| "Config error")?;
+
+    let (esb_app, esb_irq, esb_timer) = BUFFER
+        .try_split(unsafe { EsbTimerEsb::take() }, ESB_RADIO_PAC, config.addresses, esb_config)
+        .map_err(|_| "Failed to initialize")?;
+    let esb_irq = esb_irq.into_ptx();
+    ESB_IRQ.lock().await.replace(esb_irq);
+    IRQ_TIMER.lock().await.replace(esb_timer);
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(Interrupt::RADIO);
+        cortex_m::peripheral::NVIC::unmask(Interrupt::TIMER0);
+    }
+
+    Ok(esb_app)
+}
+
+/// Runs PTX side of ESB.
+///
+/// - If `reports` is given, HID reports from it are sent.
+/// - If `split_poll_interval` is given, split data is sent, and split data from PRX side is
+///   received as an ACK payload. If there is nothing to send, poll packet is sent every
+///   `split_poll_interval`.
+pub(super) async fn run(
+    esb_app: EsbApp<1024, 1024>,
+    pipe: u8,
+    reports: Option<Receiver<'static, DongleData, 16>>,
+    split_poll_interval: Option<Duration>,
+) {
+    let mut cnt: u8 = 0;
+    let mut pid = 0;
+    let (mut s, mut r) = esb_app.split();
+    join(
+        async move {
+            loop {
+                let packet = match select(
+                    async {
+                        match &reports {
+                            Some(reports) => reports.receive().await,
+                            None => pending().await,
+                        }
+                    },
+                    async {
+                        match split_poll_interval {
+                            Some(interval) => with_timeout(interval, split::LINK.next_chunk())
+                                .await
+                                .map_or(EsbPacket::Poll, EsbPacket::Split),
+                            None => pending().await,
+                        }
+                    },
+                )
+                .await
+                {
+                    Either::First(report) => {
+                        cnt = cnt.wrapping_add(1);
+                        EsbPacket::Report(cnt, report)
+                    }
+                    Either::Second(packet) => packet,
+                };
+
+                let mut buf = [0; EsbPacket::POSTCARD_MAX_SIZE];
+                let Some(slice) = packet.encode(&mut buf) else {
+                    continue;
+                };
+
+                let esb_header = EsbHeader::build()
+                    .max_payload(super::MAX_PAYLOAD_SIZE)
+                    .pid(pid)
+                    .pipe(pipe)
+                    .no_ack(false)
+                    .check()
+                    .unwrap();
+                let mut payload = match s.wait_grant_packet(esb_header).await {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("Grant packet error: {:?}", Debug2Format(&e));
+                        continue;
+                    }
+                };
+                payload[..slice.len()].copy_from_slice(slice);
+                payload.commit(slice.len());
+                s.start_tx();
+
+                debug!("Sent packet: {:?}", slice);
+
+                pid = (pid + 1) % 4;
+            }
+        },
+        async move {
+            loop {
+                // ACK payload from PRX side
+                let payload = r.wait_read_packet().await;
+                let packet = postcard::from_bytes::<EsbPacket>(&payload);
+                payload.release();
+                match packet {
+                    Ok(EsbPacket::Split(chunk)) => split::LINK.on_chunk_received(&chunk),
+                    Ok(_) => {}
+                    Err(e) => warn!("Invalid packet: {:?}", Debug2Format(&e)),
+                }
+            }
+        },
+    )
+    .await;
+}
@@ -0,0 +1,197 @@
+//! Split driver using ESB.
+//!
+//! Slave is PTX and master is PRX. Slave sends data as soon as it is written (or poll packet if
+//! nothing is written for `poll_interval`), and master sends data as an ACK payload of it. So
+//! latency of master to slave data depends on `poll_interval`. As the radio is used for split,
+//! master cannot use ESB reporter in this mode.
+//!
+//! In dongle relay mode, both halves are PTX and the dongle relays split data between them without
+//! processing it (see [`super::dongle`]). On master half, use
+//! [`super::reporter::EsbReporterDriverBuilder::with_split`] so that reporter and split share the
+//! radio, and use [`EsbSplitDriverBuilder::build_slave`] on slave half with the different pipe.
+//!
+//! When the dongle processes keys, both halves are slaves and use
+//! [`EsbSplitDriverBuilder::build_slave`], and the dongle uses
+//! [`super::dongle::EsbDongleDriverBuilder::build_central`].
+//!
+//! Interrupts must be bound to handlers in [`super::prx`] on master and [`super::ptx`] on slave.
+
+use embassy_futures::{join::join, select::select_array};
+use embassy_nrf::{
+    interrupt::typelevel::Binding,
+    radio::{self},
+    timer,
+};
+use embassy_sync::pipe::Pipe;
+use embassy_time::Duration;
+use esb_ng::{EsbApp, EsbHeader};
+use postcard::experimental::max_size::MaxSize as _;
+use rktk::{drivers::interface::split::SplitDriver, utils::RawMutex};
+use rktk_log::{helper::Debug2Format, warn};
+
+use super::{
+    Config, EsbRadio, EsbTimer, MAX_PAYLOAD_SIZE,
+    packet::{EsbPacket, SPLIT_CHUNK_SIZE, SplitChunk},
+    prx, ptx,
+};
+
+/// Buffers of one split link.
+pub(super) struct Link {
+    tx: Pipe<RawMutex, 128>,
+    rx: Pipe<RawMutex, 128>,
+}
+
+impl Link {
+    const fn new() -> Self {
+        Self { tx: Pipe::new(), rx: Pipe::new() }
+    }
+
+    /// Waits data to send and returns it as a chunk.
+    pub(super) async fn next_chunk(&self) -> SplitChunk {
+        let mut data = [0u8; SPLIT_CHUNK_SIZE];
+        let len = self.tx.read(&mut data).await;
+        SplitChunk { len: len as u8, data }
+    }
+
+    /// Writes received chunk.
+    ///
+    /// This is called from the radio loop, so it must not wait. If the pipe doesn't have room for
+    /// the whole chunk, it is dropped and retransmitted by the split handler.
+    pub(super) fn on_chunk_received(&self, chunk: &SplitChunk) {
+        let data = chunk.as_bytes();
+        if self.rx.free_capacity() < data.len()
+            || !matches!(self.rx.try_write(data), Ok(n) if n == data.len())
+        {
+            warn!("Split rx pipe full. chunk dropped.");
+        }
+    }
+}
+
+/// Link to the other half, used on the keyboard.
+pub(super) static LINK: Link = Link::new();
+/// Links to the left and right halves, used on the dongle which processes keys. Index is the pipe.
+pub(super) static CENTRAL_LINKS: [Link; 2] = [Link::new(), Link::new()];
+
+/// Split driver which sends and receives data through the ESB task.
+pub struct EsbSplitDriver {
+    link: &'static Link,
+}
+
+impl EsbSplitDriver {
+    pub(super) fn new(link: &'static Link) -> Self {
+        Self { link }
+    }
+}
+
+impl SplitDriver for EsbSplitDriver {
+    type Error = core::convert::Infallible;
+
+    async fn recv(&mut self, buf: &mut [u8], _is_master: bool) -> Result<usize, Self::Error> {
+        Ok(self.link.rx.read(buf).await)
+    }
+
+    async fn send_all(&mut self, buf: &[u8], _is_master: bool) -> Result<(), Self::Error> {
+        self.link.tx.write_all(buf).await;
+        Ok(())
+    }
+}
+
+pub struct EsbSplitDriverBuilder {
+    _timer: EsbTimer,
+    _radio: EsbRadio,
+    config: Config,
+}
+
+impl EsbSplitDriverBuilder {
+    pub fn new(timer: EsbTimer, radio: EsbRadio, config: Config) -> Self {
+        Self { _timer: timer, _radio: radio, config }
+    }
+
+    /// Builds split driver for master side. ESB is used as PRX.
+    ///
+    /// Returned future must be run along with rktk (e.g. join it with [`rktk::task::start`]).
+    pub async fn build_master(
+        self,
+        _irqs: impl Binding<<EsbTimer as timer::Instance>::Interrupt, prx::TimerInterruptHandler>
+        + Binding<<EsbRadio as radio::Instance>::Interrupt, prx::EsbInterruptHandler>,
+    ) -> Result<(EsbSplitDriver, impl Future<Output = ()> + 'static), &'static str> {
+        let pipe = self.config.pipe;
+        let esb_app = prx::init(self.config).await?;
+        Ok((EsbSplitDriver::new(&LINK), prx_task(esb_app, [(pipe, &LINK)])))
+    }
+
+    /// Builds split driver for slave side. ESB is used as PTX.
+    ///
+    /// `poll_interval` is the interval to poll data from master when slave has nothing to send.
+    ///
+    /// Returned future must be run along with rktk (e.g. join it with [`rktk::task::start`]).
+    pub async fn build_slave(
+        self,
+        _irqs: impl Binding<<EsbTimer as timer::Instance>::Interrupt, ptx::TimerInterruptHandler>
+        + Binding<<EsbRadio as radio::Instance>::Interrupt, ptx::EsbInterruptHandler>,
+        poll_interval: Duration,
+    ) -> Result<(EsbSplitDriver, impl Future<Output = ()> + 'static), &'static str> {
+        let pipe = self.config.pipe;
+        let esb_app = ptx::init(self.config).await?;
+        Ok((EsbSplitDriver::new(&LINK), ptx::run(esb_app, pipe, None, Some(poll_interval))))
+    }
+}
+
+/// Runs PRX side of split. Each link in `links` is bound to its pipe.
+pub(super) async fn prx_task<const N: usize>(
+    esb_app: EsbApp<1024, 1024>,
+    links: [(u8, &'static Link); N],
+) {
+    let mut pid = 0;
+    let (mut s, mut r) = esb_app.split();
+    join(
+        async move {
+            loop {
+                let (chunk, i) = select_array(links.map(|(_, link)| link.next_chunk())).await;
+                let packet = EsbPacket::Split(chunk);
+                let mut buf = [0; EsbPacket::POSTCARD_MAX_SIZE];
+                let Some(slice) = packet.encode(&mut buf) else {
+                    continue;
+                };
+
+                let esb_header = EsbHeader::build()
+                    .max_payload(MAX_PAYLOAD_SIZE)
+                    .pid(pid)
+                    .pipe(links[i].0)
+                    .no_ack(false)
+                    .check()
+                    .unwrap();
+                // Sent as an ACK payload when the other side sends next packet. If it is not
+                // polling, the queue is full and data is dropped. Split handler retransmits it.
+                let mut payload = match s.grant_packet(esb_header) {
+                    Ok(p) => p,
+                    Err(e) => {
+                        warn!("Grant packet error: {:?}", Debug2Format(&e));
+                        continue;
+                    }
+                };
+                payload[..slice.len()].copy_from_slice(slice);
+                payload.commit(slice.len());
+
+                pid = (pid + 1) % 4;
+            }
+        },
+        async move {
+            loop {
+                let payload = r.wait_read_packet().await;
+                let pipe = payload.pipe();
+                let packet = postcard::from_bytes::<EsbPacket>(&payload);
+                payload.release();
+                let Some((_, link)) = links.iter().find(|(p, _)| *p == pipe) else {
+                    continue;
+                };
+                match packet {
+                    Ok(EsbPacket::Split(chunk)) => link.on_chunk_received(&chunk),
+                    Ok(_) => {}
+                    Err(e) => warn!("Invalid packet: {:?}", Debug2Format(&e)),
+                }
+            }
+        },
+    )
+    .await;
+}
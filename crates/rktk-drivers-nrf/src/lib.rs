@@ -14,6 +14,7 @@
 #![no_std]
 #![cfg_attr(doc, feature(doc_cfg))]
 
+pub mod battery;
 pub mod display;
 #[cfg(feature = "esb")]
 pub mod esb;
@@ -33,6 +33,7 @@ macro_rules! init_sdc {
         mtu: $l2cap_mtu:expr,
         txq: $l2cap_txq:expr,
         rxq: $l2cap_rxq:expr
+        $(, central: $central_count:expr)?
     ) => {
         let __mpsl_p =
             $crate::sdc::MpslPeripherals::new($rtc0, $timer0, $temp, $ppi1, $ppi2, $ppi3);
@@ -40,9 +41,16 @@ macro_rules! init_sdc {
             $ppis1, $ppis2, $ppis3, $ppis4, $ppis5, $ppis6, $ppis7, $ppis8, $ppis9, $ppis10,
             $ppis11, $ppis12,
         );
-        let $sdc_name = $crate::sdc::init_sdc(
-            $spawner, __mpsl_p, __sdc_p, $rng, $l2cap_mtu, $l2cap_txq, $l2cap_rxq, $irqs,
-        );
+        #[allow(unused_mut)]
+        let mut __config = $crate::sdc::SdcConfig {
+            l2cap_mtu: $l2cap_mtu,
+            l2cap_txq: $l2cap_txq,
+            l2cap_rxq: $l2cap_rxq,
+            ..Default::default()
+        };
+        $(__config.central_count = $central_count;)?
+        let $sdc_name =
+            $crate::sdc::init_sdc_with_config($spawner, __mpsl_p, __sdc_p, $rng, __config, $irqs);
     };
 }
 
@@ -51,8 +59,38 @@ async fn mpsl_task(mpsl: &'static MultiprotocolServiceLayer<'static>) -> ! {
     mpsl.run().await
 }
 
+/// Memory required by the controller with one peripheral link and the buffer config used by
+/// keyboards (`mtu: 72`, `txq: 3`, `rxq: 3`).
+///
+/// This is the value returned by `sdc::Builder::required_memory` for that config. Larger buffers
+/// require more memory, and `Builder::build` fails reporting the required size.
+const SDC_MEM_SIZE: usize = 3312;
+/// Memory additionally required by scanner, central role and one central link with the same
+/// buffer config as [`SDC_MEM_SIZE`].
+const SDC_MEM_SIZE_PER_CENTRAL: usize = 3232;
+/// Max number of central links which memory is reserved for.
+const MAX_CENTRAL_COUNT: u8 = 1;
+
+/// Config of softdevice controller.
+pub struct SdcConfig {
+    pub l2cap_mtu: u16,
+    pub l2cap_txq: u8,
+    pub l2cap_rxq: u8,
+    /// Number of central links. If this is not 0, central role is also enabled (e.g. to be master
+    /// of BLE split). Up to 1 is supported.
+    pub central_count: u8,
+}
+
+impl Default for SdcConfig {
+    fn default() -> Self {
+        Self { l2cap_mtu: 72, l2cap_txq: 3, l2cap_rxq: 3, central_count: 0 }
+    }
+}
+
 /// Initialize softdevice controller(sdc) and starts mpsl task.
 ///
+/// Only peripheral role is enabled. Use [`init_sdc_with_config`] to enable central role.
+///
 /// This function must be called only once.
 #[allow(clippy::too_many_arguments)]
 pub fn init_sdc<
@@ -76,6 +114,39 @@ pub fn init_sdc<
     l2cap_rxq: u8,
     irqs: I,
 ) -> Result<nrf_sdc::SoftdeviceController<'static>, mpsl::Error> {
+    init_sdc_with_config(
+        spawner,
+        mpsl_peripherals,
+        sdc_peripherals,
+        rng,
+        SdcConfig { l2cap_mtu, l2cap_txq, l2cap_rxq, central_count: 0 },
+        irqs,
+    )
+}
+
+/// Initialize softdevice controller(sdc) with `config` and starts mpsl task.
+///
+/// This function must be called only once.
+pub fn init_sdc_with_config<
+    T: Interrupt,
+    I: Binding<T, LowPrioInterruptHandler>
+        + Binding<interrupt::typelevel::RADIO, HighPrioInterruptHandler>
+        + Binding<interrupt::typelevel::TIMER0, HighPrioInterruptHandler>
+        + Binding<interrupt::typelevel::RTC0, HighPrioInterruptHandler>
+        + Binding<interrupt::typelevel::CLOCK_POWER, ClockInterruptHandler>
+        + Binding<interrupt::typelevel::RNG, InterruptHandler<RNG>>
+        + 'static
+        + Clone,
+    RM: Mode + Send,
+>(
+    spawner: embassy_executor::Spawner,
+    mpsl_peripherals: mpsl::Peripherals<'static>,
+    sdc_peripherals: sdc::Peripherals<'static>,
+    rng: &'static mut Rng<'static, RM>,
+    config: SdcConfig,
+    irqs: I,
+) -> Result<nrf_sdc::SoftdeviceController<'static>, mpsl::Error> {
+    let SdcConfig { l2cap_mtu, l2cap_txq, l2cap_rxq, central_count } = config;
     let lfclk_cfg = mpsl::raw::mpsl_clock_lfclk_cfg_t {
         source: mpsl::raw::MPSL_CLOCK_LF_SRC_RC as u8,
         rc_ctiv: mpsl::raw::MPSL_RECOMMENDED_RC_CTIV as u8,
@@ -88,14 +159,22 @@ pub fn init_sdc<
 
     spawner.spawn(mpsl_task(&*mpsl).unwrap());
 
-    let sdc_mem = singleton!(sdc::Mem::<3312>::new(), sdc::Mem::<3312>);
+    let mut builder =
+        sdc::Builder::new()?.support_adv().support_peripheral().peripheral_count(1)?;
+    if central_count > 0 {
+        builder = builder.support_scan().support_central().central_count(central_count)?;
+    }
+    let builder = builder.buffer_cfg(l2cap_mtu, l2cap_mtu, l2cap_txq, l2cap_rxq)?;
 
-    let sdc = sdc::Builder::new()?
-        .support_adv()
-        .support_peripheral()
-        .peripheral_count(1)?
-        .buffer_cfg(l2cap_mtu, l2cap_mtu, l2cap_txq, l2cap_rxq)?
-        .build(sdc_peripherals, rng, mpsl, sdc_mem)?;
+    let sdc = if central_count > 0 {
+        // Each connection needs its own buffers.
+        const SIZE: usize = SDC_MEM_SIZE + SDC_MEM_SIZE_PER_CENTRAL * MAX_CENTRAL_COUNT as usize;
+        let sdc_mem = singleton!(sdc::Mem::<SIZE>::new(), sdc::Mem::<SIZE>);
+        builder.build(sdc_peripherals, rng, mpsl, sdc_mem)?
+    } else {
+        let sdc_mem = singleton!(sdc::Mem::<SDC_MEM_SIZE>::new(), sdc::Mem::<SDC_MEM_SIZE>);
+        builder.build(sdc_peripherals, rng, mpsl, sdc_mem)?
+    };
 
     Ok(sdc)
 }
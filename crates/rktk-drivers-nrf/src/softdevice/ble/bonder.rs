@@ -1,6 +1,6 @@
 //! Bonder (security handler). Heavily inspired by rmk's implemention (https://github.com/HaoboGu/rmk/blob/main/rmk/src/ble/nrf/bonder.rs)
 
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
 
 use nrf_softdevice::ble::{
     Connection, EncryptionInfo, IdentityKey, MasterId,
@@ -24,8 +24,29 @@ use crate::softdevice::flash::{SoftdeviceFlashPartition, SoftdeviceFlashStorage}
 
 const MAX_PEER_COUNT: usize = 8;
 
+/// Each profile is bonded to at most one host. Only bonds of the active profile are used.
 pub struct Bonder {
     devices: RefCell<Devices>,
+    profile: Cell<u8>,
+}
+
+impl Bonder {
+    pub fn profile(&self) -> u8 {
+        self.profile.get()
+    }
+
+    pub fn set_profile(&self, profile: u8) {
+        self.profile.set(profile);
+    }
+
+    /// Removes the bond of the active profile.
+    pub fn clear_profile(&self) {
+        let mut devices = self.devices.borrow_mut();
+        let profile = self.profile.get();
+        devices.retain(|b| b.profile != profile);
+        info!("Cleared bond of profile {}", profile);
+        BOND_SAVE.signal(devices.clone());
+    }
 }
 
 impl SecurityHandler for Bonder {
@@ -45,8 +66,10 @@ impl SecurityHandler for Bonder {
         peer_id: IdentityKey,
     ) {
         let mut devices = self.devices.borrow_mut();
+        let profile = self.profile.get();
 
-        devices.retain(|d| !(d.peer_id.is_match(peer_id.addr)));
+        // The new host replaces the one bonded to the active profile.
+        devices.retain(|b| b.profile != profile);
 
         let mut sys_attrs = heapless::Vec::new();
         let capacity = sys_attrs.capacity();
@@ -54,15 +77,15 @@ impl SecurityHandler for Bonder {
         let len = get_sys_attrs(conn, &mut sys_attrs).unwrap() as u16;
         sys_attrs.truncate(usize::from(len));
 
-        let device_data =
+        let device =
             DeviceData { peer_id, master_id, encryption_info: enc, sys_attrs: Some(sys_attrs) };
 
-        if let Err(data) = devices.push(device_data) {
+        if let Err(data) = devices.push(Bond { profile, device }) {
             devices.remove(0);
             devices.push(data).unwrap();
         }
 
-        info!("Bonded: {:?}", master_id.ediv);
+        info!("Bonded: {:?} (profile {})", master_id.ediv, profile);
 
         BOND_SAVE.signal(devices.clone());
     }
@@ -71,12 +94,15 @@ impl SecurityHandler for Bonder {
         let encryption_info = {
             let mut data = self.devices.borrow_mut();
 
-            let Some(device) = data.iter_mut().find(|d| d.master_id == master_id) else {
+            let Some(bond) = data
+                .iter_mut()
+                .find(|b| b.profile == self.profile.get() && b.device.master_id == master_id)
+            else {
                 info!("Key not found: {:?}", master_id);
                 return None;
             };
 
-            device.encryption_info
+            bond.device.encryption_info
         };
 
         // NOTE: Without this, the BleGattsSysAttrMissing error occurs.
@@ -91,7 +117,9 @@ impl SecurityHandler for Bonder {
     fn save_sys_attrs(&self, conn: &Connection) {
         let mut devices = self.devices.borrow_mut();
 
-        if let Some(device) = devices.iter_mut().find(|d| d.peer_id.is_match(conn.peer_address())) {
+        if let Some(bond) = devices.iter_mut().find(|b| {
+            b.profile == self.profile.get() && b.device.peer_id.is_match(conn.peer_address())
+        }) {
             let mut sys_attrs = heapless::Vec::new();
             sys_attrs.resize(sys_attrs.capacity(), 0).unwrap();
             let len = get_sys_attrs(conn, &mut sys_attrs).unwrap() as u16;
@@ -100,7 +128,7 @@ impl SecurityHandler for Bonder {
             // NOTE: Without this, cannot reconnect on windows
             // ref: https://github.com/embassy-rs/nrf-softdevice/issues/256
             if len > 0 {
-                device.sys_attrs = Some(sys_attrs);
+                bond.device.sys_attrs = Some(sys_attrs);
                 BOND_SAVE.signal(devices.clone());
             } else {
                 info!("Got empty sys_attrs. skipping save.");
@@ -115,8 +143,10 @@ impl SecurityHandler for Bonder {
 
         let _res = match devices
             .iter()
-            .find(|d| d.peer_id.is_match(conn.peer_address()))
-            .map(|d| &d.sys_attrs)
+            .find(|b| {
+                b.profile == self.profile.get() && b.device.peer_id.is_match(conn.peer_address())
+            })
+            .map(|b| &b.device.sys_attrs)
         {
             Some(Some(sys_attrs)) => set_sys_attrs(conn, Some(sys_attrs.as_slice())),
             _ => {
@@ -143,5 +173,5 @@ pub async fn init_bonder(
 
     info!("Loaded {} bond info", bond_map.iter().count());
 
-    SEC.init(Bonder { devices: RefCell::new(bond_map) })
+    SEC.init(Bonder { devices: RefCell::new(bond_map), profile: Cell::new(0) })
 }
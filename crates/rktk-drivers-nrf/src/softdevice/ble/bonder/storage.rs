@@ -4,7 +4,12 @@ use rktk_log::{info, warn};
 
 use crate::softdevice::flash::SoftdeviceFlashStorage;
 
-use super::{Devices, MAX_PEER_COUNT};
+use super::{Bond, Devices, LegacyDevices, MAX_PEER_COUNT};
+
+/// Key of bonds with profiles
+const BONDS_KEY: u8 = 1;
+/// Key of bonds stored by the firmware without profiles
+const LEGACY_BONDS_KEY: u8 = 0;
 
 pub static BOND_SAVE: Signal<Devices> = Signal::new();
 
@@ -42,7 +47,7 @@ pub async fn bonder_save_task(mut storage: SoftdeviceFlashStorage) {
                 };
 
                 match storage
-                    .store_item(&mut [0; DEVICES_MAX_SIZE + 1], &0u8, &data_slice.as_ref())
+                    .store_item(&mut [0; DEVICES_MAX_SIZE + 1], &BONDS_KEY, &data_slice.as_ref())
                     .await
                 {
                     Ok(_) => {
@@ -60,16 +65,24 @@ pub async fn bonder_save_task(mut storage: SoftdeviceFlashStorage) {
 
 pub async fn read_bond_map(storage: &mut SoftdeviceFlashStorage) -> Option<Devices> {
     let mut buf = [0; DEVICES_MAX_SIZE + 1];
-    let Ok(Some(data)) = storage.fetch_item(&mut buf, &0u8).await else {
+    if let Ok(Some(data)) = storage.fetch_item(&mut buf, &BONDS_KEY).await {
+        return postcard::from_bytes(data)
+            .inspect_err(|e| {
+                warn!("Failed to deserialize bond map: {:?}", e);
+            })
+            .ok();
+    }
+
+    let Ok(Some(data)) = storage.fetch_item(&mut buf, &LEGACY_BONDS_KEY).await else {
         warn!("Failed to read bond map");
         return None;
     };
-
-    let data = postcard::from_bytes(data)
+    let legacy: LegacyDevices = postcard::from_bytes(data)
         .inspect_err(|e| {
             warn!("Failed to deserialize bond map: {:?}", e);
         })
         .ok()?;
+    info!("Loaded legacy bond map as profile 0");
 
-    Some(data)
+    Some(legacy.into_iter().map(|device| Bond { profile: 0, device }).collect())
 }
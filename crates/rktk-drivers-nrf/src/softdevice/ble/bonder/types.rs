@@ -70,4 +70,15 @@ pub struct DeviceData {
     pub sys_attrs: Option<heapless::Vec<u8, 62>>,
 }
 
-pub type Devices = heapless::Vec<DeviceData, MAX_PEER_COUNT>;
+/// Bond of a host to a profile.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bond {
+    pub profile: u8,
+    pub device: DeviceData,
+}
+
+pub type Devices = heapless::Vec<Bond, MAX_PEER_COUNT>;
+
+/// Bonds stored by the firmware without profiles. They are loaded as bonds of profile 0.
+pub type LegacyDevices = heapless::Vec<DeviceData, MAX_PEER_COUNT>;
@@ -11,3 +11,14 @@ pub const HARDWARE_REVISION: Uuid = Uuid::new_16(0x2a27);
 pub const SOFTWARE_REVISION: Uuid = Uuid::new_16(0x2a28);
 pub const MANUFACTURER_NAME: Uuid = Uuid::new_16(0x2a29);
 pub const PNP_ID: Uuid = Uuid::new_16(0x2a50);
+
+// rrp service uuid: 8c5a0001-5e2f-4a8b-9d1e-72726b746b00 (bytes are in little-endian)
+pub const RRP_SERVICE: Uuid = Uuid::new_128(&[
+    0x00, 0x6b, 0x74, 0x6b, 0x72, 0x72, 0x1e, 0x9d, 0x8b, 0x4a, 0x2f, 0x5e, 0x01, 0x00, 0x5a, 0x8c,
+]);
+pub const RRP_REQUEST: Uuid = Uuid::new_128(&[
+    0x00, 0x6b, 0x74, 0x6b, 0x72, 0x72, 0x1e, 0x9d, 0x8b, 0x4a, 0x2f, 0x5e, 0x02, 0x00, 0x5a, 0x8c,
+]);
+pub const RRP_RESPONSE: Uuid = Uuid::new_128(&[
+    0x00, 0x6b, 0x74, 0x6b, 0x72, 0x72, 0x1e, 0x9d, 0x8b, 0x4a, 0x2f, 0x5e, 0x03, 0x00, 0x5a, 0x8c,
+]);
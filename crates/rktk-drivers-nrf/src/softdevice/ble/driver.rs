@@ -1,7 +1,11 @@
 use rktk::drivers::interface::{reporter::ReporterDriver, wireless::WirelessReporterDriver};
 use usbd_hid::descriptor::{KeyboardReport, MediaKeyboardReport, MouseReport};
 
-use super::{INPUT_REPORT_CHAN, InputReport, KB_OUTPUT_LED_SIGNAL, bonder::BOND_FLASH};
+use super::{
+    BATTERY_LEVEL_SIGNAL, INPUT_REPORT_CHAN, InputReport, KB_OUTPUT_LED_SIGNAL,
+    PROFILE_COMMAND_CHAN, PROFILE_COUNT, ProfileCommand, RRP_RECV_PIPE, RRP_SEND_PIPE,
+    bonder::BOND_FLASH,
+};
 
 pub struct SoftdeviceBleReporterDriver {}
 
@@ -55,8 +59,13 @@ impl ReporterDriver for SoftdeviceBleReporterDriver {
         Ok(leds)
     }
 
-    async fn send_rrp_data(&self, _data: &[u8]) -> Result<(), Self::Error> {
-        Err(BleError::NotSupported)
+    async fn send_rrp_data(&self, data: &[u8]) -> Result<(), Self::Error> {
+        RRP_SEND_PIPE.write_all(data).await;
+        Ok(())
+    }
+
+    async fn recv_rrp_data(&self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(RRP_RECV_PIPE.read(buf).await)
     }
 
     fn wakeup(&self) -> Result<bool, Self::Error> {
@@ -70,4 +79,29 @@ impl WirelessReporterDriver for SoftdeviceBleReporterDriver {
         BOND_FLASH.signal(super::bonder::BondFlashCommand::Clear);
         Ok(())
     }
+
+    fn profile_count(&self) -> u8 {
+        PROFILE_COUNT
+    }
+
+    async fn select_profile(
+        &self,
+        profile: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        PROFILE_COMMAND_CHAN.send(ProfileCommand::Select(profile)).await;
+        Ok(())
+    }
+
+    async fn clear_profile_bond_data(&self) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        PROFILE_COMMAND_CHAN.send(ProfileCommand::ClearBond).await;
+        Ok(())
+    }
+
+    async fn set_battery_level(
+        &self,
+        level: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        BATTERY_LEVEL_SIGNAL.signal(level);
+        Ok(())
+    }
 }
@@ -1,8 +1,9 @@
+use embassy_sync::pipe::Pipe;
 use nrf_softdevice::{Softdevice, raw};
 
 use rktk::{
     drivers::interface::wireless::WirelessReporterDriverBuilder,
-    utils::{Channel, Signal},
+    utils::{Channel, RawMutex, Signal},
 };
 pub use server::Server;
 pub use services::device_information::DeviceInformation;
@@ -24,10 +25,25 @@ pub enum InputReport {
     Mouse(MouseReport),
 }
 
+/// Number of host profiles. Each profile has its own address and bond.
+const PROFILE_COUNT: u8 = 4;
+
+enum ProfileCommand {
+    Select(u8),
+    ClearBond,
+}
+
 // Channel for input (device to host) report
 static INPUT_REPORT_CHAN: Channel<InputReport, 8> = Channel::new();
 // Channel for keyboard output report (only leds field)
 static KB_OUTPUT_LED_SIGNAL: Signal<u8> = Signal::new();
+// Pipes for rrp data
+static RRP_SEND_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+static RRP_RECV_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+// Channel for profile commands, handled by the softdevice task
+static PROFILE_COMMAND_CHAN: Channel<ProfileCommand, 4> = Channel::new();
+// Latest battery level, which is set to the battery service when connected
+static BATTERY_LEVEL_SIGNAL: Signal<u8> = Signal::new();
 
 pub fn init_ble_server(sd: &mut Softdevice, device_info: DeviceInformation) -> Server {
     unsafe {
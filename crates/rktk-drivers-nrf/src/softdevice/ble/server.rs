@@ -10,12 +10,14 @@ use super::services::{
     battery::BatteryService,
     device_information::{DeviceInformation, DeviceInformationService, PnPID, VidSource},
     hid::HidService,
+    rrp::RrpService,
 };
 
 pub struct Server {
     pub _dis: DeviceInformationService,
     pub bas: BatteryService,
     pub hid: HidService,
+    pub rrp: RrpService,
 }
 
 impl Server {
@@ -39,7 +41,9 @@ impl Server {
 
         let hid = HidService::new(sd)?;
 
-        Ok(Self { _dis: dis, bas, hid })
+        let rrp = RrpService::new(sd)?;
+
+        Ok(Self { _dis: dis, bas, hid, rrp })
     }
 }
 
@@ -56,6 +60,7 @@ impl gatt_server::Server for Server {
     ) -> Option<Self::Event> {
         self.hid.on_write(conn, handle, data);
         self.bas.on_write(handle, data);
+        self.rrp.on_write(handle, data);
         None
     }
 }
@@ -1,3 +1,4 @@
 pub mod battery;
 pub mod device_information;
 pub mod hid;
+pub mod rrp;
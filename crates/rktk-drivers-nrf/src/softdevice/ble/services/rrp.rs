@@ -0,0 +1,81 @@
+use nrf_softdevice::{
+    Softdevice,
+    ble::{
+        Connection, SecurityMode,
+        gatt_server::{
+            self, RegisterError,
+            builder::ServiceBuilder,
+            characteristic::{Attribute, Metadata, Properties},
+        },
+    },
+};
+use rktk::drivers::interface::wireless::RRP_BLE_BUFFER_SIZE;
+
+use super::super::{
+    RRP_RECV_PIPE,
+    constant::{RRP_REQUEST, RRP_RESPONSE, RRP_SERVICE},
+};
+
+/// rrp (rktk remote protocol) service.
+///
+/// Client writes requests to `request` characteristic and receives responses as notifications of
+/// `response` characteristic.
+pub struct RrpService {
+    request_handle: u16,
+    response_handle: u16,
+}
+
+impl RrpService {
+    pub fn new(sd: &mut Softdevice) -> Result<Self, RegisterError> {
+        let mut service_builder = ServiceBuilder::new(sd, RRP_SERVICE)?;
+
+        let request = service_builder
+            .add_characteristic(
+                RRP_REQUEST,
+                Attribute::new([0u8; RRP_BLE_BUFFER_SIZE]).security(SecurityMode::JustWorks),
+                Metadata::new(Properties::new().write().write_without_response()),
+            )?
+            .build();
+        let response = service_builder
+            .add_characteristic(
+                RRP_RESPONSE,
+                Attribute::new([0u8; RRP_BLE_BUFFER_SIZE]).security(SecurityMode::JustWorks),
+                Metadata::new(Properties::new().read().notify()),
+            )?
+            .build();
+
+        let _service_handle = service_builder.build();
+
+        Ok(RrpService {
+            request_handle: request.value_handle,
+            response_handle: response.value_handle,
+        })
+    }
+
+    pub fn on_write(&self, handle: u16, data: &[u8]) {
+        if handle != self.request_handle || data.is_empty() {
+            return;
+        }
+
+        let len = data[0] as usize;
+        if len > 0 && len < RRP_BLE_BUFFER_SIZE && len < data.len() {
+            // Writing only a part of the packet corrupts the stream, so the whole packet is
+            // dropped if the pipe doesn't have room for it.
+            if RRP_RECV_PIPE.free_capacity() < len
+                || !matches!(RRP_RECV_PIPE.try_write(&data[1..=len]), Ok(n) if n == len)
+            {
+                rktk_log::warn!("rrp recv pipe full. packet dropped.");
+            }
+        } else {
+            rktk_log::warn!("Invalid rrp packet");
+        }
+    }
+
+    pub fn send(
+        &self,
+        conn: &Connection,
+        data: &[u8],
+    ) -> Result<(), gatt_server::NotifyValueError> {
+        gatt_server::notify_value(conn, self.response_handle, data)
+    }
+}
@@ -1,19 +1,22 @@
 use embassy_embedded_hal::flash::partition::Partition;
-use embassy_futures::select::select;
+use embassy_futures::select::{Either, select, select4};
 use nrf_softdevice::ble::{
+    Address, AddressType,
     advertisement_builder::{
         AdvertisementDataType, Flag, LegacyAdvertisementBuilder, LegacyAdvertisementPayload,
         ServiceList, ServiceUuid16,
     },
-    gatt_server, peripheral,
+    gatt_server, get_address, peripheral,
 };
-use nrf_softdevice::{Flash, Softdevice};
-use rktk::utils::RawMutex;
-use rktk_log::{info, warn};
+use nrf_softdevice::{Flash, Softdevice, raw};
+use rktk::{drivers::interface::wireless::RRP_BLE_BUFFER_SIZE, utils::RawMutex};
+use rktk_log::{debug, info, warn};
 
-use crate::softdevice::ble::INPUT_REPORT_CHAN;
+use crate::softdevice::ble::{
+    BATTERY_LEVEL_SIGNAL, INPUT_REPORT_CHAN, RRP_RECV_PIPE, RRP_SEND_PIPE,
+};
 
-use super::server::Server;
+use super::{PROFILE_COMMAND_CHAN, ProfileCommand, bonder::Bonder, server::Server};
 
 pub async fn softdevice_task(
     spawner: embassy_executor::Spawner,
@@ -47,45 +50,115 @@ pub async fn softdevice_task(
     };
 
     let bonder = super::bonder::init_bonder(spawner, flash).await;
+    let base_address = get_address(sd);
 
     loop {
+        set_profile_address(base_address, bonder.profile());
         rktk::print!("Advertising");
 
         let mut cnt = 0;
-        let conn = loop {
-            match peripheral::advertise_pairable(sd, adv, &config, bonder).await {
-                Ok(conn) => break conn,
-                Err(peripheral::AdvertiseError::Timeout) => {
-                    cnt += 1;
-                    if cnt > 10 {
-                        rktk::print!("Failed to pair (10 tries)");
+        let advertise = async {
+            loop {
+                match peripheral::advertise_pairable(sd, adv, &config, bonder).await {
+                    Ok(conn) => break conn,
+                    Err(peripheral::AdvertiseError::Timeout) => {
+                        cnt += 1;
+                        if cnt > 10 {
+                            rktk::print!("Failed to pair (10 tries)");
+                        }
+                    }
+                    Err(e) => {
+                        rktk::print!("Pair error: {:?}", e);
+                        continue;
                     }
-                }
-                Err(e) => {
-                    rktk::print!("Pair error: {:?}", e);
-                    continue;
                 }
             }
         };
+        let conn = match select(advertise, PROFILE_COMMAND_CHAN.receive()).await {
+            Either::First(conn) => conn,
+            Either::Second(cmd) => {
+                handle_profile_command(bonder, cmd);
+                continue;
+            }
+        };
 
         // rktk::print!("Connected: {:X?}", conn.peer_address().bytes);
 
-        select(
+        select4(
             async {
                 let e = gatt_server::run(&conn, &server, |_| {}).await;
                 info!("Server exited: {:?}", e);
             },
             async {
                 loop {
-                    let report = INPUT_REPORT_CHAN.receive().await;
-                    if let Err(e) = server.hid.send_report(&conn, report) {
-                        warn!("BLE hid failed: {:?}", e);
-                    };
+                    match select(INPUT_REPORT_CHAN.receive(), BATTERY_LEVEL_SIGNAL.wait()).await {
+                        Either::First(report) => {
+                            if let Err(e) = server.hid.send_report(&conn, report) {
+                                warn!("BLE hid failed: {:?}", e);
+                            };
+                        }
+                        Either::Second(level) => {
+                            let _ = server.bas.battery_level_set(sd, level);
+                            // Fails if the host hasn't enabled notification.
+                            if let Err(e) = server.bas.battery_level_notify(&conn, level) {
+                                debug!("BLE battery notify failed: {:?}", e);
+                            }
+                        }
+                    }
+                }
+            },
+            async {
+                loop {
+                    let mut data = [0u8; RRP_BLE_BUFFER_SIZE];
+                    let to_send_bytes = RRP_SEND_PIPE.read(&mut data[1..]).await;
+                    data[0] = to_send_bytes as u8;
+                    if let Err(e) = server.rrp.send(&conn, &data) {
+                        warn!("BLE rrp failed: {:?}", e);
+                    }
                 }
             },
+            async {
+                while !handle_profile_command(bonder, PROFILE_COMMAND_CHAN.receive().await) {}
+                let _ = conn.disconnect();
+            },
         )
         .await;
 
+        // Partial rrp data of the previous connection would corrupt the stream of the next one.
+        RRP_SEND_PIPE.clear();
+        RRP_RECV_PIPE.clear();
         rktk::print!("Disconnected");
     }
 }
+
+/// Applies `cmd` to the bonder, and returns true if the current host should be disconnected.
+fn handle_profile_command(bonder: &Bonder, cmd: ProfileCommand) -> bool {
+    match cmd {
+        ProfileCommand::Select(profile) => {
+            if profile == bonder.profile() {
+                return false;
+            }
+            bonder.set_profile(profile);
+            rktk::print!("Profile {}", profile);
+        }
+        ProfileCommand::ClearBond => bonder.clear_profile(),
+    }
+    true
+}
+
+/// Sets the address of `profile`, so that hosts see each profile as a different device.
+///
+/// Profile 0 uses the default address, so that bonds made before profiles were introduced are
+/// still valid.
+fn set_profile_address(base: Address, profile: u8) {
+    let mut bytes = base.bytes();
+    bytes[0] = bytes[0].wrapping_add(profile);
+    let address = Address::new(AddressType::RandomStatic, bytes);
+
+    // `nrf_softdevice::ble::set_address` panics if the address can't be changed (e.g. while split
+    // is scanning), so raw API is used here.
+    let ret = unsafe { raw::sd_ble_gap_addr_set(address.as_raw()) };
+    if ret != raw::NRF_SUCCESS {
+        warn!("Failed to set address of profile {}: {}", profile, ret);
+    }
+}
@@ -0,0 +1,47 @@
+use rktk::config::{
+    CONST_CONFIG,
+    keymap::{Keymap, Layer, prelude::*},
+};
+
+const ROWS: usize = 5;
+const COLS: usize = 14;
+
+#[rustfmt::skip]
+const L0: [[KeyAction; COLS]; ROWS] = [
+    [ ESCAPE, D1    , D2    , D3    , D4    , D5    , GRAVE , /**/ EQUAL , D6    , D7    , D8    , D9    , D0    , BACKSPACE ],
+    [ TAB   , Q     , W     , E     , R     , T     , LBRC  , /**/ RBRC  , Y     , U     , I     , O     , P     , MINUS     ],
+    [ L_CTRL, A     , S     , D     , F     , G     , _____ , /**/ _____ , H     , J     , K     , L     , SCLN  , QUOTE     ],
+    [ L_SHFT, Z     , X     , C     , V     , B     , _____ , /**/ _____ , N     , M     , COMM  , DOT   , SLASH , R_SHFT    ],
+    [ _____ , L_GUI , L_ALT , MO(1) , SPACE , _____ , _____ , /**/ _____ , _____ , ENTER , MO(1) , R_ALT , BSLSH , _____     ],
+];
+
+#[rustfmt::skip]
+const L1: [[KeyAction; COLS]; ROWS] = [
+    [ _____ , F1    , F2    , F3    , F4    , F5    , F6    , /**/ F7    , F8    , F9    , F10   , F11   , F12   , DELETE    ],
+    [ _____ , _____ , _____ , _____ , _____ , _____ , _____ , /**/ _____ , _____ , _____ , _____ , _____ , _____ , _____     ],
+    [ _____ , _____ , _____ , _____ , _____ , _____ , _____ , /**/ _____ , LEFT  , DOWN  , UP    , RIGHT , _____ , _____     ],
+    [ _____ , _____ , _____ , _____ , _____ , _____ , _____ , /**/ _____ , HOME  , PGDN  , PGUP  , END   , _____ , _____     ],
+    [ _____ , _____ , _____ , _____ , _____ , _____ , _____ , /**/ _____ , _____ , _____ , _____ , _____ , _____ , _____     ],
+];
+
+/// Copies `keys` into the top-left of `layer`, as the matrix size depends on the rktk config.
+const fn layer(keys: &[[KeyAction; COLS]; ROWS]) -> Layer {
+    let mut layer = Layer::const_default();
+    let mut row = 0;
+    while row < ROWS && row < CONST_CONFIG.keyboard.rows as usize {
+        let mut col = 0;
+        while col < COLS && col < CONST_CONFIG.keyboard.cols as usize {
+            layer.keymap[row][col] = keys[row][col];
+            col += 1;
+        }
+        row += 1;
+    }
+    layer
+}
+
+pub const KEYMAP: Keymap = {
+    let mut keymap = Keymap::const_default();
+    keymap.layers[0] = layer(&L0);
+    keymap.layers[1] = layer(&L1);
+    keymap
+};
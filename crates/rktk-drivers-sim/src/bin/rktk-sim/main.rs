@@ -0,0 +1,139 @@
+use std::{io::BufReader, net::SocketAddr, path::PathBuf};
+
+use anyhow::Context as _;
+use clap::Parser;
+use embassy_executor::Spawner;
+use embedded_graphics::prelude::Size;
+use rktk::{
+    config::{Hand, new_rktk_opts},
+    drivers::{Drivers, dummy},
+    hooks::create_empty_hooks,
+};
+use rktk_drivers_common::storage::emulated_flash::FileStorage;
+use rktk_drivers_sim::{
+    display::SimDisplay,
+    keyscan::SimKeyscan,
+    reporter::{DEFAULT_RRP_ADDR, SimReporterBuilder},
+    script::{self, Event},
+    split::SimSplit,
+    system::SimSystem,
+};
+
+mod keymap;
+
+const STORAGE_PAGE_SIZE: usize = 4096;
+const STORAGE_SIZE: u32 = 64 * 1024;
+
+#[derive(Parser)]
+#[command(version, about = "Run rktk on the host")]
+struct Cli {
+    /// Read key events from the file instead of stdin, and exit at the end of it
+    #[arg(short, long)]
+    script: Option<PathBuf>,
+    /// Address to serve rrp on
+    #[arg(long, default_value = DEFAULT_RRP_ADDR)]
+    rrp: SocketAddr,
+    /// Flash image file to store keymap and config. If not set, changes are lost on exit.
+    #[arg(long)]
+    storage: Option<PathBuf>,
+    /// Write the display to the file as PBM image
+    #[arg(long)]
+    display: Option<PathBuf>,
+    /// Run as the master side of split keyboard and wait for the slave side on the address
+    #[arg(long, conflicts_with = "split_connect")]
+    split_listen: Option<SocketAddr>,
+    /// Run as the slave side of split keyboard and connect to the master side on the address
+    #[arg(long)]
+    split_connect: Option<SocketAddr>,
+    #[arg(long, default_value = "warn")]
+    log_level: log::LevelFilter,
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let cli = Cli::parse();
+    log::set_logger(&StderrLogger).expect("Logger is already set");
+    log::set_max_level(cli.log_level);
+
+    if let Err(e) = run(spawner, cli).await {
+        eprintln!("Error: {e:#}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(spawner: Spawner, cli: Cli) -> anyhow::Result<()> {
+    let (keyscan, events) = SimKeyscan::new();
+    match &cli.script {
+        Some(path) => {
+            let script = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let script = script::parse(&script)
+                .map_err(|(line, e)| anyhow::anyhow!("{}:{line}: {e}", path.display()))?;
+            std::thread::spawn(move || {
+                script.into_iter().chain([Event::Exit]).for_each(|e| events.send_blocking(e));
+            });
+        }
+        None => events.spawn_reader(BufReader::new(std::io::stdin())),
+    }
+
+    let split = match (cli.split_listen, cli.split_connect) {
+        (Some(addr), _) => {
+            eprintln!("Waiting for the slave side on {addr}");
+            Some(SimSplit::listen(addr).context("Failed to accept the slave side")?)
+        }
+        (_, Some(addr)) => {
+            Some(SimSplit::connect(addr).context("Failed to connect to the master side")?)
+        }
+        _ => None,
+    };
+    let is_slave = cli.split_connect.is_some();
+
+    let storage = match &cli.storage {
+        Some(path) => Some(
+            FileStorage::<STORAGE_PAGE_SIZE>::open(path, STORAGE_SIZE)
+                .with_context(|| format!("Failed to open {}", path.display()))?,
+        ),
+        None => None,
+    };
+
+    let drivers = Drivers {
+        system: SimSystem,
+        keyscan,
+        // Without usb, rktk runs as the slave side.
+        usb_builder: (!is_slave).then(|| SimReporterBuilder::new().rrp_addr(cli.rrp)),
+        storage,
+        split,
+        display: Some(SimDisplay::new(Size::new(32, 128), cli.display)),
+        mouse: dummy::mouse(),
+        dfu: dummy::dfu(),
+        battery: dummy::battery(),
+        rgb: dummy::rgb(),
+        ble_builder: dummy::ble_builder(),
+        debounce: dummy::debounce(),
+        encoder: dummy::encoder(),
+    };
+
+    rktk::task::start(
+        spawner,
+        drivers,
+        create_empty_hooks(),
+        new_rktk_opts(&keymap::KEYMAP, Some(if is_slave { Hand::Right } else { Hand::Left })),
+    )
+    .await;
+
+    Ok(())
+}
+
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        eprintln!("[{:<5} {}] {}", record.level(), record.target(), record.args());
+    }
+
+    fn flush(&self) {}
+}
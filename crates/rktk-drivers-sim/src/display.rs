@@ -0,0 +1,139 @@
+//! Display driver which draws into memory and dumps the screen to a PBM image.
+
+use std::{convert::Infallible, path::PathBuf};
+
+use display_interface::DisplayError;
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use rktk::drivers::interface::display::DisplayDriver;
+
+/// Monochrome framebuffer.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    size: Size,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    pub fn new(size: Size) -> Self {
+        Self { size, pixels: vec![false; (size.width * size.height) as usize] }
+    }
+
+    fn index(&self, point: Point) -> Option<usize> {
+        let (x, y) = (u32::try_from(point.x).ok()?, u32::try_from(point.y).ok()?);
+        (x < self.size.width && y < self.size.height).then_some((y * self.size.width + x) as usize)
+    }
+
+    /// Returns whether the pixel is on. Pixels out of the screen are off.
+    pub fn pixel(&self, point: Point) -> bool {
+        self.index(point).is_some_and(|i| self.pixels[i])
+    }
+
+    /// Encodes the screen as plain PBM (`P1`) image.
+    pub fn to_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{} {}\n", self.size.width, self.size.height);
+        for row in self.pixels.chunks(self.size.width as usize) {
+            let row = row.iter().map(|on| if *on { "1" } else { "0" });
+            pbm.push_str(&row.collect::<Vec<_>>().join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for Framebuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some(i) = self.index(point) {
+                self.pixels[i] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Display of the simulator.
+///
+/// If `path` is set, the screen is written to the file as PBM image on each flush which changed
+/// it.
+pub struct SimDisplay {
+    frame: Framebuffer,
+    flushed: Option<Framebuffer>,
+    path: Option<PathBuf>,
+}
+
+impl SimDisplay {
+    pub fn new(size: Size, path: Option<PathBuf>) -> Self {
+        Self { frame: Framebuffer::new(size), flushed: None, path }
+    }
+
+    /// Returns the screen at the last flush.
+    pub fn screen(&self) -> Option<&Framebuffer> {
+        self.flushed.as_ref()
+    }
+}
+
+impl DisplayDriver for SimDisplay {
+    type Color = BinaryColor;
+    type Display = Framebuffer;
+
+    fn draw_target(&mut self) -> &mut Self::Display {
+        &mut self.frame
+    }
+
+    async fn flush(&mut self) -> Result<(), DisplayError> {
+        if self.flushed.as_ref() == Some(&self.frame) {
+            return Ok(());
+        }
+        if let Some(path) = &self.path {
+            std::fs::write(path, self.frame.to_pbm()).map_err(|_| DisplayError::BusWriteError)?;
+        }
+        self.flushed = Some(self.frame.clone());
+        Ok(())
+    }
+
+    async fn clear(&mut self) -> Result<(), DisplayError> {
+        let _ = self.frame.clear(BinaryColor::Off);
+        Ok(())
+    }
+
+    async fn set_brightness(&mut self, _brightness: u8) -> Result<(), DisplayError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::{
+        pixelcolor::BinaryColor,
+        prelude::*,
+        primitives::{PrimitiveStyle, Rectangle},
+    };
+
+    use super::Framebuffer;
+
+    #[test]
+    fn draw_and_encode() {
+        let mut frame = Framebuffer::new(Size::new(3, 2));
+        Rectangle::new(Point::new(1, 0), Size::new(5, 1))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(&mut frame)
+            .unwrap();
+
+        assert!(frame.pixel(Point::new(2, 0)));
+        assert!(!frame.pixel(Point::new(0, 0)));
+        assert!(!frame.pixel(Point::new(-1, 0)));
+        assert_eq!(frame.to_pbm(), "P1\n3 2\n0 1 1\n0 0 0\n");
+    }
+}
@@ -0,0 +1,92 @@
+//! Keyscan driver which receives key events from [`EventSender`].
+
+use std::{
+    convert::Infallible,
+    io::{BufRead, Write as _},
+    thread,
+};
+
+use embassy_time::{Duration, Timer};
+use rktk::{
+    drivers::interface::keyscan::{KeyChangeEvent, KeyscanDriver},
+    utils::Channel,
+};
+
+use crate::{
+    pipe::{leak, send_blocking},
+    script::{Event, parse_line},
+};
+
+type EventChannel = Channel<Event, 64>;
+
+/// Handle to send events to [`SimKeyscan`]. It can be used from any thread.
+#[derive(Clone, Copy)]
+pub struct EventSender {
+    channel: &'static EventChannel,
+}
+
+impl EventSender {
+    /// Sends the event, blocking the current thread while the queue is full.
+    ///
+    /// Don't call this from the executor thread, as it can't make progress while blocked.
+    pub fn send_blocking(&self, event: Event) {
+        send_blocking(self.channel, event);
+    }
+
+    /// Spawns a thread which sends events parsed from each line of `reader`.
+    ///
+    /// Invalid lines are reported to stderr and skipped.
+    pub fn spawn_reader(self, reader: impl BufRead + Send + 'static) {
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match parse_line(&line) {
+                    Ok(events) => events.into_iter().for_each(|e| self.send_blocking(e)),
+                    Err(e) => eprintln!("{e}"),
+                }
+            }
+        });
+    }
+}
+
+/// Keyscan driver of the simulator.
+pub struct SimKeyscan {
+    channel: &'static EventChannel,
+}
+
+impl SimKeyscan {
+    pub fn new() -> (Self, EventSender) {
+        let channel = leak(EventChannel::new());
+        (Self { channel }, EventSender { channel })
+    }
+}
+
+impl KeyscanDriver for SimKeyscan {
+    type CalibrationError = Infallible;
+
+    async fn scan(&mut self, mut callback: impl FnMut(KeyChangeEvent)) {
+        let mut event = self.channel.receive().await;
+        loop {
+            match event {
+                Event::Key(e) => callback(e),
+                Event::Wait(duration) => {
+                    Timer::after(Duration::from_micros(duration.as_micros() as u64)).await;
+                    return;
+                }
+                Event::Exit => {
+                    // Give the report task time to send reports of the previous events.
+                    Timer::after_millis(100).await;
+                    let _ = std::io::stdout().flush();
+                    std::process::exit(0);
+                }
+            }
+            // Keys without wait between them are reported in the same scan.
+            match self.channel.try_receive() {
+                Ok(e) => event = e,
+                Err(_) => return,
+            }
+        }
+    }
+}
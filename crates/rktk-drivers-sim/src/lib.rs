@@ -0,0 +1,34 @@
+//! Drivers to run rktk on the host.
+//!
+//! These drivers replace the hardware of a keyboard with things available on a normal computer, so
+//! that a keymap can be tested without flashing firmware:
+//!
+//! - [`keyscan::SimKeyscan`]: Key events are sent from [`script`] lines read from stdin or a file.
+//! - [`reporter::SimReporterBuilder`]: HID reports are printed to stdout and rrp is served over a
+//!   TCP socket.
+//! - [`display::SimDisplay`]: Screen is kept in memory and dumped to a PBM image.
+//! - [`split::SimSplit`]: Split link over an in-process pipe or a TCP socket.
+//! - [`system::SimSystem`]: Reset exits the process.
+//!
+//! For storage, use `FileStorage` of `rktk-drivers-common` with `std` feature.
+//!
+//! These drivers run on the std embassy executor (`platform-std` feature of `embassy-executor`).
+//! `rktk-sim` binary of this crate combines them into a keyboard which can be used from the
+//! command line.
+
+use rktk::drivers::interface::Error;
+
+pub mod display;
+pub mod keyscan;
+mod pipe;
+pub mod reporter;
+pub mod script;
+pub mod split;
+pub mod system;
+
+/// IO error of the drivers.
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct IoError(#[from] pub std::io::Error);
+
+impl Error for IoError {}
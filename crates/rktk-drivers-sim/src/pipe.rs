@@ -0,0 +1,66 @@
+//! Bridges between blocking std IO and async drivers.
+
+use std::{
+    io::{Read, Write},
+    thread,
+    time::Duration,
+};
+
+use embassy_sync::{channel::TrySendError, pipe::Pipe};
+use rktk::utils::{Channel, RawMutex};
+
+pub(crate) type BytePipe = Pipe<RawMutex, 1024>;
+
+/// Interval to retry when the receiver is full. Threads can't await the executor, so they poll.
+const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+pub(crate) fn leak<T>(value: T) -> &'static T {
+    Box::leak(Box::new(value))
+}
+
+/// Writes all of `data` into `pipe` from a thread which is not running the executor.
+pub(crate) fn write_blocking(pipe: &BytePipe, mut data: &[u8]) {
+    while !data.is_empty() {
+        match pipe.try_write(data) {
+            Ok(n) => data = &data[n..],
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
+
+/// Sends `value` into `channel` from a thread which is not running the executor.
+pub(crate) fn send_blocking<T, const N: usize>(channel: &Channel<T, N>, mut value: T) {
+    while let Err(TrySendError::Full(v)) = channel.try_send(value) {
+        value = v;
+        thread::sleep(RETRY_INTERVAL);
+    }
+}
+
+/// Spawns a thread which copies bytes from `reader` into `pipe` until EOF or error.
+pub(crate) fn spawn_copy(mut reader: impl Read + Send + 'static, pipe: &'static BytePipe) {
+    thread::spawn(move || {
+        let mut buf = [0; 256];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => write_blocking(pipe, &buf[..n]),
+            }
+        }
+    });
+}
+
+/// Spawns a thread which writes bytes from `pipe` into `writer`, so that async drivers don't block
+/// the executor. After a write error, bytes are dropped like a disconnected link.
+pub(crate) fn spawn_drain(pipe: &'static BytePipe, mut writer: impl Write + Send + 'static) {
+    thread::spawn(move || {
+        let mut buf = [0; 256];
+        let mut connected = true;
+        loop {
+            match pipe.try_read(&mut buf) {
+                Ok(n) if connected => connected = writer.write_all(&buf[..n]).is_ok(),
+                Ok(_) => {}
+                Err(_) => thread::sleep(RETRY_INTERVAL),
+            }
+        }
+    });
+}
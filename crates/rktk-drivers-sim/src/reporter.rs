@@ -0,0 +1,169 @@
+//! Reporter which prints HID reports and serves rrp over TCP.
+
+use std::{
+    convert::Infallible,
+    io::Write as _,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use rktk::drivers::interface::{
+    reporter::ReporterDriver,
+    usb::{UsbReporterDriver, UsbReporterDriverBuilder},
+};
+use usbd_hid::descriptor::{KeyboardReport, MediaKeyboardReport, MouseReport};
+
+use crate::{
+    IoError,
+    pipe::{BytePipe, leak, spawn_copy},
+};
+
+/// Default address of the rrp socket.
+pub const DEFAULT_RRP_ADDR: &str = "127.0.0.1:7707";
+
+pub struct SimReporterBuilder {
+    rrp_addr: Option<SocketAddr>,
+}
+
+impl SimReporterBuilder {
+    /// Creates a reporter without rrp.
+    pub fn new() -> Self {
+        Self { rrp_addr: None }
+    }
+
+    /// Serves rrp on `addr`. One client can be connected at a time.
+    pub fn rrp_addr(mut self, addr: SocketAddr) -> Self {
+        self.rrp_addr = Some(addr);
+        self
+    }
+}
+
+impl Default for SimReporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UsbReporterDriverBuilder for SimReporterBuilder {
+    type Output = SimReporter;
+    type Error = IoError;
+
+    async fn build(
+        self,
+    ) -> Result<(Self::Output, impl Future<Output = ()> + 'static), Self::Error> {
+        let rrp = match self.rrp_addr {
+            Some(addr) => Some(RrpSocket::listen(addr)?),
+            None => None,
+        };
+        Ok((SimReporter { start: Instant::now(), rrp }, async {}))
+    }
+}
+
+/// rrp connection. Bytes received from the client are copied into `rx` by a thread.
+struct RrpSocket {
+    rx: &'static BytePipe,
+    client: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl RrpSocket {
+    fn listen(addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("rrp listening on {}", listener.local_addr()?);
+
+        let rx = leak(BytePipe::new());
+        let client = Arc::new(Mutex::new(None::<TcpStream>));
+        let accepted = client.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (Ok(reader), Ok(peer)) = (stream.try_clone(), stream.peer_addr()) else {
+                    continue;
+                };
+                log::info!("rrp client connected: {peer}");
+                // Data of the previous client may remain, which would break framing of the new one.
+                rx.clear();
+                *accepted.lock().unwrap() = Some(stream);
+                spawn_copy(reader, rx);
+            }
+        });
+
+        Ok(Self { rx, client })
+    }
+}
+
+/// Reporter of the simulator.
+///
+/// Reports are printed to stdout, one line for each, with milliseconds since the reporter is
+/// built.
+pub struct SimReporter {
+    start: Instant,
+    rrp: Option<RrpSocket>,
+}
+
+impl SimReporter {
+    fn print(&self, kind: &str, report: std::fmt::Arguments<'_>) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{:>8} {kind:<8} {report}", self.start.elapsed().as_millis());
+        let _ = stdout.flush();
+    }
+}
+
+impl ReporterDriver for SimReporter {
+    type Error = Infallible;
+
+    fn try_send_keyboard_report(&self, report: KeyboardReport) -> Result<(), Self::Error> {
+        let keys = report.keycodes.iter().filter(|k| **k != 0);
+        let keys = keys.map(|k| format!("{k:02x}")).collect::<Vec<_>>().join(" ");
+        self.print("keyboard", format_args!("mod={:02x} keys=[{keys}]", report.modifier));
+        Ok(())
+    }
+
+    fn try_send_media_keyboard_report(
+        &self,
+        report: MediaKeyboardReport,
+    ) -> Result<(), Self::Error> {
+        self.print("media", format_args!("usage={:04x}", report.usage_id));
+        Ok(())
+    }
+
+    fn try_send_mouse_report(&self, report: MouseReport) -> Result<(), Self::Error> {
+        self.print(
+            "mouse",
+            format_args!(
+                "buttons={:02x} x={} y={} wheel={} pan={}",
+                report.buttons, report.x, report.y, report.wheel, report.pan
+            ),
+        );
+        Ok(())
+    }
+
+    async fn send_rrp_data(&self, data: &[u8]) -> Result<(), Self::Error> {
+        if let Some(rrp) = &self.rrp {
+            let mut client = rrp.client.lock().unwrap();
+            if let Some(stream) = client.as_mut()
+                && stream.write_all(data).is_err()
+            {
+                *client = None;
+            }
+        }
+        Ok(())
+    }
+
+    async fn recv_rrp_data(&self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match &self.rrp {
+            Some(rrp) => Ok(rrp.rx.read(buf).await),
+            None => core::future::pending().await,
+        }
+    }
+
+    fn wakeup(&self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+}
+
+impl UsbReporterDriver for SimReporter {
+    type Error = Infallible;
+
+    async fn vbus_detect(&self) {}
+}
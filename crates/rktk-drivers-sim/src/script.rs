@@ -0,0 +1,147 @@
+//! Text format of key events.
+//!
+//! Each line is one command. Empty lines and lines starting with `#` are ignored.
+//!
+//! | Command                     | Description                                              |
+//! | --------------------------- | -------------------------------------------------------- |
+//! | `press <row> <col>`         | Presses the key.                                         |
+//! | `release <row> <col>`       | Releases the key.                                        |
+//! | `tap <row> <col> [hold_ms]` | Presses the key and releases it after `hold_ms` (20ms).  |
+//! | `wait <ms>`                 | Waits before the next command.                           |
+//! | `exit`                      | Terminates the simulator.                                |
+//!
+//! `row` and `col` are the position in the keyscan driver, that is, the position in each half for
+//! split keyboard.
+
+use std::{str::SplitWhitespace, time::Duration};
+
+use rktk::drivers::interface::keyscan::KeyChangeEvent;
+
+/// Hold time of `tap` command if not specified.
+pub const DEFAULT_TAP_DURATION: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Key(KeyChangeEvent),
+    Wait(Duration),
+    Exit,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("unknown command: {0}")]
+    UnknownCommand(String),
+    #[error("missing argument: {0}")]
+    MissingArgument(&'static str),
+    #[error("invalid number: {0}")]
+    InvalidNumber(String),
+    #[error("too many arguments")]
+    TooManyArguments,
+}
+
+/// Parses one line into events. Returns empty vec for empty line or comment.
+pub fn parse_line(line: &str) -> Result<Vec<Event>, ParseError> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(Vec::new());
+    }
+
+    let mut args = line.split_whitespace();
+    let command = args.next().unwrap_or_default();
+    let events = match command {
+        "press" | "release" => {
+            let (row, col) = (arg(&mut args, "row")?, arg(&mut args, "col")?);
+            vec![Event::Key(KeyChangeEvent { row, col, pressed: command == "press" })]
+        }
+        "tap" => {
+            let (row, col) = (arg(&mut args, "row")?, arg(&mut args, "col")?);
+            let hold = match args.next() {
+                Some(ms) => Duration::from_millis(number(ms)?),
+                None => DEFAULT_TAP_DURATION,
+            };
+            vec![
+                Event::Key(KeyChangeEvent { row, col, pressed: true }),
+                Event::Wait(hold),
+                Event::Key(KeyChangeEvent { row, col, pressed: false }),
+            ]
+        }
+        "wait" => vec![Event::Wait(Duration::from_millis(arg(&mut args, "ms")?))],
+        "exit" => vec![Event::Exit],
+        _ => return Err(ParseError::UnknownCommand(command.to_string())),
+    };
+
+    if args.next().is_some() {
+        return Err(ParseError::TooManyArguments);
+    }
+    Ok(events)
+}
+
+/// Parses all lines. Error contains the line number, starting from 1.
+pub fn parse(script: &str) -> Result<Vec<Event>, (usize, ParseError)> {
+    let mut events = Vec::new();
+    for (i, line) in script.lines().enumerate() {
+        events.extend(parse_line(line).map_err(|e| (i + 1, e))?);
+    }
+    Ok(events)
+}
+
+fn arg<T: std::str::FromStr>(
+    args: &mut SplitWhitespace<'_>,
+    name: &'static str,
+) -> Result<T, ParseError> {
+    number(args.next().ok_or(ParseError::MissingArgument(name))?)
+}
+
+fn number<T: std::str::FromStr>(s: &str) -> Result<T, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidNumber(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rktk::drivers::interface::keyscan::KeyChangeEvent;
+
+    use super::{DEFAULT_TAP_DURATION, Event, ParseError, parse, parse_line};
+
+    fn key(row: u8, col: u8, pressed: bool) -> Event {
+        Event::Key(KeyChangeEvent { row, col, pressed })
+    }
+
+    #[test]
+    fn parse_commands() {
+        let script = "
+            # comment
+            press 1 2
+            wait 30
+            release 1 2
+            tap 0 3
+            tap 0 4 100
+            exit
+        ";
+        assert_eq!(
+            parse(script),
+            Ok(vec![
+                key(1, 2, true),
+                Event::Wait(Duration::from_millis(30)),
+                key(1, 2, false),
+                key(0, 3, true),
+                Event::Wait(DEFAULT_TAP_DURATION),
+                key(0, 3, false),
+                key(0, 4, true),
+                Event::Wait(Duration::from_millis(100)),
+                key(0, 4, false),
+                Event::Exit,
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_line("hold 1 2"), Err(ParseError::UnknownCommand("hold".into())));
+        assert_eq!(parse_line("press 1"), Err(ParseError::MissingArgument("col")));
+        assert_eq!(parse_line("press 1 300"), Err(ParseError::InvalidNumber("300".into())));
+        assert_eq!(parse_line("wait 1 2"), Err(ParseError::TooManyArguments));
+        assert_eq!(parse("wait 1\nexit 1"), Err((2, ParseError::TooManyArguments)));
+    }
+}
@@ -0,0 +1,97 @@
+//! Split driver over an in-process pipe or a TCP socket.
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use rktk::drivers::interface::split::SplitDriver;
+
+use crate::{
+    IoError,
+    pipe::{BytePipe, leak, spawn_copy, spawn_drain},
+};
+
+/// One side of the split link.
+pub struct SimSplit {
+    rx: &'static BytePipe,
+    tx: &'static BytePipe,
+}
+
+impl SimSplit {
+    /// Creates both sides of a link which are connected in the process.
+    pub fn pair() -> (Self, Self) {
+        let (a, b) = (leak(BytePipe::new()), leak(BytePipe::new()));
+        (Self { rx: a, tx: b }, Self { rx: b, tx: a })
+    }
+
+    /// Waits for the other side to connect to `addr`.
+    pub fn listen(addr: SocketAddr) -> std::io::Result<Self> {
+        let (stream, _) = TcpListener::bind(addr)?.accept()?;
+        Self::from_stream(stream)
+    }
+
+    /// Connects to the other side which is listening on `addr`.
+    pub fn connect(addr: SocketAddr) -> std::io::Result<Self> {
+        Self::from_stream(TcpStream::connect(addr)?)
+    }
+
+    fn from_stream(stream: TcpStream) -> std::io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let (rx, tx) = (leak(BytePipe::new()), leak(BytePipe::new()));
+        spawn_copy(stream.try_clone()?, rx);
+        spawn_drain(tx, stream);
+        Ok(Self { rx, tx })
+    }
+}
+
+impl SplitDriver for SimSplit {
+    type Error = IoError;
+
+    async fn recv(&mut self, buf: &mut [u8], _is_master: bool) -> Result<usize, Self::Error> {
+        Ok(self.rx.read(buf).await)
+    }
+
+    async fn send_all(&mut self, buf: &[u8], _is_master: bool) -> Result<(), Self::Error> {
+        self.tx.write_all(buf).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+
+    use embassy_futures::block_on;
+    use rktk::drivers::interface::split::SplitDriver as _;
+
+    use super::SimSplit;
+
+    fn exchange(a: &mut SimSplit, b: &mut SimSplit) {
+        block_on(async {
+            a.send_all(&[1, 2, 3], true).await.unwrap();
+            b.send_all(&[4], false).await.unwrap();
+
+            let mut buf = [0; 8];
+            let mut len = 0;
+            while len < 3 {
+                len += b.recv(&mut buf[len..], false).await.unwrap();
+            }
+            assert_eq!(&buf[..3], &[1, 2, 3]);
+            assert_eq!(a.recv(&mut buf, true).await.unwrap(), 1);
+            assert_eq!(buf[0], 4);
+        });
+    }
+
+    #[test]
+    fn pair_is_connected() {
+        let (mut a, mut b) = SimSplit::pair();
+        exchange(&mut a, &mut b);
+    }
+
+    #[test]
+    fn tcp_is_connected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut a = SimSplit::from_stream(stream).unwrap();
+        let mut b = SimSplit::from_stream(listener.accept().unwrap().0).unwrap();
+        exchange(&mut a, &mut b);
+    }
+}
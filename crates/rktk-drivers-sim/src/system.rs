@@ -0,0 +1,20 @@
+//! System driver which terminates the simulator on reset.
+
+use rktk::drivers::interface::system::SystemDriver;
+
+/// System driver of the simulator.
+///
+/// As the simulator can't restart itself, reset exits the process. Run it again to simulate boot.
+pub struct SimSystem;
+
+impl SystemDriver for SimSystem {
+    fn reset(&self) {
+        eprintln!("Reset requested. Exiting.");
+        std::process::exit(0);
+    }
+
+    fn reset_to_bootloader(&self) {
+        eprintln!("Reset to bootloader requested. Exiting.");
+        std::process::exit(0);
+    }
+}
@@ -2,17 +2,27 @@
 
 use core::fmt::Display;
 
-use crate::transport::{ReadTransport, TransportError, WriteTransport};
+use crate::transport::{ReadTransport, Target, TransportError, WriteTransport};
 
 /// Client to make requests to the rrp server.
 pub struct Client<RT: ReadTransport + Unpin, WT: WriteTransport + Unpin> {
     pub(crate) reader: RT,
     pub(crate) writer: WT,
+    pub(crate) target: Target,
 }
 
 impl<RT: ReadTransport + Unpin, WT: WriteTransport + Unpin> Client<RT, WT> {
     pub fn new(reader: RT, writer: WT) -> Self {
-        Self { reader, writer }
+        Self { reader, writer, target: Target::Master }
+    }
+
+    /// Sets the side of the keyboard to which subsequent requests are sent.
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
+
+    pub fn target(&self) -> Target {
+        self.target
     }
 }
 
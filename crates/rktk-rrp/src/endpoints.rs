@@ -132,3 +132,295 @@ pub mod set_calibration_mode {
     pub type Request = bool;
     pub type Response = ();
 }
+
+pub mod get_calibration {
+    use macro_rules_attribute::apply;
+
+    /// Max size of calibration data in one [`CalibrationChunk`].
+    pub const CHUNK_SIZE: usize = 64;
+
+    #[apply(super::common_derive)]
+    pub struct CalibrationChunk {
+        /// Offset of `data` in the whole calibration data
+        pub offset: u16,
+        #[cfg(not(feature = "std"))]
+        pub data: heapless::Vec<u8, CHUNK_SIZE>,
+        #[cfg(feature = "std")]
+        pub data: Vec<u8>,
+    }
+
+    pub type Request = ();
+    /// Calibration data of the keyscan driver in chunks. Stream is empty if the keyscan driver
+    /// doesn't support calibration.
+    pub type Response = CalibrationChunk;
+}
+
+pub mod update_firmware {
+    use macro_rules_attribute::apply;
+
+    /// Max size of firmware data in one [`FirmwareUpdateRequest::Chunk`].
+    pub const CHUNK_SIZE: usize = 64;
+
+    /// Request stream of firmware update.
+    ///
+    /// Stream must start with [`FirmwareUpdateRequest::Start`] and followed by chunks in order.
+    #[apply(super::common_derive)]
+    pub enum FirmwareUpdateRequest {
+        Start {
+            /// Size of the whole firmware image
+            size: u32,
+            /// CRC-32 of the whole firmware image
+            crc: u32,
+        },
+        Chunk {
+            offset: u32,
+            #[cfg(not(feature = "std"))]
+            data: heapless::Vec<u8, CHUNK_SIZE>,
+            #[cfg(feature = "std")]
+            data: Vec<u8>,
+            /// CRC-32 of `data`
+            crc: u32,
+        },
+    }
+
+    #[apply(super::common_derive)]
+    pub enum FirmwareUpdateError {
+        /// Firmware update is not supported by the keyboard.
+        NotSupported,
+        /// Firmware image is larger than the staging region.
+        TooLarge,
+        /// Request stream is broken (missing start, unexpected offset or receive error).
+        InvalidSequence,
+        /// CRC of the chunk doesn't match.
+        ChunkCrcMismatch { offset: u32 },
+        /// Failed to erase or write flash.
+        Flash,
+        /// Data read back from flash doesn't match written data.
+        VerifyFailed { offset: u32 },
+        /// Received size or CRC of the whole image doesn't match.
+        ImageMismatch,
+    }
+
+    pub type Request = FirmwareUpdateRequest;
+    /// If update succeeded, keyboard reboots into the bootloader after sending response.
+    pub type Response = Result<(), FirmwareUpdateError>;
+}
+
+pub mod get_backup {
+    use kmsm::{interface::state::config::StateConfig, keycode::KeyCode};
+    use macro_rules_attribute::apply;
+
+    /// Version of the backup format. Increment this when [`BackupItem`] is changed incompatibly.
+    pub const BACKUP_VERSION: u16 = 1;
+
+    /// Max size of calibration data in one [`BackupItem::Calibration`].
+    pub const CALIBRATION_CHUNK_SIZE: usize = 64;
+
+    #[apply(super::common_derive)]
+    pub struct BackupHeader {
+        /// [`BACKUP_VERSION`] of the backup
+        pub version: u16,
+        /// Storage version of the keyboard which created the backup
+        pub storage_version: u16,
+        pub rows: u8,
+        pub cols: u8,
+        pub layers: u8,
+        /// Number of profiles in the backup
+        pub profiles: u8,
+    }
+
+    /// An item of the configuration backup.
+    ///
+    /// Backup is a stream of items which starts with [`BackupItem::Header`].
+    #[apply(super::common_derive)]
+    pub enum BackupItem {
+        Header(BackupHeader),
+        /// Following config and keymap items belong to this profile.
+        Profile(u8),
+        StateConfig(StateConfig),
+        Key(super::KeyActionLoc),
+        Encoder {
+            layer: u8,
+            index: u8,
+            keys: (Option<KeyCode>, Option<KeyCode>),
+        },
+        LayerOptions {
+            layer: u8,
+            arrow_mouse: bool,
+        },
+        Calibration {
+            offset: u16,
+            #[cfg(not(feature = "std"))]
+            data: heapless::Vec<u8, CALIBRATION_CHUNK_SIZE>,
+            #[cfg(feature = "std")]
+            data: Vec<u8>,
+        },
+    }
+
+    impl BackupHeader {
+        /// Checks that the backup can be restored to the keyboard.
+        pub fn check_compatible(
+            &self,
+            info: &super::get_keyboard_info::KeyboardInfo,
+        ) -> Result<(), super::set_backup::RestoreError> {
+            if self.version != BACKUP_VERSION {
+                return Err(super::set_backup::RestoreError::InvalidHeader);
+            }
+            if self.rows != info.rows
+                || self.cols != info.cols
+                || self.layers != info.keymap.layer_count
+            {
+                return Err(super::set_backup::RestoreError::KeyboardMismatch);
+            }
+            Ok(())
+        }
+    }
+
+    pub type Request = ();
+    pub type Response = BackupItem;
+}
+
+pub mod set_backup {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub enum RestoreError {
+        /// Backup doesn't start with a header or its version is not supported.
+        InvalidHeader,
+        /// Backup was created on a keyboard with different rows, cols or layer count, or contains
+        /// more profiles than the keyboard has.
+        KeyboardMismatch,
+        /// Backup contains an item out of range.
+        InvalidItem,
+        /// Restored to the current state, but failed to write to storage.
+        Storage,
+    }
+
+    pub type Request = super::get_backup::BackupItem;
+    pub type Response = Result<(), RestoreError>;
+}
+
+pub mod get_profiles {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub struct ProfileInfo {
+        /// Number of profiles. Profiles are numbered from 0 to `count - 1`.
+        pub count: u8,
+        pub active: u8,
+    }
+
+    pub type Request = ();
+    pub type Response = ProfileInfo;
+}
+
+pub mod set_active_profile {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub enum ProfileError {
+        OutOfRange,
+        /// Profiles are not available because the keyboard has no storage.
+        NoStorage,
+        Storage,
+    }
+
+    /// Index of the profile to activate
+    pub type Request = u8;
+    pub type Response = Result<(), ProfileError>;
+}
+
+pub mod copy_profile {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub struct CopyProfile {
+        pub from: u8,
+        pub to: u8,
+    }
+
+    /// Copies keymap and config. If `to` is the active profile, it is reloaded.
+    pub type Request = CopyProfile;
+    pub type Response = Result<(), super::set_active_profile::ProfileError>;
+}
+
+pub mod get_storage_stats {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub struct StorageUsage {
+        /// Number of erased pages since boot
+        pub erase_count: u32,
+        /// Bytes which can be written without erasing a page
+        pub free_bytes: u32,
+        pub capacity: u32,
+    }
+
+    #[apply(super::common_derive)]
+    pub struct StorageStats {
+        /// `None` if the storage driver doesn't report usage.
+        pub usage: Option<StorageUsage>,
+        /// True if some changes are not written to the storage yet.
+        pub pending: bool,
+    }
+
+    pub type Request = ();
+    /// `None` if the keyboard has no storage.
+    pub type Response = Option<StorageStats>;
+}
+
+pub mod commit_storage {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub enum CommitError {
+        NoStorage,
+        Storage,
+    }
+
+    /// Writes pending changes to the storage immediately.
+    pub type Request = ();
+    pub type Response = Result<(), CommitError>;
+}
+
+pub mod reset_key {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub struct KeyPos {
+        pub layer: u8,
+        pub row: u8,
+        pub col: u8,
+    }
+
+    /// Resets the key to the one in the default keymap of the firmware.
+    pub type Request = KeyPos;
+    /// Key action after reset
+    pub type Response = super::KeyAction;
+}
+
+pub mod get_split_stats {
+    use macro_rules_attribute::apply;
+
+    #[apply(super::common_derive)]
+    pub struct SplitStats {
+        /// False if nothing is received from the other side for a while.
+        pub alive: bool,
+        pub frames_sent: u32,
+        pub frames_received: u32,
+        /// Frames retransmitted because ACK didn't arrive in time
+        pub retransmits: u32,
+        /// Received frames dropped because of CRC or framing errors
+        pub errors: u32,
+        /// Messages which were given up or skipped
+        pub lost: u32,
+        /// Number of times the split driver was re-initialized
+        pub reinits: u32,
+        /// Round trip time (ms) of the last ping. Only measured on the master side.
+        pub latency_ms: Option<u32>,
+    }
+
+    pub type Request = ();
+    /// `None` if the keyboard is not split.
+    pub type Response = Option<SplitStats>;
+}
@@ -0,0 +1,161 @@
+//! Firmware update over rrp.
+//!
+//! Client sends the firmware image through [`update_firmware`](crate::endpoints::update_firmware)
+//! endpoint as [`FirmwareUpdateRequest::Start`] followed by [`FirmwareUpdateRequest::Chunk`]s.
+//! Every chunk has its own CRC-32 and the whole image is checked with CRC-32 again before the
+//! keyboard marks it as updated.
+
+use crate::endpoints::update_firmware::*;
+
+/// CRC-32 (IEEE 802.3) hasher.
+pub struct Crc32(u32);
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.0 = CRC32_TABLE[((self.0 ^ *b as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+/// Splits firmware image into requests of [`update_firmware`](crate::endpoints::update_firmware).
+#[cfg(feature = "client")]
+pub fn firmware_update_requests(image: &[u8]) -> Vec<FirmwareUpdateRequest> {
+    let mut requests = Vec::with_capacity(image.len() / CHUNK_SIZE + 2);
+    requests.push(FirmwareUpdateRequest::Start { size: image.len() as u32, crc: crc32(image) });
+    for (i, data) in image.chunks(CHUNK_SIZE).enumerate() {
+        requests.push(FirmwareUpdateRequest::Chunk {
+            offset: (i * CHUNK_SIZE) as u32,
+            data: data.to_vec(),
+            crc: crc32(data),
+        });
+    }
+    requests
+}
+
+/// Flash region where the new firmware is staged.
+#[cfg(feature = "server")]
+#[allow(async_fn_in_trait)]
+pub trait FirmwareFlash {
+    type Error: core::fmt::Debug;
+
+    /// Size of the staging region.
+    fn capacity(&self) -> u32;
+
+    /// Erases the staging region so that `size` bytes of firmware can be written.
+    async fn erase(&mut self, size: u32) -> Result<(), Self::Error>;
+
+    /// Writes data to the staging region.
+    ///
+    /// `offset` is always multiple of [`CHUNK_SIZE`] and only the last write can be shorter than
+    /// [`CHUNK_SIZE`].
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Marks the staged firmware to be installed on next boot.
+    async fn mark_updated(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Writes firmware received from `req` to `flash`.
+///
+/// Each chunk is verified by its CRC and read back after written. When all chunks are written,
+/// size and CRC of the whole image are checked and the firmware is marked as updated.
+/// Remaining requests are drained on error so that the transport can be reused.
+#[cfg(feature = "server")]
+pub async fn receive_firmware<E>(
+    flash: &mut impl FirmwareFlash,
+    req: impl futures::Stream<Item = Result<FirmwareUpdateRequest, E>>,
+) -> Result<(), FirmwareUpdateError> {
+    use futures::StreamExt as _;
+
+    let mut req = core::pin::pin!(req);
+    let res = write_image(flash, &mut req).await;
+    if res.is_err() {
+        while req.next().await.is_some() {}
+    }
+    res
+}
+
+#[cfg(feature = "server")]
+async fn write_image<E>(
+    flash: &mut impl FirmwareFlash,
+    req: &mut (impl futures::Stream<Item = Result<FirmwareUpdateRequest, E>> + Unpin),
+) -> Result<(), FirmwareUpdateError> {
+    use futures::StreamExt as _;
+
+    let Some(Ok(FirmwareUpdateRequest::Start { size, crc: image_crc })) = req.next().await else {
+        return Err(FirmwareUpdateError::InvalidSequence);
+    };
+    if size > flash.capacity() {
+        return Err(FirmwareUpdateError::TooLarge);
+    }
+    flash.erase(size).await.map_err(|_| FirmwareUpdateError::Flash)?;
+
+    let mut written = 0u32;
+    let mut crc = Crc32::new();
+    let mut buf = [0u8; CHUNK_SIZE];
+    while let Some(chunk) = req.next().await {
+        let Ok(FirmwareUpdateRequest::Chunk { offset, data, crc: chunk_crc }) = chunk else {
+            return Err(FirmwareUpdateError::InvalidSequence);
+        };
+        if offset != written || data.len() > CHUNK_SIZE || offset + data.len() as u32 > size {
+            return Err(FirmwareUpdateError::InvalidSequence);
+        }
+        if crc32(&data) != chunk_crc {
+            return Err(FirmwareUpdateError::ChunkCrcMismatch { offset });
+        }
+
+        flash.write(offset, &data).await.map_err(|_| FirmwareUpdateError::Flash)?;
+        let read = &mut buf[..data.len()];
+        flash.read(offset, read).await.map_err(|_| FirmwareUpdateError::Flash)?;
+        if *read != data[..] {
+            return Err(FirmwareUpdateError::VerifyFailed { offset });
+        }
+
+        crc.update(read);
+        written += data.len() as u32;
+    }
+
+    if written != size || crc.finish() != image_crc {
+        return Err(FirmwareUpdateError::ImageMismatch);
+    }
+
+    flash.mark_updated().await.map_err(|_| FirmwareUpdateError::Flash)
+}
@@ -8,6 +8,7 @@ compile_error!("At least, one of the `client` or `server` features should be ena
 #[cfg(feature = "client")]
 pub mod client;
 pub mod endpoints;
+pub mod firmware;
 #[cfg(feature = "server")]
 pub mod server;
 pub mod transport;
@@ -51,7 +51,7 @@ macro_rules! generate_client {
 
                     self.writer.send_request_header(RequestHeader {
                         request_id: 0,
-                        endpoint_id: $endpoint_id,
+                        endpoint_id: $endpoint_id | self.target.endpoint_flag(),
                     }).await.map_err(TransportError::SendError)?;
                     send_request!($req_kind, self.writer, req).map_err(TransportError::SendError)?;
 
@@ -1,7 +1,7 @@
 #[cfg(feature = "client")]
 mod client;
 #[cfg(feature = "server")]
-mod server;
+pub(crate) mod server;
 
 macro_rules! generate_impls {
     ($($endpoint_id:tt: $endpoint_name:ident($req_kind:tt) -> $res_kind:tt;)*) => {
@@ -32,6 +32,17 @@ generate_impls!(
     6: get_now(normal) -> normal;
     7: get_log(normal) -> stream;
     8: set_calibration_mode(normal) -> normal;
+    9: update_firmware(stream) -> normal;
+    10: get_backup(normal) -> stream;
+    11: set_backup(stream) -> normal;
+    12: get_profiles(normal) -> normal;
+    13: set_active_profile(normal) -> normal;
+    14: copy_profile(normal) -> normal;
+    15: get_storage_stats(normal) -> normal;
+    16: commit_storage(normal) -> normal;
+    17: reset_key(normal) -> normal;
+    18: get_split_stats(normal) -> normal;
+    19: get_calibration(normal) -> stream;
 );
 
 #[cfg(test)]
@@ -45,8 +56,19 @@ generate_impls!(
     6: get_now(normal) -> normal;
     7: get_log(normal) -> stream;
     8: set_calibration_mode(normal) -> normal;
-    9: test_normal_normal(normal) -> normal;
-    10: test_stream_normal(stream) -> normal;
-    11: test_normal_stream(normal) -> stream;
-    12: test_stream_stream(stream) -> stream;
+    9: update_firmware(stream) -> normal;
+    10: get_backup(normal) -> stream;
+    11: set_backup(stream) -> normal;
+    12: get_profiles(normal) -> normal;
+    13: set_active_profile(normal) -> normal;
+    14: copy_profile(normal) -> normal;
+    15: get_storage_stats(normal) -> normal;
+    16: commit_storage(normal) -> normal;
+    17: reset_key(normal) -> normal;
+    18: get_split_stats(normal) -> normal;
+    19: get_calibration(normal) -> stream;
+    120: test_normal_normal(normal) -> normal;
+    121: test_stream_normal(stream) -> normal;
+    122: test_normal_stream(normal) -> stream;
+    123: test_stream_stream(stream) -> stream;
 );
@@ -1,3 +1,7 @@
+use crate::transport::{
+    ResponseHeader, WriteTransport, error::SendError, write::WriteTransportExt as _,
+};
+
 macro_rules! gen_ep_sig {
     ($ep:ident, normal: $ty_req:ty, normal: $ty_res:ty) => {
         async fn $ep(&mut self, _req: $ty_req) -> Result<$ty_res, Self::Error> {
@@ -48,6 +52,21 @@ macro_rules! send_response_body {
 }
 pub(crate) use send_response_body;
 
+/// Sends response with non-zero status and `error` as the message.
+pub(crate) async fn send_error_response<WT: WriteTransport, const BUF_SIZE: usize>(
+    writer: &mut WT,
+    request_id: u8,
+    error: impl core::fmt::Display,
+) -> Result<(), SendError<WT::Error>> {
+    use core::fmt::Write as _;
+
+    writer.send_response_header(ResponseHeader { request_id, status: 1 }).await?;
+    // Message is truncated if it is too long.
+    let mut message = heapless::String::<64>::new();
+    let _ = write!(message, "{error}");
+    writer.send_body_normal::<_, BUF_SIZE>(&message.as_str()).await
+}
+
 macro_rules! generate_server_handlers {
     ($($endpoint_id:tt: $endpoint_name:ident($req_kind:tt: $req_type:ty) -> $res_kind:tt: $res_type:ty;)*) => {
         use core::fmt::Display;
@@ -75,7 +94,8 @@ macro_rules! generate_server_handlers {
                 RT: ReadTransport,
                 WT: WriteTransport,
                 H: ServerHandlers<RT::Error, WT::Error>,
-            > Server<RT, WT, H>
+                F: Forwarder,
+            > Server<RT, WT, H, F>
         {
             pub(crate) async fn handle<const BUF_SIZE: usize>(&mut self, header: RequestHeader) -> Result<(), TransportError<RT::Error, WT::Error>> {
                 match header.endpoint_id {
@@ -83,8 +103,12 @@ macro_rules! generate_server_handlers {
                         $endpoint_id => {
                             let req = recv_request_body!($req_kind, self.reader);
 
-                            let Ok(res) = self.handlers.$endpoint_name(req).await else {
-                                return Ok(());
+                            let res = match self.handlers.$endpoint_name(req).await {
+                                Ok(res) => res,
+                                Err(e) => {
+                                    send_error_response::<_, BUF_SIZE>(&mut self.writer, header.request_id, e).await?;
+                                    return Ok(());
+                                }
                             };
 
                             self.writer.send_response_header(ResponseHeader {
@@ -2,19 +2,38 @@ pub use crate::macros::server_generated::ServerHandlers;
 use crate::transport::read::ReadTransportExt as _;
 use crate::transport::*;
 
-pub struct Server<RT: ReadTransport, WT: WriteTransport, H: ServerHandlers<RT::Error, WT::Error>> {
+pub use forward::{Forwarder, NoForwarder, TransportForwarder};
+
+mod forward;
+
+pub struct Server<
+    RT: ReadTransport,
+    WT: WriteTransport,
+    H: ServerHandlers<RT::Error, WT::Error>,
+    F: Forwarder = NoForwarder,
+> {
     pub(crate) reader: RT,
     pub(crate) writer: WT,
     pub(crate) handlers: H,
+    pub(crate) forwarder: F,
 }
 
 impl<RT: ReadTransport, WT: WriteTransport, H: ServerHandlers<RT::Error, WT::Error>>
-    Server<RT, WT, H>
+    Server<RT, WT, H, NoForwarder>
 {
     pub fn new(reader: RT, writer: WT, handlers: H) -> Self {
-        Self { reader, writer, handlers }
+        Self { reader, writer, handlers, forwarder: NoForwarder }
     }
 
+    /// Sets forwarder which handles requests addressed to [`Target::Slave`].
+    pub fn with_forwarder<F: Forwarder>(self, forwarder: F) -> Server<RT, WT, H, F> {
+        Server { reader: self.reader, writer: self.writer, handlers: self.handlers, forwarder }
+    }
+}
+
+impl<RT: ReadTransport, WT: WriteTransport, H: ServerHandlers<RT::Error, WT::Error>, F: Forwarder>
+    Server<RT, WT, H, F>
+{
     pub async fn start<const BUF_SIZE: usize>(&mut self) {
         loop {
             let _ = self.process_request::<BUF_SIZE>().await;
@@ -26,7 +45,12 @@ impl<RT: ReadTransport, WT: WriteTransport, H: ServerHandlers<RT::Error, WT::Err
     ) -> Result<(), TransportError<RT::Error, WT::Error>> {
         let req_header = self.reader.recv_request_header().await?;
 
-        self.handle::<BUF_SIZE>(req_header).await?;
+        match Target::from_endpoint_id(req_header.endpoint_id) {
+            Target::Master => self.handle::<BUF_SIZE>(req_header).await?,
+            Target::Slave => {
+                self.forwarder.forward(req_header, &mut self.reader, &mut self.writer).await?
+            }
+        }
 
         Ok(())
     }
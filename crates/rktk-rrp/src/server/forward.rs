@@ -0,0 +1,196 @@
+//! Forwarding of requests addressed to the other side of split keyboard.
+
+use core::convert::Infallible;
+
+use crate::{
+    macros::server::send_error_response,
+    transport::{
+        Indicator, ReadTransport, RequestHeader, SLAVE_TARGET_FLAG, TransportError, WriteTransport,
+        error::{ReceiveError, SendError},
+        read::ReadTransportExt as _,
+    },
+};
+
+/// Buffer size to serialize the error response sent when a request can't be forwarded.
+const ERROR_BUF_SIZE: usize = 80;
+
+/// Handles requests addressed to [`crate::transport::Target::Slave`].
+#[allow(async_fn_in_trait)]
+pub trait Forwarder {
+    /// Forwards the request whose header is already received and writes back the response.
+    ///
+    /// The request body has to be read from `reader` entirely even if the request can't be
+    /// forwarded, and an error response has to be written, otherwise subsequent requests are
+    /// broken and the client waits forever.
+    async fn forward<RT: ReadTransport, WT: WriteTransport>(
+        &mut self,
+        header: RequestHeader,
+        reader: &mut RT,
+        writer: &mut WT,
+    ) -> Result<(), TransportError<RT::Error, WT::Error>>;
+}
+
+/// Forwarder which rejects all requests addressed to the slave side.
+pub struct NoForwarder;
+
+impl Forwarder for NoForwarder {
+    async fn forward<RT: ReadTransport, WT: WriteTransport>(
+        &mut self,
+        header: RequestHeader,
+        reader: &mut RT,
+        writer: &mut WT,
+    ) -> Result<(), TransportError<RT::Error, WT::Error>> {
+        discard_body(reader).await?;
+        send_error_response::<_, ERROR_BUF_SIZE>(writer, header.request_id, "No slave").await?;
+        Ok(())
+    }
+}
+
+/// Forwarder which relays requests to another rrp server through the given transports.
+///
+/// The data is relayed frame by frame without deserialization, so the forwarder doesn't have to
+/// know about endpoint types.
+///
+/// If the request can't be sent or no response arrives, an error response is written to the
+/// client instead.
+pub struct TransportForwarder<R: ReadTransport, W: WriteTransport> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: ReadTransport, W: WriteTransport> TransportForwarder<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl<R: ReadTransport, W: WriteTransport> Forwarder for TransportForwarder<R, W> {
+    async fn forward<RT: ReadTransport, WT: WriteTransport>(
+        &mut self,
+        header: RequestHeader,
+        reader: &mut RT,
+        writer: &mut WT,
+    ) -> Result<(), TransportError<RT::Error, WT::Error>> {
+        // request: client -> other side
+        // Body is read entirely even if the other side can't be written.
+        let mut relay = Fallible { writer: &mut self.writer, failed: false };
+        let _ = relay
+            .write_all(&[
+                Indicator::Start as u8,
+                header.request_id,
+                header.endpoint_id & !SLAVE_TARGET_FLAG,
+            ])
+            .await;
+        relay_body(reader, &mut relay).await.map_err(|e| match e {
+            TransportError::RecvError(e) => TransportError::RecvError(e),
+            TransportError::SendError(_) => unreachable!(),
+        })?;
+        if relay.failed {
+            send_error_response::<_, ERROR_BUF_SIZE>(
+                writer,
+                header.request_id,
+                "Failed to send request to slave",
+            )
+            .await?;
+            return Ok(());
+        }
+
+        // response: other side -> client
+        let mut response_header = [0u8; 3];
+        let received = self.reader.read_exact(&mut response_header).await.is_ok();
+        if !received || response_header[0] != Indicator::Start as u8 {
+            send_error_response::<_, ERROR_BUF_SIZE>(
+                writer,
+                header.request_id,
+                "No response from slave",
+            )
+            .await?;
+            return Ok(());
+        }
+        writer.write_all(&response_header).await.map_err(SendError::Write)?;
+        // Response is already partially written, so the error can't be reported to the client.
+        relay_body(&mut self.reader, writer).await.map_err(|e| match e {
+            TransportError::RecvError(_) => forward_failed(),
+            TransportError::SendError(e) => TransportError::SendError(e),
+        })?;
+
+        Ok(())
+    }
+}
+
+fn forward_failed<RE: core::fmt::Display, WE: core::fmt::Display>() -> TransportError<RE, WE> {
+    TransportError::RecvError(ReceiveError::FrameError("Failed to forward request"))
+}
+
+/// Reads request body from `reader` and drops it.
+async fn discard_body<R: ReadTransport, WE: core::fmt::Display>(
+    reader: &mut R,
+) -> Result<(), TransportError<R::Error, WE>> {
+    relay_body(reader, &mut Discard).await.map_err(|e| match e {
+        TransportError::RecvError(e) => TransportError::RecvError(e),
+        TransportError::SendError(_) => unreachable!(),
+    })
+}
+
+/// Copies request/response body (step 4-7) from `reader` to `writer` as is.
+async fn relay_body<R: ReadTransport, W: WriteTransport>(
+    reader: &mut R,
+    writer: &mut W,
+) -> Result<(), TransportError<R::Error, W::Error>> {
+    loop {
+        match reader.recv_indicator().await? {
+            Indicator::Start => {
+                return Err(ReceiveError::FrameError("Invalid indicator").into());
+            }
+            Indicator::End => {
+                writer.write_all(&[Indicator::End as u8]).await.map_err(SendError::Write)?;
+                return Ok(());
+            }
+            Indicator::Continue => {
+                let mut size = [0u8; 4];
+                reader.read_exact(&mut size).await.map_err(ReceiveError::Read)?;
+                writer.write_all(&[Indicator::Continue as u8]).await.map_err(SendError::Write)?;
+                writer.write_all(&size).await.map_err(SendError::Write)?;
+
+                let mut remaining = u32::from_le_bytes(size) as usize;
+                let mut buf = [0u8; 32];
+                while remaining > 0 {
+                    let len = remaining.min(buf.len());
+                    reader.read_exact(&mut buf[..len]).await.map_err(ReceiveError::Read)?;
+                    writer.write_all(&buf[..len]).await.map_err(SendError::Write)?;
+                    remaining -= len;
+                }
+            }
+        }
+    }
+}
+
+/// Writer which stops writing after the first error instead of returning it.
+struct Fallible<'a, W: WriteTransport> {
+    writer: &'a mut W,
+    failed: bool,
+}
+
+impl<W: WriteTransport> WriteTransport for Fallible<'_, W> {
+    type Error = Infallible;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.failed {
+            match self.writer.write(buf).await {
+                Ok(len) => return Ok(len),
+                Err(_) => self.failed = true,
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+struct Discard;
+
+impl WriteTransport for Discard {
+    type Error = Infallible;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Ok(buf.len())
+    }
+}
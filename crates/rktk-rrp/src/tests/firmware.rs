@@ -0,0 +1,240 @@
+use core::pin::pin;
+
+use futures::{future::select, stream};
+use tokio::io::duplex;
+
+use crate::{
+    client::Client,
+    endpoints::update_firmware::{CHUNK_SIZE, FirmwareUpdateError, FirmwareUpdateRequest},
+    firmware::{FirmwareFlash, crc32, firmware_update_requests, receive_firmware},
+};
+
+use super::test_transport;
+
+/// In-memory flash which emulates the staging region.
+struct MockFlash {
+    data: Vec<u8>,
+    updated: bool,
+    /// Flips bits of the byte at this offset on write.
+    broken_at: Option<u32>,
+}
+
+impl MockFlash {
+    fn new(capacity: usize) -> Self {
+        Self { data: vec![0; capacity], updated: false, broken_at: None }
+    }
+}
+
+impl FirmwareFlash for MockFlash {
+    type Error = ();
+
+    fn capacity(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    async fn erase(&mut self, size: u32) -> Result<(), Self::Error> {
+        self.data[..size as usize].fill(0xFF);
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+        if let Some(broken_at) = self.broken_at {
+            let broken_at = broken_at as usize;
+            if (offset..offset + data.len()).contains(&broken_at) {
+                self.data[broken_at] ^= 0xFF;
+            }
+        }
+        Ok(())
+    }
+
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = offset as usize;
+        buf.copy_from_slice(&self.data[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    async fn mark_updated(&mut self) -> Result<(), Self::Error> {
+        self.updated = true;
+        Ok(())
+    }
+}
+
+fn image(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+async fn receive(
+    flash: &mut MockFlash,
+    requests: Vec<FirmwareUpdateRequest>,
+) -> Result<(), FirmwareUpdateError> {
+    receive_firmware(flash, stream::iter(requests.into_iter().map(Ok::<_, ()>))).await
+}
+
+#[test]
+fn test_crc32() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn test_firmware_update_requests() {
+    let image = image(CHUNK_SIZE * 3 + 10);
+    let requests = firmware_update_requests(&image);
+
+    assert_eq!(
+        requests[0],
+        FirmwareUpdateRequest::Start { size: image.len() as u32, crc: crc32(&image) }
+    );
+    assert_eq!(requests.len(), 1 + 4);
+    let FirmwareUpdateRequest::Chunk { offset, data, crc } = &requests[4] else {
+        panic!("expected chunk");
+    };
+    assert_eq!(*offset, (CHUNK_SIZE * 3) as u32);
+    assert_eq!(data.len(), 10);
+    assert_eq!(*crc, crc32(data));
+}
+
+#[tokio::test]
+async fn test_receive_firmware() {
+    let image = image(1000);
+    let mut flash = MockFlash::new(1024);
+
+    receive(&mut flash, firmware_update_requests(&image)).await.unwrap();
+
+    assert_eq!(&flash.data[..image.len()], &image[..]);
+    assert!(flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_too_large() {
+    let mut flash = MockFlash::new(512);
+
+    let res = receive(&mut flash, firmware_update_requests(&image(513))).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::TooLarge));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_broken_chunk() {
+    let mut flash = MockFlash::new(1024);
+    let mut requests = firmware_update_requests(&image(300));
+    if let FirmwareUpdateRequest::Chunk { data, .. } = &mut requests[2] {
+        data[3] ^= 0x01;
+    }
+
+    let res = receive(&mut flash, requests).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::ChunkCrcMismatch { offset: CHUNK_SIZE as u32 }));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_out_of_order() {
+    let mut flash = MockFlash::new(1024);
+    let mut requests = firmware_update_requests(&image(300));
+    requests.swap(1, 2);
+
+    let res = receive(&mut flash, requests).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::InvalidSequence));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_missing_chunk() {
+    let mut flash = MockFlash::new(1024);
+    let mut requests = firmware_update_requests(&image(300));
+    requests.pop();
+
+    let res = receive(&mut flash, requests).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::ImageMismatch));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_image_crc_mismatch() {
+    let image = image(300);
+    let mut flash = MockFlash::new(1024);
+    let mut requests = firmware_update_requests(&image);
+    requests[0] = FirmwareUpdateRequest::Start { size: image.len() as u32, crc: !crc32(&image) };
+
+    let res = receive(&mut flash, requests).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::ImageMismatch));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_receive_firmware_verify_failed() {
+    let mut flash = MockFlash::new(1024);
+    flash.broken_at = Some(200);
+
+    let res = receive(&mut flash, firmware_update_requests(&image(300))).await;
+
+    assert_eq!(res, Err(FirmwareUpdateError::VerifyFailed { offset: CHUNK_SIZE as u32 * 3 }));
+    assert!(!flash.updated);
+}
+
+#[tokio::test]
+async fn test_update_firmware_endpoint() {
+    use crate::server::ServerHandlers;
+    use crate::transport::error::ReceiveError;
+    use core::fmt::Display;
+
+    struct FirmwareHandlers(MockFlash);
+
+    impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for FirmwareHandlers {
+        type Error = &'static str;
+
+        async fn update_firmware(
+            &mut self,
+            req: impl futures::Stream<Item = Result<FirmwareUpdateRequest, ReceiveError<RE>>>,
+        ) -> Result<Result<(), FirmwareUpdateError>, Self::Error> {
+            Ok(receive_firmware(&mut self.0, req).await)
+        }
+    }
+
+    let image = image(CHUNK_SIZE * 5 + 1);
+    let c2s = duplex(2048);
+    let s2c = duplex(2048);
+
+    select(
+        pin!(async {
+            let mut server = crate::server::Server::<_, _, _>::new(
+                test_transport::TestReader(c2s.1),
+                test_transport::TestWriter(s2c.0),
+                FirmwareHandlers(MockFlash::new(1024)),
+            );
+            server.start::<1024>().await;
+        }),
+        pin!(async {
+            let mut client = Client::<_, _>::new(
+                test_transport::TestReader(s2c.1),
+                test_transport::TestWriter(c2s.0),
+            );
+
+            let res = client
+                .update_firmware(stream::iter(firmware_update_requests(&image)))
+                .await
+                .unwrap();
+            assert_eq!(res, Ok(()));
+
+            // Server must be able to handle next request after failed update.
+            let mut requests = firmware_update_requests(&image);
+            requests.remove(1);
+            let res = client.update_firmware(stream::iter(requests)).await.unwrap();
+            assert_eq!(res, Err(FirmwareUpdateError::InvalidSequence));
+
+            let res = client
+                .update_firmware(stream::iter(firmware_update_requests(&image)))
+                .await
+                .unwrap();
+            assert_eq!(res, Ok(()));
+        }),
+    )
+    .await;
+}
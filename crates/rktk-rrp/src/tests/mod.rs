@@ -1,11 +1,16 @@
 use core::pin::pin;
 
 use futures::{StreamExt, future::select};
-use test_server::Handlers;
+use test_server::{FailingHandlers, Handlers, SlaveHandlers};
 use tokio::io::duplex;
 
-use crate::client::Client;
+use crate::{
+    client::{Client, ClientError},
+    server::TransportForwarder,
+    transport::Target,
+};
 
+mod firmware;
 mod test_server;
 mod test_transport;
 
@@ -111,3 +116,150 @@ async fn test_stream_stream() {
     };
     execute_test!(Handlers, test);
 }
+
+#[tokio::test]
+async fn test_error_response() {
+    let test = |reader, writer| async move {
+        let mut client = Client::<_, _>::new(reader, writer);
+
+        let res = client.test_normal_normal("fail".to_string()).await;
+        assert!(matches!(
+            res,
+            Err(ClientError::Failed { status: 1, message }) if message == "Request failed"
+        ));
+
+        // Endpoint which is not implemented by the handlers.
+        let res = client.test_normal_stream(vec!["a".to_string()]).await.err();
+        assert!(matches!(res, Some(ClientError::Failed { status: 1, .. })));
+
+        // Server keeps serving after errors.
+        let res = client.test_normal_normal("ping".to_string()).await.unwrap();
+        assert_eq!("ping", res);
+    };
+    execute_test!(FailingHandlers, test);
+}
+
+#[tokio::test]
+async fn test_forward_to_slave() {
+    // client -> master
+    let c2m = duplex(2048);
+    // master -> client
+    let m2c = duplex(2048);
+    // master -> slave
+    let m2s = duplex(2048);
+    // slave -> master
+    let s2m = duplex(2048);
+
+    select(
+        pin!(async {
+            let mut server = crate::server::Server::<_, _, _>::new(
+                test_transport::TestReader(c2m.1),
+                test_transport::TestWriter(m2c.0),
+                Handlers,
+            )
+            .with_forwarder(TransportForwarder::new(
+                test_transport::TestReader(s2m.1),
+                test_transport::TestWriter(m2s.0),
+            ));
+            server.start::<1024>().await;
+        }),
+        pin!(select(
+            pin!(async {
+                let mut server = crate::server::Server::<_, _, _>::new(
+                    test_transport::TestReader(m2s.1),
+                    test_transport::TestWriter(s2m.0),
+                    SlaveHandlers,
+                );
+                server.start::<1024>().await;
+            }),
+            pin!(async {
+                let mut client = Client::<_, _>::new(
+                    test_transport::TestReader(m2c.1),
+                    test_transport::TestWriter(c2m.0),
+                );
+
+                let res = client.test_normal_normal("ping".to_string()).await.unwrap();
+                assert_eq!("ping", res);
+
+                client.set_target(Target::Slave);
+                let res = client.test_normal_normal("ping".to_string()).await.unwrap();
+                assert_eq!("slave:ping", res);
+
+                let req = vec!["a".to_string(), "bbb".to_string()];
+                let res: Vec<String> = client
+                    .test_stream_stream(futures::stream::iter(req))
+                    .await
+                    .unwrap()
+                    .filter_map(|x| async { x.ok() })
+                    .collect()
+                    .await;
+                assert_eq!(vec!["slave:a".to_string(), "slave:bbb".to_string()], res);
+
+                client.set_target(Target::Master);
+                let res = client.test_normal_normal("pong".to_string()).await.unwrap();
+                assert_eq!("pong", res);
+            }),
+        )),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_forward_timeout() {
+    // client -> master
+    let c2m = duplex(2048);
+    // master -> client
+    let m2c = duplex(2048);
+    // master -> slave, which never responds
+    let m2s = duplex(2048);
+
+    select(
+        pin!(async {
+            let mut server = crate::server::Server::<_, _, _>::new(
+                test_transport::TestReader(c2m.1),
+                test_transport::TestWriter(m2c.0),
+                Handlers,
+            )
+            .with_forwarder(TransportForwarder::new(
+                test_transport::TimeoutReader,
+                test_transport::TestWriter(m2s.0),
+            ));
+            server.start::<1024>().await;
+        }),
+        pin!(async {
+            let mut client = Client::<_, _>::new(
+                test_transport::TestReader(m2c.1),
+                test_transport::TestWriter(c2m.0),
+            );
+
+            client.set_target(Target::Slave);
+            let res = client.test_normal_normal("ping".to_string()).await;
+            assert!(matches!(
+                res,
+                Err(ClientError::Failed { status: 1, message }) if message == "No response from slave"
+            ));
+
+            // Request body is consumed, so the next request is served.
+            client.set_target(Target::Master);
+            let res = client.test_normal_normal("pong".to_string()).await.unwrap();
+            assert_eq!("pong", res);
+        }),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_no_forwarder() {
+    let test = |reader, writer| async move {
+        let mut client = Client::<_, _>::new(reader, writer);
+
+        client.set_target(Target::Slave);
+        let res = client.test_normal_normal("ping".to_string()).await;
+        assert!(matches!(res, Err(ClientError::Failed { status: 1, .. })));
+
+        client.set_target(Target::Master);
+        let res = client.test_normal_normal("pong".to_string()).await.unwrap();
+        assert_eq!("pong", res);
+    };
+    execute_test!(Handlers, test);
+}
@@ -36,3 +36,32 @@ impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for Handlers {
         Ok(req.filter_map(|x| async move { x.ok() }))
     }
 }
+
+/// Handlers that act as the slave side of split keyboard.
+pub struct SlaveHandlers;
+
+impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for SlaveHandlers {
+    type Error = &'static str;
+
+    async fn test_normal_normal(&mut self, req: String) -> Result<String, Self::Error> {
+        Ok(format!("slave:{req}"))
+    }
+
+    async fn test_stream_stream(
+        &mut self,
+        req: impl Stream<Item = Result<String, ReceiveError<RE>>>,
+    ) -> Result<impl Stream<Item = String>, Self::Error> {
+        Ok(req.filter_map(|x| async move { x.ok().map(|x| format!("slave:{x}")) }))
+    }
+}
+
+/// Handlers that fail when the request is `"fail"`.
+pub struct FailingHandlers;
+
+impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for FailingHandlers {
+    type Error = &'static str;
+
+    async fn test_normal_normal(&mut self, req: String) -> Result<String, Self::Error> {
+        if req == "fail" { Err("Request failed") } else { Ok(req) }
+    }
+}
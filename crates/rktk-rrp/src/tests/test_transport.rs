@@ -19,3 +19,13 @@ impl WriteTransport for TestWriter {
         self.0.write(buf).await
     }
 }
+
+/// Reader which fails as if the other side didn't respond in time.
+pub struct TimeoutReader;
+impl ReadTransport for TimeoutReader {
+    type Error = std::io::Error;
+
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Err(std::io::ErrorKind::TimedOut.into())
+    }
+}
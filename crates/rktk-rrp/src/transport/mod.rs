@@ -31,6 +31,34 @@ impl TryFrom<u8> for Indicator {
     }
 }
 
+/// Side of the keyboard which the request is addressed to.
+///
+/// On split keyboard, the client is always connected to the master side. Requests addressed to the
+/// slave side are forwarded by the master side through the split link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    #[default]
+    Master,
+    Slave,
+}
+
+/// If this bit of endpoint id is set, the request is addressed to [`Target::Slave`].
+pub const SLAVE_TARGET_FLAG: u8 = 0x80;
+
+impl Target {
+    pub fn from_endpoint_id(endpoint_id: u8) -> Self {
+        if endpoint_id & SLAVE_TARGET_FLAG != 0 { Self::Slave } else { Self::Master }
+    }
+
+    #[cfg(feature = "client")]
+    pub(crate) fn endpoint_flag(self) -> u8 {
+        match self {
+            Self::Master => 0,
+            Self::Slave => SLAVE_TARGET_FLAG,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RequestHeader {
     pub request_id: u8,
@@ -0,0 +1,557 @@
+//! Generates `Keymap` const from `keymap` key of json.
+
+use kmsm::keycode::{key::Key, media::Media, modifier::Modifier, mouse::Mouse, special::Special};
+use kmsm_rktk::RktkKeys;
+use strum::IntoEnumIterator as _;
+
+use crate::schema::{
+    constant::ConstantConfig,
+    keymap::{KeymapComboConfig, KeymapConfig, KeymapLayerConfig, KeymapTapDanceConfig},
+};
+
+/// Generates code which defines `KEYMAP_FROM_FILE`.
+///
+/// If the keymap is invalid, returns error messages with the location in json.
+pub fn generate(keymap: &KeymapConfig, constant: &ConstantConfig) -> Result<String, Vec<String>> {
+    let mut g = Generator {
+        constant,
+        keycodes: keycodes(),
+        tap_dance_count: keymap.tap_dance.len(),
+        errors: Vec::new(),
+    };
+
+    let layer_count = constant.key_manager.layer_count as usize;
+    if keymap.layers.len() > layer_count {
+        g.error(
+            "keymap.layers",
+            format!("{} layers are defined, but layer_count is {layer_count}", keymap.layers.len()),
+        );
+    }
+    let layers: Vec<_> = keymap
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, layer)| {
+            format!("keymap.layers[{i}] = {};", g.layer(&format!("keymap.layers[{i}]"), layer))
+        })
+        .collect();
+
+    let max_tap_dance = constant.key_manager.tap_dance_max_definitions;
+    if keymap.tap_dance.len() > max_tap_dance {
+        g.error(
+            "keymap.tap_dance",
+            format!(
+                "{} tap dances are defined, but tap_dance_max_definitions is {max_tap_dance}",
+                keymap.tap_dance.len()
+            ),
+        );
+    }
+    let tap_dance: Vec<_> = keymap
+        .tap_dance
+        .iter()
+        .enumerate()
+        .map(|(i, td)| {
+            format!(
+                "keymap.tap_dance[{i}] = Some({});",
+                g.tap_dance(&format!("keymap.tap_dance[{i}]"), td)
+            )
+        })
+        .collect();
+
+    let max_combo = constant.key_manager.combo_key_max_definitions;
+    if keymap.combo.len() > max_combo {
+        g.error(
+            "keymap.combo",
+            format!(
+                "{} combos are defined, but combo_key_max_definitions is {max_combo}",
+                keymap.combo.len()
+            ),
+        );
+    }
+    let combo: Vec<_> = keymap
+        .combo
+        .iter()
+        .enumerate()
+        .map(|(i, combo)| {
+            format!("keymap.combo[{i}] = Some({});", g.combo(&format!("keymap.combo[{i}]"), combo))
+        })
+        .collect();
+
+    if !g.errors.is_empty() {
+        return Err(g.errors);
+    }
+
+    let layers = layers.join("\n");
+    let tap_dance = tap_dance.join("\n");
+    let combo = combo.join("\n");
+    Ok(format!(
+        r#"
+        mod generate_keymap {{
+            #![allow(unused_imports)]
+            use kmsm::{{
+                keycode::{{
+                    KeyAction, KeyCode, key::Key, layer::LayerOp, media::Media, modifier::Modifier,
+                    mouse::Mouse, special::Special,
+                }},
+                keymap::{{ComboDefinition, Layer, TapDanceDefinition}},
+            }};
+            use super::keymap::Keymap;
+
+            #[doc = "Keymap generated from `keymap` key of json"]
+            pub const KEYMAP_FROM_FILE: Keymap = {{
+                let mut keymap = Keymap::const_default();
+                {layers}
+                {tap_dance}
+                {combo}
+                keymap
+            }};
+        }}
+
+        pub use generate_keymap::KEYMAP_FROM_FILE;
+    "#
+    ))
+}
+
+/// Keycode which can be written by name.
+struct NamedKeycode {
+    /// Name of the enum which defines the keycode. Keycode can also be written as `ty::name`.
+    ty: &'static str,
+    name: &'static str,
+    code: String,
+}
+
+/// Returns all keycodes which can be written by name.
+fn keycodes() -> Vec<NamedKeycode> {
+    let mut keycodes = Vec::new();
+    macro_rules! add {
+        ($ty:ident, $variant:ident) => {
+            keycodes.extend($ty::iter().map(|k| {
+                let name: &'static str = k.into();
+                NamedKeycode {
+                    ty: stringify!($ty),
+                    name,
+                    code: format!("KeyCode::{}({}::{name})", stringify!($variant), stringify!($ty)),
+                }
+            }));
+        };
+    }
+    add!(Key, Key);
+    add!(Modifier, Modifier);
+    add!(Mouse, Mouse);
+    add!(Media, Media);
+    add!(Special, Special);
+    keycodes.extend(RktkKeys::iter().map(|k| NamedKeycode {
+        ty: "RktkKeys",
+        name: k.into(),
+        code: format!("KeyCode::Custom1({})", k as u8),
+    }));
+    keycodes.push(NamedKeycode { ty: "KeyCode", name: "None", code: "KeyCode::None".to_string() });
+    keycodes
+}
+
+struct Generator<'a> {
+    constant: &'a ConstantConfig,
+    keycodes: Vec<NamedKeycode>,
+    /// Number of tap dances defined in `keymap.tap_dance`.
+    tap_dance_count: usize,
+    errors: Vec<String>,
+}
+
+impl Generator<'_> {
+    fn error(&mut self, path: &str, message: impl std::fmt::Display) {
+        self.errors.push(format!("Invalid keymap in rktk config: {path}: {message}"));
+    }
+
+    fn layer(&mut self, path: &str, layer: &KeymapLayerConfig) -> String {
+        let rows = self.constant.keyboard.rows as usize;
+        let cols = self.constant.keyboard.cols as usize;
+        if layer.keys.len() != rows {
+            self.error(
+                &format!("{path}.keys"),
+                format!("{} rows are defined, but keyboard has {rows} rows", layer.keys.len()),
+            );
+        }
+        let keys: Vec<_> = layer
+            .keys
+            .iter()
+            .enumerate()
+            .map(|(r, row)| {
+                if row.len() != cols {
+                    self.error(
+                        &format!("{path}.keys[{r}]"),
+                        format!("{} keys are defined, but keyboard has {cols} cols", row.len()),
+                    );
+                }
+                let row: Vec<_> = row
+                    .iter()
+                    .enumerate()
+                    .map(|(c, key)| self.action(&format!("{path}.keys[{r}][{c}]"), key))
+                    .collect();
+                format!("[{}]", row.join(", "))
+            })
+            .collect();
+
+        let encoder_count = self.constant.keyboard.encoder_count as usize;
+        if layer.encoders.len() > encoder_count {
+            self.error(
+                &format!("{path}.encoders"),
+                format!(
+                    "{} encoders are defined, but encoder_count is {encoder_count}",
+                    layer.encoders.len()
+                ),
+            );
+        }
+        let encoders: Vec<_> = (0..encoder_count)
+            .map(|i| match layer.encoders.get(i) {
+                Some(encoder) => format!(
+                    "({}, {})",
+                    self.optional_keycode(
+                        &format!("{path}.encoders[{i}].ccw"),
+                        encoder.ccw.as_deref()
+                    ),
+                    self.optional_keycode(
+                        &format!("{path}.encoders[{i}].cw"),
+                        encoder.cw.as_deref()
+                    )
+                ),
+                None => "(None, None)".to_string(),
+            })
+            .collect();
+
+        format!(
+            "Layer {{ keymap: [{}], encoder_keys: [{}], arrow_mouse: {} }}",
+            keys.join(", "),
+            encoders.join(", "),
+            layer.arrow_mouse
+        )
+    }
+
+    fn tap_dance(&mut self, path: &str, td: &KeymapTapDanceConfig) -> String {
+        let max_repeats = self.constant.key_manager.tap_dance_max_repeats;
+        let mut keys = |name: &str, keys: &[Option<String>]| {
+            if keys.len() > max_repeats {
+                self.error(
+                    &format!("{path}.{name}"),
+                    format!(
+                        "{} keys are defined, but tap_dance_max_repeats is {max_repeats}",
+                        keys.len()
+                    ),
+                );
+            }
+            let keys: Vec<_> = (0..max_repeats)
+                .map(|i| {
+                    let key = keys.get(i).and_then(|k| k.as_deref());
+                    self.optional_keycode(&format!("{path}.{name}[{i}]"), key)
+                })
+                .collect();
+            keys.join(", ")
+        };
+        let tap = keys("tap", &td.tap);
+        let hold = keys("hold", &td.hold);
+        format!("TapDanceDefinition {{ tap: [{tap}], hold: [{hold}] }}")
+    }
+
+    fn combo(&mut self, path: &str, combo: &KeymapComboConfig) -> String {
+        let max_sources = self.constant.key_manager.combo_key_max_sources;
+        if combo.src.len() > max_sources {
+            self.error(
+                &format!("{path}.src"),
+                format!(
+                    "{} keys are defined, but combo_key_max_sources is {max_sources}",
+                    combo.src.len()
+                ),
+            );
+        }
+        let src: Vec<_> = (0..max_sources)
+            .map(|i| {
+                let key = combo.src.get(i).map(|k| k.as_str());
+                self.optional_keycode(&format!("{path}.src[{i}]"), key)
+            })
+            .collect();
+        let dst = self.keycode(&format!("{path}.dst"), &combo.dst);
+        format!("ComboDefinition {{ src: [{}], dst: {dst} }}", src.join(", "))
+    }
+
+    fn action(&mut self, path: &str, s: &str) -> String {
+        let s = s.trim();
+        if !s.is_empty() && s.chars().all(|c| c == '_') {
+            return "KeyAction::Inherit".to_string();
+        }
+        if let Some(args) = call(s, "TH") {
+            let args = split_top_level(args, ',');
+            let [tap, hold] = args.as_slice() else {
+                self.error(path, format!("`{s}`: TH requires 2 keycodes"));
+                return "KeyAction::Inherit".to_string();
+            };
+            return format!(
+                "KeyAction::TapHold({}, {})",
+                self.keycode(path, tap),
+                self.keycode(path, hold)
+            );
+        }
+        if let Some(arg) = call(s, "OS") {
+            return format!("KeyAction::OneShot({})", self.keycode(path, arg));
+        }
+        if let Some(arg) = call(s, "TD") {
+            let count = self.tap_dance_count;
+            let limit = format!("{count} tap dances are defined in keymap.tap_dance");
+            return match self.index(path, arg, count, &limit) {
+                Some(id) => format!("KeyAction::TapDance({id})"),
+                None => "KeyAction::Inherit".to_string(),
+            };
+        }
+        match split_top_level(s, '+').as_slice() {
+            [key] => format!("KeyAction::Normal({})", self.keycode(path, key)),
+            [k1, k2] => format!(
+                "KeyAction::Normal2({}, {})",
+                self.keycode(path, k1),
+                self.keycode(path, k2)
+            ),
+            _ => {
+                self.error(path, format!("`{s}`: up to 2 keycodes can be combined with `+`"));
+                "KeyAction::Inherit".to_string()
+            }
+        }
+    }
+
+    fn optional_keycode(&mut self, path: &str, s: Option<&str>) -> String {
+        match s {
+            Some(s) => format!("Some({})", self.keycode(path, s)),
+            None => "None".to_string(),
+        }
+    }
+
+    fn keycode(&mut self, path: &str, s: &str) -> String {
+        let s = s.trim();
+        let layer_count = self.constant.key_manager.layer_count as usize;
+        for (func, op) in [("MO", "Momentary"), ("TG", "Toggle")] {
+            if let Some(arg) = call(s, func) {
+                let limit = format!("layer_count is {layer_count}");
+                return match self.index(path, arg, layer_count, &limit) {
+                    Some(l) => format!("KeyCode::Layer(LayerOp::{op}({l}))"),
+                    None => "KeyCode::None".to_string(),
+                };
+            }
+        }
+        for custom in ["Custom1", "Custom2", "Custom3"] {
+            if let Some(arg) = call(s, custom) {
+                return match arg.trim().parse::<u8>() {
+                    Ok(id) => format!("KeyCode::{custom}({id})"),
+                    Err(_) => {
+                        self.error(path, format!("`{s}`: invalid id"));
+                        "KeyCode::None".to_string()
+                    }
+                };
+            }
+        }
+
+        let (ty, name) = match s.split_once("::") {
+            Some((ty, name)) => (Some(ty.trim()), name.trim()),
+            None => (None, s),
+        };
+        let found: Vec<_> = self
+            .keycodes
+            .iter()
+            .filter(|k| k.name == name && ty.is_none_or(|ty| k.ty == ty))
+            .collect();
+        match found.as_slice() {
+            [keycode] => return keycode.code.clone(),
+            [] => {}
+            _ => {
+                let candidates: Vec<_> =
+                    found.iter().map(|k| format!("`{}::{}`", k.ty, k.name)).collect();
+                let message = format!("`{s}` is ambiguous. Use one of {}", candidates.join(", "));
+                self.error(path, message);
+                return "KeyCode::None".to_string();
+            }
+        }
+
+        let message = match suggest(name, self.keycodes.iter().map(|k| k.name)) {
+            Some(name) => format!("unknown keycode `{s}`. Did you mean `{name}`?"),
+            None => format!("unknown keycode `{s}`"),
+        };
+        self.error(path, message);
+        "KeyCode::None".to_string()
+    }
+
+    /// Parses `s` as an index which is less than `max`. `limit` describes `max` in error message.
+    fn index(&mut self, path: &str, s: &str, max: usize, limit: &str) -> Option<usize> {
+        match s.trim().parse::<usize>() {
+            Ok(i) if i < max => Some(i),
+            Ok(i) => {
+                self.error(path, format!("{i} is out of range. {limit}"));
+                None
+            }
+            Err(_) => {
+                self.error(path, format!("`{s}` is not a number"));
+                None
+            }
+        }
+    }
+}
+
+/// Returns arguments if `s` is a call of `func` (ex: `MO(1)`).
+fn call<'a>(s: &'a str, func: &str) -> Option<&'a str> {
+    s.strip_prefix(func)?.trim_start().strip_prefix('(')?.strip_suffix(')')
+}
+
+/// Splits `s` by `sep` which is not in parentheses.
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == sep && depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+/// Finds the name which is similar to `s`.
+fn suggest<'a>(s: &str, names: impl Iterator<Item = &'a str> + Clone) -> Option<&'a str> {
+    if let Some(name) = names.clone().find(|name| name.eq_ignore_ascii_case(s)) {
+        return Some(name);
+    }
+    let s = s.to_ascii_lowercase();
+    names
+        .map(|name| (edit_distance(&s, &name.to_ascii_lowercase()), name))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, name)| name)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            cur.push((prev[j] + (ca != *cb) as usize).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant() -> ConstantConfig {
+        serde_json::from_str(
+            r#"{ "keyboard": { "cols": 3, "rows": 1 }, "key_manager": { "layer_count": 2 } }"#,
+        )
+        .unwrap()
+    }
+
+    fn generate_json(keymap: &str) -> Result<String, Vec<String>> {
+        generate(&serde_json::from_str(keymap).unwrap(), &constant())
+    }
+
+    #[test]
+    fn generate_valid() {
+        let code = generate_json(
+            r#"{
+                "layers": [{ "keys": [["A", "TH(Enter, MO(1))", "TD(0)"]] }],
+                "tap_dance": [{ "tap": ["Q", "Key::W"] }],
+                "combo": [{ "src": ["A", "B"], "dst": "LCtrl" }]
+            }"#,
+        )
+        .unwrap();
+        assert!(code.contains("KeyAction::Normal(KeyCode::Key(Key::A))"));
+        assert!(code.contains(
+            "KeyAction::TapHold(KeyCode::Key(Key::Enter), KeyCode::Layer(LayerOp::Momentary(1)))"
+        ));
+        assert!(code.contains("KeyAction::TapDance(0)"));
+        assert!(code.contains("Some(KeyCode::Key(Key::W))"));
+        assert!(code.contains("dst: KeyCode::Modifier(Modifier::LCtrl)"));
+    }
+
+    #[test]
+    fn generate_unknown_name() {
+        let errors =
+            generate_json(r#"{ "layers": [{ "keys": [["Escap", "Media::A", "Xyzzy"]] }] }"#)
+                .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][0]: unknown keycode `Escap`. Did you mean `Escape`?",
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][1]: unknown keycode `Media::A`. Did you mean `A`?",
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][2]: unknown keycode `Xyzzy`",
+            ]
+        );
+    }
+
+    #[test]
+    fn generate_out_of_range() {
+        let errors = generate_json(
+            r#"{
+                "layers": [{ "keys": [["MO(2)", "TD(1)", "TG(x)"]] }],
+                "tap_dance": [{ "tap": ["Q"] }]
+            }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][0]: 2 is out of range. layer_count is 2",
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][1]: 1 is out of range. 1 tap dances are defined in keymap.tap_dance",
+                "Invalid keymap in rktk config: keymap.layers[0].keys[0][2]: `x` is not a number",
+            ]
+        );
+    }
+
+    #[test]
+    fn ambiguous_name() {
+        let constant = constant();
+        let keycode = |ty, code: &str| NamedKeycode { ty, name: "X", code: code.to_string() };
+        let mut g = Generator {
+            constant: &constant,
+            keycodes: vec![keycode("Key", "KeyCode::Key(Key::X)"), keycode("Media", "media")],
+            tap_dance_count: 0,
+            errors: Vec::new(),
+        };
+        assert_eq!(g.keycode("path", "X"), "KeyCode::None");
+        assert_eq!(
+            g.errors,
+            [
+                "Invalid keymap in rktk config: path: `X` is ambiguous. Use one of `Key::X`, `Media::X`"
+            ]
+        );
+        assert_eq!(g.keycode("path", "Media::X"), "media");
+        assert_eq!(g.errors.len(), 1);
+    }
+
+    #[test]
+    fn split_top_level_nested() {
+        assert_eq!(split_top_level("Enter, MO(1)", ','), ["Enter", "MO(1)"]);
+        assert_eq!(split_top_level("TH(A, B) , C", ','), ["TH(A, B)", "C"]);
+        assert_eq!(split_top_level("LCtrl + C", '+'), ["LCtrl", "C"]);
+        assert_eq!(split_top_level("A", '+'), ["A"]);
+    }
+
+    #[test]
+    fn suggest_similar() {
+        let names = ["Escape", "Enter", "LCtrl"];
+        assert_eq!(suggest("escape", names.into_iter()), Some("Escape"));
+        assert_eq!(suggest("Entr", names.into_iter()), Some("Enter"));
+        assert_eq!(suggest("lctl", names.into_iter()), Some("LCtrl"));
+        assert_eq!(suggest("Xyzzy", names.into_iter()), None);
+    }
+
+    #[test]
+    fn edit_distance_values() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+    }
+}
@@ -3,6 +3,7 @@ use std::{env, fs, path::Path};
 use const_gen::*;
 use schemars::schema_for;
 
+mod keymap;
 mod schema;
 
 fn main() {
@@ -90,6 +91,13 @@ pub fn generate(value: &str) -> Result<String, Box<dyn std::error::Error>> {
         pub DYNAMIC_CONFIG_FROM_FILE = config.dynamic
     );
 
+    let code_keymap = match &config.keymap {
+        Some(keymap) => keymap::generate(keymap, &config.constant).unwrap_or_else(|errors| {
+            errors.iter().map(|e| format!("compile_error!({e:?});")).collect::<Vec<_>>().join("\n")
+        }),
+        None => String::new(),
+    };
+
     let code = format!(
         r#"
         /// Configuration schema
@@ -108,6 +116,8 @@ pub fn generate(value: &str) -> Result<String, Box<dyn std::error::Error>> {
         }}
 
         pub use generate_config::{{CONST_CONFIG, DYNAMIC_CONFIG_FROM_FILE}};
+
+        {code_keymap}
     "#
     );
 
@@ -75,6 +75,15 @@ pub struct KeymanagerConstantConfig {
     #[default(4)]
     pub oneshot_state_size: usize,
 
+    /// Number of keymap profiles. Each profile has its own keymap and config in the storage.
+    #[default(1)]
+    pub profile_count: u8,
+
+    /// Store only keys which differ from the default keymap instead of whole layers.
+    ///
+    /// Reduces the size of the stored keymap, especially for keyboards with large matrix.
+    pub sparse_keymap: bool,
+
     #[default(2)]
     pub tap_dance_max_definitions: usize,
 
@@ -33,6 +33,21 @@ struct MouseConfig {
 
     #[default(-12)]
     pub scroll_divider_y: i8,
+
+    /// Mode of each pointing device. Pointing device 0 is the one on the left half and 1 is the
+    /// one on the right half.
+    #[default([PointerMode::Move, PointerMode::Move])]
+    pub pointer_modes: [PointerMode; 2],
+}
+
+#[macro_rules_attribute::apply(crate::schema::common_derive)]
+#[derive(SmartDefault)]
+enum PointerMode {
+    /// Moves the cursor. Scrolls while `MoScrl` is pressed.
+    #[default]
+    Move,
+    /// Always scrolls.
+    Scroll,
 }
 
 #[macro_rules_attribute::apply(crate::schema::common_derive)]
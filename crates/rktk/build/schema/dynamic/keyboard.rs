@@ -51,6 +51,11 @@ json layout format."#]
  Also, there is no need to set it in the case of a non-split keyboard, as it is not used."#]
     #[serde(default)]
     pub split_right_shift: Option<u8>,
+
+    /// Number added to the encoder ids of the right hand in a split keyboard, like
+    /// `split_right_shift` for cols. With the default `0`, both hands use the ids as is.
+    #[serde(default)]
+    pub split_right_encoder_shift: u8,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
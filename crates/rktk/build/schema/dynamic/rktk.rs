@@ -5,6 +5,7 @@
 pub struct RktkConfig {
     pub rgb: RktkRgbConfig,
     pub role_detection: RktkRoleDetectionConfig,
+    pub battery: RktkBatteryConfig,
 
     /// Threshold for double tap (ms).
     #[default(500)]
@@ -41,6 +42,26 @@ pub struct RktkConfig {
     /// This setting specifies that interval. (ms)
     #[default(10)]
     pub state_update_interval: u64,
+
+    /// Time(ms) to wait after the last config change before it is written to the storage.
+    ///
+    /// Changes made during this period are coalesced into one write, which reduces flash wear.
+    #[default(3000)]
+    pub storage_commit_delay: u64,
+
+    /// Time(ms) without any data from the other side after which the split link is considered dead.
+    ///
+    /// While the link is dead, the split driver is re-initialized every this period and the master
+    /// releases all keys pressed on the slave side.
+    #[default(3000)]
+    pub split_link_timeout: u64,
+
+    /// Policy of selecting output when both USB and BLE are available.
+    ///
+    /// - `Manual`: USB is used at boot. Output is switched only by `OutputUsb` and `OutputBle` keys.
+    /// - `Auto`: USB is used while it is connected to the host, and BLE is used otherwise.
+    ///   `OutputUsb` and `OutputBle` keys override it until USB is plugged or unplugged.
+    pub output_policy: RktkOutputPolicy,
 }
 
 /// RKTK RGB config
@@ -63,6 +84,22 @@ pub struct RktkRgbConfig {
     pub default_brightness: f32,
 }
 
+/// RKTK battery config
+#[macro_rules_attribute::apply(crate::schema::common_derive)]
+#[derive(smart_default::SmartDefault)]
+#[serde(default)]
+pub struct RktkBatteryConfig {
+    /// Time(ms) to wait for the next battery measurement
+    #[default(60000)]
+    pub update_interval: u64,
+
+    /// Battery level (%) below which the low battery warning is shown on the display and RGB.
+    ///
+    /// Set to 0 to disable the warning.
+    #[default(10)]
+    pub low_level: u8,
+}
+
 /// RKTK role detection config
 #[macro_rules_attribute::apply(crate::schema::common_derive)]
 #[derive(smart_default::SmartDefault)]
@@ -95,3 +132,11 @@ pub enum RktkRoleDetectionTimeoutBehavior {
     ForceMaster,
     ForceSlave,
 }
+
+#[macro_rules_attribute::apply(crate::schema::common_derive)]
+#[derive(smart_default::SmartDefault)]
+pub enum RktkOutputPolicy {
+    #[default]
+    Manual,
+    Auto,
+}
@@ -0,0 +1,78 @@
+//! Keymap defined in json.
+//!
+//! Unlike other configs, this is not embedded as is. `Keymap` const is generated from this by the
+//! build script.
+
+#[doc = r#"
+Keymap of the keyboard.
+
+If this is set, `rktk::config::KEYMAP_FROM_FILE` is generated from this.
+
+Each key is written as a string:
+
+| Syntax                   | Key action                                  |
+| ------------------------ | ------------------------------------------- |
+| `_`                      | Inherit from lower layer                    |
+| `<keycode>`              | Normal key                                  |
+| `<keycode> + <keycode>`  | Two keys which are sent together            |
+| `TH(<tap>, <hold>)`      | Tap-hold key                                |
+| `OS(<keycode>)`          | One-shot key                                |
+| `TD(<id>)`               | Tap dance defined in `tap_dance`            |
+
+Keycode is one of the following:
+- Name of key, modifier, mouse button, media key or special key of kmsm. (ex: `A`, `LShft`, `MLeft`, `VolumeIncrement`, `MoScrl`)
+- Name of rktk specific key. (ex: `Bootloader`, `OutputBle`)
+- `MO(<layer>)`, `TG(<layer>)`: Momentary and toggle layer.
+- `Custom1(<id>)`, `Custom2(<id>)`, `Custom3(<id>)`: Custom keys.
+- `None`: Key which does nothing."#]
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapConfig {
+    /// Layers from the lowest one. Missing layers are filled with `_`.
+    pub layers: Vec<KeymapLayerConfig>,
+    #[serde(default)]
+    pub tap_dance: Vec<KeymapTapDanceConfig>,
+    #[serde(default)]
+    pub combo: Vec<KeymapComboConfig>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapLayerConfig {
+    /// Keys of each row. The size must be the same as `constant.keyboard`.
+    pub keys: Vec<Vec<String>>,
+    /// Keycodes of each encoder.
+    #[serde(default)]
+    pub encoders: Vec<KeymapEncoderConfig>,
+    #[serde(default)]
+    pub arrow_mouse: bool,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapEncoderConfig {
+    /// Keycode for counter clockwise rotation. If not set, inherits from lower layer.
+    pub ccw: Option<String>,
+    /// Keycode for clockwise rotation. If not set, inherits from lower layer.
+    pub cw: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapTapDanceConfig {
+    /// Keycode sent when the key is tapped n+1 times. `null` sends nothing.
+    #[serde(default)]
+    pub tap: Vec<Option<String>>,
+    /// Keycode held when the key is held after tapped n times. `null` holds nothing.
+    #[serde(default)]
+    pub hold: Vec<Option<String>>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapComboConfig {
+    /// Keycodes which trigger the combo when pressed together.
+    pub src: Vec<String>,
+    /// Keycode sent by the combo.
+    pub dst: String,
+}
@@ -4,6 +4,7 @@ use macro_rules_attribute::attribute_alias;
 
 pub mod constant;
 pub mod dynamic;
+pub mod keymap;
 
 attribute_alias! {
     #[apply(common_derive)] =
@@ -17,4 +18,5 @@ attribute_alias! {
 pub struct Config {
     pub constant: constant::ConstantConfig,
     pub dynamic: dynamic::DynamicConfig,
+    pub keymap: Option<keymap::KeymapConfig>,
 }
@@ -65,6 +65,12 @@
 //!
 //! For each configuration, see the [`schema::ConstantConfig`] and [`schema::DynamicConfig`] struct.
 //!
+//! ## Keymap
+//! Keymap can also be written in the `keymap` key of the json file. If it is set, `KEYMAP_FROM_FILE` const is
+//! generated from it and can be passed to [`crate::task::start`] instead of the keymap defined in the code.
+//! Invalid keycodes and sizes which don't match const config are reported as compile errors.
+//! For the syntax, see `schema.json` or the keymap page of the rktk documentation.
+//!
 //! # Storage
 //!
 //! In addition to the above programmatic config, config may also be loaded from storage.
@@ -126,4 +132,13 @@ impl Hand {
             Hand::Right => Hand::Left,
         }
     }
+
+    /// Id of the pointing device on this hand, which selects its mode in `pointer_modes` of the
+    /// mouse config.
+    pub fn pointer_id(&self) -> u8 {
+        match self {
+            Hand::Left => 0,
+            Hand::Right => 1,
+        }
+    }
 }
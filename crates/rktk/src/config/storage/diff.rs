@@ -0,0 +1,95 @@
+//! Sparse layer encoding.
+//!
+//! A layer is encoded as a sequence of entries which differ from the compiled-in default layer,
+//! so the stored size depends on the number of changed keys instead of the size of the matrix.
+
+use kmsm::keycode::{KeyAction, KeyCode};
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{CONST_CONFIG, keymap::Layer};
+
+#[derive(Debug, Serialize, Deserialize, MaxSize)]
+enum DiffEntry {
+    Key { row: u8, col: u8, action: KeyAction },
+    Encoder { index: u8, keys: (Option<KeyCode>, Option<KeyCode>) },
+    ArrowMouse(bool),
+}
+
+/// Max size of the stored diff.
+///
+/// Diff which doesn't fit in the serialized [`Layer`] is not stored because the whole layer is
+/// smaller, so this never exceeds the layer size.
+pub(super) const MAX_DIFF_SIZE: usize = {
+    // The case where every entry differs from the default.
+    let all_entries = DiffEntry::POSTCARD_MAX_SIZE
+        * (CONST_CONFIG.keyboard.rows as usize * CONST_CONFIG.keyboard.cols as usize
+            + CONST_CONFIG.keyboard.encoder_count as usize
+            + 1);
+    if all_entries < Layer::POSTCARD_MAX_SIZE { all_entries } else { Layer::POSTCARD_MAX_SIZE }
+};
+
+fn entries<'a>(layer: &'a Layer, default: &'a Layer) -> impl Iterator<Item = DiffEntry> + 'a {
+    let keys = layer.keymap.iter().zip(default.keymap.iter()).enumerate().flat_map(
+        |(row, (keys, default_keys))| {
+            keys.iter().zip(default_keys.iter()).enumerate().filter(|(_, (k, d))| k != d).map(
+                move |(col, (action, _))| DiffEntry::Key {
+                    row: row as u8,
+                    col: col as u8,
+                    action: *action,
+                },
+            )
+        },
+    );
+    let encoders = layer
+        .encoder_keys
+        .iter()
+        .zip(default.encoder_keys.iter())
+        .enumerate()
+        .filter(|(_, (k, d))| k != d)
+        .map(|(index, (keys, _))| DiffEntry::Encoder { index: index as u8, keys: *keys });
+    let arrow_mouse = (layer.arrow_mouse != default.arrow_mouse)
+        .then_some(DiffEntry::ArrowMouse(layer.arrow_mouse));
+
+    keys.chain(encoders).chain(arrow_mouse)
+}
+
+/// Encodes entries of `layer` which differ from `default` into `buf` and returns the length.
+///
+/// Fails with [`postcard::Error::SerializeBufferFull`] if the diff doesn't fit in `buf`.
+pub(super) fn encode(
+    layer: &Layer,
+    default: &Layer,
+    buf: &mut [u8],
+) -> Result<usize, postcard::Error> {
+    let mut len = 0;
+    for entry in entries(layer, default) {
+        len += postcard::to_slice(&entry, &mut buf[len..])?.len();
+    }
+    Ok(len)
+}
+
+/// Applies encoded diff to `layer`, which should be the default layer.
+pub(super) fn apply(mut data: &[u8], layer: &mut Layer) -> Result<(), postcard::Error> {
+    while !data.is_empty() {
+        let (entry, rest) = postcard::take_from_bytes::<DiffEntry>(data)?;
+        match entry {
+            DiffEntry::Key { row, col, action } => {
+                *layer
+                    .keymap
+                    .get_mut(row as usize)
+                    .and_then(|keys| keys.get_mut(col as usize))
+                    .ok_or(postcard::Error::DeserializeBadEncoding)? = action;
+            }
+            DiffEntry::Encoder { index, keys } => {
+                *layer
+                    .encoder_keys
+                    .get_mut(index as usize)
+                    .ok_or(postcard::Error::DeserializeBadEncoding)? = keys;
+            }
+            DiffEntry::ArrowMouse(arrow_mouse) => layer.arrow_mouse = arrow_mouse,
+        }
+        data = rest;
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+//! Migration of values written by older firmware.
+//!
+//! When the serialized layout of a stored value changes, bump its [`ConfigKey::schema`] and add a
+//! [`Migration`] from the previous tag to [`MIGRATIONS`]. Migrations are usually implemented by
+//! decoding the payload with a copy of the old type and encoding the converted value.
+//!
+//! At boot, [`StorageConfigManager::migrate`] upgrades every stored record by applying migrations
+//! one by one. Records which cannot be upgraded are left untouched and the compile-time config is
+//! used for them instead.
+
+use kmsm::interface::state::config::{
+    KeyResolverConfig, MAX_POINTERS, MouseConfig, PointerMode, StateConfig,
+};
+use rktk_log::helper::Debug2Format;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::CONST_CONFIG, drivers::interface::storage::StorageDriver};
+
+use super::{
+    ConfigKey, ConfigWriteError, MAX_PAYLOAD_SIZE, STORAGE_VERSION, StorageConfigManager,
+    record::legacy_key,
+};
+
+/// Converts payload of a key from schema `from` to `from + 1`.
+pub struct Migration {
+    pub key: ConfigKey,
+    pub from: u16,
+    /// Reads old payload and writes new payload. Returns length of the new payload.
+    pub migrate: fn(old: &[u8], new: &mut [u8]) -> Result<usize, MigrationError>,
+}
+
+/// Migrations applied at boot.
+pub static MIGRATIONS: &[Migration] =
+    &[Migration { key: ConfigKey::StateConfig, from: 1, migrate: state_config_v1_to_v2 }];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationError {
+    /// Value was written by newer firmware.
+    Newer { found: u16 },
+    /// No migration is registered from this schema tag.
+    Missing { from: u16 },
+    /// Payload couldn't be converted.
+    Invalid,
+}
+
+/// Layout of [`StateConfig`] in schema 1, before `pointer_modes` was added.
+#[derive(Serialize, Deserialize)]
+pub(super) struct StateConfigV1 {
+    pub mouse: MouseConfigV1,
+    pub key_resolver: KeyResolverConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct MouseConfigV1 {
+    pub auto_mouse_layer: u8,
+    pub auto_mouse_duration: u32,
+    pub auto_mouse_threshold: u8,
+    pub scroll_divider_x: i8,
+    pub scroll_divider_y: i8,
+}
+
+/// All pointing devices move the cursor, which was the only behavior in schema 1.
+pub(super) fn state_config_v1_to_v2(old: &[u8], new: &mut [u8]) -> Result<usize, MigrationError> {
+    let old: StateConfigV1 = postcard::from_bytes(old).map_err(|_| MigrationError::Invalid)?;
+    let config = StateConfig {
+        mouse: MouseConfig {
+            auto_mouse_layer: old.mouse.auto_mouse_layer,
+            auto_mouse_duration: old.mouse.auto_mouse_duration,
+            auto_mouse_threshold: old.mouse.auto_mouse_threshold,
+            scroll_divider_x: old.mouse.scroll_divider_x,
+            scroll_divider_y: old.mouse.scroll_divider_y,
+            pointer_modes: [PointerMode::Move; MAX_POINTERS],
+        },
+        key_resolver: old.key_resolver,
+    };
+    postcard::to_slice(&config, new).map(|s| s.len()).map_err(|_| MigrationError::Invalid)
+}
+
+/// Upgrades payload `buf[..len]` of `key` from schema `from` to `to` in place.
+///
+/// `tmp` is used as a scratch buffer and must be as large as `buf`. Returns the new length.
+pub fn upgrade(
+    migrations: &[Migration],
+    key: ConfigKey,
+    from: u16,
+    to: u16,
+    buf: &mut [u8],
+    mut len: usize,
+    tmp: &mut [u8],
+) -> Result<usize, MigrationError> {
+    if from > to {
+        return Err(MigrationError::Newer { found: from });
+    }
+
+    for tag in from..to {
+        let migration = migrations
+            .iter()
+            .find(|m| m.key == key && m.from == tag)
+            .ok_or(MigrationError::Missing { from: tag })?;
+        len = (migration.migrate)(&buf[..len], tmp)?;
+        buf[..len].copy_from_slice(&tmp[..len]);
+    }
+
+    Ok(len)
+}
+
+/// All records which may exist in the storage, as `(key, profile, index)`.
+fn records() -> impl Iterator<Item = (ConfigKey, u8, u8)> {
+    let km = &CONST_CONFIG.key_manager;
+    [
+        (ConfigKey::Calibration, 0, 0),
+        (ConfigKey::ActiveProfile, 0, 0),
+        (ConfigKey::BleProfile, 0, 0),
+    ]
+    .into_iter()
+    .chain((0..km.profile_count).flat_map(|profile| {
+        core::iter::once((ConfigKey::StateConfig, profile, 0)).chain((0..km.layer_count).flat_map(
+            move |layer| {
+                [(ConfigKey::StateKeymap, profile, layer), (ConfigKey::KeymapDiff, profile, layer)]
+            },
+        ))
+    }))
+}
+
+impl<S: StorageDriver> StorageConfigManager<S> {
+    /// Upgrades storage of `version` to the current layout and schema.
+    ///
+    /// Returns the number of records which couldn't be upgraded. These are kept as is, so reading
+    /// them fails with [`SchemaMismatch`](super::ConfigReadError::SchemaMismatch).
+    pub async fn migrate(
+        &self,
+        version: u16,
+        migrations: &[Migration],
+    ) -> Result<usize, ConfigWriteError<S::Error>> {
+        if version < 2 {
+            self.convert_legacy_records().await?;
+        }
+
+        let mut failed = 0;
+        let mut buf = [0; MAX_PAYLOAD_SIZE];
+        let mut tmp = [0; MAX_PAYLOAD_SIZE];
+        for (key, profile, index) in records() {
+            let header = match self.read_record_header(key, profile, index).await {
+                Ok(header) if header.tag == key.schema() => continue,
+                Ok(header) => header,
+                // Not stored
+                Err(_) => continue,
+            };
+
+            let res = match self.read_record(key, profile, index, &mut buf).await {
+                Ok(_) => upgrade(
+                    migrations,
+                    key,
+                    header.tag,
+                    key.schema(),
+                    &mut buf,
+                    header.len,
+                    &mut tmp,
+                ),
+                Err(_) => Err(MigrationError::Invalid),
+            };
+            match res {
+                Ok(len) => {
+                    rktk_log::info!(
+                        "Migrated record {}/{}/{} from schema {}",
+                        key as u8,
+                        profile,
+                        index,
+                        header.tag
+                    );
+                    self.write_record(key, profile, index, key.schema(), &buf[..len]).await?;
+                }
+                Err(_e) => {
+                    rktk_log::warn!(
+                        "Failed to migrate record {}/{}/{}: {:?}",
+                        key as u8,
+                        profile,
+                        index,
+                        Debug2Format(&_e)
+                    );
+                    failed += 1;
+                }
+            }
+        }
+
+        if version != STORAGE_VERSION {
+            self.write_version(STORAGE_VERSION).await?;
+        }
+
+        Ok(failed)
+    }
+
+    /// Rewrites values of storage version 1 as records.
+    ///
+    /// Version 1 stored raw postcard bytes padded to the max size of the type, which is the first
+    /// schema of each key. It had no profiles, so values are converted into profile 0. Converted
+    /// values are removed to free the space.
+    async fn convert_legacy_records(&self) -> Result<(), ConfigWriteError<S::Error>> {
+        let mut buf = [0; MAX_PAYLOAD_SIZE];
+        for (key, _, index) in records().filter(|(_, profile, _)| *profile == 0) {
+            // These keys didn't exist in version 1.
+            if matches!(
+                key,
+                ConfigKey::Version
+                    | ConfigKey::ActiveProfile
+                    | ConfigKey::BleProfile
+                    | ConfigKey::KeymapDiff
+            ) {
+                continue;
+            }
+            let legacy_key = legacy_key(key, index);
+            if let Some(len) = self.storage.read(legacy_key, &mut buf).await? {
+                self.write_record(key, 0, index, 1, &buf[..len]).await?;
+                self.storage.remove(legacy_key).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,124 @@
-use crate::drivers::interface::storage::StorageDriver;
+//! Persistent config storage.
+//!
+//! Each value is stored as a record which consists of a header and payload chunks.
+//! The header has a schema tag of the payload, so data written by older firmware can be detected
+//! and upgraded by [`migration`] at boot instead of being decoded as garbage.
+//!
+//! Keymap and config changed at runtime should be staged with `stage_*` methods rather than
+//! written directly. They are written together later to reduce flash wear. See [`pending`].
 
+use kmsm::interface::state::config::StateConfig;
+use portable_atomic::{AtomicU8, Ordering};
+use postcard::experimental::max_size::MaxSize as _;
+
+use crate::{
+    config::{
+        CONST_CONFIG,
+        keymap::{Keymap, Layer},
+    },
+    drivers::interface::storage::StorageDriver,
+    utils::{Mutex, Signal},
+};
+
+mod diff;
+pub mod migration;
+mod pending;
 mod read;
+mod record;
 mod write;
 
+#[cfg(test)]
+mod tests;
+
+pub use read::ConfigReadError;
+pub use write::ConfigWriteError;
+
+/// Version of the data layout in the storage.
+///
+/// - 1: Values are stored as raw postcard bytes without schema tags.
+/// - 2: Values are stored as tagged records.
+pub const STORAGE_VERSION: u16 = 2;
+
+/// Max payload size of all keys. Used as the buffer size for migration.
+pub(crate) const MAX_PAYLOAD_SIZE: usize = {
+    let mut size = StateConfig::POSTCARD_MAX_SIZE;
+    if Layer::POSTCARD_MAX_SIZE > size {
+        size = Layer::POSTCARD_MAX_SIZE;
+    }
+    if diff::MAX_DIFF_SIZE > size {
+        size = diff::MAX_DIFF_SIZE;
+    }
+    if CONST_CONFIG.buffer.calibration > size {
+        size = CONST_CONFIG.buffer.calibration;
+    }
+    size
+};
+
 pub struct StorageConfigManager<S: StorageDriver> {
     pub storage: S,
+    /// Compiled-in keymap. Sparse keymap is stored as the diff against this.
+    default_keymap: &'static Keymap,
+    active_profile: AtomicU8,
+    pending: Mutex<pending::PendingWrites>,
+    /// Signaled when a value is staged.
+    staged: Signal<()>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfigKey {
     Version = 0,
     StateConfig = 1,
     StateKeymap = 2,
     Calibration = 3,
+    ActiveProfile = 4,
+    /// Keys which differ from the default keymap. See [`diff`].
+    KeymapDiff = 5,
+    /// Active host profile of the wireless reporter.
+    BleProfile = 6,
+}
+
+impl ConfigKey {
+    /// Current schema tag of the value stored in this key.
+    ///
+    /// Bump this when the serialized layout of the value changes, and register a migration from
+    /// the previous tag in [`migration::MIGRATIONS`].
+    pub const fn schema(self) -> u16 {
+        match self {
+            ConfigKey::Version => 1,
+            ConfigKey::StateConfig => 2,
+            ConfigKey::StateKeymap => 1,
+            ConfigKey::Calibration => 1,
+            ConfigKey::ActiveProfile => 1,
+            ConfigKey::KeymapDiff => 1,
+            ConfigKey::BleProfile => 1,
+        }
+    }
 }
 
 impl<S: StorageDriver> StorageConfigManager<S> {
-    pub fn new(storage: S) -> Self {
-        Self { storage }
+    pub fn new(storage: S, default_keymap: &'static Keymap) -> Self {
+        Self {
+            storage,
+            default_keymap,
+            active_profile: AtomicU8::new(0),
+            pending: Mutex::new(pending::PendingWrites::new()),
+            staged: Signal::new(),
+        }
+    }
+
+    /// Profile whose keymap and config are currently used.
+    pub fn active_profile(&self) -> u8 {
+        self.active_profile.load(Ordering::Relaxed)
+    }
+
+    /// Loads the active profile from storage. Falls back to profile 0 if it is not stored or out
+    /// of range.
+    pub async fn load_active_profile(&self) -> u8 {
+        let profile = match self.read_active_profile().await {
+            Ok(p) if p < CONST_CONFIG.key_manager.profile_count => p,
+            _ => 0,
+        };
+        self.active_profile.store(profile, Ordering::Relaxed);
+        profile
     }
 }
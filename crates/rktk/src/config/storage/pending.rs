@@ -0,0 +1,127 @@
+//! Write-behind cache of config values.
+//!
+//! Changes from the configurator come in bursts (ex: every key of a keymap), and writing each of
+//! them wears the flash. Changed values are staged here instead and written together after no
+//! change is made for a while, or when a commit is requested explicitly.
+
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Timer};
+use kmsm::interface::state::config::StateConfig;
+use rktk_log::helper::Debug2Format;
+
+use crate::{
+    config::{CONST_CONFIG, keymap::Layer},
+    drivers::interface::storage::StorageDriver,
+};
+
+use super::{ConfigWriteError, StorageConfigManager};
+
+/// Values which are changed but not written to the storage yet.
+///
+/// Only values of one profile are held. Staging a value of another profile commits them first.
+pub(super) struct PendingWrites {
+    profile: u8,
+    state_config: Option<StateConfig>,
+    layers: [Option<Layer>; LAYER_COUNT],
+}
+
+const LAYER_COUNT: usize = CONST_CONFIG.key_manager.layer_count as usize;
+
+impl PendingWrites {
+    pub const fn new() -> Self {
+        Self { profile: 0, state_config: None, layers: [const { None }; LAYER_COUNT] }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.state_config.is_none() && self.layers.iter().all(Option::is_none)
+    }
+
+    pub fn state_config(&self, profile: u8) -> Option<&StateConfig> {
+        self.state_config.as_ref().filter(|_| self.profile == profile)
+    }
+
+    pub fn layer(&self, profile: u8, layer: u8) -> Option<&Layer> {
+        self.layers.get(layer as usize)?.as_ref().filter(|_| self.profile == profile)
+    }
+}
+
+impl<S: StorageDriver> StorageConfigManager<S> {
+    /// Stages state config to be written by the next commit.
+    pub async fn stage_state_config(
+        &self,
+        profile: u8,
+        data: &StateConfig,
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        let mut pending = self.pending.lock().await;
+        if pending.profile != profile {
+            self.commit_pending(&mut pending).await?;
+            pending.profile = profile;
+        }
+        pending.state_config = Some(data.clone());
+        self.staged.signal(());
+        Ok(())
+    }
+
+    /// Stages keymap layer to be written by the next commit.
+    pub async fn stage_keymap(
+        &self,
+        profile: u8,
+        layer: u8,
+        data: &Layer,
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        let mut pending = self.pending.lock().await;
+        if pending.profile != profile {
+            self.commit_pending(&mut pending).await?;
+            pending.profile = profile;
+        }
+        pending.layers[layer as usize] = Some(data.clone());
+        self.staged.signal(());
+        Ok(())
+    }
+
+    /// Returns true if there are staged values which are not committed yet.
+    pub async fn has_pending(&self) -> bool {
+        !self.pending.lock().await.is_empty()
+    }
+
+    /// Writes all staged values to the storage.
+    pub async fn commit(&self) -> Result<(), ConfigWriteError<S::Error>> {
+        let mut pending = self.pending.lock().await;
+        self.commit_pending(&mut pending).await
+    }
+
+    async fn commit_pending(
+        &self,
+        pending: &mut PendingWrites,
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        // Values are removed only after they are written, so that failed ones are retried by the
+        // next commit.
+        if let Some(config) = &pending.state_config {
+            self.write_state_config(pending.profile, config).await?;
+            pending.state_config = None;
+        }
+        for (l, layer) in pending.layers.iter_mut().enumerate() {
+            if let Some(data) = layer {
+                self.write_keymap(pending.profile, l as u8, data).await?;
+                *layer = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// Commits staged values when nothing is staged for `delay`.
+    pub async fn commit_task(&self, delay: Duration) {
+        loop {
+            self.staged.wait().await;
+            while let Either::Second(_) = select(Timer::after(delay), self.staged.wait()).await {}
+
+            match self.commit().await {
+                Ok(()) => rktk_log::debug!("Staged config committed"),
+                Err(_e) => {
+                    rktk_log::error!("Failed to commit config: {:?}", Debug2Format(&_e));
+                    crate::print!("Failed to save config");
+                }
+            }
+        }
+    }
+}
@@ -4,12 +4,21 @@ use postcard::experimental::max_size::MaxSize as _;
 
 use crate::{config::keymap::Layer, drivers::interface::storage::StorageDriver};
 
-use super::{ConfigKey, StorageConfigManager};
+use super::{ConfigKey, StorageConfigManager, diff, record::RecordHeader};
 
 #[derive(Debug)]
 pub enum ConfigReadError<E: Debug> {
     ReadError(E),
+    /// Value is not stored.
+    NotFound,
     DecodeError(postcard::Error),
+    /// Stored value has a different schema from the current firmware and couldn't be migrated.
+    SchemaMismatch {
+        expected: u16,
+        found: u16,
+    },
+    /// Stored record header is broken or the value is larger than expected.
+    InvalidRecord,
 }
 
 impl<E: Debug> From<E> for ConfigReadError<E> {
@@ -18,36 +27,100 @@ impl<E: Debug> From<E> for ConfigReadError<E> {
     }
 }
 
+fn check_schema<E: Debug>(key: ConfigKey, header: RecordHeader) -> Result<(), ConfigReadError<E>> {
+    if header.tag != key.schema() {
+        return Err(ConfigReadError::SchemaMismatch { expected: key.schema(), found: header.tag });
+    }
+    Ok(())
+}
+
 impl<S: StorageDriver> StorageConfigManager<S> {
     pub async fn read_version(&self) -> Result<u16, ConfigReadError<S::Error>> {
         let mut buf = [0; 2];
         let key = u64::from_le_bytes([ConfigKey::Version as u8, 0, 0, 0, 0, 0, 0, 0]);
-        self.storage.read::<2>(key, &mut buf).await?;
+        match self.storage.read(key, &mut buf).await? {
+            Some(2) => {}
+            Some(_) => return Err(ConfigReadError::InvalidRecord),
+            None => return Err(ConfigReadError::NotFound),
+        }
         Ok(u16::from_le_bytes(buf))
     }
 
-    pub async fn read_state_config(&self) -> Result<StateConfig, ConfigReadError<S::Error>> {
+    /// Reads state config. Staged value is returned if exists.
+    pub async fn read_state_config(
+        &self,
+        profile: u8,
+    ) -> Result<StateConfig, ConfigReadError<S::Error>> {
+        if let Some(config) = self.pending.lock().await.state_config(profile) {
+            return Ok(config.clone());
+        }
+
         let mut buf = [0; StateConfig::POSTCARD_MAX_SIZE];
-        let key = u64::from_le_bytes([ConfigKey::StateConfig as u8, 0, 0, 0, 0, 0, 0, 0]);
-        self.storage.read::<{ StateConfig::POSTCARD_MAX_SIZE }>(key, &mut buf).await?;
-        let res = postcard::from_bytes(&buf).map_err(ConfigReadError::DecodeError)?;
+        let header = self.read_record(ConfigKey::StateConfig, profile, 0, &mut buf).await?;
+        check_schema(ConfigKey::StateConfig, header)?;
+        let res = postcard::from_bytes(&buf[..header.len]).map_err(ConfigReadError::DecodeError)?;
         Ok(res)
     }
 
-    pub async fn read_keymap(&self, layer: u8) -> Result<Layer, ConfigReadError<S::Error>> {
+    /// Reads keymap layer. Staged value is returned if exists.
+    ///
+    /// Whole layer is used if stored, otherwise the stored diff is applied to the default layer.
+    pub async fn read_keymap(
+        &self,
+        profile: u8,
+        layer: u8,
+    ) -> Result<Layer, ConfigReadError<S::Error>> {
+        if let Some(data) = self.pending.lock().await.layer(profile, layer) {
+            return Ok(data.clone());
+        }
+
         let mut buf = [0; Layer::POSTCARD_MAX_SIZE];
-        let key = u64::from_le_bytes([ConfigKey::StateKeymap as u8, layer, 0, 0, 0, 0, 0, 0]);
-        self.storage.read::<{ Layer::POSTCARD_MAX_SIZE }>(key, &mut buf).await?;
-        let res = postcard::from_bytes(&buf).map_err(ConfigReadError::DecodeError)?;
+        match self.read_record(ConfigKey::StateKeymap, profile, layer, &mut buf).await {
+            Ok(header) => {
+                check_schema(ConfigKey::StateKeymap, header)?;
+                return postcard::from_bytes(&buf[..header.len])
+                    .map_err(ConfigReadError::DecodeError);
+            }
+            Err(ConfigReadError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+
+        let mut res = self
+            .default_keymap
+            .layers
+            .get(layer as usize)
+            .ok_or(ConfigReadError::InvalidRecord)?
+            .clone();
+        let mut buf = [0; diff::MAX_DIFF_SIZE];
+        let header = self.read_record(ConfigKey::KeymapDiff, profile, layer, &mut buf).await?;
+        check_schema(ConfigKey::KeymapDiff, header)?;
+        diff::apply(&buf[..header.len], &mut res).map_err(ConfigReadError::DecodeError)?;
         Ok(res)
     }
 
-    pub async fn read_calibration<const N: usize>(
+    /// Reads calibration data into `buf` and returns length of the data.
+    pub async fn read_calibration(
         &self,
         buf: &mut [u8],
-    ) -> Result<(), ConfigReadError<S::Error>> {
-        let key = u64::from_le_bytes([ConfigKey::Calibration as u8, 0, 0, 0, 0, 0, 0, 0]);
-        self.storage.read::<N>(key, buf).await?;
-        Ok(())
+    ) -> Result<usize, ConfigReadError<S::Error>> {
+        let header = self.read_record(ConfigKey::Calibration, 0, 0, buf).await?;
+        check_schema(ConfigKey::Calibration, header)?;
+        Ok(header.len)
+    }
+
+    /// Reads the stored active profile. Use [`Self::active_profile`] to get the current one.
+    pub async fn read_active_profile(&self) -> Result<u8, ConfigReadError<S::Error>> {
+        let mut buf = [0; 1];
+        let header = self.read_record(ConfigKey::ActiveProfile, 0, 0, &mut buf).await?;
+        check_schema(ConfigKey::ActiveProfile, header)?;
+        Ok(buf[0])
+    }
+
+    /// Reads the stored host profile of the wireless reporter.
+    pub async fn read_ble_profile(&self) -> Result<u8, ConfigReadError<S::Error>> {
+        let mut buf = [0; 1];
+        let header = self.read_record(ConfigKey::BleProfile, 0, 0, &mut buf).await?;
+        check_schema(ConfigKey::BleProfile, header)?;
+        Ok(buf[0])
     }
 }
@@ -0,0 +1,210 @@
+//! Layout of records in the storage.
+//!
+//! A record of `(key, profile, index)` is stored as multiple storage items:
+//! - Header item: schema tag and length of the payload.
+//! - Chunk items: payload split into [`CHUNK_SIZE`] bytes. The last chunk may be shorter, or padded
+//!   with zeros if it was written by older firmware.
+//!
+//! Items of a record are written in one [`StorageDriver::commit`], so an interrupted write leaves
+//! either the old or the new record. Records which depend on each other (ex: keymap diff and the
+//! whole layer which takes precedence over it) are changed in one commit with
+//! [`StorageConfigManager::commit_records`].
+
+use crate::drivers::interface::storage::{StorageDriver, StorageOp};
+
+use super::{ConfigKey, ConfigReadError, ConfigWriteError, MAX_PAYLOAD_SIZE, StorageConfigManager};
+
+pub(super) const CHUNK_SIZE: usize = 64;
+const HEADER_SIZE: usize = 4;
+const MAX_CHUNKS: usize = MAX_PAYLOAD_SIZE.div_ceil(CHUNK_SIZE);
+/// Max number of records changed in one [`StorageConfigManager::commit_records`].
+const MAX_BATCH_RECORDS: usize = 2;
+/// A record needs writes of its chunks and header, and removals of chunks left by the old record.
+const MAX_BATCH_OPS: usize = MAX_BATCH_RECORDS * (2 * MAX_CHUNKS + 1);
+/// Distinguishes keys of records from keys of storage version 1, which have zero in this byte.
+const RECORD_KEY_MARK: u8 = 1;
+
+/// Key of the item in storage version 1 layout.
+pub(super) fn legacy_key(key: ConfigKey, index: u8) -> u64 {
+    u64::from_le_bytes([key as u8, index, 0, 0, 0, 0, 0, 0])
+}
+
+fn item_key(key: ConfigKey, profile: u8, index: u8, part: u8) -> u64 {
+    u64::from_le_bytes([key as u8, index, part, profile, 0, 0, 0, RECORD_KEY_MARK])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct RecordHeader {
+    pub tag: u16,
+    pub len: usize,
+}
+
+impl RecordHeader {
+    fn chunks(&self) -> usize {
+        self.len.div_ceil(CHUNK_SIZE)
+    }
+}
+
+/// Change of a record applied by [`StorageConfigManager::commit_records`].
+pub(super) enum RecordChange<'a> {
+    Write {
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+        tag: u16,
+        data: &'a [u8],
+    },
+    /// Does nothing if the record is not stored.
+    Remove {
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+    },
+}
+
+impl RecordChange<'_> {
+    fn id(&self) -> (ConfigKey, u8, u8) {
+        match *self {
+            Self::Write { key, profile, index, .. } | Self::Remove { key, profile, index } => {
+                (key, profile, index)
+            }
+        }
+    }
+}
+
+impl<S: StorageDriver> StorageConfigManager<S> {
+    pub(super) async fn read_record_header(
+        &self,
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+    ) -> Result<RecordHeader, ConfigReadError<S::Error>> {
+        let mut buf = [0; HEADER_SIZE];
+        match self.storage.read(item_key(key, profile, index, 0), &mut buf).await? {
+            Some(HEADER_SIZE) => {}
+            Some(_) => return Err(ConfigReadError::InvalidRecord),
+            None => return Err(ConfigReadError::NotFound),
+        }
+        Ok(RecordHeader {
+            tag: u16::from_le_bytes([buf[0], buf[1]]),
+            len: u16::from_le_bytes([buf[2], buf[3]]) as usize,
+        })
+    }
+
+    /// Reads payload of the record into `buf` and returns its header.
+    pub(super) async fn read_record(
+        &self,
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+        buf: &mut [u8],
+    ) -> Result<RecordHeader, ConfigReadError<S::Error>> {
+        let header = self.read_record_header(key, profile, index).await?;
+        let buf = buf.get_mut(..header.len).ok_or(ConfigReadError::InvalidRecord)?;
+
+        for (i, chunk) in buf.chunks_mut(CHUNK_SIZE).enumerate() {
+            let mut item = [0; CHUNK_SIZE];
+            let len = self
+                .storage
+                .read(item_key(key, profile, index, i as u8 + 1), &mut item)
+                .await?
+                .ok_or(ConfigReadError::InvalidRecord)?;
+            if len < chunk.len() {
+                return Err(ConfigReadError::InvalidRecord);
+            }
+            chunk.copy_from_slice(&item[..chunk.len()]);
+        }
+
+        Ok(header)
+    }
+
+    pub(super) async fn write_record(
+        &self,
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+        tag: u16,
+        data: &[u8],
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        self.commit_records(&[RecordChange::Write { key, profile, index, tag, data }]).await
+    }
+
+    /// Removes the record. Does nothing if it is not stored.
+    pub(super) async fn remove_record(
+        &self,
+        key: ConfigKey,
+        profile: u8,
+        index: u8,
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        self.commit_records(&[RecordChange::Remove { key, profile, index }]).await
+    }
+
+    /// Applies all `changes` in one [`StorageDriver::commit`].
+    pub(super) async fn commit_records(
+        &self,
+        changes: &[RecordChange<'_>],
+    ) -> Result<(), ConfigWriteError<S::Error>> {
+        // Headers are collected first because the operations borrow them.
+        let mut olds = heapless::Vec::<_, MAX_BATCH_RECORDS>::new();
+        let mut headers = heapless::Vec::<_, MAX_BATCH_RECORDS>::new();
+        for change in changes {
+            let header = match change {
+                RecordChange::Write { tag, data, .. } => {
+                    if data.len() > MAX_CHUNKS * CHUNK_SIZE {
+                        return Err(ConfigWriteError::TooLarge);
+                    }
+                    let tag = tag.to_le_bytes();
+                    let len = (data.len() as u16).to_le_bytes();
+                    [tag[0], tag[1], len[0], len[1]]
+                }
+                RecordChange::Remove { .. } => [0; HEADER_SIZE],
+            };
+            let (key, profile, index) = change.id();
+            let old = self.read_record_header(key, profile, index).await.ok();
+            if olds.push(old).is_err() || headers.push(header).is_err() {
+                return Err(ConfigWriteError::TooLarge);
+            }
+        }
+
+        // Capacity is checked above, so pushing never fails.
+        let mut ops = heapless::Vec::<_, MAX_BATCH_OPS>::new();
+        for ((change, old), header) in changes.iter().zip(&olds).zip(&headers) {
+            let (key, profile, index) = change.id();
+            let new_chunks = match change {
+                RecordChange::Write { data, .. } => {
+                    for (i, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+                        let _ = ops.push(StorageOp::Write {
+                            key: item_key(key, profile, index, i as u8 + 1),
+                            value: chunk,
+                        });
+                    }
+                    let _ = ops.push(StorageOp::Write {
+                        key: item_key(key, profile, index, 0),
+                        value: header,
+                    });
+                    data.len().div_ceil(CHUNK_SIZE)
+                }
+                RecordChange::Remove { .. } => {
+                    if old.is_some() {
+                        let _ =
+                            ops.push(StorageOp::Remove { key: item_key(key, profile, index, 0) });
+                    }
+                    0
+                }
+            };
+            // Chunks beyond the new length are no longer referenced by the header.
+            if let Some(old) = old {
+                for i in new_chunks..old.chunks() {
+                    let _ = ops.push(StorageOp::Remove {
+                        key: item_key(key, profile, index, i as u8 + 1),
+                    });
+                }
+            }
+        }
+
+        if !ops.is_empty() {
+            self.storage.commit(&ops).await?;
+        }
+        Ok(())
+    }
+}
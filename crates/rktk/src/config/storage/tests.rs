@@ -0,0 +1,599 @@
+extern crate std;
+
+use core::cell::{Cell, RefCell};
+use std::{collections::BTreeMap, vec, vec::Vec};
+
+use embassy_futures::block_on;
+use kmsm::{
+    interface::state::config::{
+        ComboConfig, KeyResolverConfig, MouseConfig, PointerMode, StateConfig, TapDanceConfig,
+        TapHoldConfig,
+    },
+    keycode::prelude::*,
+};
+use postcard::experimental::max_size::MaxSize as _;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        CONST_CONFIG,
+        keymap::{Keymap, Layer},
+    },
+    drivers::interface::{
+        Error,
+        storage::{StorageDriver, StorageOp, key_has_prefix},
+    },
+};
+
+use super::{
+    ConfigKey, ConfigReadError, STORAGE_VERSION, StorageConfigManager, diff,
+    migration::{
+        MIGRATIONS, Migration, MigrationError, MouseConfigV1, StateConfigV1, state_config_v1_to_v2,
+        upgrade,
+    },
+    record::{CHUNK_SIZE, legacy_key},
+};
+
+#[derive(Debug, PartialEq)]
+enum MemoryStorageError {
+    BufferTooSmall,
+}
+
+impl Error for MemoryStorageError {}
+
+/// In-memory storage. Operations of a commit are applied at once, as nothing can interrupt them.
+///
+/// The second field counts commits.
+#[derive(Default)]
+struct MemoryStorage(RefCell<BTreeMap<u64, Vec<u8>>>, Cell<usize>);
+
+impl StorageDriver for MemoryStorage {
+    type Error = MemoryStorageError;
+
+    async fn format(&self) -> Result<(), Self::Error> {
+        self.0.borrow_mut().clear();
+        Ok(())
+    }
+
+    async fn read(&self, key: u64, buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        let items = self.0.borrow();
+        let Some(item) = items.get(&key) else {
+            return Ok(None);
+        };
+        buf.get_mut(..item.len()).ok_or(MemoryStorageError::BufferTooSmall)?.copy_from_slice(item);
+        Ok(Some(item.len()))
+    }
+
+    async fn write(&self, key: u64, value: &[u8]) -> Result<(), Self::Error> {
+        self.0.borrow_mut().insert(key, value.to_vec());
+        Ok(())
+    }
+
+    async fn remove(&self, key: u64) -> Result<(), Self::Error> {
+        self.0.borrow_mut().remove(&key);
+        Ok(())
+    }
+
+    async fn keys(&self, prefix: &[u8], keys: &mut [u64]) -> Result<usize, Self::Error> {
+        let items = self.0.borrow();
+        let found = items.keys().filter(|k| key_has_prefix(**k, prefix));
+        Ok(keys.iter_mut().zip(found).map(|(dst, k)| *dst = *k).count())
+    }
+
+    async fn commit(&self, ops: &[StorageOp<'_>]) -> Result<(), Self::Error> {
+        self.1.set(self.1.get() + 1);
+        let mut items = self.0.borrow_mut();
+        for op in ops {
+            match op {
+                StorageOp::Write { key, value } => items.insert(*key, value.to_vec()),
+                StorageOp::Remove { key } => items.remove(key),
+            };
+        }
+        Ok(())
+    }
+}
+
+static DEFAULT_KEYMAP: Keymap = {
+    let mut keymap = Keymap::const_default();
+    keymap.layers[0].keymap[0][0] = B;
+    keymap.layers[0].keymap[1][0] = C;
+    keymap
+};
+
+fn manager() -> StorageConfigManager<MemoryStorage> {
+    StorageConfigManager::new(MemoryStorage::default(), &DEFAULT_KEYMAP)
+}
+
+/// Creates manager which has the same data as `m`, as if the keyboard was rebooted.
+fn manager_with(m: &StorageConfigManager<MemoryStorage>) -> StorageConfigManager<MemoryStorage> {
+    StorageConfigManager::new(
+        MemoryStorage(RefCell::new(m.storage.0.borrow().clone()), Cell::default()),
+        &DEFAULT_KEYMAP,
+    )
+}
+
+fn state_config() -> StateConfig {
+    StateConfig {
+        mouse: MouseConfig {
+            auto_mouse_layer: 1,
+            auto_mouse_duration: 500,
+            auto_mouse_threshold: 1,
+            scroll_divider_x: 20,
+            scroll_divider_y: -12,
+            pointer_modes: [PointerMode::Move, PointerMode::Move],
+        },
+        key_resolver: KeyResolverConfig {
+            tap_hold: TapHoldConfig { threshold: 200, hold_on_other_key: true },
+            tap_dance: TapDanceConfig { threshold: 200 },
+            combo: ComboConfig { threshold: 20 },
+        },
+    }
+}
+
+/// [`state_config`] serialized by storage version 1, which is schema 1 of [`StateConfig`].
+const LEGACY_STATE_CONFIG: [u8; 12] =
+    [0x01, 0xF4, 0x03, 0x01, 0x14, 0xF4, 0xC8, 0x01, 0x01, 0xC8, 0x01, 0x14];
+
+fn layer() -> Layer {
+    let mut layer = Layer::default();
+    layer.keymap[0][0] = A;
+    layer.keymap[0][1] =
+        KeyAction::TapHold(KeyCode::Key(Key::B), KeyCode::Modifier(Modifier::LShft));
+    layer.arrow_mouse = true;
+    layer
+}
+
+/// [`layer`] serialized by storage version 1.
+fn legacy_layer() -> Vec<u8> {
+    let keys = CONST_CONFIG.keyboard.rows as usize * CONST_CONFIG.keyboard.cols as usize;
+    let encoders = CONST_CONFIG.keyboard.encoder_count as usize;
+    [
+        &[0x01, 0x01, 0x00][..],
+        &[0x03, 0x01, 0x01, 0x03, 0x01][..],
+        &vec![0x00; keys - 2][..],
+        &vec![0x00; encoders * 2][..],
+        &[0x01][..],
+    ]
+    .concat()
+}
+
+/// Writes value as storage version 1 did: raw postcard bytes padded to the max size.
+fn write_legacy(storage: &MemoryStorage, key: ConfigKey, index: u8, data: &[u8], size: usize) {
+    let mut item = vec![0; size];
+    item[..data.len()].copy_from_slice(data);
+    storage.0.borrow_mut().insert(legacy_key(key, index), item);
+}
+
+#[test]
+fn test_state_config_round_trip() {
+    let m = manager();
+    block_on(async {
+        m.write_state_config(0, &state_config()).await.unwrap();
+        assert_eq!(m.read_state_config(0).await.unwrap(), state_config());
+    });
+}
+
+#[test]
+fn test_keymap_round_trip() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap(0, 1, &layer()).await.unwrap();
+        assert_eq!(m.read_keymap(0, 1).await.unwrap(), layer());
+        assert!(matches!(m.read_keymap(0, 0).await, Err(ConfigReadError::NotFound)));
+    });
+}
+
+#[test]
+fn test_keymap_diff_round_trip() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap_diff(0, 0, &layer()).await.unwrap();
+        assert_eq!(m.read_keymap(0, 0).await.unwrap(), layer());
+
+        // Only A at (0, 0), TapHold at (0, 1) and arrow_mouse are stored.
+        let mut expected = layer();
+        expected.keymap[1][0] = C;
+        m.write_keymap_diff(0, 0, &expected).await.unwrap();
+        let header = m.read_record_header(ConfigKey::KeymapDiff, 0, 0).await.unwrap();
+        assert!(header.len < Layer::POSTCARD_MAX_SIZE / 4);
+        assert_eq!(manager_with(&m).read_keymap(0, 0).await.unwrap(), expected);
+    });
+}
+
+#[test]
+fn test_default_layer_diff_is_empty() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap_diff(0, 0, &DEFAULT_KEYMAP.layers[0]).await.unwrap();
+        assert_eq!(m.read_record_header(ConfigKey::KeymapDiff, 0, 0).await.unwrap().len, 0);
+        assert_eq!(m.read_keymap(0, 0).await.unwrap(), DEFAULT_KEYMAP.layers[0]);
+    });
+}
+
+#[test]
+fn test_large_diff_is_stored_as_layer() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap_diff(0, 0, &layer()).await.unwrap();
+
+        // Every key differs from the default, so the diff is larger than the whole layer.
+        let action = KeyAction::TapHold(
+            KeyCode::Layer(LayerOp::Momentary(1)),
+            KeyCode::Layer(LayerOp::Toggle(2)),
+        );
+        let mut full = Layer::default();
+        full.keymap =
+            [[action; CONST_CONFIG.keyboard.cols as usize]; CONST_CONFIG.keyboard.rows as usize];
+        m.write_keymap_diff(0, 0, &full).await.unwrap();
+
+        assert!(m.read_record_header(ConfigKey::StateKeymap, 0, 0).await.is_ok());
+        assert!(m.read_record_header(ConfigKey::KeymapDiff, 0, 0).await.is_err());
+        assert_eq!(manager_with(&m).read_keymap(0, 0).await.unwrap(), full);
+    });
+}
+
+#[test]
+fn test_keymap_diff_replaces_layer_in_one_commit() {
+    let m = manager();
+    block_on(async {
+        let mut other = layer();
+        other.keymap[2][2] = D;
+        m.write_keymap_layer(0, 0, &other).await.unwrap();
+
+        // Interrupted write must not leave the old whole layer, which takes precedence.
+        let commits = m.storage.1.get();
+        m.write_keymap_diff(0, 0, &layer()).await.unwrap();
+        assert_eq!(m.storage.1.get(), commits + 1);
+        assert!(m.read_record_header(ConfigKey::StateKeymap, 0, 0).await.is_err());
+        assert_eq!(manager_with(&m).read_keymap(0, 0).await.unwrap(), layer());
+    });
+}
+
+#[test]
+fn test_latest_keymap_format_wins() {
+    let m = manager();
+    block_on(async {
+        let mut other = layer();
+        other.keymap[2][2] = D;
+
+        m.write_keymap_layer(0, 0, &other).await.unwrap();
+        m.write_keymap_diff(0, 0, &layer()).await.unwrap();
+        assert_eq!(m.read_keymap(0, 0).await.unwrap(), layer());
+
+        m.write_keymap_layer(0, 0, &other).await.unwrap();
+        assert_eq!(m.read_keymap(0, 0).await.unwrap(), other);
+    });
+}
+
+#[test]
+fn test_diff_out_of_range() {
+    let mut buf = [0; diff::MAX_DIFF_SIZE];
+    let mut l = Layer::default();
+    let len = diff::encode(&layer(), &l, &mut buf).unwrap();
+
+    // Diff stored by a keyboard with larger matrix.
+    buf[1] = CONST_CONFIG.keyboard.rows;
+    assert!(diff::apply(&buf[..len], &mut l).is_err());
+}
+
+#[test]
+fn test_profiles_are_separated() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap(1, 0, &layer()).await.unwrap();
+        m.write_state_config(1, &state_config()).await.unwrap();
+
+        assert_eq!(m.read_keymap(1, 0).await.unwrap(), layer());
+        assert_eq!(m.read_state_config(1).await.unwrap(), state_config());
+        assert!(matches!(m.read_keymap(0, 0).await, Err(ConfigReadError::NotFound)));
+        assert!(matches!(m.read_state_config(0).await, Err(ConfigReadError::NotFound)));
+    });
+}
+
+#[test]
+fn test_active_profile() {
+    let m = manager();
+    block_on(async {
+        assert_eq!(m.load_active_profile().await, 0);
+
+        let last = CONST_CONFIG.key_manager.profile_count - 1;
+        m.set_active_profile(last).await.unwrap();
+        assert_eq!(m.active_profile(), last);
+        assert_eq!(manager_with(&m).load_active_profile().await, last);
+
+        // Out of range profile, e.g. stored by firmware with more profiles.
+        m.set_active_profile(last + 1).await.unwrap();
+        assert_eq!(manager_with(&m).load_active_profile().await, 0);
+    });
+}
+
+#[test]
+fn test_ble_profile() {
+    let m = manager();
+    block_on(async {
+        assert!(matches!(m.read_ble_profile().await, Err(ConfigReadError::NotFound)));
+
+        m.write_ble_profile(2).await.unwrap();
+        assert_eq!(manager_with(&m).read_ble_profile().await.unwrap(), 2);
+        // Independent of the keymap profile.
+        assert_eq!(manager_with(&m).load_active_profile().await, 0);
+    });
+}
+
+#[test]
+fn test_staged_values_are_committed() {
+    let m = manager();
+    block_on(async {
+        m.stage_keymap(0, 1, &layer()).await.unwrap();
+        m.stage_state_config(0, &state_config()).await.unwrap();
+        assert!(m.has_pending().await);
+
+        // Staged values are visible but not written yet.
+        assert_eq!(m.read_keymap(0, 1).await.unwrap(), layer());
+        assert_eq!(m.read_state_config(0).await.unwrap(), state_config());
+        assert!(m.storage.0.borrow().is_empty());
+
+        m.commit().await.unwrap();
+        assert!(!m.has_pending().await);
+        let rebooted = manager_with(&m);
+        assert_eq!(rebooted.read_keymap(0, 1).await.unwrap(), layer());
+        assert_eq!(rebooted.read_state_config(0).await.unwrap(), state_config());
+    });
+}
+
+#[test]
+fn test_staging_coalesces_writes() {
+    let m = manager();
+    block_on(async {
+        let mut l = layer();
+        for key in [A, B, C] {
+            l.keymap[1][1] = key;
+            m.stage_keymap(0, 0, &l).await.unwrap();
+        }
+        m.commit().await.unwrap();
+        assert_eq!(manager_with(&m).read_keymap(0, 0).await.unwrap().keymap[1][1], C);
+    });
+}
+
+#[test]
+fn test_staging_other_profile_commits_pending() {
+    let m = manager();
+    block_on(async {
+        m.stage_keymap(0, 0, &layer()).await.unwrap();
+        m.stage_state_config(1, &state_config()).await.unwrap();
+
+        let rebooted = manager_with(&m);
+        assert_eq!(rebooted.read_keymap(0, 0).await.unwrap(), layer());
+        assert!(matches!(rebooted.read_state_config(1).await, Err(ConfigReadError::NotFound)));
+        assert_eq!(m.read_state_config(1).await.unwrap(), state_config());
+    });
+}
+
+#[test]
+fn test_calibration_round_trip() {
+    let m = manager();
+    let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 3).map(|i| i as u8).collect();
+    block_on(async {
+        m.write_calibration(&data).await.unwrap();
+
+        let mut buf = vec![0; data.len() + 10];
+        let len = m.read_calibration(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], &data[..]);
+
+        let mut small = vec![0; data.len() - 1];
+        assert!(matches!(
+            m.read_calibration(&mut small).await,
+            Err(ConfigReadError::InvalidRecord)
+        ));
+    });
+}
+
+#[test]
+fn test_shrunk_record_removes_chunks() {
+    let m = manager();
+    block_on(async {
+        m.write_calibration(&[1; CHUNK_SIZE * 3]).await.unwrap();
+        let commits = m.storage.1.get();
+        m.write_calibration(&[2; CHUNK_SIZE + 1]).await.unwrap();
+        // Stale chunks are removed with the new record.
+        assert_eq!(m.storage.1.get(), commits + 1);
+
+        let mut keys = [0; 8];
+        let count = m.storage.keys(&[ConfigKey::Calibration as u8], &mut keys).await.unwrap();
+        // Header and two chunks
+        assert_eq!(count, 3);
+
+        let mut buf = [0; CHUNK_SIZE * 3];
+        let len = m.read_calibration(&mut buf).await.unwrap();
+        assert_eq!(&buf[..len], &[2; CHUNK_SIZE + 1]);
+    });
+}
+
+#[test]
+fn test_remove_record() {
+    let m = manager();
+    block_on(async {
+        m.write_keymap_layer(0, 0, &layer()).await.unwrap();
+        m.remove_record(ConfigKey::StateKeymap, 0, 0).await.unwrap();
+        assert!(m.storage.0.borrow().is_empty());
+
+        // Removing missing record is not an error.
+        m.remove_record(ConfigKey::StateKeymap, 0, 0).await.unwrap();
+    });
+}
+
+#[test]
+fn test_legacy_fixtures() {
+    let m = manager();
+    write_legacy(
+        &m.storage,
+        ConfigKey::StateConfig,
+        0,
+        &LEGACY_STATE_CONFIG,
+        StateConfig::POSTCARD_MAX_SIZE,
+    );
+    write_legacy(&m.storage, ConfigKey::StateKeymap, 2, &legacy_layer(), Layer::POSTCARD_MAX_SIZE);
+
+    block_on(async {
+        m.write_version(1).await.unwrap();
+        let version = m.read_version().await.unwrap();
+
+        assert_eq!(m.migrate(version, MIGRATIONS).await.unwrap(), 0);
+
+        assert_eq!(m.read_version().await.unwrap(), STORAGE_VERSION);
+        assert_eq!(m.read_state_config(0).await.unwrap(), state_config());
+        assert_eq!(m.read_keymap(0, 2).await.unwrap(), layer());
+        assert!(matches!(m.read_keymap(0, 0).await, Err(ConfigReadError::NotFound)));
+
+        // Converted values are removed.
+        let items = m.storage.0.borrow();
+        assert!(!items.contains_key(&legacy_key(ConfigKey::StateConfig, 0)));
+        assert!(!items.contains_key(&legacy_key(ConfigKey::StateKeymap, 2)));
+    });
+}
+
+#[test]
+fn test_legacy_conversion_skips_new_keys() {
+    let m = manager();
+    // Version 1 had no BleProfile, so an item at its legacy key is not a stored value.
+    m.storage.0.borrow_mut().insert(legacy_key(ConfigKey::BleProfile, 0), vec![3]);
+
+    block_on(async {
+        m.write_version(1).await.unwrap();
+        assert_eq!(m.migrate(1, MIGRATIONS).await.unwrap(), 0);
+
+        assert!(matches!(m.read_ble_profile().await, Err(ConfigReadError::NotFound)));
+        assert!(m.storage.0.borrow().contains_key(&legacy_key(ConfigKey::BleProfile, 0)));
+    });
+}
+
+#[test]
+fn test_migrate_is_idempotent() {
+    let m = manager();
+    block_on(async {
+        m.write_version(STORAGE_VERSION).await.unwrap();
+        m.write_keymap(0, 0, &layer()).await.unwrap();
+        let before = m.storage.0.borrow().clone();
+
+        assert_eq!(m.migrate(STORAGE_VERSION, MIGRATIONS).await.unwrap(), 0);
+
+        assert_eq!(*m.storage.0.borrow(), before);
+    });
+}
+
+#[test]
+fn test_newer_schema_is_not_decoded() {
+    let m = manager();
+    block_on(async {
+        m.write_record(ConfigKey::StateConfig, 0, 0, 100, &[0xFF; 4]).await.unwrap();
+
+        assert_eq!(m.migrate(STORAGE_VERSION, MIGRATIONS).await.unwrap(), 1);
+
+        assert!(matches!(
+            m.read_state_config(0).await,
+            Err(ConfigReadError::SchemaMismatch { expected: 2, found: 100 })
+        ));
+    });
+}
+
+/// Layout of [`StateConfig`] in hypothetical schema 0, which had no combo config.
+#[derive(Serialize, Deserialize)]
+struct StateConfigV0 {
+    mouse: MouseConfigV1,
+    tap_hold: TapHoldConfig,
+    tap_dance: TapDanceConfig,
+}
+
+fn state_config_v0_to_v1(old: &[u8], new: &mut [u8]) -> Result<usize, MigrationError> {
+    let old: StateConfigV0 = postcard::from_bytes(old).map_err(|_| MigrationError::Invalid)?;
+    let config = StateConfigV1 {
+        mouse: old.mouse,
+        key_resolver: KeyResolverConfig {
+            tap_hold: old.tap_hold,
+            tap_dance: old.tap_dance,
+            combo: ComboConfig { threshold: 20 },
+        },
+    };
+    postcard::to_slice(&config, new).map(|s| s.len()).map_err(|_| MigrationError::Invalid)
+}
+
+#[test]
+fn test_migrate_registered_migration() {
+    let migrations = [
+        Migration { key: ConfigKey::StateConfig, from: 0, migrate: state_config_v0_to_v1 },
+        Migration { key: ConfigKey::StateConfig, from: 1, migrate: state_config_v1_to_v2 },
+    ];
+    let config = state_config();
+    let old = StateConfigV0 {
+        mouse: MouseConfigV1 {
+            auto_mouse_layer: config.mouse.auto_mouse_layer,
+            auto_mouse_duration: config.mouse.auto_mouse_duration,
+            auto_mouse_threshold: config.mouse.auto_mouse_threshold,
+            scroll_divider_x: config.mouse.scroll_divider_x,
+            scroll_divider_y: config.mouse.scroll_divider_y,
+        },
+        tap_hold: config.key_resolver.tap_hold.clone(),
+        tap_dance: config.key_resolver.tap_dance.clone(),
+    };
+    let mut buf = [0; 64];
+    let old = postcard::to_slice(&old, &mut buf).unwrap();
+
+    let m = manager();
+    block_on(async {
+        m.write_record(ConfigKey::StateConfig, 0, 0, 0, old).await.unwrap();
+
+        assert_eq!(m.migrate(STORAGE_VERSION, &migrations).await.unwrap(), 0);
+
+        assert_eq!(m.read_state_config(0).await.unwrap(), config);
+    });
+}
+
+fn append_one(old: &[u8], new: &mut [u8]) -> Result<usize, MigrationError> {
+    new[..old.len()].copy_from_slice(old);
+    new[old.len()] = 1;
+    Ok(old.len() + 1)
+}
+
+fn append_two(old: &[u8], new: &mut [u8]) -> Result<usize, MigrationError> {
+    new[..old.len()].copy_from_slice(old);
+    new[old.len()] = 2;
+    Ok(old.len() + 1)
+}
+
+#[test]
+fn test_upgrade_chain() {
+    let migrations = [
+        Migration { key: ConfigKey::StateKeymap, from: 2, migrate: append_two },
+        Migration { key: ConfigKey::StateKeymap, from: 1, migrate: append_one },
+        Migration { key: ConfigKey::StateConfig, from: 1, migrate: append_two },
+    ];
+    let mut buf = [0; 8];
+    let mut tmp = [0; 8];
+    buf[0] = 9;
+
+    let len = upgrade(&migrations, ConfigKey::StateKeymap, 1, 3, &mut buf, 1, &mut tmp).unwrap();
+    assert_eq!(&buf[..len], &[9, 1, 2]);
+
+    let len = upgrade(&migrations, ConfigKey::StateKeymap, 3, 3, &mut buf, len, &mut tmp).unwrap();
+    assert_eq!(&buf[..len], &[9, 1, 2]);
+}
+
+#[test]
+fn test_upgrade_errors() {
+    let migrations = [Migration { key: ConfigKey::StateKeymap, from: 1, migrate: append_one }];
+    let mut buf = [0; 8];
+    let mut tmp = [0; 8];
+
+    assert_eq!(
+        upgrade(&migrations, ConfigKey::StateKeymap, 1, 3, &mut buf, 0, &mut tmp),
+        Err(MigrationError::Missing { from: 2 })
+    );
+    assert_eq!(
+        upgrade(&migrations, ConfigKey::StateConfig, 1, 2, &mut buf, 0, &mut tmp),
+        Err(MigrationError::Missing { from: 1 })
+    );
+    assert_eq!(
+        upgrade(&migrations, ConfigKey::StateKeymap, 4, 2, &mut buf, 0, &mut tmp),
+        Err(MigrationError::Newer { found: 4 })
+    );
+}
@@ -1,15 +1,21 @@
 use core::fmt::Debug;
 use kmsm::interface::state::config::StateConfig;
+use portable_atomic::Ordering;
 use postcard::experimental::max_size::MaxSize as _;
 
-use crate::{config::keymap::Layer, drivers::interface::storage::StorageDriver};
+use crate::{
+    config::{CONST_CONFIG, keymap::Layer},
+    drivers::interface::storage::StorageDriver,
+};
 
-use super::{ConfigKey, StorageConfigManager};
+use super::{ConfigKey, StorageConfigManager, diff, record::RecordChange};
 
 #[derive(Debug)]
 pub enum ConfigWriteError<E: Debug> {
     WriteError(E),
     EncodeError(postcard::Error),
+    /// Value is too large to be stored as a record.
+    TooLarge,
 }
 
 impl<E: Debug> From<E> for ConfigWriteError<E> {
@@ -22,41 +28,125 @@ impl<S: StorageDriver> StorageConfigManager<S> {
     pub async fn write_version(&self, version: u16) -> Result<(), ConfigWriteError<S::Error>> {
         let key = u64::from_le_bytes([ConfigKey::Version as u8, 0, 0, 0, 0, 0, 0, 0]);
 
-        self.storage.write::<2>(key, &version.to_le_bytes()).await?;
+        self.storage.write(key, &version.to_le_bytes()).await?;
         Ok(())
     }
 
     pub async fn write_state_config(
         &self,
+        profile: u8,
         data: &StateConfig,
     ) -> Result<(), ConfigWriteError<S::Error>> {
-        let key = u64::from_le_bytes([ConfigKey::StateConfig as u8, 0, 0, 0, 0, 0, 0, 0]);
-
         let mut buf = [0; StateConfig::POSTCARD_MAX_SIZE];
-        let _slice = postcard::to_slice(data, &mut buf).map_err(ConfigWriteError::EncodeError)?;
-        self.storage.write::<{ StateConfig::POSTCARD_MAX_SIZE }>(key, &buf).await?;
-        Ok(())
+        let slice = postcard::to_slice(data, &mut buf).map_err(ConfigWriteError::EncodeError)?;
+        self.write_record(
+            ConfigKey::StateConfig,
+            profile,
+            0,
+            ConfigKey::StateConfig.schema(),
+            slice,
+        )
+        .await
     }
 
+    /// Writes keymap layer as a whole layer or as a diff, depending on `sparse_keymap` config.
     pub async fn write_keymap(
         &self,
+        profile: u8,
         layer: u8,
         data: &Layer,
     ) -> Result<(), ConfigWriteError<S::Error>> {
-        let key = u64::from_le_bytes([ConfigKey::StateKeymap as u8, layer, 0, 0, 0, 0, 0, 0]);
+        if CONST_CONFIG.key_manager.sparse_keymap {
+            self.write_keymap_diff(profile, layer, data).await
+        } else {
+            self.write_keymap_layer(profile, layer, data).await
+        }
+    }
 
+    pub(super) async fn write_keymap_layer(
+        &self,
+        profile: u8,
+        layer: u8,
+        data: &Layer,
+    ) -> Result<(), ConfigWriteError<S::Error>> {
         let mut buf = [0; Layer::POSTCARD_MAX_SIZE];
-        let _slice = postcard::to_slice(data, &mut buf).map_err(ConfigWriteError::EncodeError)?;
-        self.storage.write::<{ Layer::POSTCARD_MAX_SIZE }>(key, &buf).await?;
-        Ok(())
+        let slice = postcard::to_slice(data, &mut buf).map_err(ConfigWriteError::EncodeError)?;
+        self.write_record(
+            ConfigKey::StateKeymap,
+            profile,
+            layer,
+            ConfigKey::StateKeymap.schema(),
+            slice,
+        )
+        .await
     }
 
-    pub async fn write_calibration<const N: usize>(
+    pub(super) async fn write_keymap_diff(
         &self,
-        data: &[u8],
+        profile: u8,
+        layer: u8,
+        data: &Layer,
     ) -> Result<(), ConfigWriteError<S::Error>> {
-        let key = u64::from_le_bytes([ConfigKey::Calibration as u8, 0, 0, 0, 0, 0, 0, 0]);
-        self.storage.write::<N>(key, data).await?;
+        let default = &self.default_keymap.layers[layer as usize];
+        let mut buf = [0; diff::MAX_DIFF_SIZE];
+        let len = match diff::encode(data, default, &mut buf) {
+            Ok(len) => len,
+            // Diff is larger than the whole layer, so the layer is stored instead.
+            Err(postcard::Error::SerializeBufferFull) => {
+                let mut buf = [0; Layer::POSTCARD_MAX_SIZE];
+                let slice =
+                    postcard::to_slice(data, &mut buf).map_err(ConfigWriteError::EncodeError)?;
+                return self
+                    .commit_records(&[
+                        RecordChange::Write {
+                            key: ConfigKey::StateKeymap,
+                            profile,
+                            index: layer,
+                            tag: ConfigKey::StateKeymap.schema(),
+                            data: slice,
+                        },
+                        RecordChange::Remove { key: ConfigKey::KeymapDiff, profile, index: layer },
+                    ])
+                    .await;
+            }
+            Err(e) => return Err(ConfigWriteError::EncodeError(e)),
+        };
+
+        // Whole layer takes precedence over diff, so it is removed in the same commit.
+        self.commit_records(&[
+            RecordChange::Write {
+                key: ConfigKey::KeymapDiff,
+                profile,
+                index: layer,
+                tag: ConfigKey::KeymapDiff.schema(),
+                data: &buf[..len],
+            },
+            RecordChange::Remove { key: ConfigKey::StateKeymap, profile, index: layer },
+        ])
+        .await
+    }
+
+    pub async fn write_calibration(&self, data: &[u8]) -> Result<(), ConfigWriteError<S::Error>> {
+        self.write_record(ConfigKey::Calibration, 0, 0, ConfigKey::Calibration.schema(), data).await
+    }
+
+    /// Stores `profile` as the active profile, which is restored at next boot.
+    pub async fn set_active_profile(&self, profile: u8) -> Result<(), ConfigWriteError<S::Error>> {
+        self.write_record(
+            ConfigKey::ActiveProfile,
+            0,
+            0,
+            ConfigKey::ActiveProfile.schema(),
+            &[profile],
+        )
+        .await?;
+        self.active_profile.store(profile, Ordering::Relaxed);
         Ok(())
     }
+
+    /// Stores the active host profile of the wireless reporter, which is restored at next boot.
+    pub async fn write_ble_profile(&self, profile: u8) -> Result<(), ConfigWriteError<S::Error>> {
+        self.write_record(ConfigKey::BleProfile, 0, 0, ConfigKey::BleProfile.schema(), &[profile])
+            .await
+    }
 }
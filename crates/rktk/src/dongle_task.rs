@@ -12,6 +12,8 @@ use crate::{
     utils::sjoin,
 };
 
+pub use crate::task::central_dongle::start_central_dongle;
+
 /// Runs dongle with the given drivers.
 pub async fn start_dongle<
     Display: DisplayDriver,
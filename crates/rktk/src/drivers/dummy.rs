@@ -10,14 +10,16 @@ use embedded_graphics::{
 use kmsm::interface::state::input_event::{EncoderDirection, KeyChangeEvent};
 
 use crate::drivers::interface::{
+    battery::BatteryDriver,
     debounce::DebounceDriver,
+    dfu::DfuDriver,
     display::DisplayDriver,
     encoder::EncoderDriver,
     mouse::MouseDriver,
     reporter::ReporterDriver,
     rgb::RgbDriver,
     split::SplitDriver,
-    storage::StorageDriver,
+    storage::{StorageDriver, StorageOp},
     usb::{UsbReporterDriver, UsbReporterDriverBuilder},
     wireless::{WirelessReporterDriver, WirelessReporterDriverBuilder},
 };
@@ -143,14 +145,19 @@ pub fn storage() -> Option<impl StorageDriver> {
         async fn format(&self) -> Result<(), Self::Error> {
             unreachable!()
         }
-        async fn read<const N: usize>(
-            &self,
-            _key: u64,
-            _buf: &mut [u8],
-        ) -> Result<(), Self::Error> {
+        async fn read(&self, _key: u64, _buf: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+            unreachable!()
+        }
+        async fn write(&self, _key: u64, _value: &[u8]) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+        async fn remove(&self, _key: u64) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+        async fn keys(&self, _prefix: &[u8], _keys: &mut [u64]) -> Result<usize, Self::Error> {
             unreachable!()
         }
-        async fn write<const N: usize>(&self, _key: u64, _buf: &[u8]) -> Result<(), Self::Error> {
+        async fn commit(&self, _ops: &[StorageOp<'_>]) -> Result<(), Self::Error> {
             unreachable!()
         }
     }
@@ -158,6 +165,44 @@ pub fn storage() -> Option<impl StorageDriver> {
     Option::<Storage>::None
 }
 
+// dfu
+pub fn dfu() -> Option<impl DfuDriver> {
+    pub enum Dfu {}
+    impl DfuDriver for Dfu {
+        type Error = Infallible;
+        fn capacity(&self) -> u32 {
+            unreachable!()
+        }
+        async fn erase(&mut self, _size: u32) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+        async fn write(&mut self, _offset: u32, _data: &[u8]) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+        async fn read(&mut self, _offset: u32, _buf: &mut [u8]) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+        async fn mark_updated(&mut self) -> Result<(), Self::Error> {
+            unreachable!()
+        }
+    }
+
+    Option::<Dfu>::None
+}
+
+// battery
+pub fn battery() -> Option<impl BatteryDriver> {
+    pub enum Battery {}
+    impl BatteryDriver for Battery {
+        type Error = Infallible;
+        async fn read_voltage(&mut self) -> Result<u16, Self::Error> {
+            unreachable!()
+        }
+    }
+
+    Option::<Battery>::None
+}
+
 // Reporter
 
 use usbd_hid::descriptor::*;
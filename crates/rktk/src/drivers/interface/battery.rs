@@ -0,0 +1,123 @@
+//! Battery driver type.
+
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
+
+/// Driver to measure the battery.
+pub trait BatteryDriver {
+    type Error: super::Error;
+
+    /// Reads voltage of the battery in millivolts.
+    async fn read_voltage(&mut self) -> Result<u16, Self::Error>;
+
+    /// Curve to convert the voltage to the battery level.
+    fn discharge_curve(&self) -> DischargeCurve {
+        DischargeCurve::LIPO
+    }
+
+    /// Returns `Some(true)` while the battery is charging, or `None` if the charger status is
+    /// unknown.
+    fn is_charging(&mut self) -> Option<bool> {
+        None
+    }
+}
+
+/// Discharge curve of the battery, which is used to convert voltage to level.
+///
+/// Points are pairs of voltage (mV) and level (%), sorted by voltage in descending order. Level
+/// between points is linearly interpolated.
+#[derive(Debug, Clone, Copy)]
+pub struct DischargeCurve(&'static [(u16, u8)]);
+
+impl DischargeCurve {
+    /// Typical curve of 3.7V LiPo battery.
+    pub const LIPO: Self = Self(&[
+        (4200, 100),
+        (4060, 90),
+        (3980, 80),
+        (3920, 70),
+        (3870, 60),
+        (3820, 50),
+        (3790, 40),
+        (3770, 30),
+        (3740, 20),
+        (3680, 10),
+        (3450, 5),
+        (3000, 0),
+    ]);
+
+    /// Creates curve from `points`. See [`DischargeCurve`] for the format.
+    pub const fn new(points: &'static [(u16, u8)]) -> Self {
+        Self(points)
+    }
+
+    /// Returns level (%) of `voltage` (mV).
+    pub fn level(&self, voltage: u16) -> u8 {
+        let Some((&(first_v, first_l), &(last_v, last_l))) = self.0.first().zip(self.0.last())
+        else {
+            return 0;
+        };
+        if voltage >= first_v {
+            return first_l;
+        }
+        if voltage <= last_v {
+            return last_l;
+        }
+
+        for w in self.0.windows(2) {
+            let ((high_v, high_l), (low_v, low_l)) = (w[0], w[1]);
+            if voltage >= low_v && high_v > low_v {
+                let level = low_l as u32
+                    + (high_l.saturating_sub(low_l) as u32 * (voltage - low_v) as u32)
+                        / (high_v - low_v) as u32;
+                return level as u8;
+            }
+        }
+        last_l
+    }
+}
+
+/// Measured status of the battery.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BatteryStatus {
+    /// Battery level (%)
+    pub level: u8,
+    /// See [`BatteryDriver::is_charging`].
+    pub charging: Option<bool>,
+    /// True if the level is lower than `rktk.battery.low_level` of the config and the battery is
+    /// not charging.
+    pub low: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_of_lipo_curve() {
+        let curve = DischargeCurve::LIPO;
+        assert_eq!(curve.level(4300), 100);
+        assert_eq!(curve.level(4200), 100);
+        assert_eq!(curve.level(4130), 95);
+        assert_eq!(curve.level(3820), 50);
+        assert_eq!(curve.level(3000), 0);
+        assert_eq!(curve.level(2500), 0);
+    }
+
+    #[test]
+    fn level_is_monotonic() {
+        let curve = DischargeCurve::LIPO;
+        let mut prev = 0;
+        for voltage in (2900..4300).step_by(5) {
+            let level = curve.level(voltage);
+            assert!(level >= prev, "{voltage}mV: {level} < {prev}");
+            prev = level;
+        }
+    }
+
+    #[test]
+    fn empty_curve() {
+        assert_eq!(DischargeCurve::new(&[]).level(3700), 0);
+    }
+}
@@ -0,0 +1,25 @@
+/// Driver to stage new firmware for the bootloader.
+///
+/// New firmware is written to the DFU partition and installed by the bootloader
+/// (ex: embassy-boot) on next boot.
+pub trait DfuDriver {
+    type Error: super::Error;
+
+    /// Size of the DFU partition.
+    fn capacity(&self) -> u32;
+
+    /// Erases the DFU partition so that `size` bytes of firmware can be written.
+    async fn erase(&mut self, size: u32) -> Result<(), Self::Error>;
+
+    /// Writes firmware to the DFU partition.
+    ///
+    /// Writes are done sequentially from offset 0. Only the last write can have unaligned length.
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Marks the written firmware to be swapped in by the bootloader.
+    ///
+    /// The keyboard is reset after this call.
+    async fn mark_updated(&mut self) -> Result<(), Self::Error>;
+}
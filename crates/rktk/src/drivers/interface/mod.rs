@@ -1,7 +1,9 @@
 //! Driver interface types
 #![allow(async_fn_in_trait)]
 
+pub mod battery;
 pub mod debounce;
+pub mod dfu;
 pub mod display;
 pub mod dongle;
 pub mod encoder;
@@ -1,3 +1,5 @@
+use postcard::experimental::max_size::MaxSize;
+use serde::{Deserialize, Serialize};
 use usbd_hid::descriptor::{KeyboardReport, MediaKeyboardReport, MouseReport};
 
 pub trait ReporterDriver {
@@ -43,7 +45,7 @@ pub trait ReporterDriver {
     fn wakeup(&self) -> Result<bool, Self::Error>;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Output {
     Usb,
@@ -1,7 +1,9 @@
+use kmsm::interface::state::input_event::EncoderDirection;
 use postcard::experimental::max_size::MaxSize;
 use serde::{Deserialize, Serialize};
 
-use super::rgb::RgbCommand;
+use super::{battery::BatteryStatus, reporter::Output, rgb::RgbCommand};
+use crate::config::CONST_CONFIG;
 
 pub trait SplitDriver: 'static {
     type Error: super::Error;
@@ -21,11 +23,88 @@ pub trait SplitDriver: 'static {
     async fn send_all(&mut self, buf: &[u8], is_master: bool) -> Result<(), Self::Error>;
 }
 
+/// Max size of rrp data in one [`RrpChunk`].
+pub const RRP_CHUNK_SIZE: usize = 16;
+
+/// Chunk of rrp data tunneled through the split link.
+#[derive(Debug, Deserialize, Serialize, MaxSize, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RrpChunk {
+    pub len: u8,
+    pub data: [u8; RRP_CHUNK_SIZE],
+}
+
+/// Health of the split link, measured by the split handler.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SplitLinkStats {
+    /// False if nothing is received from the other side for `split_link_timeout`.
+    pub alive: bool,
+    pub frames_sent: u32,
+    pub frames_received: u32,
+    /// Frames retransmitted because ACK didn't arrive in time.
+    pub retransmits: u32,
+    /// Received frames dropped because of CRC or framing errors.
+    pub errors: u32,
+    /// Messages which were given up or skipped.
+    pub lost: u32,
+    /// Number of times the split driver was re-initialized because the link was dead.
+    pub reinits: u32,
+    /// Round trip time (ms) of the last ping. Only measured on the master side.
+    pub latency_ms: Option<u32>,
+}
+
+/// Size of [`SyncState::payload`].
+pub const SYNC_PAYLOAD_SIZE: usize = 8;
+
+/// State of the master which is synchronized to the slave.
+///
+/// Master sends this to the slave when it is changed. The latest one can be obtained with
+/// [`crate::hooks::channels::split::sync_state`] on both sides.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, MaxSize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SyncState {
+    /// Bitmask of active layers. Bit `n` is set if layer `n` is active. Only the first 32 layers
+    /// are included.
+    pub layers: u32,
+    /// LED state sent by the host. Bit 0 is Num Lock, bit 1 is Caps Lock and bit 2 is Scroll Lock.
+    pub leds: u8,
+    /// `None` until master selects the output.
+    pub output: Option<Output>,
+    /// Active keymap profile
+    pub profile: u8,
+    /// Active host profile of the wireless reporter. `None` if it doesn't have multiple profiles.
+    pub ble_profile: Option<u8>,
+    /// Arbitrary data set by [`crate::hooks::channels::split::set_sync_payload`].
+    pub payload: [u8; SYNC_PAYLOAD_SIZE],
+}
+
+impl SyncState {
+    pub fn layer_active(&self, layer: u8) -> bool {
+        layer < 32 && self.layers & (1 << layer) != 0
+    }
+
+    pub fn num_lock(&self) -> bool {
+        self.leds & 1 != 0
+    }
+
+    pub fn caps_lock(&self) -> bool {
+        self.leds & 2 != 0
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, MaxSize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum MasterToSlave {
     Rgb(RgbCommand),
     Message(u8),
+    /// Keepalive sent by the split handler. Slave replies with [`SlaveToMaster::Pong`] with the
+    /// same value.
+    Ping(u8),
+    /// rrp request forwarded to the slave
+    Rrp(RrpChunk),
+    /// Sent when the state of the master is changed.
+    State(SyncState),
 }
 
 #[derive(Debug, Deserialize, Serialize, MaxSize)]
@@ -33,6 +112,36 @@ pub enum MasterToSlave {
 pub enum SlaveToMaster {
     Pressed(u8, u8),
     Released(u8, u8),
-    Mouse { x: i8, y: i8 },
+    /// Movement of the pointing device `id`. See [`crate::config::Hand::pointer_id`].
+    Pointer {
+        id: u8,
+        x: i8,
+        y: i8,
+    },
+    /// Encoder `id` of the slave side, in the same numbering as its encoder driver.
+    Encoder {
+        id: u8,
+        dir: EncoderDirection,
+    },
     Message(u8),
+    /// Reply to [`MasterToSlave::Ping`].
+    Pong(u8),
+    /// rrp response from the slave
+    Rrp(RrpChunk),
+    /// Bitmask of pressed cols in `row`.
+    ///
+    /// Slave sends this for each row periodically, so that master can recover from lost
+    /// `Pressed`/`Released`.
+    KeyState {
+        row: u8,
+        pressed: u32,
+    },
+    /// Battery status of the slave. Sent every time the battery is measured.
+    Battery(BatteryStatus),
 }
+
+// `SlaveToMaster::KeyState` has a bit for each col.
+const _: () = assert!(
+    CONST_CONFIG.keyboard.cols as u32 <= u32::BITS,
+    "Split keyboard supports up to 32 cols"
+);
@@ -1,8 +1,59 @@
+/// Usage statistics of the storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Number of erased pages since boot.
+    pub erase_count: u32,
+    /// Bytes which can be written without erasing a page.
+    pub free_bytes: u32,
+    pub capacity: u32,
+}
+
+/// Operation of a batch passed to [`StorageDriver::commit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageOp<'a> {
+    Write { key: u64, value: &'a [u8] },
+    Remove { key: u64 },
+}
+
+/// Returns true if the little-endian bytes of `key` start with `prefix`.
+pub fn key_has_prefix(key: u64, prefix: &[u8]) -> bool {
+    key.to_le_bytes().starts_with(prefix)
+}
+
 /// Storage driver interface
+///
+/// Storage is a map of `u64` keys to variable-length values. Keys whose most significant byte is
+/// `0xFF` are reserved for the driver's internal use.
 pub trait StorageDriver {
     type Error: super::Error;
 
+    /// Removes all values.
     async fn format(&self) -> Result<(), Self::Error>;
-    async fn read<const N: usize>(&self, key: u64, buf: &mut [u8]) -> Result<(), Self::Error>;
-    async fn write<const N: usize>(&self, key: u64, buf: &[u8]) -> Result<(), Self::Error>;
+
+    /// Reads value of `key` into `buf` and returns its length, or `None` if `key` is not stored.
+    ///
+    /// Fails if `buf` is smaller than the value.
+    async fn read(&self, key: u64, buf: &mut [u8]) -> Result<Option<usize>, Self::Error>;
+
+    async fn write(&self, key: u64, value: &[u8]) -> Result<(), Self::Error>;
+
+    /// Removes value of `key`. Does nothing if `key` is not stored.
+    async fn remove(&self, key: u64) -> Result<(), Self::Error>;
+
+    /// Stores keys which start with `prefix` (see [`key_has_prefix`]) into `keys` in no particular
+    /// order, and returns the number of them.
+    ///
+    /// If there are more keys than `keys.len()`, the rest is not returned.
+    async fn keys(&self, prefix: &[u8], keys: &mut [u64]) -> Result<usize, Self::Error>;
+
+    /// Applies all operations in order, atomically.
+    ///
+    /// Even if power is lost in the middle, either all or none of the operations are applied when
+    /// the storage is accessed next time.
+    async fn commit(&self, ops: &[StorageOp<'_>]) -> Result<(), Self::Error>;
+
+    /// Returns usage statistics of the storage, or `None` if the driver doesn't track them.
+    async fn stats(&self) -> Result<Option<StorageStats>, Self::Error> {
+        Ok(None)
+    }
 }
@@ -4,6 +4,19 @@ pub trait UsbReporterDriver: ReporterDriver {
     type Error: super::Error;
 
     async fn vbus_detect(&self);
+
+    /// Returns true if USB is plugged and the device is configured by the host.
+    ///
+    /// This is used to select output automatically. Drivers which cannot detect it always return
+    /// `true`.
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    /// Waits until the value of [`Self::is_connected`] may have changed.
+    async fn wait_connection_change(&self) {
+        core::future::pending::<()>().await
+    }
 }
 
 super::generate_builder!(UsbReporterDriver);
@@ -2,12 +2,52 @@
 
 use super::reporter::ReporterDriver;
 
+/// Size of one rrp packet over GATT of wireless reporters.
+///
+/// Like rrp over USB HID, the first byte of each packet is the length of the payload.
+pub const RRP_BLE_BUFFER_SIZE: usize = 32;
+
 /// BLE driver type.
 pub trait WirelessReporterDriver: ReporterDriver {
     type Error: super::Error;
 
     /// Clears all bond data
     async fn clear_bond_data(&self) -> Result<(), <Self as WirelessReporterDriver>::Error>;
+
+    /// Number of host profiles.
+    ///
+    /// Each profile has its own bond and advertising identity, so the keyboard can be paired with
+    /// multiple hosts and switched between them.
+    fn profile_count(&self) -> u8 {
+        1
+    }
+
+    /// Switches to `profile`, which is smaller than [`Self::profile_count`].
+    ///
+    /// The current host is disconnected and the keyboard connects to the host bonded to `profile`,
+    /// or advertises for a new host if none is bonded. This is also called at boot with the
+    /// profile restored from storage.
+    async fn select_profile(
+        &self,
+        _profile: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        Ok(())
+    }
+
+    /// Clears bond data of the active profile. Other profiles are kept.
+    async fn clear_profile_bond_data(&self) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        self.clear_bond_data().await
+    }
+
+    /// Sets battery level (%) which is reported to the host with Battery Service.
+    ///
+    /// This is called every time the battery of the master side is measured.
+    async fn set_battery_level(
+        &self,
+        _level: u8,
+    ) -> Result<(), <Self as WirelessReporterDriver>::Error> {
+        Ok(())
+    }
 }
 
 super::generate_builder!(WirelessReporterDriver);
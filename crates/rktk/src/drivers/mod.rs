@@ -6,8 +6,8 @@ use interface::{
 };
 
 use crate::drivers::interface::{
-    debounce::DebounceDriver, encoder::EncoderDriver, keyscan::KeyscanDriver, rgb::RgbDriver,
-    storage::StorageDriver,
+    battery::BatteryDriver, debounce::DebounceDriver, dfu::DfuDriver, encoder::EncoderDriver,
+    keyscan::KeyscanDriver, rgb::RgbDriver, storage::StorageDriver,
 };
 
 pub mod dummy;
@@ -29,6 +29,8 @@ pub struct Drivers<
     Usb: UsbReporterDriverBuilder,
     Display: DisplayDriver,
     Mouse: MouseDriver,
+    Dfu: DfuDriver,
+    Battery: BatteryDriver,
 > {
     pub system: System,
     pub keyscan: KeyScan,
@@ -41,4 +43,8 @@ pub struct Drivers<
     pub usb_builder: Option<Usb>,
     pub mouse: Option<Mouse>,
     pub display: Option<Display>,
+    /// Used to update firmware over rrp.
+    pub dfu: Option<Dfu>,
+    /// Used to report the battery level to the host and the display.
+    pub battery: Option<Battery>,
 }
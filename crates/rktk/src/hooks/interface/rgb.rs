@@ -1,4 +1,4 @@
-use crate::drivers::interface::rgb::*;
+use crate::drivers::interface::{rgb::*, split::SyncState};
 
 /// Hooks related to RGB functionality.
 ///
@@ -20,5 +20,22 @@ pub trait RgbHooks: 'static {
     /// * `_driver`: [`RgbDriver`] instance to control RGB.
     /// * `_rgb_mode`: [`RgbMode`] to be processed.
     async fn on_rgb_process(&mut self, _driver: &mut impl RgbDriver, _rgb_mode: &mut RgbMode) {}
+
+    /// Invoked when [`SyncState`] is changed. On the slave, this is invoked when the state is
+    /// received from the master.
+    ///
+    /// You can use this hook to indicate layers, LED state or the active BLE profile. After this
+    /// hook, the RGB task processes `_rgb_mode` again.
+    ///
+    /// * `_driver`: [`RgbDriver`] instance to control RGB.
+    /// * `_state`: The latest [`SyncState`].
+    /// * `_rgb_mode`: Current [`RgbMode`].
+    async fn on_sync_state(
+        &mut self,
+        _driver: &mut impl RgbDriver,
+        _state: &SyncState,
+        _rgb_mode: &mut RgbMode,
+    ) {
+    }
     async fn custom_rgb(&mut self, _driver: &mut impl RgbDriver, _brightness: f32) {}
 }
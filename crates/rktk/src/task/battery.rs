@@ -0,0 +1,50 @@
+use embassy_time::{Duration, Timer};
+use rktk_log::{debug, error};
+
+use crate::{
+    config::{Hand, schema::DynamicConfig},
+    drivers::interface::battery::{BatteryDriver, BatteryStatus},
+    task::channels::battery::{LOW_BATTERY_RGB_SIGNAL, update_battery_status},
+};
+
+/// Measures the battery periodically.
+///
+/// Measured status is sent to the display and RGB of this side, and passed to `report`, which
+/// reports it to the host on the master and to the master on the slave.
+pub async fn start<B: BatteryDriver>(
+    config: &'static DynamicConfig,
+    hand: Hand,
+    battery: Option<B>,
+    mut report: impl AsyncFnMut(BatteryStatus),
+) {
+    let Some(mut battery) = battery else {
+        debug!("No battery");
+        return;
+    };
+
+    let interval = Duration::from_millis(config.rktk.battery.update_interval);
+    loop {
+        match battery.read_voltage().await {
+            Ok(voltage) => {
+                let level = battery.discharge_curve().level(voltage);
+                let charging = battery.is_charging();
+                let status = BatteryStatus {
+                    level,
+                    charging,
+                    low: level < config.rktk.battery.low_level && charging != Some(true),
+                };
+                debug!("Battery: {}mV {:?}", voltage, status);
+
+                if status.low {
+                    LOW_BATTERY_RGB_SIGNAL.signal(());
+                }
+                update_battery_status(hand, status);
+                report(status).await;
+            }
+            Err(e) => {
+                error!("Failed to read battery: {:?}", e);
+            }
+        }
+        Timer::after(interval).await;
+    }
+}
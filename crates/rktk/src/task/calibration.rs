@@ -0,0 +1,33 @@
+//! Reading calibration data of the keyscan driver for rrp.
+
+use embassy_time::{Duration, with_timeout};
+use rktk_rrp::endpoints::get_calibration::{CHUNK_SIZE, CalibrationChunk};
+
+use super::channels::report::{
+    CALIBRATION_DATA_SIGNAL, CalibrationData, KEYBOARD_CONTROL_CHANNEL, KeyboardCommand,
+};
+
+/// Time to wait for the keyscan task to reply.
+const READ_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Reads calibration data from the keyscan task, and returns it as chunks of `get_calibration`.
+pub(crate) async fn read_calibration_chunks()
+-> Result<impl Iterator<Item = CalibrationChunk>, &'static str> {
+    CALIBRATION_DATA_SIGNAL.reset();
+    let data = with_timeout(READ_TIMEOUT, async {
+        KEYBOARD_CONTROL_CHANNEL.send(KeyboardCommand::ReadCalibration).await;
+        CALIBRATION_DATA_SIGNAL.wait().await
+    })
+    .await
+    .map_err(|_| "Calibration read timeout")?;
+    Ok(chunks(data))
+}
+
+fn chunks(data: CalibrationData) -> impl Iterator<Item = CalibrationChunk> {
+    (0..data.len()).step_by(CHUNK_SIZE).map(move |offset| {
+        let end = (offset + CHUNK_SIZE).min(data.len());
+        let mut chunk = heapless::Vec::new();
+        let _ = chunk.extend_from_slice(&data[offset..end]);
+        CalibrationChunk { offset: offset as u16, data: chunk }
+    })
+}
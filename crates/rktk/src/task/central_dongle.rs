@@ -0,0 +1,156 @@
+//! Dongle which processes keys of both halves.
+
+use embassy_futures::join::{join, join3, join5};
+use embassy_time::Duration;
+use rktk_log::info;
+
+use super::{
+    channels::split::{M2S_CHANNEL, M2sChannel, S2M_CHANNEL, S2mChannel},
+    display::{self, DisplayConfig},
+    initializers, master,
+    split_handler::{self, LinkState},
+};
+use crate::{
+    config::{Hand, RktkOpts},
+    drivers::{
+        dummy,
+        interface::{
+            dfu::DfuDriver, display::DisplayDriver, split::SplitDriver, storage::StorageDriver,
+            system::SystemDriver, usb::UsbReporterDriverBuilder,
+        },
+    },
+    hooks::interface::MasterHooks,
+    utils::{Channel, sjoin},
+};
+
+// Channels and state of the link to the right half. The left half uses the same ones as the
+// keyboard, so that rrp requests to the slave are forwarded to the left half.
+static RIGHT_S2M_CHANNEL: S2mChannel = Channel::new();
+static RIGHT_M2S_CHANNEL: M2sChannel = Channel::new();
+static RIGHT_LINK: LinkState = LinkState::new();
+
+/// Runs dongle which processes keys of both halves.
+///
+/// Unlike [`crate::dongle_task::start_dongle`], halves don't run keymap. Both halves run as slaves
+/// of split and send key events to the dongle through `left` and `right` split drivers, and the
+/// dongle runs keymap, rrp server and storage as master. So halves can sleep more and
+/// configuration is kept on the dongle.
+///
+/// The dongle must be built with the same `rktk.json` as the keyboard.
+#[allow(clippy::too_many_arguments)]
+pub async fn start_central_dongle<
+    Display: DisplayDriver,
+    D: DisplayConfig<Color = Display::Color> + 'static,
+    RL: blinksy::layout::Layout2d + 'static,
+>(
+    #[allow(unused_variables, reason = "`spawner` is unused when `alloc` is disabled")]
+    spawner: embassy_executor::Spawner,
+    system: impl SystemDriver,
+    usb: impl UsbReporterDriverBuilder,
+    left: impl SplitDriver,
+    right: impl SplitDriver,
+    storage: Option<impl StorageDriver>,
+    #[allow(unused_variables, reason = "`dfu` is unused when `rrp` is disabled")] dfu: Option<
+        impl DfuDriver,
+    >,
+    hooks: impl MasterHooks,
+    display: Option<Display>,
+    mut opts: RktkOpts<D, RL>,
+) {
+    info!("Booting rktk dongle");
+
+    let ((wireless, _), (usb, usb_task)) =
+        initializers::init_reporters(dummy::ble_builder(), Some(usb)).await;
+    crate::utils::display_state!(Master, Some(true));
+
+    let (config, keymap) = (opts.config, opts.keymap);
+    let link_timeout = Duration::from_millis(config.rktk.split_link_timeout);
+
+    sjoin::join!(
+        spawner,
+        async {
+            let config_store = master::utils::init_storage(storage, keymap).await;
+            let state = master::utils::load_state(&config.key_manager, &config_store, keymap).await;
+
+            join3(
+                join5(
+                    master::report::report_task(
+                        config,
+                        keymap,
+                        &system,
+                        &state,
+                        &config_store,
+                        &wireless,
+                        &usb,
+                        hooks,
+                    ),
+                    master::handle_slave::start(
+                        config,
+                        Hand::Left,
+                        S2M_CHANNEL.receiver(),
+                        &split_handler::LINK,
+                    ),
+                    master::handle_slave::start(
+                        config,
+                        Hand::Right,
+                        RIGHT_S2M_CHANNEL.receiver(),
+                        &RIGHT_LINK,
+                    ),
+                    split_handler::start(
+                        left,
+                        S2M_CHANNEL.sender(),
+                        M2S_CHANNEL.receiver(),
+                        true,
+                        link_timeout,
+                        &split_handler::LINK,
+                    ),
+                    split_handler::start(
+                        right,
+                        RIGHT_S2M_CHANNEL.sender(),
+                        RIGHT_M2S_CHANNEL.receiver(),
+                        true,
+                        link_timeout,
+                        &RIGHT_LINK,
+                    ),
+                ),
+                async {
+                    #[cfg(feature = "rrp")]
+                    master::rrp_server::start(
+                        config,
+                        keymap,
+                        &system,
+                        &usb,
+                        &wireless,
+                        &state,
+                        &config_store,
+                        dfu,
+                    )
+                    .await;
+                },
+                join(
+                    async {
+                        if let Some(store) = &config_store {
+                            store
+                                .commit_task(Duration::from_millis(
+                                    config.rktk.storage_commit_delay,
+                                ))
+                                .await;
+                        }
+                    },
+                    master::sync_state::start(&[M2S_CHANNEL.sender(), RIGHT_M2S_CHANNEL.sender()]),
+                ),
+            )
+            .await;
+        },
+        async {
+            if let Some(usb_task) = usb_task {
+                usb_task.await
+            }
+        },
+        async move {
+            if let Some(mut display) = display {
+                display::start(&mut display, &mut opts.display).await;
+            }
+        }
+    );
+}
@@ -18,45 +18,169 @@ pub mod rgb {
 }
 
 pub mod split {
+    use core::cell::Cell;
+
+    use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+
     use crate::{
         config::CONST_CONFIG,
-        drivers::interface::split::{MasterToSlave, SlaveToMaster},
+        drivers::interface::split::{MasterToSlave, SYNC_PAYLOAD_SIZE, SlaveToMaster, SyncState},
+        utils::{RawMutex, Signal},
     };
 
     use super::*;
 
-    type S2mChannel = Channel<SlaveToMaster, { CONST_CONFIG.buffer.split_channel }>;
+    pub(crate) type S2mChannel = Channel<SlaveToMaster, { CONST_CONFIG.buffer.split_channel }>;
     pub type S2mRx<'a> = Receiver<'a, SlaveToMaster, { CONST_CONFIG.buffer.split_channel }>;
     pub type S2mTx<'a> = Sender<'a, SlaveToMaster, { CONST_CONFIG.buffer.split_channel }>;
 
-    type M2sChannel = Channel<MasterToSlave, { CONST_CONFIG.buffer.split_channel }>;
+    pub(crate) type M2sChannel = Channel<MasterToSlave, { CONST_CONFIG.buffer.split_channel }>;
     pub type M2sRx<'a> = Receiver<'a, MasterToSlave, { CONST_CONFIG.buffer.split_channel }>;
     pub type M2sTx<'a> = Sender<'a, MasterToSlave, { CONST_CONFIG.buffer.split_channel }>;
 
     pub(crate) static M2S_CHANNEL: M2sChannel = Channel::new();
     pub(crate) static S2M_CHANNEL: S2mChannel = Channel::new();
+
+    static SYNC_STATE: BlockingMutex<RawMutex, Cell<SyncState>> =
+        BlockingMutex::new(Cell::new(SyncState {
+            layers: 0,
+            leds: 0,
+            output: None,
+            profile: 0,
+            ble_profile: None,
+            payload: [0; SYNC_PAYLOAD_SIZE],
+        }));
+    /// Signaled on master when [`SyncState`] should be sent to the slave.
+    pub(crate) static SYNC_STATE_SEND_SIGNAL: Signal<()> = Signal::new();
+    /// Signaled when [`SyncState`] is changed, to notify the RGB task.
+    pub(crate) static SYNC_STATE_RGB_SIGNAL: Signal<()> = Signal::new();
+
+    /// Returns the latest [`SyncState`].
+    ///
+    /// On the slave, this is the state received from the master.
+    pub fn sync_state() -> SyncState {
+        SYNC_STATE.lock(|s| s.get())
+    }
+
+    /// Sets [`SyncState::payload`], which is sent to the slave if changed.
+    ///
+    /// This should be called on the master. On the slave, the payload is overwritten by the one
+    /// received from the master.
+    pub fn set_sync_payload(payload: [u8; SYNC_PAYLOAD_SIZE]) {
+        update_sync_state(|s| s.payload = payload);
+    }
+
+    pub(crate) fn update_sync_state(f: impl FnOnce(&mut SyncState)) {
+        let changed = SYNC_STATE.lock(|s| {
+            let mut state = s.get();
+            f(&mut state);
+            s.replace(state) != state
+        });
+        if changed {
+            SYNC_STATE_SEND_SIGNAL.signal(());
+            SYNC_STATE_RGB_SIGNAL.signal(());
+        }
+    }
+}
+
+pub mod battery {
+    use core::cell::Cell;
+
+    use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+
+    use crate::{
+        config::Hand,
+        drivers::interface::battery::BatteryStatus,
+        utils::{RawMutex, Signal, display_state},
+    };
+
+    /// Latest battery status of the left and right side.
+    static BATTERY_STATUS: BlockingMutex<RawMutex, Cell<[Option<BatteryStatus>; 2]>> =
+        BlockingMutex::new(Cell::new([None; 2]));
+    /// Signaled every time the battery of this side is measured as low, to warn on RGB.
+    pub(crate) static LOW_BATTERY_RGB_SIGNAL: Signal<()> = Signal::new();
+
+    /// Returns the latest battery status of `hand`.
+    ///
+    /// On the master, status of the slave is the one received over split. On the slave, only the
+    /// status of its own side is available.
+    pub fn battery_status(hand: Hand) -> Option<BatteryStatus> {
+        BATTERY_STATUS.lock(|s| s.get()[index(hand)])
+    }
+
+    pub(crate) fn update_battery_status(hand: Hand, status: BatteryStatus) {
+        let changed = BATTERY_STATUS.lock(|s| {
+            let mut all = s.get();
+            let prev = all[index(hand)].replace(status);
+            s.set(all);
+            prev != Some(status)
+        });
+        if changed {
+            display_state!(Battery, (hand, status));
+        }
+    }
+
+    fn index(hand: Hand) -> usize {
+        match hand {
+            Hand::Left => 0,
+            Hand::Right => 1,
+        }
+    }
 }
 
 pub mod report {
     use core::sync::atomic::Ordering;
 
-    use kmsm::interface::state::input_event::{EncoderDirection, KeyChangeEvent};
+    use kmsm::interface::state::{
+        config::MAX_POINTERS,
+        input_event::{EncoderDirection, KeyChangeEvent},
+    };
     use portable_atomic::AtomicI8;
 
-    use crate::{config::CONST_CONFIG, utils::Signal};
+    use crate::{config::CONST_CONFIG, drivers::interface::keyscan::KeyscanDriver, utils::Signal};
 
     use super::*;
 
-    pub(crate) static MOUSE_CHANGE_X: AtomicI8 = AtomicI8::new(0);
-    pub(crate) static MOUSE_CHANGE_Y: AtomicI8 = AtomicI8::new(0);
+    /// Movement of each pointing device which is not reported yet. Index is the pointer id.
+    static MOUSE_CHANGE: [(AtomicI8, AtomicI8); MAX_POINTERS] =
+        [const { (AtomicI8::new(0), AtomicI8::new(0)) }; MAX_POINTERS];
     pub(crate) static MOUSE_CHANGE_SIGNAL: Signal<()> = Signal::new();
 
+    /// Same as `update_pointer(0, x, y)`.
     pub fn update_mouse(x: i8, y: i8) {
-        MOUSE_CHANGE_X.fetch_add(x, Ordering::Release);
-        MOUSE_CHANGE_Y.fetch_add(y, Ordering::Release);
+        update_pointer(0, x, y);
+    }
+
+    /// Reports movement of the pointing device `id`.
+    ///
+    /// Pointer id is `0` for the left hand and `1` for the right hand (see
+    /// [`crate::config::Hand::pointer_id`]). Movement of ids larger than that is ignored.
+    pub fn update_pointer(id: u8, x: i8, y: i8) {
+        let Some((change_x, change_y)) = MOUSE_CHANGE.get(id as usize) else {
+            return;
+        };
+        change_x.fetch_add(x, Ordering::Release);
+        change_y.fetch_add(y, Ordering::Release);
         MOUSE_CHANGE_SIGNAL.signal(());
     }
 
+    /// Takes movement of one pointing device which has moved.
+    ///
+    /// If other devices have also moved, [`MOUSE_CHANGE_SIGNAL`] is signaled again so that they are
+    /// taken next time.
+    pub(crate) fn take_pointer_move() -> Option<(u8, (i8, i8))> {
+        let moved = |(x, y): &(AtomicI8, AtomicI8)| {
+            x.load(Ordering::Acquire) != 0 || y.load(Ordering::Acquire) != 0
+        };
+        let id = MOUSE_CHANGE.iter().position(moved)?;
+        let (x, y) = &MOUSE_CHANGE[id];
+        let movement = (x.swap(0, Ordering::Acquire), y.swap(0, Ordering::Acquire));
+        if MOUSE_CHANGE[id + 1..].iter().any(moved) {
+            MOUSE_CHANGE_SIGNAL.signal(());
+        }
+        Some((id as u8, movement))
+    }
+
     pub(crate) static KEYBOARD_EVENT_REPORT_CHANNEL: Channel<
         KeyChangeEvent,
         { CONST_CONFIG.buffer.keyboard_event },
@@ -81,7 +205,27 @@ pub mod report {
     pub enum KeyboardCommand {
         StartCalibration,
         EndCalibration,
+        /// Reads calibration data of the keyscan driver into [`CALIBRATION_DATA_SIGNAL`].
+        ReadCalibration,
     }
 
     pub(crate) static KEYBOARD_CONTROL_CHANNEL: Channel<KeyboardCommand, 2> = Channel::new();
+
+    /// Calibration data of the keyscan driver. Empty if the driver doesn't support calibration.
+    pub(crate) type CalibrationData = heapless::Vec<u8, { CONST_CONFIG.buffer.calibration }>;
+
+    /// Signaled by the keyscan task in reply to [`KeyboardCommand::ReadCalibration`].
+    pub(crate) static CALIBRATION_DATA_SIGNAL: Signal<CalibrationData> = Signal::new();
+
+    /// Reads calibration data of `keyscan`.
+    pub(crate) fn read_calibration<K: KeyscanDriver>(keyscan: &K) -> CalibrationData {
+        let mut data = CalibrationData::new();
+        if K::CALIBRATION_SIZE > 0
+            && data.resize(K::CALIBRATION_SIZE, 0).is_ok()
+            && keyscan.save_calibration(&mut data).is_err()
+        {
+            data.clear();
+        }
+        data
+    }
 }
@@ -8,6 +8,7 @@ use embedded_graphics::{
 use images::*;
 
 use crate::{
+    config::Hand,
     drivers::interface::{display::DisplayDriver, reporter::Output},
     utils::{Channel, Signal},
 };
@@ -117,6 +118,65 @@ impl DisplayConfig for DefaultDisplayConfig {
                         )
                         .draw(display.draw_target());
                     }
+                    DisplayMessage::Profile(profile) => {
+                        let style = MonoTextStyleBuilder::new()
+                            .font(&FONT_8X13)
+                            .text_color(BinaryColor::On)
+                            .background_color(BinaryColor::Off)
+                            .build();
+                        let _ = Text::with_baseline("P", Point::new(14, 55), style, Baseline::Top)
+                            .draw(display.draw_target());
+                        let _ = Text::with_baseline(
+                            get_last_digit_str(profile),
+                            Point::new(22, 55),
+                            style,
+                            Baseline::Top,
+                        )
+                        .draw(display.draw_target());
+                    }
+                    DisplayMessage::BleProfile(profile) => {
+                        let style = MonoTextStyleBuilder::new()
+                            .font(&FONT_8X13)
+                            .text_color(BinaryColor::On)
+                            .background_color(BinaryColor::Off)
+                            .build();
+                        let _ = Text::with_baseline("B", Point::new(14, 68), style, Baseline::Top)
+                            .draw(display.draw_target());
+                        let _ = Text::with_baseline(
+                            get_last_digit_str(profile),
+                            Point::new(22, 68),
+                            style,
+                            Baseline::Top,
+                        )
+                        .draw(display.draw_target());
+                    }
+                    DisplayMessage::Battery((hand, status)) => {
+                        let (label, y) = match hand {
+                            Hand::Left => ('L', 94),
+                            Hand::Right => ('R', 107),
+                        };
+                        let mut text = heapless::String::<4>::new();
+                        let _ = core::fmt::write(
+                            &mut text,
+                            format_args!("{}{:>3}", label, status.level),
+                        );
+                        // Inverted while the battery is low.
+                        let style = MonoTextStyleBuilder::new()
+                            .font(&FONT_8X13)
+                            .text_color(if status.low {
+                                BinaryColor::Off
+                            } else {
+                                BinaryColor::On
+                            })
+                            .background_color(if status.low {
+                                BinaryColor::On
+                            } else {
+                                BinaryColor::Off
+                            })
+                            .build();
+                        let _ = Text::with_baseline(&text, Point::new(0, y), style, Baseline::Top)
+                            .draw(display.draw_target());
+                    }
                     DisplayMessage::Brightness(brightness) => {
                         let _ = display.set_brightness(brightness).await;
                     }
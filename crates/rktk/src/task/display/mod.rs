@@ -3,7 +3,9 @@ use rktk_log::error;
 use crate::{
     config::CONST_CONFIG,
     config::Hand,
-    drivers::interface::{display::DisplayDriver, reporter::Output},
+    drivers::interface::{
+        battery::BatteryStatus, display::DisplayDriver, reporter::Output, split::SplitLinkStats,
+    },
     utils::{Channel, Signal},
 };
 
@@ -22,8 +24,17 @@ pub enum DisplayMessage {
     Hand(Option<Hand>),
     NumLock(bool),
     CapsLock(bool),
+    /// Active keymap profile. Only sent if the keyboard has more than one profile.
+    Profile(u8),
+    /// Active host profile of the wireless reporter. Only sent if it has more than one profile.
+    BleProfile(u8),
     Brightness(u8),
     On(bool),
+    /// Sent when the split link becomes alive or dead, and when latency is measured.
+    SplitLink(SplitLinkStats),
+    /// Sent when the battery status of either side is changed. On the master, this is also sent
+    /// for the slave side.
+    Battery((Hand, BatteryStatus)),
 }
 
 use embedded_graphics::prelude::PixelColor;
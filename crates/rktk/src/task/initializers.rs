@@ -10,7 +10,7 @@ use crate::{
         usb::{UsbReporterDriver, UsbReporterDriverBuilder},
         wireless::{WirelessReporterDriver, WirelessReporterDriverBuilder},
     },
-    task::split_handler,
+    task::split_handler::{self, LINK},
 };
 
 use super::channels::split::{M2S_CHANNEL, M2sRx, M2sTx, S2M_CHANNEL, S2mRx, S2mTx};
@@ -104,6 +104,7 @@ pub async fn init_split<'a>(
     }
 
     let is_master = detect_role(config, &split, usb, wireless).await;
+    let link_timeout = Duration::from_millis(config.rktk.split_link_timeout);
 
     let s2m_tx = S2M_CHANNEL.sender();
     let s2m_rx = S2M_CHANNEL.receiver();
@@ -117,7 +118,8 @@ pub async fn init_split<'a>(
         KeyboardRoleRes::Master {
             sender: m2s_tx,
             receiver: s2m_rx,
-            task: split.map(|s| split_handler::start(s, s2m_tx, m2s_rx, is_master)),
+            task: split
+                .map(|s| split_handler::start(s, s2m_tx, m2s_rx, is_master, link_timeout, &LINK)),
         }
     } else {
         debug!("Split is slave");
@@ -125,7 +127,8 @@ pub async fn init_split<'a>(
         KeyboardRoleRes::Slave {
             sender: s2m_tx,
             receiver: m2s_rx,
-            task: split.map(|s| split_handler::start(s, m2s_tx, s2m_rx, is_master)),
+            task: split
+                .map(|s| split_handler::start(s, m2s_tx, s2m_rx, is_master, link_timeout, &LINK)),
         }
     }
 }
@@ -10,7 +10,8 @@ use crate::{
         debounce::DebounceDriver, keyscan::KeyscanDriver, storage::StorageDriver,
     },
     task::channels::report::{
-        KEYBOARD_CONTROL_CHANNEL, KEYBOARD_EVENT_REPORT_CHANNEL, KeyboardCommand,
+        CALIBRATION_DATA_SIGNAL, KEYBOARD_CONTROL_CHANNEL, KEYBOARD_EVENT_REPORT_CHANNEL,
+        KeyboardCommand, read_calibration,
     },
 };
 
@@ -67,17 +68,23 @@ pub async fn start<KeyScan: KeyscanDriver, Debounce: DebounceDriver, S: StorageD
                     {
                         let mut buf = [0u8; crate::config::CONST_CONFIG.buffer.calibration];
                         if keyscan.save_calibration(&mut buf[..KeyScan::CALIBRATION_SIZE]).is_ok() {
-                            match store.write_calibration::<{ crate::config::CONST_CONFIG.buffer.calibration }>(&buf).await {
-                                        Ok(()) => {
-                                            debug!("Calibration data saved to flash successfully");
-                                        }
-                                        Err(e) => {
-                                            warn!("Failed to save calibration data: {:?}", Debug2Format(&e));
-                                        }
-                                    }
+                            match store.write_calibration(&buf).await {
+                                Ok(()) => {
+                                    debug!("Calibration data saved to flash successfully");
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        "Failed to save calibration data: {:?}",
+                                        Debug2Format(&e)
+                                    );
+                                }
+                            }
                         }
                     }
                 }
+                KeyboardCommand::ReadCalibration => {
+                    CALIBRATION_DATA_SIGNAL.signal(read_calibration(&keyscan));
+                }
             },
             Either::Second(_) => {}
         }
@@ -1,11 +1,16 @@
 use embassy_time::Duration;
 
 use crate::{
-    config::schema::DynamicConfig, drivers::interface::mouse::MouseDriver,
-    task::channels::report::update_mouse,
+    config::{Hand, schema::DynamicConfig},
+    drivers::interface::mouse::MouseDriver,
+    task::channels::report::update_pointer,
 };
 
-pub async fn start(config: &'static DynamicConfig, mut mouse: Option<impl MouseDriver>) {
+pub async fn start(
+    config: &'static DynamicConfig,
+    hand: Hand,
+    mut mouse: Option<impl MouseDriver>,
+) {
     if let Some(mouse) = &mut mouse {
         let mut latest = embassy_time::Instant::from_millis(0);
         let interval = Duration::from_millis(config.rktk.scan_interval_mouse);
@@ -37,7 +42,7 @@ pub async fn start(config: &'static DynamicConfig, mut mouse: Option<impl MouseD
                 continue;
             }
 
-            update_mouse(mouse_move.0, mouse_move.1);
+            update_pointer(hand.pointer_id(), mouse_move.0, mouse_move.1);
 
             latest = embassy_time::Instant::now();
         }
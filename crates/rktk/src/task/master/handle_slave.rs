@@ -1,42 +1,109 @@
+use embassy_futures::select::{Either, select};
 use kmsm::interface::state::input_event::KeyChangeEvent;
-use rktk_log::debug;
+use rktk_log::{debug, warn};
 
-use super::utils::get_split_right_shift;
+use super::utils::{get_split_right_shift, resolve_entire_encoder_id};
 use crate::{
     config::Hand,
-    config::schema::DynamicConfig,
+    config::{CONST_CONFIG, schema::DynamicConfig},
     drivers::interface::split::SlaveToMaster,
     task::channels::{
-        report::{KEYBOARD_EVENT_REPORT_CHANNEL, update_mouse},
+        battery::update_battery_status,
+        report::{ENCODER_EVENT_REPORT_CHANNEL, KEYBOARD_EVENT_REPORT_CHANNEL, update_pointer},
         split::S2mRx,
     },
+    task::split_handler::LinkState,
 };
 
 use super::utils::resolve_entire_key_pos;
 
-pub async fn start(config: &'static DynamicConfig, hand: Hand, s2m_rx: S2mRx<'_>) {
+/// Handles messages from the slave of `slave_hand`.
+pub async fn start(
+    config: &'static DynamicConfig,
+    slave_hand: Hand,
+    s2m_rx: S2mRx<'_>,
+    link: &LinkState,
+) {
     debug!("split recv start");
 
-    let slave_hand = hand.other();
     let shift = get_split_right_shift(config);
+    // Pressed cols of each row of the slave side, which master has received.
+    let mut slave_state = [0u32; CONST_CONFIG.keyboard.rows as usize];
+    let send_key = async |row: u8, col: u8, pressed: bool| {
+        let mut ev = KeyChangeEvent { col, row, pressed };
+        resolve_entire_key_pos(&mut ev, slave_hand, shift);
+        KEYBOARD_EVENT_REPORT_CHANNEL.send(ev).await;
+    };
     loop {
-        s2m_rx.ready_to_receive().await;
+        let link_down = select(s2m_rx.ready_to_receive(), link.down.wait()).await;
+        if let Either::Second(()) = link_down {
+            // Release events from the slave may be lost, so release all keys of the slave side.
+            for (row, state) in slave_state.iter_mut().enumerate() {
+                for col in (0..32).filter(|col| *state & (1 << col) != 0) {
+                    send_key(row as u8, col, false).await;
+                }
+                *state = 0;
+            }
+            continue;
+        }
         while let Ok(cmd_from_slave) = s2m_rx.try_receive() {
             match cmd_from_slave {
                 SlaveToMaster::Pressed(row, col) => {
-                    let mut ev = KeyChangeEvent { col, row, pressed: true };
-                    resolve_entire_key_pos(&mut ev, slave_hand, shift);
-                    KEYBOARD_EVENT_REPORT_CHANNEL.send(ev).await;
+                    if let Some(state) = slave_state.get_mut(row as usize)
+                        && col < 32
+                    {
+                        *state |= 1 << col;
+                    }
+                    send_key(row, col, true).await;
                 }
                 SlaveToMaster::Released(row, col) => {
-                    let mut ev = KeyChangeEvent { col, row, pressed: false };
-                    resolve_entire_key_pos(&mut ev, slave_hand, shift);
-                    KEYBOARD_EVENT_REPORT_CHANNEL.send(ev).await;
+                    if let Some(state) = slave_state.get_mut(row as usize)
+                        && col < 32
+                    {
+                        *state &= !(1 << col);
+                    }
+                    send_key(row, col, false).await;
+                }
+                SlaveToMaster::KeyState { row, pressed } => {
+                    let Some(state) = slave_state.get_mut(row as usize) else {
+                        continue;
+                    };
+                    let diff = *state ^ pressed;
+                    if diff != 0 {
+                        warn!(
+                            "Slave key state mismatch in row {}: {:b} -> {:b}",
+                            row, *state, pressed
+                        );
+                        *state = pressed;
+                    }
+                    for col in (0..32).filter(|col| diff & (1 << col) != 0) {
+                        send_key(row, col, pressed & (1 << col) != 0).await;
+                    }
                 }
-                SlaveToMaster::Mouse { x, y } => {
-                    update_mouse(x, y);
+                SlaveToMaster::Pointer { id, x, y } => {
+                    update_pointer(id, x, y);
+                }
+                SlaveToMaster::Encoder { id, dir } => {
+                    let Some(id) = resolve_entire_encoder_id(id, slave_hand, config) else {
+                        warn!("enc id out of range: {}", id);
+                        continue;
+                    };
+                    if ENCODER_EVENT_REPORT_CHANNEL.try_send((id, dir)).is_err() {
+                        warn!("enc full");
+                    }
+                }
+                SlaveToMaster::Battery(status) => {
+                    update_battery_status(slave_hand, status);
                 }
                 SlaveToMaster::Message(_) => {}
+                // Handled in the split handler
+                SlaveToMaster::Pong(_) => {}
+                #[cfg(feature = "rrp")]
+                SlaveToMaster::Rrp(chunk) => {
+                    crate::task::split_rrp::on_chunk_received(chunk).await;
+                }
+                #[cfg(not(feature = "rrp"))]
+                SlaveToMaster::Rrp(_) => {}
             }
         }
     }
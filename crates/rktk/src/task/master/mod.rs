@@ -8,20 +8,31 @@ pub(super) mod handle_slave;
 pub(super) mod report;
 #[cfg(feature = "rrp")]
 pub(super) mod rrp_server;
+pub(super) mod sync_state;
 pub(super) mod utils;
 
 pub(super) mod handle_encoder {
     use rktk_log::warn;
 
+    use super::utils::resolve_entire_encoder_id;
     use crate::{
+        config::{Hand, schema::DynamicConfig},
         drivers::interface::encoder::EncoderDriver,
         task::channels::report::ENCODER_EVENT_REPORT_CHANNEL,
     };
 
-    pub async fn start(enc: &mut Option<impl EncoderDriver>) {
+    pub async fn start(
+        config: &'static DynamicConfig,
+        hand: Hand,
+        enc: &mut Option<impl EncoderDriver>,
+    ) {
         if let Some(encoder) = enc.as_mut() {
             loop {
                 let (id, dir) = encoder.read_wait().await;
+                let Some(id) = resolve_entire_encoder_id(id, hand, config) else {
+                    warn!("enc id out of range: {}", id);
+                    continue;
+                };
                 if ENCODER_EVENT_REPORT_CHANNEL.try_send((id, dir)).is_err() {
                     warn!("enc full");
                 }
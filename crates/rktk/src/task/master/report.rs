@@ -1,17 +1,17 @@
-use core::sync::atomic::Ordering;
-
-use embassy_futures::select::{Either, Either4, select, select4};
+use embassy_futures::select::{Either3, Either4, select3, select4};
 use embassy_time::{Duration, Instant};
 use kmsm::interface::state::output_event::EventType;
 use kmsm::interface::state::{input_event::InputEvent, output_event::OutputEvent};
 use kmsm::state::hid_report::Report;
 use rktk_log::{debug, helper::Debug2Format};
 
-use crate::config::keymap::prelude::RktkKeys;
-use crate::config::schema::DynamicConfig;
+use crate::config::CONST_CONFIG;
+use crate::config::keymap::{Keymap, prelude::RktkKeys};
+use crate::config::schema::{DynamicConfig, RktkOutputPolicy};
 use crate::drivers::interface::rgb::{RgbCommand, RgbMode, RgbPattern};
-use crate::task::channels::report::{MOUSE_CHANGE_SIGNAL, MOUSE_CHANGE_X, MOUSE_CHANGE_Y};
+use crate::task::channels::report::{MOUSE_CHANGE_SIGNAL, take_pointer_move};
 use crate::task::channels::rgb::RGB_CHANNEL;
+use crate::task::channels::split::update_sync_state;
 use crate::{
     config::storage::StorageConfigManager,
     drivers::interface::{
@@ -29,7 +29,10 @@ use crate::{
     utils::display_state,
 };
 
-use super::SharedState;
+use super::{
+    SharedState,
+    utils::{load_ble_profile, switch_ble_profile, switch_profile},
+};
 
 pub async fn report_task<
     System: SystemDriver,
@@ -39,6 +42,7 @@ pub async fn report_task<
     MH: MasterHooks,
 >(
     config: &'static DynamicConfig,
+    keymap: &'static Keymap,
     system: &System,
     state: &SharedState,
     config_store: &Option<StorageConfigManager<S>>,
@@ -49,7 +53,14 @@ pub async fn report_task<
     debug!("report task start");
 
     let mut prev_update_time = embassy_time::Instant::now();
-    let mut current_output = if usb.is_some() { Output::Usb } else { Output::Ble };
+    let auto_output = matches!(config.rktk.output_policy, RktkOutputPolicy::Auto);
+    let mut current_output = if auto_output {
+        auto_select_output(usb, ble)
+    } else if usb.is_some() {
+        Output::Usb
+    } else {
+        Output::Ble
+    };
     let mut display_off =
         DisplayOffController::new(Duration::from_millis(config.rktk.display_timeout));
     let mut mag_cal_enabled = false;
@@ -57,31 +68,39 @@ pub async fn report_task<
     let mut last_layer_active = None;
     let mut last_output = None;
 
+    let ble_profile_count = ble.as_ref().map_or(1, |ble| ble.profile_count().max(1));
+    let mut ble_profile = load_ble_profile(ble, config_store).await;
+
     loop {
         let event = match select4(
             MOUSE_CHANGE_SIGNAL.wait(),
             KEYBOARD_EVENT_REPORT_CHANNEL.receive(),
             ENCODER_EVENT_REPORT_CHANNEL.receive(),
-            select(
+            select3(
                 read_keyboard_report(usb, ble, current_output),
                 embassy_time::Timer::after(Duration::from_millis(
                     config.rktk.state_update_interval,
                 )),
+                async {
+                    match usb {
+                        Some(usb) if auto_output => usb.wait_connection_change().await,
+                        _ => core::future::pending().await,
+                    }
+                },
             ),
         )
         .await
         {
             Either4::First(_) => {
-                let mut mouse_move: (i8, i8) = (
-                    MOUSE_CHANGE_X.swap(0, Ordering::Acquire),
-                    MOUSE_CHANGE_Y.swap(0, Ordering::Acquire),
-                );
+                let Some((id, mut mouse_move)) = take_pointer_move() else {
+                    continue;
+                };
 
                 if !master_hooks.on_mouse_event(&mut mouse_move).await {
                     continue;
                 }
 
-                InputEvent::Mouse(mouse_move)
+                InputEvent::Pointer((id, mouse_move))
             }
             Either4::Second(mut event) => {
                 if !master_hooks.on_keyboard_event(&mut event).await {
@@ -98,14 +117,19 @@ pub async fn report_task<
                 InputEvent::Encoder((id, dir))
             }
             Either4::Fourth(r) => match r {
-                Either::First(report) => {
+                Either3::First(report) => {
                     if let Ok(report) = report {
                         crate::utils::display_state!(NumLock, (report & 1) == 1);
                         crate::utils::display_state!(CapsLock, (report & 2) == 2);
+                        update_sync_state(|s| s.leds = report);
                     }
                     continue;
                 }
-                Either::Second(_) => InputEvent::None,
+                Either3::Second(_) => InputEvent::None,
+                Either3::Third(_) => {
+                    current_output = auto_select_output(usb, ble);
+                    InputEvent::None
+                }
             },
         };
 
@@ -115,6 +139,9 @@ pub async fn report_task<
             flash_clear: bool,
             power_off: bool,
             mag_cal: bool,
+            profile: Option<u8>,
+            ble_profile: Option<u8>,
+            ble_profile_clear: bool,
         }
         let mut rktk_key_state = RktkKeyState {
             bootloader: false,
@@ -122,6 +149,9 @@ pub async fn report_task<
             flash_clear: false,
             power_off: false,
             mag_cal: false,
+            profile: None,
+            ble_profile: None,
+            ble_profile_clear: false,
         };
 
         let (mut state_report, layer_active) = {
@@ -141,6 +171,32 @@ pub async fn report_task<
                                 RktkKeys::Bootloader => rktk_key_state.bootloader = true,
                                 RktkKeys::PowerOff => rktk_key_state.power_off = true,
                                 RktkKeys::MagCal => rktk_key_state.mag_cal = true,
+                                RktkKeys::ProfileNext => {
+                                    rktk_key_state.profile = config_store.as_ref().map(|s| {
+                                        (s.active_profile() + 1)
+                                            % CONST_CONFIG.key_manager.profile_count.max(1)
+                                    })
+                                }
+                                RktkKeys::Profile0 => rktk_key_state.profile = Some(0),
+                                RktkKeys::Profile1 => rktk_key_state.profile = Some(1),
+                                RktkKeys::Profile2 => rktk_key_state.profile = Some(2),
+                                RktkKeys::Profile3 => rktk_key_state.profile = Some(3),
+                                RktkKeys::BleProfileNext => {
+                                    rktk_key_state.ble_profile =
+                                        Some((ble_profile + 1) % ble_profile_count)
+                                }
+                                RktkKeys::BleProfilePrev => {
+                                    rktk_key_state.ble_profile = Some(
+                                        (ble_profile + ble_profile_count - 1) % ble_profile_count,
+                                    )
+                                }
+                                RktkKeys::BleProfile0 => rktk_key_state.ble_profile = Some(0),
+                                RktkKeys::BleProfile1 => rktk_key_state.ble_profile = Some(1),
+                                RktkKeys::BleProfile2 => rktk_key_state.ble_profile = Some(2),
+                                RktkKeys::BleProfile3 => rktk_key_state.ble_profile = Some(3),
+                                RktkKeys::BleProfileClear => {
+                                    rktk_key_state.ble_profile_clear = true
+                                }
                                 RktkKeys::RgbOff => {
                                     let _ = RGB_CHANNEL
                                         .sender()
@@ -180,6 +236,13 @@ pub async fn report_task<
 
         if last_layer_active != Some(layer_active) {
             crate::utils::display_state!(LayerState, layer_active);
+            update_sync_state(|s| {
+                s.layers = layer_active
+                    .iter()
+                    .take(32)
+                    .enumerate()
+                    .fold(0, |mask, (i, active)| mask | ((*active as u32) << i));
+            });
             last_layer_active = Some(layer_active);
         }
 
@@ -201,12 +264,48 @@ pub async fn report_task<
             }
         }
 
+        if let Some(profile) = rktk_key_state.profile {
+            if profile >= CONST_CONFIG.key_manager.profile_count {
+                crate::print!("Profile {} is not available", profile);
+            } else if let Some(storage) = config_store.as_ref() {
+                if profile != storage.active_profile()
+                    && let Err(e) =
+                        switch_profile(&config.key_manager, state, storage, keymap, profile).await
+                {
+                    rktk_log::error!("Failed to switch profile: {:?}", Debug2Format(&e));
+                    crate::print!("Failed to switch profile: {:?}", Debug2Format(&e));
+                }
+            } else {
+                crate::print!("Profiles require storage");
+            }
+        }
+
         if rktk_key_state.ble_bond_clear
             && let Some(ble) = &ble
         {
             let _ = ble.clear_bond_data().await;
         }
 
+        if let Some(profile) = rktk_key_state.ble_profile
+            && let Some(ble) = &ble
+            && profile != ble_profile
+        {
+            if profile >= ble_profile_count {
+                crate::print!("BLE profile {} is not available", profile);
+            } else if let Err(e) = switch_ble_profile(ble, config_store, profile).await {
+                rktk_log::error!("Failed to switch BLE profile: {:?}", Debug2Format(&e));
+                crate::print!("Failed to switch BLE profile: {:?}", Debug2Format(&e));
+            } else {
+                ble_profile = profile;
+            }
+        }
+
+        if rktk_key_state.ble_profile_clear
+            && let Some(ble) = &ble
+        {
+            let _ = ble.clear_profile_bond_data().await;
+        }
+
         if rktk_key_state.power_off {
             system.power_off().await;
         }
@@ -223,6 +322,9 @@ pub async fn report_task<
         }
 
         if last_output != Some(current_output) {
+            if let Some(prev_output) = last_output {
+                release_all(usb, ble, prev_output);
+            }
             match current_output {
                 Output::Usb => {
                     crate::utils::display_state!(Output, Output::Usb);
@@ -231,6 +333,7 @@ pub async fn report_task<
                     crate::utils::display_state!(Output, Output::Ble);
                 }
             }
+            update_sync_state(|s| s.output = Some(current_output));
             last_output = Some(current_output);
         }
 
@@ -246,6 +349,54 @@ pub async fn report_task<
     }
 }
 
+/// Output selected by [`RktkOutputPolicy::Auto`]. USB is used while it is connected to the host.
+fn auto_select_output(
+    usb: &Option<impl UsbReporterDriver>,
+    ble: &Option<impl WirelessReporterDriver>,
+) -> Output {
+    match usb {
+        Some(usb) if usb.is_connected() || ble.is_none() => Output::Usb,
+        Some(_) => Output::Ble,
+        None => Output::Ble,
+    }
+}
+
+/// Sends reports which release all keys and buttons to `output`, so that no key is stuck on the
+/// host of the previous output.
+fn release_all(
+    usb: &Option<impl UsbReporterDriver>,
+    ble: &Option<impl WirelessReporterDriver>,
+    output: Output,
+) {
+    fn release(reporter: &impl ReporterDriver) {
+        let _ = reporter.try_send_keyboard_report(usbd_hid::descriptor::KeyboardReport::default());
+        let _ =
+            reporter.try_send_media_keyboard_report(usbd_hid::descriptor::MediaKeyboardReport {
+                usage_id: 0,
+            });
+        let _ = reporter.try_send_mouse_report(usbd_hid::descriptor::MouseReport {
+            buttons: 0,
+            x: 0,
+            y: 0,
+            wheel: 0,
+            pan: 0,
+        });
+    }
+
+    match output {
+        Output::Usb => {
+            if let Some(usb) = usb {
+                release(usb);
+            }
+        }
+        Output::Ble => {
+            if let Some(ble) = ble {
+                release(ble);
+            }
+        }
+    }
+}
+
 async fn send_report(reporter: &impl ReporterDriver, state_report: Report) -> bool {
     let mut reported = false;
     if let Some(report) = state_report.keyboard_report {
@@ -1,50 +1,126 @@
 use core::{fmt::Display, str::FromStr as _};
 
+use embassy_futures::join::join3;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
 use futures::{Stream, StreamExt as _};
+use rktk_log::helper::Debug2Format;
 use rktk_rrp::{
-    endpoints::*,
+    endpoints::{
+        commit_storage::CommitError, set_active_profile::ProfileError, set_backup::RestoreError,
+        update_firmware::FirmwareUpdateError, *,
+    },
+    firmware::{FirmwareFlash, receive_firmware},
     server::ServerHandlers,
     transport::{ReadTransport, WriteTransport, error::ReceiveError},
 };
 
 use crate::{
     config::{
+        keymap::Keymap,
         storage::StorageConfigManager,
         {CONST_CONFIG, schema::DynamicConfig},
     },
     drivers::interface::{
-        reporter::ReporterDriver, storage::StorageDriver, usb::UsbReporterDriver,
-        wireless::WirelessReporterDriver,
+        dfu::DfuDriver, reporter::ReporterDriver, storage::StorageDriver, system::SystemDriver,
+        usb::UsbReporterDriver, wireless::WirelessReporterDriver,
     },
+    utils::{Mutex, RawMutex},
+};
+
+use crate::task::{
+    channels::report::{KEYBOARD_CONTROL_CHANNEL, KeyboardCommand},
+    split_rrp::SplitForwarder,
+};
+
+use super::{
+    ConfiguredState, SharedState,
+    utils::{copy_profile, load_profile, switch_profile},
 };
 
-use crate::task::channels::report::{KEYBOARD_CONTROL_CHANNEL, KeyboardCommand};
+mod backup;
+
+/// Signaled when new firmware is staged. Keyboard is reset to let the bootloader install it.
+static FIRMWARE_UPDATED: Signal<RawMutex, ()> = Signal::new();
 
-use super::{ConfiguredState, SharedState};
+/// Serializes handlers which change the state or the storage, since servers of all reporters are
+/// running at the same time.
+static HANDLER_LOCK: Mutex<()> = Mutex::new(());
 
+/// Starts rrp servers for all available reporters.
+///
+/// Each reporter gets its own server, so the configurator can connect through whichever output
+/// is currently in use (e.g. BLE on a wireless board without cable). Servers of USB and BLE are
+/// live at the same time, and handlers which change the state are serialized with
+/// [`HANDLER_LOCK`].
+#[allow(clippy::too_many_arguments)]
 pub async fn start(
     config: &'static DynamicConfig,
+    keymap: &'static Keymap,
+    system: &impl SystemDriver,
     usb: &Option<impl UsbReporterDriver>,
-    _ble: &Option<impl WirelessReporterDriver>,
+    ble: &Option<impl WirelessReporterDriver>,
     state: &SharedState,
     config_store: &Option<StorageConfigManager<impl StorageDriver>>,
+    dfu: Option<impl DfuDriver>,
 ) {
-    if let Some(usb) = &usb {
-        let mut server = rktk_rrp::server::Server::<_, _, _>::new(
-            ServerTransport::new(usb),
-            ServerTransport::new(usb),
-            Handlers { state, storage: config_store.as_ref(), config },
-        );
-        server.start::<{ CONST_CONFIG.buffer.rrp }>().await;
-    }
+    let dfu = dfu.map(Mutex::new);
+
+    join3(
+        async {
+            if let Some(usb) = &usb {
+                serve(usb, config, keymap, state, config_store, dfu.as_ref()).await;
+            }
+        },
+        async {
+            if let Some(ble) = &ble {
+                serve(ble, config, keymap, state, config_store, dfu.as_ref()).await;
+            }
+        },
+        async {
+            FIRMWARE_UPDATED.wait().await;
+            // Give the reporter time to send the response.
+            Timer::after_millis(500).await;
+            rktk_log::info!("Firmware updated. Resetting...");
+            if let Some(storage) = config_store
+                && let Err(_e) = storage.commit().await
+            {
+                rktk_log::error!("Failed to commit storage: {:?}", Debug2Format(&_e));
+            }
+            system.reset();
+        },
+    )
+    .await;
+}
+
+async fn serve(
+    reporter: &impl ReporterDriver,
+    config: &'static DynamicConfig,
+    keymap: &'static Keymap,
+    state: &SharedState,
+    config_store: &Option<StorageConfigManager<impl StorageDriver>>,
+    dfu: Option<&Mutex<impl DfuDriver>>,
+) {
+    let mut server = rktk_rrp::server::Server::<_, _, _>::new(
+        ServerTransport::new(reporter),
+        ServerTransport::new(reporter),
+        Handlers { state, storage: config_store.as_ref(), config, keymap, dfu },
+    )
+    .with_forwarder(SplitForwarder);
+    server.start::<{ CONST_CONFIG.buffer.rrp }>().await;
 }
 
-struct Handlers<'a, S: StorageDriver> {
+struct Handlers<'a, S: StorageDriver, D: DfuDriver> {
     state: &'a SharedState,
     storage: Option<&'a StorageConfigManager<S>>,
     config: &'static DynamicConfig,
+    /// Default keymap used for values which are not stored in a profile.
+    keymap: &'static Keymap,
+    dfu: Option<&'a Mutex<D>>,
 }
-impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Handlers<'_, S> {
+impl<RE: Display, WE: Display, S: StorageDriver, D: DfuDriver> ServerHandlers<RE, WE>
+    for Handlers<'_, S, D>
+{
     type Error = &'static str;
 
     async fn get_keyboard_info(
@@ -101,6 +177,7 @@ impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Hand
         &mut self,
         req: impl Stream<Item = Result<set_keymaps::Request, ReceiveError<RE>>>,
     ) -> Result<set_keymaps::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
         let mut req = core::pin::pin!(req);
 
         let (mut keymap, config) = {
@@ -108,13 +185,18 @@ impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Hand
             (state.inner().get_keymap().clone(), state.inner().get_config().clone())
         };
 
+        let mut changed = [false; CONST_CONFIG.key_manager.layer_count as usize];
         while let Some(Ok(key)) = req.next().await {
             keymap.layers[key.layer as usize].keymap[key.row as usize][key.col as usize] = key.key;
-            if let Some(storage) = self.storage
-                && let Err(_e) =
-                    storage.write_keymap(key.layer, &keymap.layers[key.layer as usize]).await
-            {
-                crate::print!("set_keymaps failed");
+            changed[key.layer as usize] = true;
+        }
+
+        if let Some(storage) = self.storage {
+            let profile = storage.active_profile();
+            for (l, layer) in keymap.layers.iter().enumerate().filter(|(l, _)| changed[*l]) {
+                if let Err(_e) = storage.stage_keymap(profile, l as u8, layer).await {
+                    crate::print!("set_keymaps failed");
+                }
             }
         }
         *self.state.lock().await = ConfiguredState::new(keymap, config);
@@ -122,6 +204,38 @@ impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Hand
         Ok(())
     }
 
+    async fn reset_key(
+        &mut self,
+        req: reset_key::Request,
+    ) -> Result<reset_key::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
+        let Some(action) = self
+            .keymap
+            .layers
+            .get(req.layer as usize)
+            .and_then(|layer| layer.keymap.get(req.row as usize)?.get(req.col as usize))
+            .copied()
+        else {
+            return Err("Key out of range");
+        };
+
+        let (mut keymap, config) = {
+            let state = self.state.lock().await;
+            (state.inner().get_keymap().clone(), state.inner().get_config().clone())
+        };
+        let layer = &mut keymap.layers[req.layer as usize];
+        layer.keymap[req.row as usize][req.col as usize] = action;
+
+        if let Some(storage) = self.storage
+            && let Err(_e) = storage.stage_keymap(storage.active_profile(), req.layer, layer).await
+        {
+            crate::print!("reset_key failed");
+        }
+        *self.state.lock().await = ConfiguredState::new(keymap, config);
+
+        Ok(action)
+    }
+
     async fn get_keymap_config(
         &mut self,
         _req: get_keymap_config::Request,
@@ -133,10 +247,11 @@ impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Hand
         &mut self,
         req: set_keymap_config::Request,
     ) -> Result<set_keymap_config::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
         let keymap = self.state.lock().await.inner().get_keymap().clone();
 
         if let Some(storage) = self.storage
-            && let Err(_e) = storage.write_state_config(&req).await
+            && let Err(_e) = storage.stage_state_config(storage.active_profile(), &req).await
         {
             crate::print!("set_keymap_config failed");
         }
@@ -174,6 +289,277 @@ impl<RE: Display, WE: Display, S: StorageDriver> ServerHandlers<RE, WE> for Hand
         let _ = KEYBOARD_CONTROL_CHANNEL.try_send(cmd);
         Ok(())
     }
+
+    async fn update_firmware(
+        &mut self,
+        req: impl Stream<Item = Result<update_firmware::Request, ReceiveError<RE>>>,
+    ) -> Result<update_firmware::Response, Self::Error> {
+        let Some(dfu) = self.dfu else {
+            req.for_each(|_| async {}).await;
+            return Ok(Err(FirmwareUpdateError::NotSupported));
+        };
+
+        let mut dfu = dfu.lock().await;
+        let res = receive_firmware(&mut DfuFlash(&mut *dfu), req).await;
+        match res {
+            Ok(()) => FIRMWARE_UPDATED.signal(()),
+            Err(_e) => rktk_log::error!("Firmware update failed: {:?}", Debug2Format(&_e)),
+        }
+        Ok(res)
+    }
+
+    async fn get_backup(
+        &mut self,
+        _req: get_backup::Request,
+    ) -> Result<impl Stream<Item = get_backup::Response>, Self::Error> {
+        let (keymap, config) = {
+            let state = self.state.lock().await;
+            (state.inner().get_keymap().clone(), state.inner().get_config().clone())
+        };
+        let storage = self.storage;
+        let active_profile = storage.map(|s| s.active_profile()).unwrap_or(0);
+        // Without storage, only the current profile exists.
+        let profiles = if storage.is_some() { CONST_CONFIG.key_manager.profile_count } else { 1 };
+
+        let mut calibration = None;
+        if let Some(storage) = storage {
+            let mut buf = [0; CONST_CONFIG.buffer.calibration];
+            if let Ok(len) = storage.read_calibration(&mut buf).await {
+                calibration = backup::CalibrationBuf::from_slice(&buf[..len]).ok();
+            }
+        }
+
+        // Other profiles are loaded from the storage one by one while sending.
+        let (dynamic_config, default_keymap) = (self.config, self.keymap);
+        let other_profiles = futures::stream::iter(storage.into_iter().flat_map(move |storage| {
+            (0..profiles).filter(move |p| *p != active_profile).map(move |p| (storage, p))
+        }))
+        .then(move |(storage, profile)| async move {
+            let km_config = &dynamic_config.key_manager;
+            let (keymap, config) = load_profile(km_config, storage, default_keymap, profile).await;
+            futures::stream::iter(backup::profile_items(profile, keymap, config))
+        })
+        .flatten();
+
+        let header = core::iter::once(backup::header(profiles));
+        let active = backup::profile_items(active_profile, keymap, config);
+        Ok(futures::stream::iter(header.chain(active))
+            .chain(other_profiles)
+            .chain(futures::stream::iter(backup::calibration_items(calibration))))
+    }
+
+    async fn set_backup(
+        &mut self,
+        req: impl Stream<Item = Result<set_backup::Request, ReceiveError<RE>>>,
+    ) -> Result<set_backup::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
+        let mut req = core::pin::pin!(req);
+
+        if let Err(e) = backup::check_header(req.next().await.and_then(|item| item.ok())) {
+            while req.next().await.is_some() {}
+            return Ok(Err(e));
+        }
+
+        let active_profile = self.storage.map(|s| s.active_profile()).unwrap_or(0);
+        let mut restored = heapless::Vec::<
+            backup::RestoredProfile,
+            { CONST_CONFIG.key_manager.profile_count as usize },
+        >::new();
+        let mut calibration = None;
+
+        let mut res = Ok(());
+        while let Some(item) = req.next().await {
+            if res.is_err() {
+                continue;
+            }
+            res = match item {
+                Ok(get_backup::BackupItem::Profile(profile)) => {
+                    let loaded = if restored.iter().any(|p| p.profile == profile)
+                        || profile >= CONST_CONFIG.key_manager.profile_count
+                    {
+                        Err(RestoreError::InvalidItem)
+                    } else if profile == active_profile {
+                        let state = self.state.lock().await;
+                        Ok((state.inner().get_keymap().clone(), state.inner().get_config().clone()))
+                    } else if let Some(storage) = self.storage {
+                        let km_config = &self.config.key_manager;
+                        Ok(load_profile(km_config, storage, self.keymap, profile).await)
+                    } else {
+                        // Profiles other than the current one can't be restored without storage.
+                        Err(RestoreError::InvalidItem)
+                    };
+                    loaded.and_then(|(keymap, config)| {
+                        restored
+                            .push(backup::RestoredProfile { profile, keymap, config })
+                            .map_err(|_| RestoreError::InvalidItem)
+                    })
+                }
+                Ok(item) => backup::apply_item(item, restored.last_mut(), &mut calibration),
+                Err(_) => Err(RestoreError::InvalidItem),
+            };
+        }
+        if res.is_err() {
+            return Ok(res);
+        }
+
+        let mut res = Ok(());
+        if let Some(storage) = self.storage {
+            let mut failed = false;
+            for p in &restored {
+                failed |= storage.stage_state_config(p.profile, &p.config).await.is_err();
+                for (i, layer) in p.keymap.layers.iter().enumerate() {
+                    failed |= storage.stage_keymap(p.profile, i as u8, layer).await.is_err();
+                }
+            }
+            failed |= storage.commit().await.is_err();
+            if let Some(calibration) = &calibration {
+                failed |= storage.write_calibration(calibration).await.is_err();
+            }
+            if failed {
+                crate::print!("set_backup failed to write storage");
+                res = Err(RestoreError::Storage);
+            }
+        }
+        if let Some(p) = restored.into_iter().find(|p| p.profile == active_profile) {
+            *self.state.lock().await = ConfiguredState::new(p.keymap, p.config);
+        }
+
+        Ok(res)
+    }
+
+    async fn get_profiles(
+        &mut self,
+        _req: get_profiles::Request,
+    ) -> Result<get_profiles::Response, Self::Error> {
+        Ok(get_profiles::ProfileInfo {
+            count: CONST_CONFIG.key_manager.profile_count,
+            active: self.storage.map(|s| s.active_profile()).unwrap_or(0),
+        })
+    }
+
+    async fn set_active_profile(
+        &mut self,
+        req: set_active_profile::Request,
+    ) -> Result<set_active_profile::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
+        if req >= CONST_CONFIG.key_manager.profile_count {
+            return Ok(Err(ProfileError::OutOfRange));
+        }
+        let Some(storage) = self.storage else {
+            return Ok(Err(ProfileError::NoStorage));
+        };
+
+        if let Err(_e) =
+            switch_profile(&self.config.key_manager, self.state, storage, self.keymap, req).await
+        {
+            rktk_log::error!("Failed to switch profile: {:?}", Debug2Format(&_e));
+            return Ok(Err(ProfileError::Storage));
+        }
+        Ok(Ok(()))
+    }
+
+    async fn copy_profile(
+        &mut self,
+        req: copy_profile::Request,
+    ) -> Result<copy_profile::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
+        let count = CONST_CONFIG.key_manager.profile_count;
+        if req.from >= count || req.to >= count {
+            return Ok(Err(ProfileError::OutOfRange));
+        }
+        let Some(storage) = self.storage else {
+            return Ok(Err(ProfileError::NoStorage));
+        };
+
+        let km_config = &self.config.key_manager;
+        let mut res = copy_profile(km_config, storage, self.keymap, req.from, req.to).await;
+        if res.is_ok() && req.to == storage.active_profile() {
+            res = switch_profile(km_config, self.state, storage, self.keymap, req.to).await;
+        }
+        if let Err(_e) = res {
+            rktk_log::error!("Failed to copy profile: {:?}", Debug2Format(&_e));
+            return Ok(Err(ProfileError::Storage));
+        }
+        Ok(Ok(()))
+    }
+
+    async fn get_storage_stats(
+        &mut self,
+        _req: get_storage_stats::Request,
+    ) -> Result<get_storage_stats::Response, Self::Error> {
+        let Some(storage) = self.storage else {
+            return Ok(None);
+        };
+
+        let usage = match storage.storage.stats().await {
+            Ok(stats) => stats.map(|s| get_storage_stats::StorageUsage {
+                erase_count: s.erase_count,
+                free_bytes: s.free_bytes,
+                capacity: s.capacity,
+            }),
+            Err(_e) => {
+                rktk_log::warn!("Failed to get storage stats: {:?}", Debug2Format(&_e));
+                None
+            }
+        };
+        Ok(Some(get_storage_stats::StorageStats { usage, pending: storage.has_pending().await }))
+    }
+
+    async fn commit_storage(
+        &mut self,
+        _req: commit_storage::Request,
+    ) -> Result<commit_storage::Response, Self::Error> {
+        let _lock = HANDLER_LOCK.lock().await;
+        let Some(storage) = self.storage else {
+            return Ok(Err(CommitError::NoStorage));
+        };
+
+        if let Err(_e) = storage.commit().await {
+            rktk_log::error!("Failed to commit storage: {:?}", Debug2Format(&_e));
+            return Ok(Err(CommitError::Storage));
+        }
+        Ok(Ok(()))
+    }
+
+    async fn get_split_stats(
+        &mut self,
+        _req: get_split_stats::Request,
+    ) -> Result<get_split_stats::Response, Self::Error> {
+        Ok(crate::task::split_handler::link_stats().map(Into::into))
+    }
+
+    async fn get_calibration(
+        &mut self,
+        _req: get_calibration::Request,
+    ) -> Result<impl Stream<Item = get_calibration::Response>, Self::Error> {
+        Ok(futures::stream::iter(crate::task::calibration::read_calibration_chunks().await?))
+    }
+}
+
+struct DfuFlash<'a, D: DfuDriver>(&'a mut D);
+
+impl<D: DfuDriver> FirmwareFlash for DfuFlash<'_, D> {
+    type Error = D::Error;
+
+    fn capacity(&self) -> u32 {
+        self.0.capacity()
+    }
+
+    async fn erase(&mut self, size: u32) -> Result<(), Self::Error> {
+        self.0.erase(size).await
+    }
+
+    async fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+        self.0.write(offset, data).await
+    }
+
+    async fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.read(offset, buf).await
+    }
+
+    async fn mark_updated(&mut self) -> Result<(), Self::Error> {
+        self.0.mark_updated().await
+    }
 }
 
 struct ServerTransport<'a, R: ReporterDriver> {
@@ -0,0 +1,146 @@
+//! Conversion between the keyboard state and [`BackupItem`]s.
+
+use kmsm::interface::state::config::StateConfig;
+use rktk_rrp::endpoints::{
+    KeyActionLoc,
+    get_backup::{BACKUP_VERSION, BackupHeader, BackupItem, CALIBRATION_CHUNK_SIZE},
+    set_backup::RestoreError,
+};
+
+use crate::config::{
+    CONST_CONFIG,
+    keymap::{Keymap, Layer},
+    storage::STORAGE_VERSION,
+};
+
+const ROWS: u8 = CONST_CONFIG.keyboard.rows;
+const COLS: u8 = CONST_CONFIG.keyboard.cols;
+const LAYERS: u8 = CONST_CONFIG.key_manager.layer_count;
+const PROFILES: u8 = CONST_CONFIG.key_manager.profile_count;
+
+/// Calibration data with its stored length.
+pub type CalibrationBuf = heapless::Vec<u8, { CONST_CONFIG.buffer.calibration }>;
+
+/// Keymap and config of a profile which is being restored.
+pub struct RestoredProfile {
+    pub profile: u8,
+    pub keymap: Keymap,
+    pub config: StateConfig,
+}
+
+/// Returns the header of a backup which contains `profiles` profiles.
+pub fn header(profiles: u8) -> BackupItem {
+    BackupItem::Header(BackupHeader {
+        version: BACKUP_VERSION,
+        storage_version: STORAGE_VERSION,
+        rows: ROWS,
+        cols: COLS,
+        layers: LAYERS,
+        profiles,
+    })
+}
+
+/// Converts keymap and config of `profile` into backup items.
+pub fn profile_items(
+    profile: u8,
+    keymap: Keymap,
+    config: StateConfig,
+) -> impl Iterator<Item = BackupItem> {
+    let layers = keymap.layers.into_iter().enumerate().flat_map(|(layer, l)| {
+        let layer = layer as u8;
+        let Layer { keymap: layer_keymap, encoder_keys, arrow_mouse } = l;
+        let keys = itertools::iproduct!(0..ROWS, 0..COLS).map(move |(row, col)| {
+            BackupItem::Key(KeyActionLoc {
+                layer,
+                row,
+                col,
+                key: layer_keymap[row as usize][col as usize],
+            })
+        });
+        let encoders = encoder_keys
+            .into_iter()
+            .enumerate()
+            .map(move |(index, keys)| BackupItem::Encoder { layer, index: index as u8, keys });
+        core::iter::once(BackupItem::LayerOptions { layer, arrow_mouse })
+            .chain(keys)
+            .chain(encoders)
+    });
+
+    [BackupItem::Profile(profile), BackupItem::StateConfig(config)].into_iter().chain(layers)
+}
+
+/// Converts calibration data into backup items. Nothing is returned if it is `None`.
+pub fn calibration_items(calibration: Option<CalibrationBuf>) -> impl Iterator<Item = BackupItem> {
+    calibration.into_iter().flat_map(|buf| {
+        (0..buf.len()).step_by(CALIBRATION_CHUNK_SIZE).map(move |offset| {
+            let end = (offset + CALIBRATION_CHUNK_SIZE).min(buf.len());
+            let mut data = heapless::Vec::new();
+            let _ = data.extend_from_slice(&buf[offset..end]);
+            BackupItem::Calibration { offset: offset as u16, data }
+        })
+    })
+}
+
+/// Checks that the backup was created for this keyboard.
+pub fn check_header(item: Option<BackupItem>) -> Result<(), RestoreError> {
+    let Some(BackupItem::Header(header)) = item else {
+        return Err(RestoreError::InvalidHeader);
+    };
+    if header.version != BACKUP_VERSION {
+        return Err(RestoreError::InvalidHeader);
+    }
+    if header.rows != ROWS
+        || header.cols != COLS
+        || header.layers != LAYERS
+        || header.profiles > PROFILES
+    {
+        return Err(RestoreError::KeyboardMismatch);
+    }
+    Ok(())
+}
+
+/// Applies a backup item to `profile`, which is the one of the last [`BackupItem::Profile`].
+///
+/// [`BackupItem::Profile`] itself must be handled by the caller.
+pub fn apply_item(
+    item: BackupItem,
+    profile: Option<&mut RestoredProfile>,
+    calibration: &mut Option<CalibrationBuf>,
+) -> Result<(), RestoreError> {
+    if let BackupItem::Calibration { offset, data } = item {
+        // Chunks are sent in order, so the length of the restored data is known.
+        let buf = calibration.get_or_insert_with(CalibrationBuf::new);
+        if offset as usize != buf.len() {
+            return Err(RestoreError::InvalidItem);
+        }
+        return buf.extend_from_slice(&data).map_err(|_| RestoreError::InvalidItem);
+    }
+
+    let RestoredProfile { keymap, config, .. } = profile.ok_or(RestoreError::InvalidItem)?;
+    match item {
+        BackupItem::Header(_) | BackupItem::Profile(_) | BackupItem::Calibration { .. } => {
+            return Err(RestoreError::InvalidItem);
+        }
+        BackupItem::StateConfig(c) => *config = c,
+        BackupItem::Key(KeyActionLoc { layer, row, col, key }) => {
+            *keymap
+                .layers
+                .get_mut(layer as usize)
+                .and_then(|l| l.keymap.get_mut(row as usize))
+                .and_then(|r| r.get_mut(col as usize))
+                .ok_or(RestoreError::InvalidItem)? = key;
+        }
+        BackupItem::Encoder { layer, index, keys } => {
+            *keymap
+                .layers
+                .get_mut(layer as usize)
+                .and_then(|l| l.encoder_keys.get_mut(index as usize))
+                .ok_or(RestoreError::InvalidItem)? = keys;
+        }
+        BackupItem::LayerOptions { layer, arrow_mouse } => {
+            keymap.layers.get_mut(layer as usize).ok_or(RestoreError::InvalidItem)?.arrow_mouse =
+                arrow_mouse;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use rktk_log::debug;
+
+use crate::{
+    drivers::interface::split::MasterToSlave,
+    task::channels::split::{M2sTx, SYNC_STATE_SEND_SIGNAL, sync_state},
+};
+
+/// Sends [`crate::drivers::interface::split::SyncState`] to the slaves when it is changed.
+pub async fn start(m2s_tx: &[M2sTx<'_>]) {
+    // Send the initial state even if nothing is changed yet.
+    SYNC_STATE_SEND_SIGNAL.signal(());
+    loop {
+        SYNC_STATE_SEND_SIGNAL.wait().await;
+        let state = sync_state();
+        debug!("Sync state: {:?}", state);
+        for tx in m2s_tx {
+            tx.send(MasterToSlave::State(state)).await;
+        }
+    }
+}
@@ -7,9 +7,13 @@ use crate::{
         CONST_CONFIG,
         keymap::Keymap,
         schema::{DynamicConfig, KeyManagerConfig},
-        storage::StorageConfigManager,
+        storage::{
+            ConfigReadError, ConfigWriteError, STORAGE_VERSION, StorageConfigManager,
+            migration::MIGRATIONS,
+        },
     },
-    drivers::interface::storage::StorageDriver,
+    drivers::interface::{storage::StorageDriver, wireless::WirelessReporterDriver},
+    task::channels::split::update_sync_state,
 };
 
 use super::{ConfiguredState, SharedState};
@@ -35,62 +39,220 @@ pub fn resolve_entire_key_pos(ev: &mut KeyChangeEvent, hand: Hand, shift: u8) {
     }
 }
 
-/// Initialise storage as configuration manager
-pub async fn init_storage<S: StorageDriver>(storage: Option<S>) -> Option<StorageConfigManager<S>> {
-    let mut config_storage = None;
-    if let Some(s) = storage {
-        let s = StorageConfigManager::new(s);
+/// Resolves encoder id of one hand to the id of the entire keyboard using
+/// split_right_encoder_shift.
+///
+/// Returns `None` if the resolved id is out of range of `encoder_count`.
+pub fn resolve_entire_encoder_id(id: u8, hand: Hand, config: &DynamicConfig) -> Option<u8> {
+    let id = if hand == Hand::Right {
+        id.checked_add(config.keyboard.split_right_encoder_shift)?
+    } else {
+        id
+    };
+    (id < CONST_CONFIG.keyboard.encoder_count).then_some(id)
+}
+
+/// Initialise storage as configuration manager.
+///
+/// Data written by older firmware is migrated to the current layout here.
+pub async fn init_storage<S: StorageDriver>(
+    storage: Option<S>,
+    keymap: &'static Keymap,
+) -> Option<StorageConfigManager<S>> {
+    let s = StorageConfigManager::new(storage?, keymap);
 
-        match s.read_version().await {
-            Ok(1) => {
-                rktk_log::info!("Storage version matched");
-                config_storage = Some(s);
+    let version = match s.read_version().await {
+        Ok(v) if v > STORAGE_VERSION => {
+            rktk_log::warn!("Storage version {} is newer than firmware", v);
+            crate::print!("Storage version mismatch: {}", v);
+            return None;
+        }
+        Ok(v) => v,
+        Err(_e) => {
+            if let Err(e) = s.write_version(STORAGE_VERSION).await {
+                rktk_log::error!("Failed to write version to storage: {:?}", Debug2Format(&e));
+                crate::print!("Failed to access storage: {:?}", e);
+                return None;
             }
-            Ok(i) => {
-                rktk_log::warn!("Storage version matched");
-                crate::print!("Storage version mismatch: {}", i);
+            return Some(s);
+        }
+    };
+
+    match s.migrate(version, MIGRATIONS).await {
+        Ok(0) => {
+            rktk_log::info!("Storage initialized");
+        }
+        Ok(failed) => {
+            rktk_log::warn!("{} stored values are incompatible with this firmware", failed);
+            crate::print!("{} stored values are incompatible. Using default.", failed);
+        }
+        Err(e) => {
+            rktk_log::error!("Failed to migrate storage: {:?}", Debug2Format(&e));
+            crate::print!("Failed to migrate storage: {:?}", e);
+            return None;
+        }
+    }
+
+    Some(s)
+}
+
+/// Loads keymap and config of `profile` from storage.
+///
+/// Values which are not stored are taken from provided static config instead.
+/// Values which exist but cannot be decoded are reported as a warning.
+pub async fn load_profile<S: StorageDriver>(
+    km_config: &KeyManagerConfig,
+    storage: &StorageConfigManager<S>,
+    keymap: &Keymap,
+    profile: u8,
+) -> (Keymap, StateConfig) {
+    let mut keymap = keymap.clone();
+    let mut incompatible = false;
+    for l in 0..CONST_CONFIG.key_manager.layer_count {
+        match storage.read_keymap(profile, l).await {
+            Ok(layer) => keymap.layers[l as usize] = layer,
+            // Not stored
+            Err(ConfigReadError::NotFound) => {}
+            Err(_e) => {
+                rktk_log::warn!(
+                    "Failed to load layer {} of profile {}: {:?}",
+                    l,
+                    profile,
+                    Debug2Format(&_e)
+                );
+                incompatible = true;
             }
-            Err(_e) => match s.write_version(1).await {
-                Ok(_) => {
-                    config_storage = Some(s);
-                }
-                Err(e) => {
-                    rktk_log::error!("Storage to write version to storage: {:?}", Debug2Format(&e));
-                    crate::print!("Failed to access storage: {:?}", e);
-                }
-            },
         }
     }
 
-    config_storage
+    let state_config = match storage.read_state_config(profile).await {
+        Ok(c) => Some(c),
+        Err(ConfigReadError::NotFound) => None,
+        Err(_e) => {
+            rktk_log::warn!(
+                "Failed to load state config of profile {}: {:?}",
+                profile,
+                Debug2Format(&_e)
+            );
+            incompatible = true;
+            None
+        }
+    };
+
+    if incompatible {
+        crate::print!("Stored config is incompatible. Using default.");
+    }
+
+    (keymap, state_config.unwrap_or_else(|| default_state_config(km_config)))
 }
 
-/// Loads config from storage and return it as state.
-/// If storage doesn't exist or read fails, uses provided static config value insted.
+fn default_state_config(km_config: &KeyManagerConfig) -> StateConfig {
+    StateConfig { mouse: km_config.mouse.clone(), key_resolver: km_config.key_resolver.clone() }
+}
+
+/// Loads config of the active profile from storage and return it as state.
+/// If storage doesn't exist, uses provided static config value insted.
 pub async fn load_state(
     km_config: &KeyManagerConfig,
     config_store: &Option<StorageConfigManager<impl StorageDriver>>,
     keymap: &Keymap,
 ) -> SharedState {
-    let mut keymap = keymap.clone();
-    let (state_config, keymap) = if let Some(storage) = &config_store {
-        for l in 0..CONST_CONFIG.key_manager.layer_count {
-            if let Ok(layer) = storage.read_keymap(l).await {
-                keymap.layers[l as usize] = layer;
-            }
+    let (keymap, state_config) = if let Some(storage) = &config_store {
+        let profile = storage.load_active_profile().await;
+        update_sync_state(|s| s.profile = profile);
+        if CONST_CONFIG.key_manager.profile_count > 1 {
+            crate::utils::display_state!(Profile, profile);
         }
+        load_profile(km_config, storage, keymap, profile).await
+    } else {
+        (keymap.clone(), default_state_config(km_config))
+    };
 
-        let c = storage.read_state_config().await;
+    SharedState::new(ConfiguredState::new(keymap, state_config))
+}
 
-        (c.ok(), keymap)
-    } else {
-        (None, keymap)
+/// Makes `profile` active and rebuilds the state with its keymap and config.
+///
+/// `profile` must be smaller than `profile_count`.
+pub async fn switch_profile<S: StorageDriver>(
+    km_config: &KeyManagerConfig,
+    state: &SharedState,
+    storage: &StorageConfigManager<S>,
+    keymap: &Keymap,
+    profile: u8,
+) -> Result<(), ConfigWriteError<S::Error>> {
+    storage.set_active_profile(profile).await?;
+    let (keymap, state_config) = load_profile(km_config, storage, keymap, profile).await;
+    *state.lock().await = ConfiguredState::new(keymap, state_config);
+    rktk_log::info!("Switched to profile {}", profile);
+    crate::utils::display_state!(Profile, profile);
+    update_sync_state(|s| s.profile = profile);
+    Ok(())
+}
+
+/// Copies keymap and config of profile `from` to profile `to`.
+///
+/// Values which are not stored in `from` are copied as their defaults, so `to` becomes identical
+/// to `from` regardless of what was stored in it.
+pub async fn copy_profile<S: StorageDriver>(
+    km_config: &KeyManagerConfig,
+    storage: &StorageConfigManager<S>,
+    keymap: &Keymap,
+    from: u8,
+    to: u8,
+) -> Result<(), ConfigWriteError<S::Error>> {
+    let (keymap, state_config) = load_profile(km_config, storage, keymap, from).await;
+    for (l, layer) in keymap.layers.iter().enumerate() {
+        storage.stage_keymap(to, l as u8, layer).await?;
+    }
+    storage.stage_state_config(to, &state_config).await?;
+    storage.commit().await
+}
+
+/// Restores the host profile of the wireless reporter from storage and returns it.
+///
+/// Falls back to profile 0 if it is not stored or out of range. Does nothing if the reporter
+/// doesn't have multiple profiles.
+pub async fn load_ble_profile<S: StorageDriver>(
+    ble: &Option<impl WirelessReporterDriver>,
+    config_store: &Option<StorageConfigManager<S>>,
+) -> u8 {
+    let Some(ble) = ble else {
+        return 0;
     };
+    let count = ble.profile_count();
+    if count <= 1 {
+        return 0;
+    }
 
-    let state_config = state_config.unwrap_or(StateConfig {
-        mouse: km_config.mouse.clone(),
-        key_resolver: km_config.key_resolver.clone(),
-    });
+    let profile = match config_store {
+        Some(storage) => storage.read_ble_profile().await.ok().filter(|p| *p < count).unwrap_or(0),
+        None => 0,
+    };
+    if let Err(e) = ble.select_profile(profile).await {
+        rktk_log::error!("Failed to select BLE profile: {:?}", Debug2Format(&e));
+    }
+    crate::utils::display_state!(BleProfile, profile);
+    update_sync_state(|s| s.ble_profile = Some(profile));
+    profile
+}
 
-    SharedState::new(ConfiguredState::new(keymap, state_config))
+/// Switches the wireless reporter to host `profile` and stores it.
+///
+/// `profile` must be smaller than [`WirelessReporterDriver::profile_count`].
+pub async fn switch_ble_profile<S: StorageDriver, Ble: WirelessReporterDriver>(
+    ble: &Ble,
+    config_store: &Option<StorageConfigManager<S>>,
+    profile: u8,
+) -> Result<(), <Ble as WirelessReporterDriver>::Error> {
+    ble.select_profile(profile).await?;
+    if let Some(storage) = config_store
+        && let Err(e) = storage.write_ble_profile(profile).await
+    {
+        rktk_log::error!("Failed to store BLE profile: {:?}", Debug2Format(&e));
+    }
+    rktk_log::info!("Switched to BLE profile {}", profile);
+    crate::utils::display_state!(BleProfile, profile);
+    update_sync_state(|s| s.ble_profile = Some(profile));
+    Ok(())
 }
@@ -3,10 +3,18 @@
 use crate::{
     config::Hand,
     drivers::interface::{
-        debounce::DebounceDriver, display::DisplayDriver, encoder::EncoderDriver,
-        keyscan::KeyscanDriver, mouse::MouseDriver, rgb::RgbDriver, split::SplitDriver,
-        storage::StorageDriver, usb::UsbReporterDriverBuilder,
-        wireless::WirelessReporterDriverBuilder,
+        battery::BatteryDriver,
+        debounce::DebounceDriver,
+        dfu::DfuDriver,
+        display::DisplayDriver,
+        encoder::EncoderDriver,
+        keyscan::KeyscanDriver,
+        mouse::MouseDriver,
+        rgb::RgbDriver,
+        split::{SlaveToMaster, SplitDriver},
+        storage::StorageDriver,
+        usb::UsbReporterDriverBuilder,
+        wireless::{WirelessReporterDriver, WirelessReporterDriverBuilder},
     },
     hooks::AllHooks,
 };
@@ -16,10 +24,14 @@ use crate::{
     utils::sjoin,
 };
 use display::DisplayConfig;
-use embassy_futures::join::{join, join5};
+use embassy_futures::join::{join, join4, join5};
 use embassy_time::Duration;
 use rktk_log::{debug, info};
 
+mod battery;
+#[cfg(feature = "rrp")]
+mod calibration;
+pub(crate) mod central_dongle;
 pub(crate) mod channels;
 // `display` module is public as internally used by macros
 pub mod display;
@@ -30,6 +42,8 @@ mod master;
 mod rgb;
 mod slave;
 mod split_handler;
+#[cfg(feature = "rrp")]
+mod split_rrp;
 
 /// Runs rktk with the given drivers and key configuration.
 ///
@@ -50,6 +64,8 @@ pub async fn start<
     Usb: UsbReporterDriverBuilder,
     Display: DisplayDriver,
     Mouse: MouseDriver,
+    Dfu: DfuDriver,
+    Battery: BatteryDriver,
     H: AllHooks,
     DC: DisplayConfig<Color = Display::Color> + 'static,
     RL: blinksy::layout::Layout2d + 'static,
@@ -68,6 +84,8 @@ pub async fn start<
         Usb,
         Display,
         Mouse,
+        Dfu,
+        Battery,
     >,
     hooks: H,
     mut opts: crate::config::RktkOpts<DC, RL>,
@@ -125,20 +143,29 @@ pub async fn start<
                                 spawner,
                                 async {
                                     let config_store =
-                                        master::utils::init_storage(drivers.storage).await;
+                                        master::utils::init_storage(drivers.storage, opts.keymap)
+                                            .await;
 
                                     if KeyScan::CALIBRATION_SIZE > 0
                                         && let Some(ref store) = config_store
                                     {
                                         let mut buf =
                                             [0u8; crate::config::CONST_CONFIG.buffer.calibration];
-                                        if let Ok(()) = store.read_calibration::<{ crate::config::CONST_CONFIG.buffer.calibration }>(&mut buf).await {
-                                                if drivers.keyscan.load_calibration(&buf[..KeyScan::CALIBRATION_SIZE]).is_err() {
-                                                    rktk_log::error!("Failed to load calibration data into keyscan driver");
-                                                } else {
-                                                    rktk_log::info!("Calibration data loaded successfully");
-                                                }
+                                        if store.read_calibration(&mut buf).await.is_ok() {
+                                            if drivers
+                                                .keyscan
+                                                .load_calibration(&buf[..KeyScan::CALIBRATION_SIZE])
+                                                .is_err()
+                                            {
+                                                rktk_log::error!(
+                                                    "Failed to load calibration data into keyscan driver"
+                                                );
+                                            } else {
+                                                rktk_log::info!(
+                                                    "Calibration data loaded successfully"
+                                                );
                                             }
+                                        }
                                     }
 
                                     let state = master::utils::load_state(
@@ -156,10 +183,11 @@ pub async fn start<
                                         )
                                         .await;
 
-                                    join(
+                                    join4(
                                         join5(
                                             master::report::report_task(
                                                 opts.config,
+                                                opts.keymap,
                                                 &drivers.system,
                                                 &state,
                                                 &config_store,
@@ -169,8 +197,9 @@ pub async fn start<
                                             ),
                                             master::handle_slave::start(
                                                 opts.config,
-                                                hand,
+                                                hand.other(),
                                                 receiver,
+                                                &split_handler::LINK,
                                             ),
                                             master::handle_keyboard::start(
                                                 opts.config,
@@ -179,20 +208,52 @@ pub async fn start<
                                                 &mut drivers.debounce,
                                                 &config_store,
                                             ),
-                                            master::handle_mouse::start(opts.config, drivers.mouse),
-                                            master::handle_encoder::start(&mut drivers.encoder),
+                                            master::handle_mouse::start(
+                                                opts.config,
+                                                hand,
+                                                drivers.mouse,
+                                            ),
+                                            master::handle_encoder::start(
+                                                opts.config,
+                                                hand,
+                                                &mut drivers.encoder,
+                                            ),
                                         ),
                                         async {
                                             #[cfg(feature = "rrp")]
                                             master::rrp_server::start(
                                                 opts.config,
+                                                opts.keymap,
+                                                &drivers.system,
                                                 &usb,
                                                 &wireless,
                                                 &state,
                                                 &config_store,
+                                                drivers.dfu,
                                             )
                                             .await;
                                         },
+                                        async {
+                                            if let Some(store) = &config_store {
+                                                store
+                                                    .commit_task(Duration::from_millis(
+                                                        opts.config.rktk.storage_commit_delay,
+                                                    ))
+                                                    .await;
+                                            }
+                                        },
+                                        battery::start(
+                                            opts.config,
+                                            hand,
+                                            drivers.battery,
+                                            async |status| {
+                                                if let Some(wireless) = &wireless {
+                                                    let _ = wireless
+                                                        .set_battery_level(status.level)
+                                                        .await;
+                                                }
+                                            },
+                                        ),
                                     )
                                     .await;
                                 },
@@ -204,7 +265,7 @@ pub async fn start<
                                 ),
                                 async move {
                                     if let Some(task) = task {
-                                        task.await;
+                                        join(task, master::sync_state::start(&[sender])).await;
                                     }
                                 }
                             );
@@ -216,16 +277,26 @@ pub async fn start<
                                 async move {
                                     slave::start(
                                         opts.config,
+                                        hand,
                                         sender,
                                         receiver,
                                         drivers.keyscan,
                                         &mut drivers.debounce,
                                         drivers.mouse,
+                                        &mut drivers.encoder,
                                         hooks.slave,
                                     )
                                     .await
                                 },
                                 rgb::start::<RL, _>(opts.config, drivers.rgb, hooks.rgb, None),
+                                battery::start(
+                                    opts.config,
+                                    hand,
+                                    drivers.battery,
+                                    async |status| {
+                                        sender.send(SlaveToMaster::Battery(status)).await;
+                                    }
+                                ),
                                 async move {
                                     if let Some(task) = task {
                                         task.await;
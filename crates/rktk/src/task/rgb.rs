@@ -1,5 +1,5 @@
-use embassy_futures::select::{Either, select};
-use embassy_time::Duration;
+use embassy_futures::select::{Either4, select4};
+use embassy_time::{Duration, Timer};
 use rktk_log::debug;
 
 use crate::{
@@ -11,7 +11,11 @@ use crate::{
     hooks::interface::RgbHooks,
 };
 
-use super::channels::{rgb::RGB_CHANNEL, split::M2sTx};
+use super::channels::{
+    battery::LOW_BATTERY_RGB_SIGNAL,
+    rgb::RGB_CHANNEL,
+    split::{M2sTx, SYNC_STATE_RGB_SIGNAL, sync_state},
+};
 use blinksy::{
     color::{ColorCorrection, IntoColor, LedRgb, LinearSrgb},
     layout::Layout2d,
@@ -22,6 +26,9 @@ use blinksy::{
     },
 };
 
+const LOW_BATTERY_BLINK_COUNT: usize = 3;
+const LOW_BATTERY_BLINK_INTERVAL: Duration = Duration::from_millis(200);
+
 pub async fn start<Layout: Layout2d, Driver: RgbDriver>(
     config: &'static DynamicConfig,
     driver: Option<Driver>,
@@ -38,8 +45,10 @@ pub async fn start<Layout: Layout2d, Driver: RgbDriver>(
     let mut current_rgb_mode = RgbMode::Off;
     let mut brightness = config.rktk.rgb.default_brightness;
     let color_correction = ColorCorrection::default();
+    // Kept across iterations so that the pattern continues when the state is changed.
+    let mut pattern_tick = 0u32;
     loop {
-        let res = select(RGB_CHANNEL.receive(), async {
+        let render = async {
             hook.on_rgb_process(&mut driver, &mut current_rgb_mode).await;
 
             match &current_rgb_mode {
@@ -73,7 +82,6 @@ pub async fn start<Layout: Layout2d, Driver: RgbDriver>(
                 }
                 RgbMode::Pattern(pat) => {
                     let interval = Duration::from_millis(config.rktk.rgb.pattern_update_interval);
-                    let mut i = 0;
                     let mut t = embassy_time::Ticker::every(interval);
 
                     macro_rules! process_pattern {
@@ -81,10 +89,10 @@ pub async fn start<Layout: Layout2d, Driver: RgbDriver>(
                             let pattern = <$pattern_ty as Pattern<_, Layout>>::new($params);
                             loop {
                                 t.next().await;
-                                i += 1;
+                                pattern_tick += 1;
                                 let led_data = <$pattern_ty as Pattern<_, Layout>>::tick(
                                     &pattern,
-                                    (i * interval).as_millis(),
+                                    (pattern_tick * interval).as_millis(),
                                 )
                                 .map(|color| {
                                     let srgb: LinearSrgb = color.into_color();
@@ -116,24 +124,53 @@ pub async fn start<Layout: Layout2d, Driver: RgbDriver>(
                 }
             }
             core::future::pending::<()>().await;
-        })
+        };
+        let res = select4(
+            RGB_CHANNEL.receive(),
+            SYNC_STATE_RGB_SIGNAL.wait(),
+            render,
+            LOW_BATTERY_RGB_SIGNAL.wait(),
+        )
         .await;
 
-        if let Either::First(new_ctrl) = res {
-            if let Some(m2s_tx) = m2s_tx {
-                m2s_tx.send(MasterToSlave::Rgb(new_ctrl.clone())).await;
-            }
-            match new_ctrl {
-                RgbCommand::Start(rgb_mode) => {
-                    current_rgb_mode = rgb_mode;
+        match res {
+            Either4::First(new_ctrl) => {
+                if let Some(m2s_tx) = m2s_tx {
+                    m2s_tx.send(MasterToSlave::Rgb(new_ctrl.clone())).await;
                 }
-                RgbCommand::Reset => {}
-                RgbCommand::Brightness(brightness_value) => {
-                    brightness = brightness_value.clamp(0.0, 1.0);
+                match new_ctrl {
+                    RgbCommand::Start(rgb_mode) => {
+                        current_rgb_mode = rgb_mode;
+                        pattern_tick = 0;
+                    }
+                    RgbCommand::Reset => {}
+                    RgbCommand::Brightness(brightness_value) => {
+                        brightness = brightness_value.clamp(0.0, 1.0);
+                    }
+                    RgbCommand::BrightnessDelta(delta) => {
+                        brightness += delta;
+                        brightness = brightness.clamp(0.0, 1.0);
+                    }
                 }
-                RgbCommand::BrightnessDelta(delta) => {
-                    brightness += delta;
-                    brightness = brightness.clamp(0.0, 1.0);
+            }
+            Either4::Second(()) => {
+                hook.on_sync_state(&mut driver, &sync_state(), &mut current_rgb_mode).await;
+            }
+            Either4::Third(()) => {}
+            Either4::Fourth(()) => {
+                // Blink red to warn low battery. Current mode is drawn again in the next loop.
+                for color in [LinearSrgb::new(1.0, 0.0, 0.0), LinearSrgb::new(0.0, 0.0, 0.0)]
+                    .into_iter()
+                    .cycle()
+                    .take(LOW_BATTERY_BLINK_COUNT * 2)
+                {
+                    let _ = driver
+                        .write(core::iter::repeat_n(
+                            LedRgb::from_linear_srgb(color, brightness, color_correction),
+                            Layout::PIXEL_COUNT,
+                        ))
+                        .await;
+                    Timer::after(LOW_BATTERY_BLINK_INTERVAL).await;
                 }
             }
         }
@@ -0,0 +1,216 @@
+use embassy_futures::{
+    join::join5,
+    select::{Either, select},
+};
+use embassy_time::{Duration, Instant, Timer};
+use rktk_log::debug;
+
+use crate::{
+    config::{CONST_CONFIG, Hand, schema::DynamicConfig},
+    drivers::interface::{
+        debounce::DebounceDriver,
+        encoder::EncoderDriver,
+        keyscan::KeyscanDriver,
+        mouse::MouseDriver,
+        split::{MasterToSlave, SlaveToMaster, SyncState},
+    },
+    hooks::interface::SlaveHooks,
+    task::channels::{
+        report::{
+            CALIBRATION_DATA_SIGNAL, KEYBOARD_CONTROL_CHANNEL, KeyboardCommand, read_calibration,
+        },
+        rgb::RGB_CHANNEL,
+        split::{M2sRx, S2mTx, update_sync_state},
+    },
+    utils::display_state,
+};
+
+#[cfg(feature = "rrp")]
+mod rrp_server;
+
+/// Interval to send the state of all rows to master.
+const KEY_STATE_SYNC_INTERVAL: Duration = Duration::from_millis(1000);
+
+#[allow(clippy::too_many_arguments)]
+pub async fn start<
+    KS: KeyscanDriver,
+    M: MouseDriver,
+    DB: DebounceDriver,
+    E: EncoderDriver,
+    SH: SlaveHooks,
+>(
+    config: &'static DynamicConfig,
+    hand: Hand,
+    s2m_tx: S2mTx<'_>,
+    m2s_rx: M2sRx<'_>,
+    mut keyscan: KS,
+    debounce: &mut Option<DB>,
+    mut mouse: Option<M>,
+    encoder: &mut Option<E>,
+    mut slave_hooks: SH,
+) {
+    crate::print!("Slave start");
+
+    slave_hooks.on_slave_init(&mut keyscan, mouse.as_mut()).await;
+
+    join5(
+        async {
+            if let Some(mouse) = &mut mouse {
+                debug!("mouse start");
+                let interval = Duration::from_millis(config.rktk.scan_interval_mouse);
+                loop {
+                    let start = embassy_time::Instant::now();
+
+                    if let Ok(data) = mouse.read().await
+                        && data != (0, 0)
+                    {
+                        let e = SlaveToMaster::Pointer {
+                            id: hand.pointer_id(),
+                            // x and y are swapped
+                            x: data.0,
+                            y: data.1,
+                        };
+                        s2m_tx.send(e).await;
+                    }
+
+                    let took = start.elapsed();
+                    if took < interval {
+                        Timer::after(interval - took).await;
+                    }
+                }
+            }
+        },
+        async {
+            if let Some(encoder) = encoder {
+                debug!("encoder start");
+                loop {
+                    let (id, dir) = encoder.read_wait().await;
+                    s2m_tx.send(SlaveToMaster::Encoder { id, dir }).await;
+                }
+            }
+        },
+        async {
+            debug!("keyscan start");
+            let interval = Duration::from_millis(config.rktk.scan_interval_keyboard);
+            let rows = CONST_CONFIG.keyboard.rows;
+            let mut key_state = [0u32; CONST_CONFIG.keyboard.rows as usize];
+            let mut sync_row = 0;
+            let mut last_sync = Instant::now();
+            loop {
+                if last_sync.elapsed() >= KEY_STATE_SYNC_INTERVAL / rows as u32 {
+                    let pressed = key_state[sync_row as usize];
+                    let _ = s2m_tx.try_send(SlaveToMaster::KeyState { row: sync_row, pressed });
+                    sync_row = (sync_row + 1) % rows;
+                    last_sync = Instant::now();
+                }
+
+                match select(KEYBOARD_CONTROL_CHANNEL.receive(), Timer::after(interval)).await {
+                    Either::First(KeyboardCommand::StartCalibration) => {
+                        debug!("Starting calibration");
+                        keyscan.start_calibration();
+                        continue;
+                    }
+                    Either::First(KeyboardCommand::EndCalibration) => {
+                        debug!("Ending calibration");
+                        keyscan.end_calibration();
+                        continue;
+                    }
+                    Either::First(KeyboardCommand::ReadCalibration) => {
+                        CALIBRATION_DATA_SIGNAL.signal(read_calibration(&keyscan));
+                        continue;
+                    }
+                    Either::Second(_) => {}
+                }
+
+                keyscan
+                    .scan(|event| {
+                        debug!("keyscan event: {:?}", event);
+                        if let Some(debounce) = debounce.as_mut()
+                            && debounce.should_ignore_event(&event, embassy_time::Instant::now())
+                        {
+                            debug!("keyscan event ignored");
+                            return;
+                        }
+
+                        if let Some(row) = key_state.get_mut(event.row as usize)
+                            && event.col < 32
+                        {
+                            if event.pressed {
+                                *row |= 1 << event.col;
+                            } else {
+                                *row &= !(1 << event.col);
+                            }
+                        }
+
+                        let event = if event.pressed {
+                            SlaveToMaster::Pressed(event.row, event.col)
+                        } else {
+                            SlaveToMaster::Released(event.row, event.col)
+                        };
+
+                        let _ = s2m_tx.try_send(event);
+                    })
+                    .await;
+            }
+        },
+        async {
+            debug!("split start");
+            let mut prev_state: Option<SyncState> = None;
+            loop {
+                let data = m2s_rx.receive().await;
+                debug!("split data recv: {:?}", data);
+                match data {
+                    MasterToSlave::Rgb(ctrl) => {
+                        let _ = RGB_CHANNEL.try_send(ctrl);
+                    }
+                    MasterToSlave::Message(_) => {}
+                    // Handled in the split handler
+                    MasterToSlave::Ping(_) => {}
+                    MasterToSlave::State(state) => {
+                        update_sync_state(|s| *s = state);
+                        display_sync_state(prev_state, &state);
+                        prev_state = Some(state);
+                    }
+                    #[cfg(feature = "rrp")]
+                    MasterToSlave::Rrp(chunk) => {
+                        crate::task::split_rrp::on_chunk_received(chunk).await;
+                    }
+                    #[cfg(not(feature = "rrp"))]
+                    MasterToSlave::Rrp(_) => {}
+                }
+            }
+        },
+        async {
+            #[cfg(feature = "rrp")]
+            rrp_server::start().await;
+        },
+    )
+    .await;
+}
+
+/// Sends changed parts of the state to the display.
+fn display_sync_state(prev: Option<SyncState>, state: &SyncState) {
+    if prev.map(|p| p.layers) != Some(state.layers) {
+        display_state!(LayerState, core::array::from_fn(|i| state.layer_active(i as u8)));
+    }
+    if prev.map(|p| p.num_lock()) != Some(state.num_lock()) {
+        display_state!(NumLock, state.num_lock());
+    }
+    if prev.map(|p| p.caps_lock()) != Some(state.caps_lock()) {
+        display_state!(CapsLock, state.caps_lock());
+    }
+    if let Some(output) = state.output
+        && prev.map(|p| p.output) != Some(state.output)
+    {
+        display_state!(Output, output);
+    }
+    if CONST_CONFIG.key_manager.profile_count > 1 && prev.map(|p| p.profile) != Some(state.profile)
+    {
+        display_state!(Profile, state.profile);
+    }
+    if let Some(profile) = state.ble_profile
+        && prev.map(|p| p.ble_profile) != Some(state.ble_profile)
+    {
+        display_state!(BleProfile, profile);
+    }
+}
@@ -0,0 +1,73 @@
+use core::fmt::Display;
+
+use futures::Stream;
+use rktk_rrp::{endpoints::*, server::ServerHandlers};
+
+use crate::{
+    config::CONST_CONFIG,
+    task::{
+        channels::report::{KEYBOARD_CONTROL_CHANNEL, KeyboardCommand},
+        split_rrp::slave_transports,
+    },
+};
+
+/// Starts rrp server which serves requests forwarded by the master through the split link.
+pub async fn start() {
+    let (reader, writer) = slave_transports();
+    let mut server = rktk_rrp::server::Server::<_, _, _>::new(reader, writer, Handlers);
+    server.start::<{ CONST_CONFIG.buffer.rrp }>().await;
+}
+
+/// Handlers for the slave side.
+///
+/// Slave doesn't have keymap state, so only endpoints related to the slave itself are served.
+struct Handlers;
+
+impl<RE: Display, WE: Display> ServerHandlers<RE, WE> for Handlers {
+    type Error = &'static str;
+
+    async fn get_log(
+        &mut self,
+        _req: get_log::Request,
+    ) -> Result<impl Stream<Item = get_log::Response>, Self::Error> {
+        Ok(futures::stream::iter(core::iter::from_fn(|| {
+            #[cfg(feature = "rrp-log")]
+            {
+                crate::task::logger::LOG_CHANNEL.try_receive().ok()
+            }
+
+            #[cfg(not(feature = "rrp-log"))]
+            {
+                None
+            }
+        })))
+    }
+
+    async fn get_now(&mut self, _req: get_now::Request) -> Result<get_now::Response, Self::Error> {
+        Ok(embassy_time::Instant::now().as_millis())
+    }
+
+    async fn set_calibration_mode(
+        &mut self,
+        req: set_calibration_mode::Request,
+    ) -> Result<set_calibration_mode::Response, Self::Error> {
+        let cmd =
+            if req { KeyboardCommand::StartCalibration } else { KeyboardCommand::EndCalibration };
+        let _ = KEYBOARD_CONTROL_CHANNEL.try_send(cmd);
+        Ok(())
+    }
+
+    async fn get_split_stats(
+        &mut self,
+        _req: get_split_stats::Request,
+    ) -> Result<get_split_stats::Response, Self::Error> {
+        Ok(crate::task::split_handler::link_stats().map(Into::into))
+    }
+
+    async fn get_calibration(
+        &mut self,
+        _req: get_calibration::Request,
+    ) -> Result<impl Stream<Item = get_calibration::Response>, Self::Error> {
+        Ok(futures::stream::iter(crate::task::calibration::read_calibration_chunks().await?))
+    }
+}
@@ -0,0 +1,187 @@
+//! Reliable delivery over the split link.
+//!
+//! Data frames are numbered and kept until the other side acknowledges them. Pending frames are
+//! retransmitted (go-back-N) when no ACK arrives in [`RETRANSMIT_TIMEOUT`], and given up after
+//! [`MAX_RETRIES`]. Receiver accepts frames only in order and acknowledges the next sequence number
+//! it expects.
+//!
+//! Either side can restart at any time, so sequence numbers are resynchronized as follows.
+//! - Data frame has `base`, the oldest sequence number the sender still has. Receiver which expects
+//!   older one skips to `base`, as frames before it were given up.
+//! - In the same session, receiver never expects a sequence number after the sender's pending
+//!   frames. If such ACK arrives, the other side has restarted and pending frames are renumbered
+//!   from what the receiver expects.
+
+use core::marker::PhantomData;
+
+use embassy_time::{Duration, Instant};
+use heapless::Deque;
+use rktk_log::{helper::Debug2Format, warn};
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::frame::{self, Frame, FrameError, MAX_FRAME_SIZE};
+use crate::drivers::interface::split::{SplitDriver, SplitLinkStats};
+
+/// Max number of frames which are sent but not acknowledged.
+pub const WINDOW: usize = 8;
+/// Time to wait for ACK before retransmitting.
+pub const RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(30);
+/// Number of retransmissions before the frame is given up.
+pub const MAX_RETRIES: u8 = 10;
+
+struct Pending<S> {
+    data: S,
+    retries: u8,
+}
+
+pub struct Arq<S, R> {
+    is_master: bool,
+    pending: Deque<Pending<S>, WINDOW>,
+    /// Sequence number of the first pending frame.
+    base: u8,
+    /// When pending frames are retransmitted. `None` if there is no pending frame.
+    deadline: Option<Instant>,
+    /// Sequence number expected from the other side. `None` until the first data frame arrives.
+    expected: Option<u8>,
+    /// Frame counters. Other fields are maintained by the caller.
+    pub stats: SplitLinkStats,
+    _received: PhantomData<R>,
+}
+
+impl<S: Serialize, R: DeserializeOwned> Arq<S, R> {
+    pub fn new(is_master: bool) -> Self {
+        Self {
+            is_master,
+            pending: Deque::new(),
+            base: 0,
+            deadline: None,
+            expected: None,
+            stats: SplitLinkStats::default(),
+            _received: PhantomData,
+        }
+    }
+
+    /// Returns true if no more data can be sent until pending frames are acknowledged.
+    pub fn is_full(&self) -> bool {
+        self.pending.is_full()
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Sends `data`. Caller must check [`Self::is_full`] before calling this.
+    pub async fn send<D: SplitDriver>(&mut self, split: &mut D, data: S, now: Instant) {
+        let seq = self.base.wrapping_add(self.pending.len() as u8);
+        let frame = Frame::Data { seq, base: self.base, data: &data };
+        transmit(split, self.is_master, &mut self.stats, &frame).await;
+        if self.pending.is_empty() {
+            self.deadline = Some(now + RETRANSMIT_TIMEOUT);
+        }
+        if self.pending.push_back(Pending { data, retries: 0 }).is_err() {
+            warn!("Split send window full");
+        }
+    }
+
+    /// Handles a received frame and returns data if it is new.
+    ///
+    /// Returns error if the frame is corrupted.
+    pub async fn on_frame<D: SplitDriver>(
+        &mut self,
+        split: &mut D,
+        buf: &mut [u8],
+        now: Instant,
+    ) -> Result<Option<R>, FrameError> {
+        let frame = frame::decode::<R>(buf).inspect_err(|e| {
+            self.stats.errors += 1;
+            warn!("Split frame dropped: {:?}", e);
+        })?;
+        self.stats.frames_received += 1;
+        match frame {
+            Frame::Data { seq, base, data } => {
+                let expected = self.expected.get_or_insert(base);
+                let skipped = base.wrapping_sub(*expected);
+                if skipped != 0 && skipped < 128 {
+                    warn!("Split data lost: {} frames", skipped);
+                    self.stats.lost += skipped as u32;
+                    *expected = base;
+                }
+                let accepted = seq == *expected;
+                if accepted {
+                    *expected = expected.wrapping_add(1);
+                }
+                let next = *expected;
+                transmit(split, self.is_master, &mut self.stats, &Frame::<&S>::Ack { next }).await;
+                Ok(accepted.then_some(data))
+            }
+            Frame::Ack { next } => {
+                self.on_ack(split, next, now).await;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Retransmits pending frames. Should be called at [`Self::deadline`].
+    pub async fn on_timeout<D: SplitDriver>(&mut self, split: &mut D, now: Instant) {
+        for p in self.pending.iter_mut() {
+            p.retries += 1;
+        }
+        while self.pending.front().is_some_and(|p| p.retries > MAX_RETRIES) {
+            self.pending.pop_front();
+            self.base = self.base.wrapping_add(1);
+            self.stats.lost += 1;
+            warn!("Split data given up after {} retries", MAX_RETRIES);
+        }
+        self.retransmit(split, now).await;
+    }
+
+    async fn on_ack<D: SplitDriver>(&mut self, split: &mut D, next: u8, now: Instant) {
+        let acked = next.wrapping_sub(self.base);
+        if acked as usize <= self.pending.len() {
+            if acked == 0 {
+                return;
+            }
+            for _ in 0..acked {
+                self.pending.pop_front();
+            }
+            self.base = next;
+            self.deadline = (!self.pending.is_empty()).then(|| now + RETRANSMIT_TIMEOUT);
+        } else if acked <= 128 {
+            warn!("Split link resynchronized: seq {} -> {}", self.base, next);
+            self.base = next;
+            self.retransmit(split, now).await;
+        }
+        // Otherwise, the receiver is behind `base` and skips to it when it receives the next frame.
+    }
+
+    async fn retransmit<D: SplitDriver>(&mut self, split: &mut D, now: Instant) {
+        for (i, p) in self.pending.iter().enumerate() {
+            let seq = self.base.wrapping_add(i as u8);
+            let frame = Frame::Data { seq, base: self.base, data: &p.data };
+            transmit(split, self.is_master, &mut self.stats, &frame).await;
+            self.stats.retransmits += 1;
+        }
+        self.deadline = (!self.pending.is_empty()).then(|| now + RETRANSMIT_TIMEOUT);
+    }
+}
+
+async fn transmit<D: SplitDriver, T: Serialize>(
+    split: &mut D,
+    is_master: bool,
+    stats: &mut SplitLinkStats,
+    frame: &Frame<T>,
+) {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    match frame::encode(frame, &mut buf) {
+        Ok(bytes) => {
+            if let Err(e) = split.send_all(bytes, is_master).await {
+                rktk_log::error!("Split send error: {:?}", Debug2Format(&e));
+            } else {
+                stats.frames_sent += 1;
+            }
+        }
+        Err(e) => {
+            warn!("Split frame encode failed: {:?}", e);
+        }
+    }
+}
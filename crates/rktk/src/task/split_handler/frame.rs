@@ -0,0 +1,122 @@
+//! Frame format of the split link.
+//!
+//! A frame is postcard encoded [`Frame`] followed by CRC-16 of it, encoded with COBS and terminated
+//! by `0`.
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use super::MAX_DATA_SIZE;
+
+/// Max size of the frame header (variant, seq and base).
+const HEADER_SIZE: usize = 3;
+const CRC_SIZE: usize = 2;
+const MAX_RAW_SIZE: usize = MAX_DATA_SIZE + HEADER_SIZE + CRC_SIZE;
+
+/// Max size of the encoded frame including COBS overhead and terminator.
+///
+/// COBS adds one code byte per 254 bytes, plus one more when the size is a multiple of 254.
+pub const MAX_FRAME_SIZE: usize = MAX_RAW_SIZE + MAX_RAW_SIZE / 254 + 2;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Frame<T> {
+    /// Data numbered with `seq`.
+    ///
+    /// `base` is the oldest sequence number which the sender still retransmits.
+    Data { seq: u8, base: u8, data: T },
+    /// Acknowledges all data before `next`.
+    Ack { next: u8 },
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    Encode,
+    Cobs,
+    Crc,
+    Decode,
+}
+
+/// Encodes `frame` to `buf` and returns encoded bytes including the terminator.
+pub fn encode<'a, T: Serialize>(
+    frame: &Frame<T>,
+    buf: &'a mut [u8; MAX_FRAME_SIZE],
+) -> Result<&'a [u8], FrameError> {
+    let mut raw = [0u8; MAX_RAW_SIZE];
+    let len = postcard::to_slice(frame, &mut raw[..MAX_RAW_SIZE - CRC_SIZE])
+        .map_err(|_| FrameError::Encode)?
+        .len();
+    let crc = crc16(&raw[..len]);
+    raw[len..len + CRC_SIZE].copy_from_slice(&crc.to_le_bytes());
+
+    let len = cobs_encode(&raw[..len + CRC_SIZE], buf);
+    buf[len] = 0;
+    Ok(&buf[..=len])
+}
+
+/// Decodes a frame in place. Terminator is optional.
+pub fn decode<T: DeserializeOwned>(buf: &mut [u8]) -> Result<Frame<T>, FrameError> {
+    let len = cobs_decode_in_place(buf)?;
+    if len < CRC_SIZE {
+        return Err(FrameError::Crc);
+    }
+    let (body, crc) = buf[..len].split_at(len - CRC_SIZE);
+    if crc16(body).to_le_bytes() != crc {
+        return Err(FrameError::Crc);
+    }
+    postcard::from_bytes(body).map_err(|_| FrameError::Decode)
+}
+
+/// CRC-16/CCITT-FALSE
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF_u16;
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+/// Encodes `src` to `dst` without terminator and returns the length.
+pub(super) fn cobs_encode(src: &[u8], dst: &mut [u8]) -> usize {
+    let mut code_idx = 0;
+    let mut out = 1;
+    let mut code = 1u8;
+    for &b in src {
+        if b != 0 {
+            dst[out] = b;
+            out += 1;
+            code += 1;
+        }
+        if b == 0 || code == 0xFF {
+            dst[code_idx] = code;
+            code_idx = out;
+            out += 1;
+            code = 1;
+        }
+    }
+    dst[code_idx] = code;
+    out
+}
+
+/// Decodes COBS data until the first `0` and returns the length of decoded data.
+fn cobs_decode_in_place(buf: &mut [u8]) -> Result<usize, FrameError> {
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let mut read = 0;
+    let mut write = 0;
+    while read < end {
+        let code = buf[read] as usize;
+        if read + code > end {
+            return Err(FrameError::Cobs);
+        }
+        buf.copy_within(read + 1..read + code, write);
+        read += code;
+        write += code - 1;
+        if code < 0xFF && read < end {
+            buf[write] = 0;
+            write += 1;
+        }
+    }
+    Ok(write)
+}
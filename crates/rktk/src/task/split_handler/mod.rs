@@ -0,0 +1,312 @@
+//! Reliable transport over [`SplitDriver`].
+//!
+//! Data is sent in CRC-checked COBS frames (see [`frame`]) and retransmitted until acknowledged
+//! (see [`arq`]).
+//!
+//! The handler also monitors the link. Master pings the slave periodically to measure latency, and
+//! if nothing is received for `split_link_timeout`, the link is considered dead and the driver is
+//! re-initialized.
+
+use core::cell::Cell;
+
+use embassy_futures::select::{Either3, select3};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_time::{Duration, Instant, Timer};
+use postcard::experimental::max_size::MaxSize;
+use rktk_log::{MaybeFormat, debug, helper::Debug2Format, info, warn};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    config::CONST_CONFIG,
+    drivers::interface::split::{MasterToSlave, SlaveToMaster, SplitDriver, SplitLinkStats},
+    task::channels::split::SYNC_STATE_SEND_SIGNAL,
+    utils::{RawMutex, Receiver, Sender, Signal, display_state},
+};
+
+mod arq;
+mod frame;
+
+#[cfg(test)]
+mod tests;
+
+use arq::Arq;
+use frame::MAX_FRAME_SIZE;
+
+pub const MAX_DATA_SIZE: usize = const {
+    if MasterToSlave::POSTCARD_MAX_SIZE > SlaveToMaster::POSTCARD_MAX_SIZE {
+        MasterToSlave::POSTCARD_MAX_SIZE
+    } else {
+        SlaveToMaster::POSTCARD_MAX_SIZE
+    }
+};
+
+/// Interval of pings sent by master.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// State of a split link which is shared with other tasks.
+pub struct LinkState {
+    stats: BlockingMutex<RawMutex, Cell<Option<SplitLinkStats>>>,
+    /// Signaled on master when the link is considered dead.
+    pub down: Signal<()>,
+}
+
+impl LinkState {
+    pub const fn new() -> Self {
+        Self { stats: BlockingMutex::new(Cell::new(None)), down: Signal::new() }
+    }
+
+    /// Returns the latest stats of the link. `None` if split handler is not running.
+    pub fn stats(&self) -> Option<SplitLinkStats> {
+        self.stats.lock(|s| s.get())
+    }
+}
+
+/// State of the split link of the keyboard.
+///
+/// On the dongle which processes keys (see [`crate::dongle_task::start_central_dongle`]), this is
+/// the link to the left half.
+pub static LINK: LinkState = LinkState::new();
+
+/// Returns the latest stats of the split link. `None` if split handler is not running.
+pub fn link_stats() -> Option<SplitLinkStats> {
+    LINK.stats()
+}
+
+/// Split message which is used as ping.
+///
+/// Master sends [`MasterToSlave::Ping`] and slave replies with [`SlaveToMaster::Pong`]. These are
+/// handled in the split handler and not passed to the channel.
+pub trait Ping {
+    fn ping(id: u8) -> Self;
+    fn as_ping(&self) -> Option<u8>;
+}
+
+impl Ping for MasterToSlave {
+    fn ping(id: u8) -> Self {
+        Self::Ping(id)
+    }
+
+    fn as_ping(&self) -> Option<u8> {
+        match self {
+            Self::Ping(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+impl Ping for SlaveToMaster {
+    fn ping(id: u8) -> Self {
+        Self::Pong(id)
+    }
+
+    fn as_ping(&self) -> Option<u8> {
+        match self {
+            Self::Pong(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "rrp")]
+impl From<SplitLinkStats> for rktk_rrp::endpoints::get_split_stats::SplitStats {
+    fn from(s: SplitLinkStats) -> Self {
+        Self {
+            alive: s.alive,
+            frames_sent: s.frames_sent,
+            frames_received: s.frames_received,
+            retransmits: s.retransmits,
+            errors: s.errors,
+            lost: s.lost,
+            reinits: s.reinits,
+            latency_ms: s.latency_ms,
+        }
+    }
+}
+
+pub struct CobsReceiver {
+    buf: heapless::Vec<u8, { MAX_FRAME_SIZE * 2 }>,
+}
+impl CobsReceiver {
+    fn new() -> Self {
+        Self { buf: heapless::Vec::new() }
+    }
+
+    /// Appends received bytes. If the buffer overflows, buffered bytes are discarded.
+    fn push(&mut self, data: &[u8]) -> Result<(), &'static str> {
+        if self.buf.extend_from_slice(data).is_err() {
+            self.buf.clear();
+            return Err("CobsReceiver buffer overflow");
+        }
+        Ok(())
+    }
+
+    /// Takes a frame terminated by zero from the buffer.
+    fn take_frame(&mut self, out_buf: &mut [u8]) -> Option<Result<usize, &'static str>> {
+        let len = self.buf.iter().position(|&b| b == 0)? + 1;
+        let res = if len > out_buf.len() {
+            Err("Output buffer too small")
+        } else {
+            out_buf[..len].copy_from_slice(&self.buf[..len]);
+            Ok(len)
+        };
+
+        // Shift remaining bytes
+        self.buf.copy_within(len.., 0);
+        self.buf.truncate(self.buf.len() - len);
+        Some(res)
+    }
+
+    #[inline(always)]
+    async fn recv_until_zero<D: SplitDriver>(
+        &mut self,
+        driver: &mut D,
+        out_buf: &mut [u8],
+        is_master: bool,
+    ) -> Result<usize, &'static str> {
+        loop {
+            if let Some(res) = self.take_frame(out_buf) {
+                return res;
+            }
+
+            let mut tmp_buf = [0u8; 64];
+            let size = driver.recv(&mut tmp_buf, is_master).await.map_err(|_e| "recv error")?;
+            self.push(&tmp_buf[..size])?;
+        }
+    }
+}
+
+/// Starts background task that
+/// - send data received from the other side to `received_sender`.
+/// - receive data from `to_send_receiver` and send it to the other side.
+/// - monitor the link and re-initialize `split` if it is dead.
+pub async fn start<
+    'a,
+    SP: SplitDriver,
+    R: DeserializeOwned + MaybeFormat + Ping,
+    S: Serialize + MaybeFormat + Ping,
+>(
+    mut split: SP,
+    received_sender: Sender<'a, R, { CONST_CONFIG.buffer.split_channel }>,
+    to_send_receiver: Receiver<'a, S, { CONST_CONFIG.buffer.split_channel }>,
+    is_master: bool,
+    link_timeout: Duration,
+    link: &LinkState,
+) {
+    debug!("split handler start");
+
+    let mut arq = Arq::<S, R>::new(is_master);
+    let mut recv = CobsReceiver::new();
+    // The link is considered dead if nothing is received until this.
+    let mut link_deadline = Instant::now() + link_timeout;
+    let mut next_ping = Instant::now();
+    let mut ping_id = 0u8;
+    // Ping which is waiting for reply and when it was sent.
+    let mut ping_sent: Option<(u8, Instant)> = None;
+
+    loop {
+        link.stats.lock(|s| s.set(Some(arq.stats)));
+
+        let mut recv_buf = [0u8; MAX_FRAME_SIZE];
+        let can_send = !arq.is_full();
+        let mut tick = link_deadline;
+        if let Some(deadline) = arq.deadline() {
+            tick = tick.min(deadline);
+        }
+        if is_master {
+            tick = tick.min(next_ping);
+        }
+
+        match select3(
+            recv.recv_until_zero(&mut split, &mut recv_buf, is_master),
+            async {
+                if can_send {
+                    to_send_receiver.receive().await
+                } else {
+                    core::future::pending().await
+                }
+            },
+            Timer::at(tick),
+        )
+        .await
+        {
+            Either3::First(Ok(len)) => {
+                let now = Instant::now();
+                let Ok(data) = arq.on_frame(&mut split, &mut recv_buf[..len], now).await else {
+                    continue;
+                };
+                link_deadline = now + link_timeout;
+                if !arq.stats.alive {
+                    info!("Split link is alive");
+                    arq.stats.alive = true;
+                    display_state!(SplitLink, arq.stats);
+                    if is_master {
+                        // Slave may have restarted and lost the state.
+                        SYNC_STATE_SEND_SIGNAL.signal(());
+                    }
+                }
+
+                let Some(data) = data else {
+                    continue;
+                };
+                match data.as_ping() {
+                    Some(id) if is_master => {
+                        if let Some((_, sent)) = ping_sent.take_if(|(sent_id, _)| *sent_id == id) {
+                            arq.stats.latency_ms = Some((now - sent).as_millis() as u32);
+                            display_state!(SplitLink, arq.stats);
+                        }
+                    }
+                    Some(id) => {
+                        if !arq.is_full() {
+                            arq.send(&mut split, S::ping(id), now).await;
+                        }
+                    }
+                    None => {
+                        if received_sender.try_send(data).is_err() {
+                            warn!("split recv chan full");
+                        }
+                    }
+                }
+            }
+            Either3::First(Err(e)) => {
+                warn!("Failed to receive split data: {:?}", Debug2Format(&e));
+            }
+            Either3::Second(send_data) => {
+                debug!("Split data send: {:?}", send_data);
+                arq.send(&mut split, send_data, Instant::now()).await;
+            }
+            Either3::Third(()) => {
+                let now = Instant::now();
+                if arq.deadline().is_some_and(|d| d <= now) {
+                    arq.on_timeout(&mut split, now).await;
+                }
+
+                if link_deadline <= now {
+                    if arq.stats.alive {
+                        warn!("Split link is dead");
+                        arq.stats.alive = false;
+                        arq.stats.latency_ms = None;
+                        if is_master {
+                            link.down.signal(());
+                        }
+                        display_state!(SplitLink, arq.stats);
+                    }
+                    if let Err(e) = split.init().await {
+                        warn!("Failed to re-initialize split driver: {:?}", Debug2Format(&e));
+                    }
+                    arq.stats.reinits += 1;
+                    recv = CobsReceiver::new();
+                    link_deadline = now + link_timeout;
+                }
+
+                if is_master && next_ping <= now {
+                    if !arq.is_full() {
+                        ping_id = ping_id.wrapping_add(1);
+                        arq.send(&mut split, S::ping(ping_id), now).await;
+                        ping_sent = Some((ping_id, now));
+                    }
+                    next_ping = now + PING_INTERVAL;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+extern crate std;
+
+use core::cell::RefCell;
+use std::{collections::VecDeque, rc::Rc, vec::Vec};
+
+use embassy_futures::block_on;
+use embassy_time::{Duration, Instant};
+
+use crate::drivers::interface::{Error, split::SplitDriver};
+
+use super::{
+    CobsReceiver,
+    arq::{Arq, MAX_RETRIES, RETRANSMIT_TIMEOUT, WINDOW},
+    frame::{self, Frame, FrameError, MAX_FRAME_SIZE},
+};
+
+#[derive(Debug)]
+struct Never;
+
+impl Error for Never {}
+
+type Wire = Rc<RefCell<VecDeque<u8>>>;
+
+/// In-memory split driver which drops and corrupts sent frames.
+struct LossySplit {
+    rx: Wire,
+    tx: Wire,
+    /// Percentage of frames to drop
+    loss: u32,
+    /// Percentage of frames to corrupt
+    corrupt: u32,
+    rng: u32,
+}
+
+impl LossySplit {
+    fn pair(loss: u32, corrupt: u32) -> (Self, Self) {
+        let (a, b) = (Wire::default(), Wire::default());
+        (
+            Self { rx: a.clone(), tx: b.clone(), loss, corrupt, rng: 1 },
+            Self { rx: b, tx: a, loss, corrupt, rng: 2 },
+        )
+    }
+
+    fn random(&mut self) -> u32 {
+        // xorshift32
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng
+    }
+}
+
+impl SplitDriver for LossySplit {
+    type Error = Never;
+
+    async fn recv(&mut self, buf: &mut [u8], _is_master: bool) -> Result<usize, Self::Error> {
+        let mut rx = self.rx.borrow_mut();
+        let len = buf.len().min(rx.len());
+        for (b, r) in buf.iter_mut().zip(rx.drain(..len)) {
+            *b = r;
+        }
+        Ok(len)
+    }
+
+    async fn send_all(&mut self, buf: &[u8], _is_master: bool) -> Result<(), Self::Error> {
+        let roll = self.random() % 100;
+        if roll < self.loss {
+            return Ok(());
+        }
+        let mut buf = buf.to_vec();
+        if roll < self.loss + self.corrupt {
+            let i = self.random() as usize % buf.len();
+            buf[i] ^= 1 << (self.random() % 8);
+        }
+        self.tx.borrow_mut().extend(buf);
+        Ok(())
+    }
+}
+
+struct Endpoint {
+    arq: Arq<u32, u32>,
+    split: LossySplit,
+    cobs: CobsReceiver,
+    received: Vec<u32>,
+}
+
+impl Endpoint {
+    fn new(split: LossySplit) -> Self {
+        Self { arq: Arq::new(true), split, cobs: CobsReceiver::new(), received: Vec::new() }
+    }
+
+    fn send(&mut self, data: u32, now: Instant) {
+        block_on(self.arq.send(&mut self.split, data, now));
+    }
+
+    /// Handles received bytes and timeout.
+    fn poll(&mut self, now: Instant) {
+        block_on(async {
+            let mut tmp = [0u8; 64];
+            let mut buf = [0u8; MAX_FRAME_SIZE];
+            loop {
+                let len = self.split.recv(&mut tmp, true).await.unwrap();
+                if len == 0 {
+                    break;
+                }
+                let _ = self.cobs.push(&tmp[..len]);
+                while let Some(res) = self.cobs.take_frame(&mut buf) {
+                    let Ok(len) = res else { continue };
+                    if let Ok(Some(data)) =
+                        self.arq.on_frame(&mut self.split, &mut buf[..len], now).await
+                    {
+                        self.received.push(data);
+                    }
+                }
+            }
+
+            if self.arq.deadline().is_some_and(|d| d <= now) {
+                self.arq.on_timeout(&mut self.split, now).await;
+            }
+        });
+    }
+}
+
+/// Sends `count` values from `a` to `b` and from `b` to `a` at the same time, as fast as the window
+/// allows, and returns the time it took.
+fn exchange(a: &mut Endpoint, b: &mut Endpoint, count: u32, start: Instant) -> Instant {
+    let (mut a_sent, mut b_sent) = (0, 0);
+    let mut now = start;
+    while a.received.len() < count as usize || b.received.len() < count as usize {
+        if a_sent < count && !a.arq.is_full() {
+            a.send(a_sent, now);
+            a_sent += 1;
+        }
+        if b_sent < count && !b.arq.is_full() {
+            b.send(b_sent + 1000, now);
+            b_sent += 1;
+        }
+        a.poll(now);
+        b.poll(now);
+        now += Duration::from_millis(1);
+        assert!(now < start + Duration::from_secs(60), "exchange did not finish");
+    }
+    now
+}
+
+#[test]
+fn frame_roundtrip() {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    for data in [0, 1, 0xFF, 0x100, 0xFFFF_FFFF] {
+        let frame = Frame::Data { seq: 0, base: 255, data };
+        let bytes = frame::encode(&frame, &mut buf).unwrap();
+        assert_eq!(bytes.iter().position(|&b| b == 0), Some(bytes.len() - 1));
+        assert_eq!(frame::decode::<u32>(&mut bytes.to_vec()), Ok(frame));
+    }
+}
+
+#[test]
+fn cobs_overhead_fits_frame_size() {
+    // Non-zero data only, which needs the most code bytes.
+    let data = [1u8; 509];
+    let mut buf = [0u8; 1024];
+    for len in [1, 253, 254, 255, 508, 509] {
+        let encoded = frame::cobs_encode(&data[..len], &mut buf);
+        // Same bound as `MAX_FRAME_SIZE` without the terminator.
+        assert!(encoded <= len + len / 254 + 1, "{len} bytes encoded to {encoded} bytes");
+    }
+}
+
+#[test]
+fn frame_corruption_detected() {
+    let mut buf = [0u8; MAX_FRAME_SIZE];
+    let bytes = frame::encode(&Frame::Data { seq: 1, base: 1, data: 12345u32 }, &mut buf).unwrap();
+    for i in 0..bytes.len() - 1 {
+        for bit in 0..8 {
+            let mut corrupted = bytes.to_vec();
+            corrupted[i] ^= 1 << bit;
+            assert!(
+                frame::decode::<u32>(&mut corrupted).is_err(),
+                "bit {bit} of byte {i} flipped but decoded"
+            );
+        }
+    }
+    assert_eq!(frame::decode::<u32>(&mut [0]), Err(FrameError::Crc));
+}
+
+#[test]
+fn delivers_in_order_over_lossless_link() {
+    let (a, b) = LossySplit::pair(0, 0);
+    let (mut a, mut b) = (Endpoint::new(a), Endpoint::new(b));
+    let now = exchange(&mut a, &mut b, 100, Instant::from_millis(0));
+    assert_eq!(b.received, (0..100).collect::<Vec<_>>());
+    assert_eq!(a.received, (1000..1100).collect::<Vec<_>>());
+
+    a.poll(now);
+    let stats = a.arq.stats;
+    // 100 data frames and 100 ACKs in each direction
+    assert_eq!((stats.frames_sent, stats.frames_received), (200, 200));
+    assert_eq!((stats.retransmits, stats.errors, stats.lost), (0, 0, 0));
+}
+
+#[test]
+fn delivers_in_order_over_lossy_link() {
+    let (a, b) = LossySplit::pair(10, 5);
+    let (mut a, mut b) = (Endpoint::new(a), Endpoint::new(b));
+    exchange(&mut a, &mut b, 500, Instant::from_millis(0));
+    assert_eq!(b.received, (0..500).collect::<Vec<_>>());
+    assert_eq!(a.received, (1000..1500).collect::<Vec<_>>());
+    for stats in [a.arq.stats, b.arq.stats] {
+        assert!(stats.retransmits > 0);
+        assert!(stats.errors > 0);
+        assert_eq!(stats.lost, 0);
+    }
+}
+
+#[test]
+fn gives_up_on_dead_link_and_recovers() {
+    let (a, b) = LossySplit::pair(100, 0);
+    let (mut a, mut b) = (Endpoint::new(a), Endpoint::new(b));
+    let mut now = Instant::from_millis(0);
+    for i in 0..WINDOW as u32 {
+        a.send(i, now);
+    }
+    assert!(a.arq.is_full());
+
+    for _ in 0..=MAX_RETRIES {
+        now += RETRANSMIT_TIMEOUT;
+        a.poll(now);
+    }
+    assert!(!a.arq.is_full());
+    assert_eq!(a.arq.deadline(), None);
+    assert_eq!(a.arq.stats.lost, WINDOW as u32);
+
+    a.split.loss = 0;
+    b.split.loss = 0;
+    a.send(100, now);
+    b.poll(now);
+    a.poll(now);
+    assert_eq!(b.received, [100]);
+    assert_eq!(a.arq.deadline(), None);
+}
+
+#[test]
+fn resyncs_after_receiver_restart() {
+    let (a, b) = LossySplit::pair(0, 0);
+    let (mut a, mut b) = (Endpoint::new(a), Endpoint::new(b));
+    let now = exchange(&mut a, &mut b, 300, Instant::from_millis(0));
+
+    let b_split = b.split;
+    let mut b = Endpoint::new(b_split);
+    a.received.clear();
+    exchange(&mut a, &mut b, 10, now);
+    assert_eq!(b.received, (0..10).collect::<Vec<_>>());
+    assert_eq!(a.received, (1000..1010).collect::<Vec<_>>());
+}
+
+#[test]
+fn resyncs_after_sender_restart() {
+    let (a, b) = LossySplit::pair(0, 0);
+    let (mut a, mut b) = (Endpoint::new(a), Endpoint::new(b));
+    let now = exchange(&mut a, &mut b, 300, Instant::from_millis(0));
+
+    let a_split = a.split;
+    let mut a = Endpoint::new(a_split);
+    b.received.clear();
+    exchange(&mut a, &mut b, 10, now);
+    assert_eq!(b.received, (0..10).collect::<Vec<_>>());
+    assert_eq!(a.received, (1000..1010).collect::<Vec<_>>());
+}
@@ -0,0 +1,126 @@
+//! rrp over the split link.
+//!
+//! rrp data is sent as [`RrpChunk`] through [`MasterToSlave::Rrp`] and [`SlaveToMaster::Rrp`].
+//! Master forwards requests addressed to the slave, and the slave runs its own rrp server.
+
+use embassy_sync::pipe::Pipe;
+use embassy_time::{Duration, with_timeout};
+use rktk_rrp::{
+    server::{Forwarder, TransportForwarder},
+    transport::{
+        ReadTransport, RequestHeader, TransportError, WriteTransport, error::ReceiveError,
+    },
+};
+
+use crate::{
+    config::CONST_CONFIG,
+    drivers::interface::split::{MasterToSlave, RRP_CHUNK_SIZE, RrpChunk, SlaveToMaster},
+    utils::{Mutex, RawMutex, Sender},
+};
+
+use super::{
+    channels::split::{M2S_CHANNEL, S2M_CHANNEL},
+    split_handler::link_stats,
+};
+
+/// rrp data received from the other side.
+static RRP_RECV_PIPE: Pipe<RawMutex, 128> = Pipe::new();
+
+/// Prevents multiple rrp servers (ex: usb and ble) from forwarding at the same time.
+static FORWARD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Timeout of the response from the slave.
+const SLAVE_RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Timeout of sending a chunk to the split handler.
+const SEND_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Timeout of the whole forwarded request, so that a stalled request doesn't block other rrp
+/// servers which wait for [`FORWARD_LOCK`].
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Passes rrp data received from the other side to the rrp server or forwarder.
+pub async fn on_chunk_received(chunk: RrpChunk) {
+    let len = (chunk.len as usize).min(RRP_CHUNK_SIZE);
+    RRP_RECV_PIPE.write_all(&chunk.data[..len]).await;
+}
+
+pub struct SplitRrpReader {
+    timeout: Option<Duration>,
+}
+
+impl ReadTransport for SplitRrpReader {
+    type Error = &'static str;
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if let Some(timeout) = self.timeout {
+            with_timeout(timeout, RRP_RECV_PIPE.read(buf)).await.map_err(|_| "Split rrp timeout")
+        } else {
+            Ok(RRP_RECV_PIPE.read(buf).await)
+        }
+    }
+}
+
+pub struct SplitRrpWriter<'a, T> {
+    sender: Sender<'a, T, { CONST_CONFIG.buffer.split_channel }>,
+    wrap: fn(RrpChunk) -> T,
+}
+
+impl<T> WriteTransport for SplitRrpWriter<'_, T> {
+    type Error = &'static str;
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        // Nothing drains the channel if the split handler is not running, and chunks sent while
+        // the link is down are lost anyway.
+        if !link_stats().is_some_and(|s| s.alive) {
+            return Err("Split link is down");
+        }
+
+        let len = buf.len().min(RRP_CHUNK_SIZE);
+        let mut chunk = RrpChunk { len: len as u8, data: [0; RRP_CHUNK_SIZE] };
+        chunk.data[..len].copy_from_slice(&buf[..len]);
+        with_timeout(SEND_TIMEOUT, self.sender.send((self.wrap)(chunk)))
+            .await
+            .map_err(|_| "Split rrp send timeout")?;
+        Ok(len)
+    }
+}
+
+/// Transports used by the slave-side rrp server.
+pub fn slave_transports() -> (SplitRrpReader, SplitRrpWriter<'static, SlaveToMaster>) {
+    (
+        SplitRrpReader { timeout: None },
+        SplitRrpWriter { sender: S2M_CHANNEL.sender(), wrap: SlaveToMaster::Rrp },
+    )
+}
+
+/// [`Forwarder`] used by the master-side rrp server to forward requests to the slave.
+///
+/// If the split link is down, requests are answered with an error response.
+pub struct SplitForwarder;
+
+impl Forwarder for SplitForwarder {
+    async fn forward<RT: ReadTransport, WT: WriteTransport>(
+        &mut self,
+        header: RequestHeader,
+        reader: &mut RT,
+        writer: &mut WT,
+    ) -> Result<(), TransportError<RT::Error, WT::Error>> {
+        let forward = async {
+            let _lock = FORWARD_LOCK.lock().await;
+
+            // Discard data left by previous timed out request.
+            RRP_RECV_PIPE.clear();
+
+            TransportForwarder::new(
+                SplitRrpReader { timeout: Some(SLAVE_RESPONSE_TIMEOUT) },
+                SplitRrpWriter { sender: M2S_CHANNEL.sender(), wrap: MasterToSlave::Rrp },
+            )
+            .forward(header, reader, writer)
+            .await
+        };
+        with_timeout(FORWARD_TIMEOUT, forward)
+            .await
+            .map_err(|_| ReceiveError::FrameError("Split rrp forward timeout"))?
+    }
+}
@@ -87,6 +87,8 @@ async fn main(spawner: Spawner) {
             CommonUsbReporterBuilder::new(opts)
         }),
         display: dummy::display(),
+        dfu: dummy::dfu(),
+        battery: dummy::battery(),
         split: dummy::split(),
         rgb: dummy::rgb(),
         ble_builder: dummy::ble_builder(),
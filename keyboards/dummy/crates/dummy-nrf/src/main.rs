@@ -43,6 +43,8 @@ bind_interrupts!(pub struct Irqs {
     SPI2 => embassy_nrf::spim::InterruptHandler<embassy_nrf::peripherals::SPI2>;
     TWISPI0 => embassy_nrf::twim::InterruptHandler<embassy_nrf::peripherals::TWISPI0>;
     UARTE0 => embassy_nrf::buffered_uarte::InterruptHandler<embassy_nrf::peripherals::UARTE0>;
+    #[cfg(feature = "battery")]
+    SAADC => embassy_nrf::saadc::InterruptHandler;
     #[cfg(feature = "ble-trouble")]
     RNG => embassy_nrf::rng::InterruptHandler<embassy_nrf::peripherals::RNG>;
     #[cfg(feature = "ble-trouble")]
@@ -271,6 +273,20 @@ async fn main(spawner: Spawner) {
         dummy::encoder()
     };
 
+    let battery = {
+        #[cfg(feature = "battery")]
+        {
+            use embassy_nrf::saadc;
+            // Battery is connected to VDDH of nice!nano.
+            let channel_config = saadc::ChannelConfig::single_ended(saadc::VddhDiv5Input);
+            let saadc =
+                saadc::Saadc::new(p.SAADC, Irqs, saadc::Config::default(), [channel_config]);
+            Some(rktk_drivers_nrf::battery::SaadcBattery::new(saadc, 5.0))
+        }
+        #[cfg(not(feature = "battery"))]
+        dummy::battery()
+    };
+
     // FIXME: Implement nrf flash without softdevice
     //
     // let storage = rktk_drivers_nrf::softdevice::flash::create_storage_driver(flash, &cache);
@@ -281,6 +297,8 @@ async fn main(spawner: Spawner) {
         mouse,
         usb_builder,
         display,
+        dfu: dummy::dfu(),
+        battery,
         split,
         rgb,
         storage: dummy::storage(),
@@ -38,6 +38,8 @@ async fn main(spawner: Spawner) {
         mouse: dummy::mouse(),
         usb_builder: dummy::usb_builder(),
         display: dummy::display(),
+        dfu: dummy::dfu(),
+        battery: dummy::battery(),
         split: dummy::split(),
         rgb: dummy::rgb(),
         ble_builder: dummy::ble_builder(),
@@ -6,6 +6,7 @@ use embassy_nrf::{
     bind_interrupts,
     gpio::{Input, Level, Output, OutputDrive, Pull},
     interrupt::{self, InterruptExt, Priority},
+    saadc,
     spim::Spim,
     twim::Twim,
 };
@@ -26,7 +27,7 @@ use rktk_drivers_common::{
     spi::EmbassySpiDevice,
 };
 use rktk_drivers_nrf::{
-    keyscan::flex_pin::NrfFlexPin, rgb::ws2812_pwm::Ws2812Pwm,
+    battery::SaadcBattery, keyscan::flex_pin::NrfFlexPin, rgb::ws2812_pwm::Ws2812Pwm,
     split::uart_half_duplex::UartHalfDuplexSplitDriver, system::NrfSystemDriver,
 };
 
@@ -42,6 +43,7 @@ bind_interrupts!(pub struct Irqs {
     SPI2 => embassy_nrf::spim::InterruptHandler<embassy_nrf::peripherals::SPI2>;
     TWISPI0 => embassy_nrf::twim::InterruptHandler<embassy_nrf::peripherals::TWISPI0>;
     UARTE0 => embassy_nrf::buffered_uarte::InterruptHandler<embassy_nrf::peripherals::UARTE0>;
+    SAADC => saadc::InterruptHandler;
 });
 
 pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap) {
@@ -54,6 +56,7 @@ pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap)
     interrupt::SPI2.set_priority(Priority::P2);
     interrupt::TWISPI0.set_priority(Priority::P2);
     interrupt::UARTE0.set_priority(Priority::P2);
+    interrupt::SAADC.set_priority(Priority::P2);
 
     let mut display = Ssd1306Driver::new(
         Twim::new(
@@ -126,6 +129,17 @@ pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap)
 
     let rgb = Ws2812Pwm::<1024, _, _>::new(p.PWM0, p.P0_09);
 
+    // Battery is connected to VDDH of nice!nano.
+    let battery = SaadcBattery::new(
+        saadc::Saadc::new(
+            p.SAADC,
+            Irqs,
+            saadc::Config::default(),
+            [saadc::ChannelConfig::single_ended(saadc::VddhDiv5Input)],
+        ),
+        5.0,
+    );
+
     #[cfg(feature = "ble")]
     let sd = rktk_drivers_nrf::softdevice::init_softdevice("keyball61");
     #[cfg(feature = "ble")]
@@ -183,6 +197,8 @@ pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap)
             usb
         },
         display: Some(display),
+        dfu: dummy::dfu(),
+        battery: Some(battery),
         split: Some(split),
         rgb: Some(rgb),
         storage,
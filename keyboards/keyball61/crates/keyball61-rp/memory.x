@@ -1,6 +1,11 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+
+    /* Flash layout for embassy-boot. The bootloader is placed at 0x10000100. */
+    /* Storage of rktk uses 1MB - 3MB (see `rktk_drivers_rp::flash`).         */
+    BOOTLOADER_STATE : ORIGIN = 0x10006000, LENGTH = 4K
+    FLASH            : ORIGIN = 0x10007000, LENGTH = 480K
+    DFU              : ORIGIN = 0x1007F000, LENGTH = 484K
 
     /* Pick one of the two options for RAM layout     */
 
@@ -16,3 +21,8 @@ MEMORY {
     /* SCRATCH_B: ORIGIN = 0x20041000, LENGTH = 4K    */
 }
 
+__bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+__bootloader_state_end = ORIGIN(BOOTLOADER_STATE) + LENGTH(BOOTLOADER_STATE) - ORIGIN(BOOT2);
+
+__bootloader_dfu_start = ORIGIN(DFU) - ORIGIN(BOOT2);
+__bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOT2);
@@ -10,6 +10,7 @@ use embassy_rp::{
     pio::Pio,
 };
 
+use embassy_embedded_hal::flash::partition::Partition;
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
 use rktk::{
     config::keymap::Keymap,
@@ -17,15 +18,17 @@ use rktk::{
     hooks::create_empty_hooks,
 };
 use rktk_drivers_common::{
+    dfu::EmbassyBootDfu,
     display::ssd1306::{self, Ssd1306Driver},
     keyscan::{detect_hand_from_matrix, duplex_matrix::DuplexMatrixScanner},
     mouse::pmw3360::Pmw3360,
     panic_utils,
     spi::EmbassySpiDevice,
+    storage::flash_sequential_map::FlashSequentialMapStorage,
     usb::*,
 };
 use rktk_drivers_rp::{
-    flash::init_storage, keyscan::flex_pin::RpFlexPin, mouse::pmw3360, rgb::ws2812_pio::Ws2812Pio,
+    keyscan::flex_pin::RpFlexPin, mouse::pmw3360, rgb::ws2812_pio::Ws2812Pio,
     split::pio_half_duplex::PioHalfDuplexSplitDriver,
 };
 
@@ -112,7 +115,16 @@ pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap)
     let pio = Pio::new(p.PIO1, Irqs);
     let rgb = Ws2812Pio::<'_, 30, _>::new(pio, p.PIN_0, p.DMA_CH2, Irqs);
 
-    let storage = init_storage::<_, { 4 * 1024 * 1024 }>(p.FLASH, p.DMA_CH3, Irqs);
+    // Flash is shared by storage (1MB - 3MB) and DFU partitions defined in memory.x.
+    let flash =
+        embassy_rp::flash::Flash::<_, _, { 4 * 1024 * 1024 }>::new(p.FLASH, p.DMA_CH3, Irqs);
+    let flash = Mutex::<NoopRawMutex, _>::new(flash);
+    let storage = FlashSequentialMapStorage::new(
+        Partition::new(&flash, 1024 * 1024, 2 * 1024 * 1024),
+        0,
+        2 * 1024 * 1024,
+    );
+    let dfu = EmbassyBootDfu::from_linkerfile(&flash);
 
     let drivers = Drivers {
         keyscan,
@@ -120,6 +132,8 @@ pub async fn start(spawner: embassy_executor::Spawner, keymap: &'static Keymap)
         mouse: Some(ball),
         usb_builder: usb,
         display: Some(display),
+        dfu: Some(dfu),
+        battery: dummy::battery(),
         split: Some(split),
         rgb: Some(rgb),
         ble_builder: dummy::ble_builder(),
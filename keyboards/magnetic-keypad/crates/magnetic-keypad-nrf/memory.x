@@ -1,7 +1,16 @@
 MEMORY
 {
-  /* for softdevice v6 */
-  FLASH : ORIGIN = 0x00026000, LENGTH = 828K
+  /* Flash layout for embassy-boot. rktk storage uses 0x000FC000 - 0x00100000. */
+  BOOTLOADER       : ORIGIN = 0x00000000, LENGTH = 24K
+  BOOTLOADER_STATE : ORIGIN = 0x00006000, LENGTH = 4K
+  FLASH            : ORIGIN = 0x00007000, LENGTH = 480K
+  DFU              : ORIGIN = 0x0007F000, LENGTH = 484K
   /* RAM MAX: 256K (0x40000) */
   RAM : ORIGIN = 0x20008000, LENGTH = 0x38000
 }
+
+__bootloader_state_start = ORIGIN(BOOTLOADER_STATE) - ORIGIN(BOOTLOADER);
+__bootloader_state_end = ORIGIN(BOOTLOADER_STATE) + LENGTH(BOOTLOADER_STATE) - ORIGIN(BOOTLOADER);
+
+__bootloader_dfu_start = ORIGIN(DFU) - ORIGIN(BOOTLOADER);
+__bootloader_dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOTLOADER);
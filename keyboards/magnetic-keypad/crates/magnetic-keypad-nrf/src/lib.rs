@@ -17,7 +17,9 @@ use rktk::{
     hooks::empty_hooks::create_empty_hooks,
 };
 
+use embassy_embedded_hal::flash::partition::Partition;
 use rktk_drivers_common::{
+    dfu::EmbassyBootDfu,
     display::mipi_display::MipiDisplayDriver,
     magnetic::{
         matrix::{MagneticMatrix, MuxScanner},
@@ -181,14 +183,17 @@ pub async fn run(spawner: Spawner, keymap: &'static Keymap) {
         embassy_nrf::gpio::Input::new(p.P0_10, embassy_nrf::gpio::Pull::Up),
     )]);
 
+    // Flash is shared by storage and DFU partitions defined in memory.x.
     let flash = embassy_nrf::nvmc::Nvmc::new(p.NVMC);
     let async_flash = embassy_embedded_hal::adapter::BlockingAsync::new(flash);
+    let async_flash = rktk::utils::Mutex::new(async_flash);
     let storage =
         rktk_drivers_common::storage::flash_sequential_map::FlashSequentialMapStorage::new(
-            async_flash,
-            0xFC000,
+            Partition::new(&async_flash, 0xFC000, 16 * 1024),
+            0,
             16 * 1024,
         );
+    let dfu = EmbassyBootDfu::from_linkerfile(&async_flash);
 
     // Turn on backlight
     let mut bl = Output::new(p.P0_22, Level::High, OutputDrive::Standard);
@@ -240,6 +245,8 @@ pub async fn run(spawner: Spawner, keymap: &'static Keymap) {
             CommonUsbReporterBuilder::new(opts)
         }),
         display: Some(DisplayWrapper(disp_drv)),
+        dfu: Some(dfu),
+        battery: dummy::battery(),
         split: dummy::split(),
         rgb: Some(rgb),
         ble_builder: dummy::ble_builder(),
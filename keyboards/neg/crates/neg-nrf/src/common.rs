@@ -23,6 +23,7 @@ pub fn init_peri() -> Peripherals {
         interrupt::SPI2.set_priority(Priority::P2);
         interrupt::SPIM3.set_priority(Priority::P2);
         interrupt::UARTE0.set_priority(Priority::P2);
+        interrupt::SAADC.set_priority(Priority::P2);
     }
 
     #[cfg(feature = "alloc")]
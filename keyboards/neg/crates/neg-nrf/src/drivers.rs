@@ -141,6 +141,19 @@ macro_rules! driver_system {
     }};
 }
 
+#[macro_export]
+macro_rules! driver_battery {
+    ($p:ident) => {{
+        use embassy_nrf::saadc;
+        use rktk_drivers_nrf::battery::SaadcBattery;
+
+        // Battery is connected to VDDH of nice!nano.
+        let channel_config = saadc::ChannelConfig::single_ended(saadc::VddhDiv5Input);
+        let saadc = saadc::Saadc::new($p.SAADC, Irqs, saadc::Config::default(), [channel_config]);
+        SaadcBattery::new(saadc, 5.0)
+    }};
+}
+
 #[macro_export]
 macro_rules! driver_debounce {
     () => {{
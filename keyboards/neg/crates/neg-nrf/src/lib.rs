@@ -38,6 +38,7 @@ bind_interrupts!(pub struct Irqs {
     SPI2 => embassy_nrf::spim::InterruptHandler<embassy_nrf::peripherals::SPI2>;
     TWISPI0 => embassy_nrf::twim::InterruptHandler<embassy_nrf::peripherals::TWISPI0>;
     UARTE0 => embassy_nrf::buffered_uarte::InterruptHandler<embassy_nrf::peripherals::UARTE0>;
+    SAADC => embassy_nrf::saadc::InterruptHandler;
 
     // These interrupts are used for sdc (trouble)
     #[cfg(feature = "trouble")]
@@ -69,6 +69,8 @@ pub async fn start_master(
         mouse: Some(driver_mouse!(p, spi)),
         usb_builder: usb,
         display: Some(driver_display!(p)),
+        dfu: dummy::dfu(),
+        battery: Some(driver_battery!(p)),
         split: Some(driver_split!(p)),
         rgb: Some(driver_rgb!(p)),
         storage: dummy::storage(),
@@ -23,6 +23,8 @@ pub async fn start_slave(spawner: embassy_executor::Spawner, p: Peripherals, hoo
         mouse: Some(driver_mouse!(p, spi)),
         usb_builder: dummy::usb_builder(),
         display: Some(driver_display!(p)),
+        dfu: dummy::dfu(),
+        battery: Some(driver_battery!(p)),
         split: Some(driver_split!(p)),
         rgb: Some(driver_rgb!(p)),
         storage: dummy::storage(),
@@ -42,6 +42,8 @@ pub async fn start_master(
         mouse: Some(driver_mouse!(p, spi)),
         usb_builder: usb,
         display: Some(driver_display!(p)),
+        dfu: dummy::dfu(),
+        battery: dummy::battery(),
         split: Some(driver_split!(p)),
         rgb: Some(driver_rgb!(p)),
         storage: Some(storage),